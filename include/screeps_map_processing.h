@@ -0,0 +1,35 @@
+#ifndef SCREEPS_MAP_PROCESSING_H
+#define SCREEPS_MAP_PROCESSING_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An open terrain database.
+typedef struct SmpDb SmpDb;
+
+// Opens the existing terrain database at `path`, a null-terminated UTF-8 string, for reading.
+//
+// Databases written by an older version of the crate are migrated first, which needs write access
+// to the file. Returns null if the path isn't valid UTF-8, there's no database at the path or it
+// can't be opened, it can't be migrated, or it was written by a newer version of the crate. The
+// database must be closed with [smp_db_close].
+SmpDb *smp_db_open(const char *path);
+
+// Closes a database opened by [smp_db_open]. Passing null does nothing.
+void smp_db_close(SmpDb *db);
+
+// Loads the terrain of a room, named by a null-terminated string like `"W1N1"`.
+//
+// The terrain is returned as 2500 bytes in row-major order, using the same values as the game:
+// 0 for plains, 1 for walls, and 2 for swamps. The length is written to `out_len` if it isn't
+// null. Returns null if the room name is invalid or the room's terrain can't be loaded. The
+// bytes must be released with [smp_bytes_free].
+uint8_t *smp_room_terrain(const SmpDb *db, const char *room_name, size_t *out_len);
+
+// Releases bytes returned by this API. Passing null does nothing.
+void smp_bytes_free(uint8_t *bytes, size_t len);
+
+#endif /* SCREEPS_MAP_PROCESSING_H */
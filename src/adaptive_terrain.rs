@@ -0,0 +1,239 @@
+//! Provides room terrain that picks the smallest available encoding for each room.
+
+use std::fmt;
+use screeps::{RoomXY, Terrain};
+
+use crate::byte_layout::{u16s_from_le_bytes, u16s_to_le_bytes};
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::run_length_encoding::rle_terrain::{PackedRLERoomTerrain, WildcardRLERoomTerrain};
+use crate::terrain_source::TerrainSource;
+
+/// The terrain encodings that [AdaptiveRoomTerrain] can choose between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TerrainEncoding {
+    /// [CompressedRoomTerrain]
+    Compressed,
+    /// [PackedRLERoomTerrain]
+    PackedRLE,
+    /// [WildcardRLERoomTerrain]
+    WildcardRLE,
+}
+
+impl TerrainEncoding {
+    /// All encodings.
+    pub const ALL: [TerrainEncoding; 3] = [TerrainEncoding::Compressed, TerrainEncoding::PackedRLE, TerrainEncoding::WildcardRLE];
+
+    /// The encoding that takes the least memory, given the size of a room's terrain in each.
+    ///
+    /// Compressed terrain only wins if it's strictly smaller than both run length encodings, and
+    /// ties between the run length encodings go to wildcard RLE.
+    pub fn smallest(size_for: impl Fn(TerrainEncoding) -> usize) -> TerrainEncoding {
+        // min_by_key keeps the first of several equal sizes, so search in reverse order of preference
+        Self::ALL.into_iter().rev().min_by_key(|encoding| size_for(*encoding)).unwrap_or(TerrainEncoding::WildcardRLE)
+    }
+
+    /// The stable name of this encoding.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerrainEncoding::Compressed => "compressed",
+            TerrainEncoding::PackedRLE => "packed-rle",
+            TerrainEncoding::WildcardRLE => "wildcard-rle",
+        }
+    }
+
+    /// Looks up an encoding by its [name](TerrainEncoding::name).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| encoding.name() == name)
+    }
+}
+
+impl fmt::Display for TerrainEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Room terrain stored in whichever encoding takes the least memory for that room.
+///
+/// Open rooms like highways compress very well with run length encoding, while mazy rooms are
+/// smaller as plain bit-packed terrain. Ties are broken by [TerrainEncoding::smallest].
+#[derive(Debug, Clone)]
+pub enum AdaptiveRoomTerrain {
+    Compressed(CompressedRoomTerrain),
+    PackedRLE(PackedRLERoomTerrain),
+    WildcardRLE(WildcardRLERoomTerrain),
+}
+
+impl AdaptiveRoomTerrain {
+    /// Converts bit-packed compressed terrain into its smallest encoding.
+    pub fn new_from_compressed_terrain(terrain: &CompressedRoomTerrain) -> Self {
+        let packed = PackedRLERoomTerrain::new_from_compressed_terrain(terrain);
+        let wildcard = WildcardRLERoomTerrain::new_from_compressed_terrain(terrain);
+
+        let encoding = TerrainEncoding::smallest(|encoding| match encoding {
+            TerrainEncoding::Compressed => terrain.memory_size(),
+            TerrainEncoding::PackedRLE => packed.memory_size(),
+            TerrainEncoding::WildcardRLE => wildcard.memory_size(),
+        });
+
+        match encoding {
+            TerrainEncoding::Compressed => {
+                let bytes = Box::new(*terrain.get_compressed_bytes());
+                Self::Compressed(CompressedRoomTerrain::new_from_compressed_bytes(bytes))
+            }
+            TerrainEncoding::PackedRLE => Self::PackedRLE(packed),
+            TerrainEncoding::WildcardRLE => Self::WildcardRLE(wildcard),
+        }
+    }
+
+    /// Converts bit-packed compressed terrain into the specified encoding.
+    pub fn new_with_encoding(terrain: &CompressedRoomTerrain, encoding: TerrainEncoding) -> Self {
+        match encoding {
+            TerrainEncoding::Compressed => Self::Compressed(CompressedRoomTerrain::new_from_compressed_bytes(Box::new(*terrain.get_compressed_bytes()))),
+            TerrainEncoding::PackedRLE => Self::PackedRLE(PackedRLERoomTerrain::new_from_compressed_terrain(terrain)),
+            TerrainEncoding::WildcardRLE => Self::WildcardRLE(WildcardRLERoomTerrain::new_from_compressed_terrain(terrain)),
+        }
+    }
+
+    /// Converts this terrain back into bit-packed compressed terrain.
+    pub fn to_compressed_terrain(&self) -> CompressedRoomTerrain {
+        match self {
+            Self::Compressed(terrain) => CompressedRoomTerrain::new_from_compressed_bytes(Box::new(*terrain.get_compressed_bytes())),
+            _ => CompressedRoomTerrain::from_sparse(Terrain::Plain, self.iter_tiles()),
+        }
+    }
+
+    /// The encoding this terrain is stored in.
+    pub fn encoding(&self) -> TerrainEncoding {
+        match self {
+            Self::Compressed(_) => TerrainEncoding::Compressed,
+            Self::PackedRLE(_) => TerrainEncoding::PackedRLE,
+            Self::WildcardRLE(_) => TerrainEncoding::WildcardRLE,
+        }
+    }
+
+    /// Gets the terrain at the specified position in this room.
+    pub fn get_xy(&self, xy: RoomXY) -> Terrain {
+        match self {
+            Self::Compressed(terrain) => terrain.get_xy(xy),
+            Self::PackedRLE(terrain) => terrain.get_xy(xy),
+            Self::WildcardRLE(terrain) => terrain.get_xy(xy),
+        }
+    }
+
+    /// The amount of memory it takes to store this data.
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Self::Compressed(terrain) => terrain.memory_size(),
+            Self::PackedRLE(terrain) => terrain.memory_size(),
+            Self::WildcardRLE(terrain) => terrain.memory_size(),
+        }
+    }
+
+    /// Serializes this terrain in its current encoding.
+    ///
+    /// Format: a tag byte for the encoding (0 for compressed, 1 for packed RLE, 2 for wildcard
+    /// RLE), followed by the [encoded bytes](AdaptiveRoomTerrain::to_encoded_bytes).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let tag = match self.encoding() {
+            TerrainEncoding::Compressed => 0,
+            TerrainEncoding::PackedRLE => 1,
+            TerrainEncoding::WildcardRLE => 2,
+        };
+        std::iter::once(tag).chain(self.to_encoded_bytes()).collect()
+    }
+
+    /// Deserializes terrain written by [to_bytes](AdaptiveRoomTerrain::to_bytes), keeping its
+    /// encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        match bytes.split_first() {
+            Some((0, data)) => Self::from_encoded_bytes(TerrainEncoding::Compressed, data),
+            Some((1, data)) => Self::from_encoded_bytes(TerrainEncoding::PackedRLE, data),
+            Some((2, data)) => Self::from_encoded_bytes(TerrainEncoding::WildcardRLE, data),
+            Some((tag, _)) => Err(MapProcessingError::InvalidData { kind: "adaptive terrain", reason: format!("unknown encoding tag {tag}") }),
+            None => Err(MapProcessingError::InvalidLength { kind: "adaptive terrain", expected: 1, found: 0 }),
+        }
+    }
+
+    /// Serializes this terrain in its current encoding, without recording which encoding it is.
+    ///
+    /// Format: the compressed bytes, the packed runs as little-endian u16s, or the 24 edge terrain
+    /// bytes and then the packed runs, for compressed, packed RLE, and wildcard RLE terrain
+    /// respectively. The runs and edge terrain bytes are laid out as described in
+    /// [byte_layout](crate::byte_layout), but without its leading version byte.
+    pub fn to_encoded_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Compressed(terrain) => terrain.get_compressed_bytes().to_vec(),
+            Self::PackedRLE(terrain) => u16s_to_le_bytes(terrain.packed_runs()).collect(),
+            Self::WildcardRLE(terrain) => terrain.edge_terrain().get_raw_bytes().into_iter()
+                .chain(u16s_to_le_bytes(terrain.packed_runs()))
+                .collect(),
+        }
+    }
+
+    /// Deserializes terrain written by [to_encoded_bytes](AdaptiveRoomTerrain::to_encoded_bytes)
+    /// in the specified encoding.
+    pub fn from_encoded_bytes(encoding: TerrainEncoding, bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        let runs_from_bytes = |bytes: &[u8]| u16s_from_le_bytes("RLE terrain", bytes);
+
+        match encoding {
+            TerrainEncoding::Compressed => Ok(Self::Compressed(CompressedRoomTerrain::try_new_from_compressed_slice(bytes)?)),
+            TerrainEncoding::PackedRLE => Ok(Self::PackedRLE(PackedRLERoomTerrain::try_new_from_packed_runs(&runs_from_bytes(bytes)?)?)),
+            TerrainEncoding::WildcardRLE if bytes.len() >= 24 => {
+                let (edge_bytes, runs) = bytes.split_at(24);
+                let edge_data = RoomEdgeTerrain::try_new_from_raw_slice(edge_bytes)?;
+                Ok(Self::WildcardRLE(WildcardRLERoomTerrain::try_new_from_packed_runs(edge_data, &runs_from_bytes(runs)?)?))
+            },
+            TerrainEncoding::WildcardRLE => Err(MapProcessingError::InvalidLength { kind: "wildcard RLE terrain", expected: 24, found: bytes.len() }),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{ROOM_AREA, ROOM_SIZE};
+
+    #[test]
+    pub fn adaptive_room_terrain_picks_smallest_encoding() {
+        // A room of plains compresses down to very few runs
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let adaptive = AdaptiveRoomTerrain::new_from_compressed_terrain(&plains);
+        assert_ne!(adaptive.encoding(), TerrainEncoding::Compressed);
+        assert!(adaptive.memory_size() < plains.memory_size());
+
+        // A checkerboard has a run for every tile
+        let mut bits = [0u8; ROOM_AREA];
+        for (idx, bit) in bits.iter_mut().enumerate() {
+            *bit = ((idx + idx / 50) % 2) as u8;
+        }
+        let checkerboard = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let adaptive = AdaptiveRoomTerrain::new_from_compressed_terrain(&checkerboard);
+        assert_eq!(adaptive.encoding(), TerrainEncoding::Compressed);
+
+        for y in 0..ROOM_SIZE {
+            for x in 0..ROOM_SIZE {
+                let xy = unsafe { RoomXY::unchecked_new(x, y) };
+                assert_eq!(adaptive.get_xy(xy), checkerboard.get_xy(xy));
+            }
+        }
+    }
+
+    #[test]
+    pub fn terrain_encoding_smallest_breaks_ties_toward_rle() {
+        let smallest = |compressed, packed, wildcard| TerrainEncoding::smallest(|encoding| match encoding {
+            TerrainEncoding::Compressed => compressed,
+            TerrainEncoding::PackedRLE => packed,
+            TerrainEncoding::WildcardRLE => wildcard,
+        });
+
+        assert_eq!(smallest(100, 200, 200), TerrainEncoding::Compressed);
+        assert_eq!(smallest(100, 100, 200), TerrainEncoding::PackedRLE);
+        assert_eq!(smallest(100, 200, 100), TerrainEncoding::WildcardRLE);
+        assert_eq!(smallest(200, 100, 100), TerrainEncoding::WildcardRLE);
+        assert_eq!(smallest(100, 100, 100), TerrainEncoding::WildcardRLE);
+    }
+}
@@ -0,0 +1,74 @@
+use screeps::{RoomXY, Terrain};
+
+use crate::compressed_terrain::sub_rect::square_around;
+use crate::terrain_source::TerrainSource;
+
+/// The range of a nuke's blast from its landing tile.
+pub const NUKE_BLAST_RADIUS: u8 = 2;
+
+/// The tiles within `radius` of a center tile, clipped to the room, in row-major order.
+///
+/// Range is measured like in-game range, so the footprint is a square `2 * radius + 1` tiles
+/// across before clipping, and includes the center tile.
+pub fn blast_footprint(center: RoomXY, radius: u8) -> Vec<RoomXY> {
+    let (top_left, bottom_right) = square_around(center, radius);
+
+    (top_left.y.u8()..=bottom_right.y.u8())
+        .flat_map(|y| (top_left.x.u8()..=bottom_right.x.u8()).map(move |x| (x, y)))
+        // Safety: x and y are between two tiles in the room
+        .map(|(x, y)| unsafe { RoomXY::unchecked_new(x, y) })
+        .collect()
+}
+
+/// The tiles affected by a blast, along with the terrain under each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlastFootprint {
+    /// Every tile within range of the center, clipped to the room, in row-major order.
+    pub tiles: Vec<(RoomXY, Terrain)>,
+}
+
+impl BlastFootprint {
+    /// Finds the tiles within `radius` of a center tile, annotated with their terrain.
+    pub fn new(terrain: &impl TerrainSource, center: RoomXY, radius: u8) -> Self {
+        let tiles = blast_footprint(center, radius)
+            .into_iter()
+            .map(|xy| (xy, terrain.get_xy(xy)))
+            .collect();
+        Self { tiles }
+    }
+
+    /// The affected tiles that are walls, where no structures can be.
+    pub fn walls(&self) -> impl Iterator<Item = RoomXY> + '_ {
+        self.tiles.iter().filter(|(_, terrain)| *terrain == Terrain::Wall).map(|(xy, _)| *xy)
+    }
+
+    /// The affected tiles that aren't walls.
+    pub fn open_tiles(&self) -> impl Iterator<Item = RoomXY> + '_ {
+        self.tiles.iter().filter(|(_, terrain)| *terrain != Terrain::Wall).map(|(xy, _)| *xy)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn blast_footprint_is_clipped_and_annotated() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        assert_eq!(blast_footprint(xy(25, 25), NUKE_BLAST_RADIUS).len(), 25);
+        assert_eq!(blast_footprint(xy(0, 0), NUKE_BLAST_RADIUS), vec![
+            xy(0, 0), xy(1, 0), xy(2, 0),
+            xy(0, 1), xy(1, 1), xy(2, 1),
+            xy(0, 2), xy(1, 2), xy(2, 2),
+        ]);
+        assert_eq!(blast_footprint(xy(49, 48), NUKE_BLAST_RADIUS).len(), 3 * 4);
+        assert_eq!(blast_footprint(xy(10, 10), 0), vec![xy(10, 10)]);
+
+        let terrain = CompressedRoomTerrain::from_sparse(Terrain::Plain, [(xy(24, 24), Terrain::Wall), (xy(26, 27), Terrain::Wall), (xy(25, 26), Terrain::Swamp)]);
+        let footprint = BlastFootprint::new(&terrain, xy(25, 25), NUKE_BLAST_RADIUS);
+        assert_eq!(footprint.walls().collect::<Vec<_>>(), vec![xy(24, 24), xy(26, 27)]);
+        assert_eq!(footprint.open_tiles().count(), 23);
+    }
+}
@@ -0,0 +1,233 @@
+//! Caches expensive analysis results in the terrain database.
+//!
+//! Results are keyed by the terrain fingerprint and the analysis parameters, so they're shared
+//! between rooms with identical terrain, survive across tool runs and bot restarts, and are
+//! naturally invalidated when a room's terrain changes.
+//!
+//! The analysis names are always available, but the cache itself needs the `db` feature.
+#![cfg_attr(not(feature = "db"), allow(unused_imports))]
+
+use std::cell::Cell;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::{RoomXY, ROOM_AREA};
+use screeps::local::terrain_index_to_xy;
+
+use crate::analysis::exit_reachability::ExitReachability;
+use crate::analysis::maze_complexity::maze_complexity;
+use crate::analysis::retreat::ticks_to_exit_map;
+use crate::analysis::upgrade::{upgrade_positions, UpgradeArea};
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+use crate::error::MapProcessingError;
+use crate::pathfinding::{exit_flow_field, TerrainCosts};
+use crate::room_connectivity::exit::{RoomExit, RoomExitsData};
+use crate::room_matrix::RoomMatrix;
+
+/// The analysis name used to store [ExitReachability] results.
+pub const EXIT_REACHABILITY_ANALYSIS: &str = "exit-reachability";
+
+/// The analysis name used to store [exit_flow_field] results.
+pub const EXIT_FLOW_FIELD_ANALYSIS: &str = "exit-flow-field";
+
+/// The analysis name used to store [maze_complexity] results.
+pub const MAZE_COMPLEXITY_ANALYSIS: &str = "maze-complexity";
+
+/// The analysis name used to store [upgrade_positions] results.
+pub const UPGRADE_POSITIONS_ANALYSIS: &str = "upgrade-positions";
+
+/// The analysis name used to store [ticks_to_exit_map] results.
+pub const TICKS_TO_EXIT_ANALYSIS: &str = "ticks-to-exit";
+
+/// How often an [AnalysisCache] found results already stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Lookups that had to compute the result, including results that couldn't be decoded.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The fraction of lookups that were hits, or 0 if there haven't been any lookups.
+    pub fn hit_rate(&self) -> f32 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 { 0.0 } else { self.hits as f32 / lookups as f32 }
+    }
+}
+
+/// A memoizing layer over analyses, backed by the `analysis_blob` table.
+#[cfg(feature = "db")]
+pub struct AnalysisCache<'a> {
+    conn: &'a Connection,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+#[cfg(feature = "db")]
+impl<'a> AnalysisCache<'a> {
+    /// Creates a cache over the database, creating the `analysis_blob` table if needed.
+    pub fn new(conn: &'a Connection) -> Result<Self, MapProcessingError> {
+        compressed_terrain_db::create_analysis_table_if_not_exists(conn)?;
+        Ok(Self { conn, hits: Cell::new(0), misses: Cell::new(0) })
+    }
+
+    /// The hits and misses of every lookup made through this cache.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits.get(), misses: self.misses.get() }
+    }
+
+    /// The exit reachability of the terrain, computed and stored if it isn't cached already.
+    pub fn exit_reachability(&self, terrain: &CompressedRoomTerrain) -> Result<ExitReachability, MapProcessingError> {
+        self.get_or_compute(terrain, EXIT_REACHABILITY_ANALYSIS, "", ExitReachability::to_bytes, |bytes| Some(ExitReachability::from_bytes(bytes)), || {
+            ExitReachability::new_from_compressed_terrain(terrain)
+        })
+    }
+
+    /// The flow field toward an exit of the terrain, computed and stored if it isn't cached already.
+    pub fn exit_flow_field(&self, terrain: &CompressedRoomTerrain, exit: &RoomExit, costs: &TerrainCosts) -> Result<RoomMatrix<u16>, MapProcessingError> {
+        let params = format!("exit={};plain={};swamp={}", exit.packed(), costs.plain_cost, costs.swamp_cost);
+        self.get_or_compute(terrain, EXIT_FLOW_FIELD_ANALYSIS, &params, flow_field_to_bytes, flow_field_from_bytes, || {
+            exit_flow_field(terrain, exit, costs)
+        })
+    }
+
+    /// The maze complexity score of the terrain, computed and stored if it isn't cached already.
+    pub fn maze_complexity(&self, terrain: &CompressedRoomTerrain) -> Result<f32, MapProcessingError> {
+        let encode = |score: &f32| score.to_le_bytes().to_vec();
+        let decode = |bytes: &[u8]| bytes.try_into().ok().map(f32::from_le_bytes);
+        self.get_or_compute(terrain, MAZE_COMPLEXITY_ANALYSIS, "", encode, decode, || maze_complexity(terrain))
+    }
+
+    /// The upgrade area around a controller, computed and stored if it isn't cached already.
+    pub fn upgrade_positions(&self, terrain: &CompressedRoomTerrain, controller_pos: RoomXY, range: u8) -> Result<UpgradeArea, MapProcessingError> {
+        let params = format!("controller={},{};range={range}", controller_pos.x.u8(), controller_pos.y.u8());
+        self.get_or_compute(terrain, UPGRADE_POSITIONS_ANALYSIS, &params, UpgradeArea::to_bytes, UpgradeArea::from_bytes, || {
+            upgrade_positions(terrain, controller_pos, range)
+        })
+    }
+
+    /// The ticks needed to reach an exit from every tile of the terrain, computed and stored if it
+    /// isn't cached already.
+    ///
+    /// `exits` must be the exits of `terrain`, since results are shared between rooms with the same
+    /// terrain.
+    pub fn ticks_to_exit_map(&self, terrain: &CompressedRoomTerrain, exits: &RoomExitsData, move_speed: &TerrainCosts) -> Result<RoomMatrix<u16>, MapProcessingError> {
+        let params = format!("plain={};swamp={}", move_speed.plain_cost, move_speed.swamp_cost);
+        self.get_or_compute(terrain, TICKS_TO_EXIT_ANALYSIS, &params, flow_field_to_bytes, flow_field_from_bytes, || {
+            ticks_to_exit_map(terrain, exits, move_speed)
+        })
+    }
+
+    /// Internal helper function that loads a cached result, or computes and stores it if it's
+    /// missing or can't be decoded.
+    fn get_or_compute<T>(
+        &self,
+        terrain: &CompressedRoomTerrain,
+        analysis: &str,
+        params: &str,
+        encode: impl FnOnce(&T) -> Vec<u8>,
+        decode: impl FnOnce(&[u8]) -> Option<T>,
+        compute: impl FnOnce() -> T,
+    ) -> Result<T, MapProcessingError> {
+        let fingerprint = terrain.fingerprint();
+        if let Some(value) = compressed_terrain_db::get_analysis_blob(self.conn, fingerprint, analysis, params)?.and_then(|bytes| decode(&bytes)) {
+            self.hits.set(self.hits.get() + 1);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(analysis, hit = true, hit_rate = self.stats().hit_rate(), "analysis cache lookup");
+            return Ok(value);
+        }
+
+        self.misses.set(self.misses.get() + 1);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(analysis, hit = false, hit_rate = self.stats().hit_rate(), "analysis cache lookup");
+
+        let value = compute();
+        compressed_terrain_db::set_analysis_blob(self.conn, fingerprint, analysis, params, &encode(&value))?;
+        Ok(value)
+    }
+}
+
+/// Internal helper function to serialize a flow field as little-endian u16s in row-major order.
+#[cfg(feature = "db")]
+fn flow_field_to_bytes(field: &RoomMatrix<u16>) -> Vec<u8> {
+    field.as_slice().iter().flat_map(|cost| cost.to_le_bytes()).collect()
+}
+
+/// Internal helper function to deserialize a flow field written by [flow_field_to_bytes].
+#[cfg(feature = "db")]
+fn flow_field_from_bytes(bytes: &[u8]) -> Option<RoomMatrix<u16>> {
+    if bytes.len() != ROOM_AREA * 2 {
+        return None;
+    }
+    let mut field = RoomMatrix::new(0);
+    for (idx, chunk) in bytes.chunks_exact(2).enumerate() {
+        field.set(terrain_index_to_xy(idx), u16::from_le_bytes([chunk[0], chunk[1]]));
+    }
+    Some(field)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{ExitDirection, RoomXY};
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn analysis_cache_reuses_stored_results() {
+        let conn = Connection::open_in_memory().unwrap();
+        let cache = AnalysisCache::new(&conn).unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let exit = RoomExit::new(0, 50, ExitDirection::Top);
+
+        let field = cache.exit_flow_field(&terrain, &exit, &TerrainCosts::default()).unwrap();
+        assert_eq!(field.get(RoomXY::checked_new(10, 49).unwrap()), 49);
+        assert_eq!(cache.exit_flow_field(&terrain, &exit, &TerrainCosts::default()).unwrap(), field);
+
+        let score = cache.maze_complexity(&terrain).unwrap();
+        assert_eq!(cache.maze_complexity(&terrain).unwrap(), score);
+
+        let controller = RoomXY::checked_new(25, 25).unwrap();
+        let area = cache.upgrade_positions(&terrain, controller, 3).unwrap();
+        assert_eq!(area.capacity(), 48);
+        assert_eq!(cache.upgrade_positions(&terrain, controller, 3).unwrap(), area);
+
+        let reachability = cache.exit_reachability(&terrain).unwrap();
+        assert_eq!(cache.exit_reachability(&terrain).unwrap(), reachability);
+
+        // Overwrite the cached result to show that it's what gets returned
+        compressed_terrain_db::set_analysis_blob(&conn, terrain.fingerprint(), EXIT_REACHABILITY_ANALYSIS, "", &[7]).unwrap();
+        assert_eq!(cache.exit_reachability(&terrain).unwrap(), ExitReachability::from_bytes(&[7]));
+
+        // Corrupt flow fields are recomputed
+        let params = format!("exit={};plain=1;swamp=5", exit.packed());
+        compressed_terrain_db::set_analysis_blob(&conn, terrain.fingerprint(), EXIT_FLOW_FIELD_ANALYSIS, &params, &[1, 2, 3]).unwrap();
+        assert_eq!(cache.exit_flow_field(&terrain, &exit, &TerrainCosts::default()).unwrap(), field);
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn analysis_cache_counts_hits_and_misses() {
+        let conn = Connection::open_in_memory().unwrap();
+        let cache = AnalysisCache::new(&conn).unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        assert_eq!(cache.stats(), CacheStats::default());
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+
+        cache.maze_complexity(&terrain).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1 });
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+
+        cache.maze_complexity(&terrain).unwrap();
+        cache.maze_complexity(&terrain).unwrap();
+        cache.exit_reachability(&terrain).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 });
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+
+        // Results that can't be decoded count as misses
+        compressed_terrain_db::set_analysis_blob(&conn, terrain.fingerprint(), MAZE_COMPLEXITY_ANALYSIS, "", &[1]).unwrap();
+        cache.maze_complexity(&terrain).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 3 });
+    }
+}
@@ -0,0 +1,54 @@
+use screeps::{RoomXY, Terrain, ROOM_USIZE};
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::compressed_terrain::room_bit_grid::{RoomBitGrid, WallBitmap};
+
+/// Marks the tiles that have no wall within `radius` tiles (Chebyshev distance).
+///
+/// Tiles within `radius` of the room boundary are never marked, since a square of that radius
+/// centered on them wouldn't fit in the room. A radius of 0 marks every non-wall tile.
+///
+/// This is computed by repeatedly dilating the wall mask, which is much cheaper than a full
+/// distance transform when only a single radius threshold matters, such as when fitting stamps.
+pub fn clearance_map(terrain: &CompressedRoomTerrain, radius: u8) -> RoomBitGrid {
+    let walls = WallBitmap::from_fn(|xy| terrain.get_xy(xy) == Terrain::Wall);
+
+    let mut blocked = walls;
+    for _ in 0..radius {
+        blocked = blocked.dilate();
+    }
+
+    let radius = radius as usize;
+    let in_bounds = RoomBitGrid::from_fn(|xy: RoomXY| {
+        let (x, y) = (xy.x.u8() as usize, xy.y.u8() as usize);
+        x >= radius && y >= radius && x + radius < ROOM_USIZE && y + radius < ROOM_USIZE
+    });
+
+    in_bounds.difference(&blocked)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn clearance_map_excludes_tiles_near_walls_and_edges() {
+        // Plains everywhere, except a single wall at (20, 20)
+        let mut bits = [0u8; ROOM_AREA];
+        bits[20 * 50 + 20] = 1;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let radius_0 = clearance_map(&terrain, 0);
+        assert_eq!(radius_0.popcount(), ROOM_AREA - 1);
+
+        let radius_2 = clearance_map(&terrain, 2);
+        // 46x46 tiles fit within the boundary, minus the 5x5 square around the wall
+        assert_eq!(radius_2.popcount(), 46 * 46 - 25);
+        assert!(!radius_2.get_xy(RoomXY::checked_new(22, 22).unwrap()));
+        assert!(radius_2.get_xy(RoomXY::checked_new(23, 22).unwrap()));
+        assert!(!radius_2.get_xy(RoomXY::checked_new(1, 30).unwrap()));
+        assert!(radius_2.get_xy(RoomXY::checked_new(2, 30).unwrap()));
+    }
+}
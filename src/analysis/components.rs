@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use screeps::{RoomXY, Terrain, ROOM_AREA};
+use screeps::local::terrain_index_to_xy;
+
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+use crate::room_connectivity::exit::{RoomExit, edge_offset_to_xy};
+use crate::room_matrix::RoomMatrix;
+use crate::terrain_source::TerrainSource;
+
+/// The label given to walls, which aren't part of any component.
+pub const NO_COMPONENT: u16 = u16::MAX;
+
+/// Which neighboring tiles are considered connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectivity {
+    /// Only orthogonal neighbors are connected.
+    Four,
+    /// Orthogonal and diagonal neighbors are connected, matching creep movement.
+    Eight,
+}
+
+/// The connected walkable regions of a room.
+///
+/// Components are labeled from 0 in row-major order of their first tile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomComponents {
+    labels: RoomMatrix<u16>,
+    sizes: Vec<usize>,
+}
+
+impl RoomComponents {
+    /// The component label of every tile, with walls labeled [NO_COMPONENT].
+    pub fn labels(&self) -> &RoomMatrix<u16> {
+        &self.labels
+    }
+
+    /// The component the tile belongs to, or None if it's a wall.
+    pub fn label(&self, xy: RoomXY) -> Option<u16> {
+        Some(self.labels.get(xy)).filter(|label| *label != NO_COMPONENT)
+    }
+
+    /// The number of tiles in each component, indexed by label.
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// The number of components in the room.
+    pub fn num_components(&self) -> usize {
+        self.sizes.len()
+    }
+
+    /// The label of the component with the most tiles, or None if the room is all walls.
+    ///
+    /// Ties go to the lowest label.
+    pub fn largest(&self) -> Option<u16> {
+        (0..self.sizes.len()).rev().max_by_key(|label| self.sizes[*label]).map(|label| label as u16)
+    }
+
+    /// Returns true if both tiles are walkable and in the same component.
+    pub fn same_component(&self, a: RoomXY, b: RoomXY) -> bool {
+        self.label(a).is_some_and(|label| self.label(b) == Some(label))
+    }
+
+    /// The component an exit belongs to, or None if the exit's tiles are walls in the terrain
+    /// that was labeled.
+    ///
+    /// Exit tiles are contiguous, so every tile of an exit is in the same component.
+    pub fn exit_label(&self, exit: &RoomExit) -> Option<u16> {
+        self.label(edge_offset_to_xy(exit.exit_direction(), exit.start()))
+    }
+
+    /// A mask of the tiles in a component.
+    pub fn mask(&self, label: u16) -> RoomBitGrid {
+        RoomBitGrid::from_fn(|xy| self.labels.get(xy) == label)
+    }
+}
+
+/// Labels the connected walkable regions of a room.
+pub fn connected_components(terrain: &impl TerrainSource, connectivity: Connectivity) -> RoomComponents {
+    let mut labels = RoomMatrix::new(NO_COMPONENT);
+    let mut sizes = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for idx in 0..ROOM_AREA {
+        let seed = terrain_index_to_xy(idx);
+        if labels.get(seed) != NO_COMPONENT || terrain.get_xy(seed) == Terrain::Wall {
+            continue;
+        }
+
+        let label = sizes.len() as u16;
+        let mut size = 0;
+        labels.set(seed, label);
+        queue.push_back(seed);
+
+        while let Some(xy) = queue.pop_front() {
+            size += 1;
+            let neighbors = xy.neighbors().into_iter()
+                .filter(|neighbor| connectivity == Connectivity::Eight || neighbor.x == xy.x || neighbor.y == xy.y);
+            for neighbor in neighbors {
+                if labels.get(neighbor) == NO_COMPONENT && terrain.get_xy(neighbor) != Terrain::Wall {
+                    labels.set(neighbor, label);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        sizes.push(size);
+    }
+
+    RoomComponents { labels, sizes }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn connected_components_respects_connectivity() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        // A pocket with a diagonal connection, and an isolated tile, in a room of walls
+        let terrain = CompressedRoomTerrain::from_sparse(Terrain::Wall, [
+            (xy(10, 10), Terrain::Plain),
+            (xy(11, 10), Terrain::Swamp),
+            (xy(12, 11), Terrain::Plain),
+            (xy(30, 30), Terrain::Plain),
+        ]);
+
+        let eight = connected_components(&terrain, Connectivity::Eight);
+        assert_eq!(eight.sizes(), &[3, 1]);
+        assert!(eight.same_component(xy(10, 10), xy(12, 11)));
+        assert_eq!(eight.largest(), Some(0));
+        assert_eq!(eight.label(xy(0, 0)), None);
+        assert_eq!(eight.mask(0).popcount(), 3);
+        assert_eq!(eight.exit_label(&RoomExit::new(1, 3, screeps::ExitDirection::Top)), None);
+
+        let four = connected_components(&terrain, Connectivity::Four);
+        assert_eq!(four.sizes(), &[2, 1, 1]);
+        assert!(!four.same_component(xy(10, 10), xy(12, 11)));
+        assert_eq!(four.label(xy(30, 30)), Some(2));
+    }
+}
@@ -0,0 +1,164 @@
+//! Rates how dangerous each room is to travel through, from its position in the shard.
+//!
+//! The score for a room is:
+//!
+//! ```text
+//! kind weight
+//!     + hostile_exit_weight * (exit width toward hostile rooms / 48)
+//!     + chokepoint_weight * (number of borders at most chokepoint_width tiles wide)
+//!     + dead_end_weight * (1 if the room is a dead end, otherwise 0)
+//! ```
+//!
+//! where the kind weight is `source_keeper_weight` for source keeper rooms, `center_weight` for
+//! center rooms, and 0 otherwise. Exit width toward hostile rooms is measured in full edges, so a
+//! room whose whole edge opens into a hostile room adds `hostile_exit_weight`.
+//!
+//! Scores can be stored with [set_danger_score](crate::compressed_terrain_db::set_danger_score),
+//! and loaded into a graph with [set_room_danger](ShardConnectivityGraph::set_room_danger) for
+//! [RouteOptions::with_danger_penalty](crate::room_connectivity::router::RouteOptions::with_danger_penalty).
+
+use std::collections::HashMap;
+use screeps::RoomName;
+
+use crate::room_connectivity::graph::{ShardConnectivityGraph, EXIT_DIRECTIONS, exit_width_toward};
+use crate::room_connectivity::router::RoomKind;
+
+/// The weights of each factor in a room's danger score; see the [module docs](self) for the formula.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DangerWeights {
+    pub source_keeper_weight: f32,
+    pub center_weight: f32,
+    /// The weight of a full edge of exits into hostile rooms.
+    pub hostile_exit_weight: f32,
+    pub chokepoint_weight: f32,
+    /// The widest border, in exit tiles, that counts as a chokepoint.
+    pub chokepoint_width: u32,
+    pub dead_end_weight: f32,
+}
+
+impl Default for DangerWeights {
+    fn default() -> Self {
+        Self {
+            source_keeper_weight: 4.0,
+            center_weight: 6.0,
+            hostile_exit_weight: 2.0,
+            chokepoint_weight: 0.5,
+            chokepoint_width: 3,
+            dead_end_weight: 1.0,
+        }
+    }
+}
+
+/// The factors that make up a room's danger score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DangerFactors {
+    pub kind: RoomKind,
+    /// The total number of exit tiles along borders with hostile rooms.
+    pub hostile_exit_width: u32,
+    /// The number of borders with exits that are at most `chokepoint_width` tiles wide.
+    pub chokepoints: u32,
+    pub dead_end: bool,
+}
+
+impl DangerFactors {
+    /// Measures the danger factors of a room in the graph.
+    ///
+    /// `is_hostile` decides which neighboring rooms are hostile, such as rooms in sectors owned by
+    /// other players. Borders with rooms that aren't in the graph are measured from this room's
+    /// side only.
+    pub fn new(graph: &ShardConnectivityGraph, room: RoomName, is_hostile: impl Fn(RoomName) -> bool, chokepoint_width: u32) -> Self {
+        let mut hostile_exit_width = 0;
+        let mut chokepoints = 0;
+
+        if let Some(exits) = graph.get_room_exits(room) {
+            for direction in EXIT_DIRECTIONS {
+                let Some(neighbor) = graph.topology().neighbor_room(room, direction) else {
+                    continue;
+                };
+                let width = graph.border_exit_width(room, direction).unwrap_or_else(|| exit_width_toward(exits, direction));
+                if is_hostile(neighbor) {
+                    hostile_exit_width += width;
+                }
+                if width > 0 && width <= chokepoint_width {
+                    chokepoints += 1;
+                }
+            }
+        }
+
+        Self {
+            kind: RoomKind::for_room(room),
+            hostile_exit_width,
+            chokepoints,
+            dead_end: graph.is_dead_end(room),
+        }
+    }
+
+    /// The danger score for these factors.
+    pub fn score(&self, weights: &DangerWeights) -> f32 {
+        let kind_weight = match self.kind {
+            RoomKind::SourceKeeper => weights.source_keeper_weight,
+            RoomKind::Center => weights.center_weight,
+            _ => 0.0,
+        };
+        kind_weight
+            + weights.hostile_exit_weight * self.hostile_exit_width as f32 / 48.0
+            + weights.chokepoint_weight * self.chokepoints as f32
+            + if self.dead_end { weights.dead_end_weight } else { 0.0 }
+    }
+}
+
+/// The danger score of every room in the graph.
+pub fn danger_scores(graph: &ShardConnectivityGraph, is_hostile: impl Fn(RoomName) -> bool, weights: &DangerWeights) -> HashMap<RoomName, f32> {
+    graph.rooms()
+        .map(|room| (room, DangerFactors::new(graph, room, &is_hostile, weights.chokepoint_width).score(weights)))
+        .collect()
+}
+
+
+#[cfg(all(test, feature = "db"))]
+mod test {
+    use super::*;
+    use rusqlite::Connection;
+    use screeps::Terrain;
+    use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+    use crate::compressed_terrain_db;
+    use crate::room_connectivity::exit::RoomExitsData;
+
+    #[test]
+    pub fn danger_factors_combine_into_weighted_score() {
+        let mut open = [Terrain::Plain; 50];
+        open[0] = Terrain::Wall;
+        open[49] = Terrain::Wall;
+        let mut narrow = [Terrain::Wall; 50];
+        narrow[20..22].fill(Terrain::Plain);
+        let walls = [Terrain::Wall; 50];
+        let room = |name: &str| RoomName::new(name).unwrap();
+
+        // W4N5 opens wide into W5N5, which is hostile, and narrowly into the dead end W4N4
+        let mut graph = ShardConnectivityGraph::new();
+        let edges = RoomEdgeTerrain::new_from_terrain_slices(&walls, &walls, &narrow, &open).unwrap();
+        graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(edges, room("W4N5")));
+        let edges = RoomEdgeTerrain::new_from_terrain_slices(&walls, &open, &walls, &walls).unwrap();
+        graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(edges, room("W5N5")));
+        let edges = RoomEdgeTerrain::new_from_terrain_slices(&narrow, &walls, &walls, &walls).unwrap();
+        graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(edges, room("W4N4")));
+
+        let is_hostile = |room: RoomName| room.x_coord() == -6;
+        let factors = DangerFactors::new(&graph, room("W4N5"), is_hostile, 3);
+        assert_eq!(factors, DangerFactors { kind: RoomKind::SourceKeeper, hostile_exit_width: 48, chokepoints: 1, dead_end: false });
+
+        let weights = DangerWeights::default();
+        assert_eq!(factors.score(&weights), 4.0 + 2.0 + 0.5);
+        let scores = danger_scores(&graph, is_hostile, &weights);
+        assert_eq!(scores[&room("W4N5")], 6.5);
+        assert_eq!(scores[&room("W5N5")], 6.0 + 1.0);
+        assert_eq!(scores[&room("W4N4")], 4.0 + 0.5 + 1.0);
+
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_danger_table_if_not_exists(&conn).unwrap();
+        for (room, score) in &scores {
+            compressed_terrain_db::set_danger_score(&conn, *room, *score).unwrap();
+        }
+        assert_eq!(compressed_terrain_db::get_danger_scores(&conn).unwrap(), scores);
+    }
+}
@@ -0,0 +1,78 @@
+use screeps::{RoomXY, Terrain, ROOM_SIZE};
+
+use crate::room_matrix::RoomMatrix;
+use crate::terrain_source::TerrainSource;
+
+/// The distance from every tile to the nearest wall (Chebyshev distance), with walls at 0.
+///
+/// Tiles past the room boundary count as walls, so walkable edge tiles are at most 1. This is
+/// the usual input for picking open areas for base layouts, where [clearance_map](crate::analysis::clearance::clearance_map)
+/// only answers a single radius.
+pub fn distance_transform(terrain: &impl TerrainSource) -> RoomMatrix<u8> {
+    let mut distances = RoomMatrix::new(0u8);
+    let xy = |x: u8, y: u8| {
+        // Safety: callers only pass coordinates inside the room
+        unsafe { RoomXY::unchecked_new(x, y) }
+    };
+    let at = |distances: &RoomMatrix<u8>, x: i16, y: i16| {
+        if (0..ROOM_SIZE as i16).contains(&x) && (0..ROOM_SIZE as i16).contains(&y) {
+            distances.get(xy(x as u8, y as u8))
+        } else {
+            0
+        }
+    };
+
+    // Forward pass, from the top left
+    for y in 0..ROOM_SIZE {
+        for x in 0..ROOM_SIZE {
+            if terrain.get_xy(xy(x, y)) == Terrain::Wall {
+                continue;
+            }
+            let (x, y) = (x as i16, y as i16);
+            let nearest = [(-1, -1), (0, -1), (1, -1), (-1, 0)].into_iter()
+                .map(|(dx, dy)| at(&distances, x + dx, y + dy))
+                .min()
+                .unwrap_or(0);
+            distances.set(xy(x as u8, y as u8), nearest.saturating_add(1));
+        }
+    }
+
+    // Backward pass, from the bottom right
+    for y in (0..ROOM_SIZE).rev() {
+        for x in (0..ROOM_SIZE).rev() {
+            let current = distances.get(xy(x, y));
+            if current == 0 {
+                continue;
+            }
+            let (x, y) = (x as i16, y as i16);
+            let nearest = [(1, 1), (0, 1), (-1, 1), (1, 0)].into_iter()
+                .map(|(dx, dy)| at(&distances, x + dx, y + dy))
+                .min()
+                .unwrap_or(0);
+            distances.set(xy(x as u8, y as u8), current.min(nearest.saturating_add(1)));
+        }
+    }
+
+    distances
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn distance_transform_measures_distance_to_walls_and_edges() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        let terrain = CompressedRoomTerrain::from_sparse(Terrain::Plain, [(xy(20, 20), Terrain::Wall), (xy(21, 20), Terrain::Swamp)]);
+        let distances = distance_transform(&terrain);
+
+        assert_eq!(distances.get(xy(20, 20)), 0);
+        assert_eq!(distances.get(xy(21, 20)), 1);
+        assert_eq!(distances.get(xy(23, 23)), 3);
+        assert_eq!(distances.get(xy(0, 30)), 1);
+        assert_eq!(distances.get(xy(3, 30)), 4);
+        assert_eq!(distances.get(xy(35, 35)), 15);
+    }
+}
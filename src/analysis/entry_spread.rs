@@ -0,0 +1,61 @@
+use screeps::Terrain;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+use crate::pathfinding::{multi_source_bfs, TerrainCosts, UNREACHABLE};
+use crate::room_connectivity::exit::{RoomExitsData, edge_offset_to_xy};
+
+/// Marks every tile that a creep entering the room could reach within `ticks` moves.
+///
+/// The spread starts from every exit tile at tick 0 and expands one tile in all 8 directions per
+/// tick, without passing through walls. Movement fatigue is not taken into account, so this is the
+/// worst case for fast-moving invaders. This is the standard primitive for deciding rampart depth
+/// and where it's safe to build.
+pub fn entry_spread(terrain: &CompressedRoomTerrain, exits: &RoomExitsData, ticks: u16) -> RoomBitGrid {
+    let sources: Vec<_> = exits.iter()
+        .flat_map(|exit| (exit.start()..=exit.end()).map(move |offset| edge_offset_to_xy(exit.exit_direction(), offset)))
+        .filter(|xy| terrain.get_xy(*xy) != Terrain::Wall)
+        .collect();
+
+    // Every step takes one tick, whatever the terrain
+    let moves = multi_source_bfs(terrain, &sources, &TerrainCosts { plain_cost: 1, swamp_cost: 1 });
+    RoomBitGrid::from_fn(|xy| {
+        let moves = moves.get(xy);
+        moves != UNREACHABLE && moves <= ticks
+    })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{RoomName, RoomXY, ROOM_AREA};
+    use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+
+    #[test]
+    pub fn entry_spread_expands_from_exit_tiles() {
+        // Plains everywhere, with walls on every edge except a 3-tile exit at x = 20..=22 on the
+        // top edge
+        let mut bits = [0u8; ROOM_AREA];
+        for i in 0..50 {
+            bits[i] = 1;
+            bits[49 * 50 + i] = 1;
+            bits[i * 50] = 1;
+            bits[i * 50 + 49] = 1;
+        }
+        bits[20..=22].fill(0);
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let edge_terrain = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        let exits = RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, RoomName::new("W1N1").unwrap());
+
+        assert_eq!(entry_spread(&terrain, &exits, 0).popcount(), 3);
+
+        let spread = entry_spread(&terrain, &exits, 1);
+        assert_eq!(spread.popcount(), 3 + 5);
+        assert!(spread.get_xy(RoomXY::checked_new(19, 1).unwrap()));
+        assert!(!spread.get_xy(RoomXY::checked_new(19, 0).unwrap()));
+
+        // Enough ticks covers the entire interior, plus the exit tiles
+        assert_eq!(entry_spread(&terrain, &exits, 100).popcount(), 48 * 48 + 3);
+    }
+}
@@ -0,0 +1,129 @@
+use screeps::Terrain;
+
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::pathfinding::{multi_source_bfs, TerrainCosts, UNREACHABLE};
+use crate::room_connectivity::exit::{RoomExit, edge_offset_to_xy};
+
+/// Which of a room's exits can be reached from each other by walking within the room.
+///
+/// Exits are indexed in the same order as [RoomExitsData::get_exit_by_index](crate::room_connectivity::exit::RoomExitsData::get_exit_by_index):
+/// top, right, bottom, then left, each in LTR/top-to-bottom order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitReachability {
+    /// The region each exit belongs to; exits in the same region can reach each other.
+    regions: Vec<u8>,
+}
+
+impl ExitReachability {
+    /// Calculates exit reachability for the terrain.
+    pub fn new_from_compressed_terrain(terrain: &CompressedRoomTerrain) -> Self {
+        let edge_terrain = RoomEdgeTerrain::new_from_compressed_room_terrain(terrain);
+        let exits = RoomExit::get_all_exits(&edge_terrain);
+
+        let mut regions: Vec<Option<u8>> = vec![None; exits.len()];
+        let mut next_region = 0;
+        for i in 0..exits.len() {
+            if regions[i].is_some() {
+                continue;
+            }
+
+            let direction = exits[i].exit_direction();
+            let sources: Vec<_> = (exits[i].start()..=exits[i].end())
+                .map(|offset| edge_offset_to_xy(direction, offset))
+                .filter(|xy| terrain.get_xy(*xy) != Terrain::Wall)
+                .collect();
+            let path_costs = multi_source_bfs(terrain, &sources, &TerrainCosts::default());
+
+            for (j, exit) in exits.iter().enumerate().skip(i) {
+                // Exit tiles are contiguous, so one tile being reachable means they all are
+                if path_costs.get(edge_offset_to_xy(exit.exit_direction(), exit.start())) != UNREACHABLE {
+                    regions[j] = Some(next_region);
+                }
+            }
+            next_region += 1;
+        }
+
+        Self { regions: regions.into_iter().map(|r| r.unwrap_or(0)).collect() }
+    }
+
+    /// The number of exits in the room.
+    pub fn num_exits(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Returns true if exit `to` can be reached from exit `from`.
+    ///
+    /// Returns false if either index is out of range.
+    pub fn is_reachable(&self, from: usize, to: usize) -> bool {
+        match (self.regions.get(from), self.regions.get(to)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// The region of an exit, or None if the index is out of range.
+    ///
+    /// Exits in the same region can reach each other.
+    pub fn region(&self, exit: usize) -> Option<u8> {
+        self.regions.get(exit).copied()
+    }
+
+    /// The number of distinct regions that the room's exits fall into.
+    pub fn num_regions(&self) -> usize {
+        self.regions.iter().max().map(|max| *max as usize + 1).unwrap_or(0)
+    }
+
+    /// The serialized form of this data: one byte holding the region of each exit, in exit order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.regions.clone()
+    }
+
+    /// Creates exit reachability data from its serialized form, as returned by
+    /// [to_bytes](ExitReachability::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self { regions: bytes.to_vec() }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.regions.len() + std::mem::size_of::<Vec<u8>>()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn exit_reachability_splits_rooms_divided_by_walls() {
+        // A vertical wall down the middle of the room separates the left and right halves
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..50 {
+            bits[y * 50] = 1;
+            bits[y * 50 + 25] = 1;
+            bits[y * 50 + 49] = 1;
+        }
+        for x in 0..50 {
+            bits[x] = 1;
+            bits[49 * 50 + x] = 1;
+        }
+        // Top edge exits on both sides of the wall, and a single bottom edge exit on the right
+        bits[5..10].fill(0);
+        for x in 30..35 {
+            bits[x] = 0;
+            bits[49 * 50 + x] = 0;
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let reachability = ExitReachability::new_from_compressed_terrain(&terrain);
+
+        assert_eq!(reachability.num_exits(), 3);
+        assert_eq!(reachability.num_regions(), 2);
+        assert!(!reachability.is_reachable(0, 1));
+        assert!(reachability.is_reachable(1, 2));
+        assert!(!reachability.is_reachable(0, 3));
+
+        assert_eq!(ExitReachability::from_bytes(&reachability.to_bytes()), reachability);
+    }
+}
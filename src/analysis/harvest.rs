@@ -0,0 +1,46 @@
+use screeps::{RoomXY, Terrain};
+
+use crate::terrain_source::TerrainSource;
+
+/// The walkable tiles adjacent to a source or mineral at `pos`, where a creep can stand to harvest
+/// it.
+///
+/// Only terrain is considered; structures and creeps aren't. Tiles are returned in the order of
+/// [RoomXY::neighbors].
+pub fn harvest_positions(terrain: &impl TerrainSource, pos: RoomXY) -> Vec<RoomXY> {
+    pos.neighbors().into_iter()
+        .filter(|xy| terrain.get_xy(*xy) != Terrain::Wall)
+        .collect()
+}
+
+/// The number of creeps that can harvest a source or mineral at `pos` at the same time, based on
+/// terrain alone.
+pub fn max_simultaneous_harvesters(terrain: &impl TerrainSource, pos: RoomXY) -> usize {
+    pos.neighbors().into_iter()
+        .filter(|xy| terrain.get_xy(*xy) != Terrain::Wall)
+        .count()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn harvest_positions_skips_walls_and_room_bounds() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        // A source in a wall pocket, with a swamp and a plain tile next to it
+        let mut terrain = CompressedRoomTerrain::from_sparse(Terrain::Wall, []);
+        terrain.set_many([(xy(10, 9), Terrain::Plain), (xy(11, 11), Terrain::Swamp)]);
+
+        let mut positions = harvest_positions(&terrain, xy(10, 10));
+        positions.sort_by_key(|xy| (xy.x.u8(), xy.y.u8()));
+        assert_eq!(positions, [xy(10, 9), xy(11, 11)]);
+        assert_eq!(max_simultaneous_harvesters(&terrain, xy(10, 10)), 2);
+
+        // A mineral in the corner only has 3 neighbors
+        let plains = CompressedRoomTerrain::from_sparse(Terrain::Plain, []);
+        assert_eq!(max_simultaneous_harvesters(&plains, xy(0, 0)), 3);
+    }
+}
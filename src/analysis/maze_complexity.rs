@@ -0,0 +1,125 @@
+use screeps::{Terrain, ROOM_USIZE};
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+
+/// The largest average clearance that's tracked; clearances beyond this are counted as this.
+const MAX_TRACKED_CLEARANCE: usize = ROOM_USIZE / 2;
+
+/// A score from 0 to 1 of how maze-like a room's interior is.
+///
+/// This is the mean of three components, each from 0 to 1:
+/// - Run density: how often rows switch between walls and walkable tiles
+/// - Fragmentation: how many separate walkable regions there are, as `1 - 1 / regions`
+/// - Tightness: how close walkable tiles are to walls, as `1 / (1 + average clearance)`
+///
+/// Open rooms score near 0, while winding, cramped, or partitioned rooms score higher. This
+/// correlates with the CPU cost of pathfinding through the room, since the pathfinder explores
+/// more tiles in mazes.
+pub fn maze_complexity(terrain: &CompressedRoomTerrain) -> f32 {
+    let passable = RoomBitGrid::from_fn(|xy| terrain.get_xy(xy) != Terrain::Wall);
+    if passable.is_empty() {
+        return 0.0;
+    }
+
+    let max_transitions = (ROOM_USIZE * (ROOM_USIZE - 1)) as f32;
+    let run_density = count_row_transitions(&passable) as f32 / max_transitions;
+    let fragmentation = 1.0 - 1.0 / count_regions(&passable) as f32;
+    let tightness = 1.0 / (1.0 + average_clearance(&passable));
+
+    (run_density + fragmentation + tightness) / 3.0
+}
+
+/// Internal helper function that counts the places where adjacent tiles in a row switch between
+/// set and unset.
+fn count_row_transitions(grid: &RoomBitGrid) -> usize {
+    const TRANSITION_MASK: u64 = (1 << (ROOM_USIZE - 1)) - 1;
+    (0..ROOM_USIZE as u8)
+        .map(|y| {
+            let row = grid.row_bits(y);
+            ((row ^ (row >> 1)) & TRANSITION_MASK).count_ones() as usize
+        })
+        .sum()
+}
+
+/// Internal helper function that counts the 8-connected regions of set tiles.
+fn count_regions(grid: &RoomBitGrid) -> usize {
+    let mut remaining = *grid;
+    let mut regions = 0;
+
+    loop {
+        let Some(seed) = remaining.iter().next() else {
+            break;
+        };
+        let mut region = RoomBitGrid::new();
+        region.set_xy(seed, true);
+        loop {
+            let grown = region.dilate().intersection(&remaining);
+            if grown == region {
+                break;
+            }
+            region = grown;
+        }
+        remaining = remaining.difference(&region);
+        regions += 1;
+    }
+
+    regions
+}
+
+/// Internal helper function that calculates the average Chebyshev distance from each set tile to
+/// the nearest unset tile inside the room, counting the set tile itself as distance 1.
+fn average_clearance(grid: &RoomBitGrid) -> f32 {
+    let total = grid.popcount();
+    let mut eroded = *grid;
+    let mut sum = 0;
+
+    // Each erosion removes the tiles at the current distance, so summing the tiles that survive
+    // each round sums the distances
+    for _ in 0..MAX_TRACKED_CLEARANCE {
+        if eroded.is_empty() {
+            break;
+        }
+        sum += eroded.popcount();
+        eroded = eroded.erode();
+    }
+
+    sum as f32 / total as f32
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn maze_complexity_ranks_mazes_above_open_rooms() {
+        let open = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+
+        // Walls on every other column, with a single gap at the bottom of each
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..49 {
+            for x in (1..50).step_by(2) {
+                bits[y * 50 + x] = 1;
+            }
+        }
+        let maze = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        // A wall down the middle splits the room into two open halves
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..50 {
+            bits[y * 50 + 25] = 1;
+        }
+        let split = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let open_score = maze_complexity(&open);
+        let split_score = maze_complexity(&split);
+        let maze_score = maze_complexity(&maze);
+        assert!(open_score < split_score, "{open_score} >= {split_score}");
+        assert!(split_score < maze_score, "{split_score} >= {maze_score}");
+        assert!((0.0..=1.0).contains(&maze_score));
+
+        assert_eq!(count_regions(&RoomBitGrid::from_fn(|xy| split.get_xy(xy) != Terrain::Wall)), 2);
+    }
+}
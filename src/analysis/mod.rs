@@ -0,0 +1,18 @@
+//! Per-room terrain analyses.
+
+pub mod clearance;
+pub mod similarity;
+pub mod entry_spread;
+pub mod exit_reachability;
+pub mod cache;
+pub mod quad;
+pub mod maze_complexity;
+pub mod harvest;
+pub mod upgrade;
+pub mod retreat;
+pub mod shard;
+pub mod components;
+pub mod blast;
+pub mod distance_transform;
+pub mod registry;
+pub mod danger;
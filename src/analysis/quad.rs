@@ -0,0 +1,55 @@
+use screeps::{RoomXY, Terrain, ROOM_SIZE};
+
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+use crate::terrain_source::TerrainSource;
+
+/// The tiles of the 2x2 formation anchored at `anchor`, top-left first, or None if it doesn't fit
+/// in the room.
+pub fn quad_tiles(anchor: RoomXY) -> Option<[RoomXY; 4]> {
+    let (x, y) = (anchor.x.u8(), anchor.y.u8());
+    if x + 1 >= ROOM_SIZE || y + 1 >= ROOM_SIZE {
+        return None;
+    }
+    // Safety: x + 1 and y + 1 were checked to be inside the room above
+    unsafe {
+        Some([
+            anchor,
+            RoomXY::unchecked_new(x + 1, y),
+            RoomXY::unchecked_new(x, y + 1),
+            RoomXY::unchecked_new(x + 1, y + 1),
+        ])
+    }
+}
+
+/// Marks the tiles where a 2x2 formation fits, with its top-left creep on the tile.
+///
+/// This is an erosion of the passable tiles toward the top-left: a tile is marked if it and its
+/// right, bottom, and bottom-right neighbors are all non-wall tiles.
+pub fn quad_passable_mask(terrain: &impl TerrainSource) -> RoomBitGrid {
+    let passable = RoomBitGrid::from_fn(|xy| terrain.get_xy(xy) != Terrain::Wall);
+    RoomBitGrid::from_fn(|xy| quad_tiles(xy).is_some_and(|tiles| tiles.iter().all(|tile| passable.get_xy(*tile))))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn quad_passable_mask_excludes_tiles_next_to_walls() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[20 * 50 + 20] = 1;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let mask = quad_passable_mask(&terrain);
+
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        for blocked in [xy(19, 19), xy(20, 19), xy(19, 20), xy(20, 20), xy(49, 0), xy(0, 49)] {
+            assert!(!mask.get_xy(blocked), "{blocked} should be blocked");
+        }
+        assert!(mask.get_xy(xy(21, 20)));
+        assert!(mask.get_xy(xy(48, 48)));
+        assert_eq!(mask.popcount(), 49 * 49 - 4);
+    }
+}
@@ -0,0 +1,190 @@
+//! A registry of the per-room analyses that can be run by name, such as from the command line.
+//!
+//! Each analysis is a pure function of a room's terrain that produces a blob, which is stored in
+//! the `analysis_blob` table under the terrain's fingerprint and the analysis's stored name.
+
+use std::time::Duration;
+#[cfg(feature = "db")]
+use std::time::Instant;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::RoomName;
+
+use crate::analysis::cache::{EXIT_REACHABILITY_ANALYSIS, MAZE_COMPLEXITY_ANALYSIS};
+use crate::analysis::components::{connected_components, Connectivity};
+use crate::analysis::distance_transform::distance_transform;
+use crate::analysis::exit_reachability::ExitReachability;
+use crate::analysis::maze_complexity::maze_complexity;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+use crate::error::MapProcessingError;
+
+/// The analysis name used to store [distance_transform] results, one byte per tile.
+pub const DISTANCE_TRANSFORM_ANALYSIS: &str = "distance-transform";
+
+/// The analysis name used to store [connected_components] labels, a little-endian u16 per tile.
+pub const REGIONS_ANALYSIS: &str = "regions";
+
+/// An analysis that can be run by name.
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredAnalysis {
+    /// The name used to select the analysis.
+    pub name: &'static str,
+    /// The analysis name its results are stored under.
+    pub stored_as: &'static str,
+    /// Runs the analysis, returning the blob to store.
+    pub run: fn(&CompressedRoomTerrain) -> Vec<u8>,
+}
+
+/// Every registered analysis.
+pub const ANALYSES: &[RegisteredAnalysis] = &[
+    RegisteredAnalysis {
+        name: "distance-transform",
+        stored_as: DISTANCE_TRANSFORM_ANALYSIS,
+        run: |terrain| distance_transform(terrain).as_slice().to_vec(),
+    },
+    RegisteredAnalysis {
+        name: "regions",
+        stored_as: REGIONS_ANALYSIS,
+        run: |terrain| connected_components(terrain, Connectivity::Eight).labels().as_slice().iter().flat_map(|label| label.to_le_bytes()).collect(),
+    },
+    RegisteredAnalysis {
+        name: "exits",
+        stored_as: EXIT_REACHABILITY_ANALYSIS,
+        run: |terrain| ExitReachability::new_from_compressed_terrain(terrain).to_bytes(),
+    },
+    RegisteredAnalysis {
+        name: "score",
+        stored_as: MAZE_COMPLEXITY_ANALYSIS,
+        run: |terrain| maze_complexity(terrain).to_le_bytes().to_vec(),
+    },
+];
+
+/// Looks up a registered analysis by name.
+pub fn find_analysis(name: &str) -> Option<&'static RegisteredAnalysis> {
+    ANALYSES.iter().find(|analysis| analysis.name == name)
+}
+
+/// Parses a comma-separated list of analysis names, like `"regions,score"`.
+pub fn parse_analysis_list(names: &str) -> Result<Vec<&'static RegisteredAnalysis>, MapProcessingError> {
+    names.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| find_analysis(name).ok_or_else(|| MapProcessingError::InvalidData { kind: "analysis", reason: format!("unknown analysis: {name}") }))
+        .collect()
+}
+
+/// Timing and size totals for one analysis over a set of rooms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisRunSummary {
+    pub name: &'static str,
+    pub rooms: usize,
+    /// The time spent running the analysis, not including storing the results.
+    pub elapsed: Duration,
+    /// The total size of the stored blobs, in bytes.
+    pub bytes: usize,
+}
+
+/// The results of [run_analyses].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisRunReport {
+    /// A summary per analysis, in the order the analyses were given.
+    pub summaries: Vec<AnalysisRunSummary>,
+    /// The requested rooms that were skipped because they have no stored terrain.
+    pub missing_rooms: Vec<RoomName>,
+}
+
+/// Runs the analyses over the rooms' stored terrain, and stores the results in the
+/// `analysis_blob` table.
+///
+/// Rooms without stored terrain are skipped and listed in the report. The results are written in
+/// a single transaction, so either every result is stored or none are.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(rooms = rooms.len())))]
+#[cfg(feature = "db")]
+pub fn run_analyses(conn: &Connection, rooms: &[RoomName], analyses: &[&RegisteredAnalysis]) -> Result<AnalysisRunReport, MapProcessingError> {
+    compressed_terrain_db::create_analysis_table_if_not_exists(conn)?;
+
+    let mut summaries: Vec<AnalysisRunSummary> = analyses.iter()
+        .map(|analysis| AnalysisRunSummary { name: analysis.name, rooms: 0, elapsed: Duration::ZERO, bytes: 0 })
+        .collect();
+    let mut missing_rooms = Vec::new();
+
+    let mut terrains = compressed_terrain_db::get_terrains_for_rooms(conn, rooms)?;
+    let transaction = conn.unchecked_transaction()?;
+
+    for room in rooms {
+        let Some(terrain) = terrains.remove(room) else {
+            missing_rooms.push(*room);
+            continue;
+        };
+        let fingerprint = terrain.fingerprint();
+
+        for (analysis, summary) in analyses.iter().zip(summaries.iter_mut()) {
+            let start = Instant::now();
+            let bytes = (analysis.run)(&terrain);
+            summary.elapsed += start.elapsed();
+            summary.rooms += 1;
+            summary.bytes += bytes.len();
+
+            compressed_terrain_db::set_analysis_blob(&transaction, fingerprint, analysis.stored_as, "", &bytes)?;
+        }
+    }
+
+    transaction.commit()?;
+
+    #[cfg(feature = "tracing")]
+    for summary in &summaries {
+        tracing::info!(analysis = summary.name, rooms = summary.rooms, bytes_written = summary.bytes, elapsed = ?summary.elapsed, "ran analysis");
+    }
+
+    Ok(AnalysisRunReport { summaries, missing_rooms })
+}
+
+
+#[cfg(all(test, feature = "db"))]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+    use crate::analysis::cache::AnalysisCache;
+
+    #[test]
+    pub fn run_analyses_stores_selected_analyses() {
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        let mut bits = [0u8; ROOM_AREA];
+        bits[25 * 50..26 * 50].fill(1); // A horizontal wall across the room
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W1N1").unwrap();
+        compressed_terrain_db::add_terrain_for_room(&conn, room, &terrain).unwrap();
+
+        let analyses = parse_analysis_list("regions, score").unwrap();
+        let summaries = run_analyses(&conn, &[room], &analyses).unwrap().summaries;
+        assert_eq!(summaries.iter().map(|summary| summary.name).collect::<Vec<_>>(), vec!["regions", "score"]);
+        assert_eq!(summaries[0].bytes, ROOM_AREA * 2);
+        assert_eq!(summaries[1].rooms, 1);
+
+        let cache = AnalysisCache::new(&conn).unwrap();
+        assert_eq!(cache.maze_complexity(&terrain).unwrap(), maze_complexity(&terrain));
+        assert!(compressed_terrain_db::get_analysis_blob(&conn, terrain.fingerprint(), REGIONS_ANALYSIS, "").unwrap().is_some());
+        assert!(compressed_terrain_db::get_analysis_blob(&conn, terrain.fingerprint(), DISTANCE_TRANSFORM_ANALYSIS, "").unwrap().is_none());
+
+        assert!(parse_analysis_list("regions,nope").is_err());
+        assert_eq!(ANALYSES.iter().filter(|analysis| find_analysis(analysis.name).is_some()).count(), 4);
+    }
+
+    #[test]
+    pub fn run_analyses_reports_missing_rooms() {
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let room = RoomName::new("W1N1").unwrap();
+        let missing_room = RoomName::new("W2N2").unwrap();
+        compressed_terrain_db::add_terrain_for_room(&conn, room, &terrain).unwrap();
+
+        let report = run_analyses(&conn, &[missing_room, room], &parse_analysis_list("score").unwrap()).unwrap();
+        assert_eq!(report.missing_rooms, vec![missing_room]);
+        assert_eq!(report.summaries[0].rooms, 1);
+        assert!(compressed_terrain_db::get_analysis_blob(&conn, terrain.fingerprint(), MAZE_COMPLEXITY_ANALYSIS, "").unwrap().is_some());
+    }
+}
@@ -0,0 +1,83 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
+
+use crate::pathfinding::{TerrainCosts, UNREACHABLE};
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::room_matrix::RoomMatrix;
+use crate::terrain_source::TerrainSource;
+
+/// Calculates the minimum number of ticks needed to reach any exit tile from every tile in the
+/// room, for a creep that takes `move_speed` ticks to move onto each kind of terrain.
+///
+/// Exit tiles have a cost of 0, and tiles that can't reach an exit have a cost of
+/// [UNREACHABLE]. Unlike [exit_flow_field](crate::pathfinding::exit_flow_field), each step costs
+/// the terrain being moved onto on the way *toward* the exit, so a creep standing in a swamp isn't
+/// charged for leaving it. See [TerrainCosts::for_body] for converting a creep body into costs.
+pub fn ticks_to_exit_map(terrain: &impl TerrainSource, exits: &RoomExitsData, move_speed: &TerrainCosts) -> RoomMatrix<u16> {
+    let mut output = RoomMatrix::new(UNREACHABLE);
+    let mut heap = BinaryHeap::new();
+
+    exits.for_each_exit_tile(|xy| {
+        output.set(xy, 0);
+        heap.push(Reverse((0u16, xy_to_terrain_index(xy))));
+    });
+
+    while let Some(Reverse((cost, idx))) = heap.pop() {
+        let xy = terrain_index_to_xy(idx);
+        if cost > output.get(xy) {
+            // We've already found a cheaper way from this tile
+            continue;
+        }
+
+        // Neighbors step onto this tile to get closer to the exit
+        let Some(step_cost) = move_speed.cost(terrain.get_xy(xy)) else {
+            continue;
+        };
+        let new_cost = cost.saturating_add(step_cost).min(UNREACHABLE - 1);
+
+        for neighbor in xy.neighbors() {
+            if move_speed.cost(terrain.get_xy(neighbor)).is_some() && new_cost < output.get(neighbor) {
+                output.set(neighbor, new_cost);
+                heap.push(Reverse((new_cost, xy_to_terrain_index(neighbor))));
+            }
+        }
+    }
+
+    output
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{RoomName, RoomXY, ROOM_AREA};
+    use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn ticks_to_exit_map_charges_terrain_moved_onto_toward_the_exit() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        // Walls everywhere except a corridor from (10, 0) down to (10, 5), with a swamp at (10, 2)
+        let mut bits = [1u8; ROOM_AREA];
+        for y in 0..=5 {
+            bits[y * 50 + 10] = 0;
+        }
+        bits[2 * 50 + 10] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let edges = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        let exits = RoomExitsData::new_from_compressed_edge_terrain_data(edges, RoomName::new("W1N1").unwrap());
+
+        let slow = TerrainCosts::for_body(1, 2).unwrap();
+        assert_eq!(slow, TerrainCosts { plain_cost: 2, swamp_cost: 10 });
+
+        let ticks = ticks_to_exit_map(&terrain, &exits, &slow);
+        assert_eq!(ticks.get(xy(10, 0)), 0);
+        assert_eq!(ticks.get(xy(10, 1)), 2);
+        // Leaving the swamp is as cheap as leaving a plain
+        assert_eq!(ticks.get(xy(10, 2)), 4);
+        assert_eq!(ticks.get(xy(10, 3)), 14);
+        assert_eq!(ticks.get(xy(10, 5)), 18);
+        assert_eq!(ticks.get(xy(20, 20)), UNREACHABLE);
+    }
+}
@@ -0,0 +1,222 @@
+//! Runs the per-room analyses over a whole shard, in parallel.
+//!
+//! Every analysis is a pure function of a room's terrain, so the output blobs only depend on the
+//! terrain. The order they're produced in depends on thread scheduling, unless deterministic
+//! ordering is requested, in which case outputs are emitted in the same order as the input rooms
+//! regardless of the number of threads.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "db")]
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::RoomName;
+
+use crate::analysis::registry::ANALYSES;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+#[cfg(feature = "db")]
+use crate::error::MapProcessingError;
+
+/// The output blob of a single analysis of a single room.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisOutput {
+    pub room: RoomName,
+    /// The fingerprint of the room's terrain, which the blob is stored under.
+    pub fingerprint: u64,
+    pub analysis: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+/// A summary of a shard analysis run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardAnalysisReport {
+    pub rooms_analyzed: usize,
+    /// Rooms whose stored terrain couldn't be decoded, which weren't analyzed.
+    pub rooms_skipped: usize,
+    pub blobs_written: usize,
+    /// A 64-bit FNV-1a hash of every output, in the order they were written.
+    ///
+    /// With deterministic ordering, this is identical across runs and thread counts for the same
+    /// terrain, so it can be used to compare analysis outputs.
+    pub digest: u64,
+}
+
+/// Runs every registered analysis over the rooms on `threads` worker threads, calling `on_output`
+/// on the current thread with each output as it's ready.
+///
+/// With `deterministic` set, outputs are emitted in the order of `rooms`, and in a fixed order of
+/// analyses within each room. Outputs that finish early are held until every room before them is
+/// done. Otherwise they're emitted in whatever order the workers finish them.
+pub fn analyze_rooms(rooms: &[(RoomName, CompressedRoomTerrain)], threads: usize, deterministic: bool, mut on_output: impl FnMut(AnalysisOutput)) {
+    let next_room = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let next_room = &next_room;
+            scope.spawn(move || loop {
+                let idx = next_room.fetch_add(1, Ordering::Relaxed);
+                let Some((room, terrain)) = rooms.get(idx) else {
+                    break;
+                };
+                if sender.send((idx, analyze_room(*room, terrain))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Room index -> outputs, for rooms that finished before an earlier room
+        let mut pending = BTreeMap::new();
+        let mut next_to_emit = 0;
+
+        for (idx, outputs) in receiver {
+            if !deterministic {
+                outputs.into_iter().for_each(&mut on_output);
+                continue;
+            }
+
+            pending.insert(idx, outputs);
+            while let Some(outputs) = pending.remove(&next_to_emit) {
+                outputs.into_iter().for_each(&mut on_output);
+                next_to_emit += 1;
+            }
+        }
+    });
+}
+
+/// Runs every registered analysis over every room in the database, and stores the outputs in the
+/// `analysis_blob` table, where [AnalysisCache](crate::analysis::cache::AnalysisCache) will find
+/// them.
+///
+/// Rooms are analyzed in the order returned by
+/// [get_rooms_with_terrain](compressed_terrain_db::get_rooms_with_terrain). See [analyze_rooms]
+/// for `threads` and `deterministic`. Rooms with malformed terrain are skipped and counted in the
+/// report. The outputs are written in a single transaction, so a failed run doesn't leave a
+/// partial set of blobs behind.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(conn)))]
+#[cfg(feature = "db")]
+pub fn analyze_shard(conn: &Connection, threads: usize, deterministic: bool) -> Result<ShardAnalysisReport, MapProcessingError> {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    compressed_terrain_db::create_analysis_table_if_not_exists(conn)?;
+
+    let mut rooms = Vec::new();
+    let mut rooms_skipped = 0;
+    compressed_terrain_db::iter_room_terrains(conn, |room, terrain| {
+        match terrain {
+            Ok(terrain) => rooms.push((room, terrain)),
+            Err(_) => rooms_skipped += 1,
+        }
+        ControlFlow::<()>::Continue(())
+    })?;
+
+    let mut report = ShardAnalysisReport { rooms_analyzed: rooms.len(), rooms_skipped, blobs_written: 0, digest: FNV_OFFSET_BASIS };
+    let tx = conn.unchecked_transaction()?;
+    let mut result = Ok(());
+    #[cfg(feature = "tracing")]
+    let mut bytes_written = 0;
+
+    analyze_rooms(&rooms, threads, deterministic, |output| {
+        if result.is_err() {
+            return;
+        }
+        result = compressed_terrain_db::set_analysis_blob(&tx, output.fingerprint, output.analysis, "", &output.bytes);
+        #[cfg(feature = "tracing")]
+        {
+            bytes_written += output.bytes.len();
+        }
+
+        let hashed = output.room.to_string().into_bytes().into_iter()
+            .chain(output.analysis.bytes())
+            .chain(output.bytes);
+        report.digest = hashed.fold(report.digest, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+        report.blobs_written += 1;
+    });
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(rooms = report.rooms_analyzed, rooms_skipped = report.rooms_skipped, blobs_written = report.blobs_written, bytes_written, "analyzed shard");
+
+    result?;
+    tx.commit()?;
+    Ok(report)
+}
+
+/// Internal helper function to run every registered analysis on a single room, in the order of
+/// [ANALYSES].
+fn analyze_room(room: RoomName, terrain: &CompressedRoomTerrain) -> Vec<AnalysisOutput> {
+    let fingerprint = terrain.fingerprint();
+
+    ANALYSES.iter()
+        .map(|analysis| AnalysisOutput { room, fingerprint, analysis: analysis.stored_as, bytes: (analysis.run)(terrain) })
+        .collect()
+}
+
+
+#[cfg(all(test, feature = "db"))]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+    use crate::analysis::cache::{AnalysisCache, MAZE_COMPLEXITY_ANALYSIS};
+
+    fn test_rooms() -> Vec<(RoomName, CompressedRoomTerrain)> {
+        (1..=8).map(|i| {
+            let mut bits = [0u8; ROOM_AREA];
+            for (idx, tile) in bits.iter_mut().enumerate() {
+                *tile = if idx % (i + 5) == 0 { 1 } else { 0 };
+            }
+            (RoomName::new(&format!("W{i}N1")).unwrap(), CompressedRoomTerrain::new_from_uncompressed_bits(&bits))
+        }).collect()
+    }
+
+    #[test]
+    pub fn analyze_rooms_is_deterministic_across_thread_counts() {
+        let rooms = test_rooms();
+        let run = |threads| {
+            let mut outputs = Vec::new();
+            analyze_rooms(&rooms, threads, true, |output| outputs.push(output));
+            outputs
+        };
+
+        let single = run(1);
+        assert_eq!(single.len(), rooms.len() * ANALYSES.len());
+        assert_eq!(run(4), single);
+
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        for (room, terrain) in &rooms {
+            compressed_terrain_db::add_terrain_for_room(&conn, *room, terrain).unwrap();
+        }
+        let report = analyze_shard(&conn, 1, true).unwrap();
+        assert_eq!(report.blobs_written, rooms.len() * ANALYSES.len());
+        assert_eq!(analyze_shard(&conn, 3, true).unwrap(), report);
+
+        let cache = AnalysisCache::new(&conn).unwrap();
+        let maze_complexity = single.iter().find(|output| output.analysis == MAZE_COMPLEXITY_ANALYSIS).unwrap();
+        assert_eq!(cache.maze_complexity(&rooms[0].1).unwrap().to_le_bytes().to_vec(), maze_complexity.bytes);
+    }
+
+    #[test]
+    pub fn analyze_shard_skips_malformed_rooms() {
+        let rooms = test_rooms();
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        for (room, terrain) in &rooms[..2] {
+            compressed_terrain_db::add_terrain_for_room(&conn, *room, terrain).unwrap();
+        }
+        conn.execute("UPDATE room_terrain SET data = x'0102' WHERE room_name = ?1", [rooms[0].0.to_string()]).unwrap();
+
+        let report = analyze_shard(&conn, 2, true).unwrap();
+        assert_eq!((report.rooms_analyzed, report.rooms_skipped), (1, 1));
+        assert_eq!(report.blobs_written, ANALYSES.len());
+        let blobs: usize = conn.query_row("SELECT COUNT(*) FROM analysis_blob", [], |row| row.get(0)).unwrap();
+        assert_eq!(blobs, ANALYSES.len());
+    }
+}
@@ -0,0 +1,51 @@
+use screeps::{RoomXY, Terrain, ROOM_AREA, ROOM_USIZE};
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+/// The fraction of tiles that have the same terrain in both rooms, from 0.0 to 1.0.
+pub fn similarity(a: &CompressedRoomTerrain, b: &CompressedRoomTerrain) -> f32 {
+    matching_fraction(|xy| a.get_xy(xy) == b.get_xy(xy))
+}
+
+/// The fraction of tiles that are either walls in both rooms or walls in neither, from 0.0 to 1.0.
+///
+/// This ignores the difference between plains and swamps, which is useful when only the layout of
+/// a room matters.
+pub fn wall_similarity(a: &CompressedRoomTerrain, b: &CompressedRoomTerrain) -> f32 {
+    matching_fraction(|xy| (a.get_xy(xy) == Terrain::Wall) == (b.get_xy(xy) == Terrain::Wall))
+}
+
+/// Internal helper function that computes the fraction of tiles matching a predicate.
+fn matching_fraction(mut matches: impl FnMut(RoomXY) -> bool) -> f32 {
+    let mut count = 0;
+    for y in 0..ROOM_USIZE as u8 {
+        for x in 0..ROOM_USIZE as u8 {
+            // Safety: x and y are both in the range [0, 49]
+            if matches(unsafe { RoomXY::unchecked_new(x, y) }) {
+                count += 1;
+            }
+        }
+    }
+    count as f32 / ROOM_AREA as f32
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn similarity_counts_matching_tiles() {
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+
+        // Swamps on the first 500 tiles, walls on the next 250
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..500].fill(2);
+        bits[500..750].fill(1);
+        let other = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        assert_eq!(similarity(&plains, &plains), 1.0);
+        assert_eq!(similarity(&plains, &other), 0.7);
+        assert_eq!(wall_similarity(&plains, &other), 0.9);
+    }
+}
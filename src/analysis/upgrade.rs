@@ -0,0 +1,84 @@
+use screeps::{RoomXY, Terrain};
+
+use crate::compressed_terrain::sub_rect::square_around;
+use crate::terrain_source::TerrainSource;
+
+/// The range that creeps can upgrade a controller from.
+pub const UPGRADE_RANGE: u8 = 3;
+
+/// The tiles that creeps can upgrade a controller from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeArea {
+    /// The walkable tiles in range of the controller, in row-major order.
+    pub positions: Vec<RoomXY>,
+}
+
+impl UpgradeArea {
+    /// The number of creeps that can upgrade the controller at the same time, based on terrain
+    /// alone.
+    pub fn capacity(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Serializes the area as `(x, y)` byte pairs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.positions.iter().flat_map(|xy| [xy.x.u8(), xy.y.u8()]).collect()
+    }
+
+    /// Deserializes an area written by [to_bytes](UpgradeArea::to_bytes), or returns None if the
+    /// bytes are malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if !bytes.len().is_multiple_of(2) {
+            return None;
+        }
+        let positions = bytes.chunks_exact(2)
+            .map(|pair| RoomXY::checked_new(pair[0], pair[1]).ok())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { positions })
+    }
+}
+
+/// Finds the walkable tiles within `range` of a controller, where upgraders, and the containers
+/// or links feeding them, can be placed.
+///
+/// Use [UPGRADE_RANGE] for the tiles creeps can upgrade from. Only terrain is considered, and the
+/// controller's own tile is excluded.
+pub fn upgrade_positions(terrain: &impl TerrainSource, controller_pos: RoomXY, range: u8) -> UpgradeArea {
+    let (top_left, bottom_right) = square_around(controller_pos, range);
+
+    let mut positions = Vec::new();
+    for y in top_left.y.u8()..=bottom_right.y.u8() {
+        for x in top_left.x.u8()..=bottom_right.x.u8() {
+            // Safety: x and y are between two tiles in the room
+            let xy = unsafe { RoomXY::unchecked_new(x, y) };
+            if xy != controller_pos && terrain.get_xy(xy) != Terrain::Wall {
+                positions.push(xy);
+            }
+        }
+    }
+
+    UpgradeArea { positions }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn upgrade_positions_counts_walkable_tiles_in_range() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        let mut terrain = CompressedRoomTerrain::from_sparse(Terrain::Plain, []);
+        terrain.set_many([(xy(20, 20), Terrain::Wall), (xy(17, 17), Terrain::Wall), (xy(23, 20), Terrain::Swamp)]);
+
+        let area = upgrade_positions(&terrain, xy(20, 20), UPGRADE_RANGE);
+        assert_eq!(area.capacity(), 7 * 7 - 2);
+        assert!(!area.positions.contains(&xy(17, 17)));
+        assert!(area.positions.contains(&xy(23, 20)));
+        assert_eq!(UpgradeArea::from_bytes(&area.to_bytes()), Some(area));
+
+        // Areas are clipped to the room
+        assert_eq!(upgrade_positions(&terrain, xy(1, 1), UPGRADE_RANGE).capacity(), 5 * 5 - 1);
+    }
+}
@@ -1,10 +1,10 @@
 use std::env;
-use std::collections::HashMap;
-use screeps::{RoomName, LocalRoomTerrain};
-use screeps_utils::offline_map::load_shard_map_json;
 
-use screeps_map_processing::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
-use screeps_map_processing::compressed_terrain_db;
+use screeps_map_processing::compressed_terrain_db::{self, ShardDb, DEFAULT_SHARD};
+use screeps_map_processing::map_import::{self, MapImport};
+use screeps_map_processing::normalize::NormalizationRules;
+use screeps_map_processing::terrain_repository::TerrainRepository;
+use screeps_map_processing::terrain_pyramid::TerrainPyramid;
 
 
 pub fn main() {
@@ -12,16 +12,48 @@ pub fn main() {
     println!("{:?}", args);
     let path_to_shard_map_file = &args[1];
     let output_file = &args[2];
-    let terrains_map = load_all_room_terrains_from_map(&path_to_shard_map_file);
+
+    // Optional comma-separated list of normalization rules; defaults to the standard rule set
+    let rules = match args.get(3) {
+        Some(names) => match NormalizationRules::from_names(names) {
+            Ok(rules) => rules,
+            Err(e) => {
+                println!("{e}");
+                return;
+            }
+        },
+        None => NormalizationRules::standard(),
+    };
+    println!("Normalization rules: {rules}");
+
+    let map_data = match map_import::load_map_json_with_rules(path_to_shard_map_file, &rules) {
+        Ok(map_data) => map_data,
+        Err(error) => {
+            println!("Error loading {path_to_shard_map_file}: {error}");
+            return;
+        }
+    };
+    report_import_problems(&map_data);
 
     if let Ok(conn) = compressed_terrain_db::open_db_file(output_file) {
-        let create_table_res = compressed_terrain_db::create_terrain_table_if_not_exists(&conn);
-        if create_table_res.is_ok() {
-            for (name, terrain) in terrains_map {
-                let compressed_terrain = process_terrain(&terrain);
-                let insert_res = compressed_terrain_db::add_terrain_for_room(&conn, name, &compressed_terrain);
-                if let Err(error) = insert_res {
-                    println!("Error inserting {name}: {error}");
+        // Migrating creates every table, and removes duplicate rooms left by older versions so
+        // terrain can be upserted
+        if let Err(error) = compressed_terrain_db::migrate(&conn) {
+            println!("Error migrating database: {error}");
+        } else {
+            if let Err(error) = compressed_terrain_db::set_normalization_rules(&conn, &rules) {
+                println!("Error recording normalization rules: {error}");
+            }
+
+            let mut repo = ShardDb::new(&conn, DEFAULT_SHARD);
+            if let Err(error) = repo.put_terrains(&map_data.rooms) {
+                println!("Error inserting terrain: {error}");
+            }
+
+            for (name, compressed_terrain) in &map_data.rooms {
+                let pyramid = TerrainPyramid::new_from_compressed_terrain(compressed_terrain);
+                if let Err(error) = repo.add_pyramid_for_room(*name, &pyramid) {
+                    println!("Error inserting pyramid for {name}: {error}");
                 }
 
                 //break; // Only do one file for testing
@@ -30,22 +62,12 @@ pub fn main() {
     }
 }
 
-pub fn load_all_room_terrains_from_map(map_path: &str) -> HashMap<RoomName, LocalRoomTerrain> {
-    let mut ret_data = HashMap::new();
-
-    // Load map data
-    let map_data = load_shard_map_json(map_path);
-
-    // Extract terrain data from each room
-    for (room_name, room_data) in map_data.rooms {
-        ret_data.insert(room_name, room_data.terrain);
+pub fn report_import_problems(map_data: &MapImport) {
+    for failure in &map_data.failures {
+        println!("Skipping {failure}");
     }
 
-    ret_data
-}
-
-pub fn process_terrain(terrain: &LocalRoomTerrain) -> CompressedRoomTerrain {
-    CompressedRoomTerrain::new_from_uncompressed_bits(terrain.get_bits())
+    for name in &map_data.rooms_without_terrain {
+        println!("Skipping {name}: no terrain data");
+    }
 }
-
-
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::process::ExitCode;
+
+use rusqlite::Connection;
+use screeps::RoomName;
+use screeps_map_processing::adaptive_terrain::{AdaptiveRoomTerrain, TerrainEncoding};
+use screeps_map_processing::analysis::{registry, shard, similarity};
+use screeps_map_processing::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use screeps_map_processing::compressed_terrain_db::{self, ShardDb, DEFAULT_SHARD};
+use screeps_map_processing::terrain_patch;
+use screeps_map_processing::terrain_repository::TerrainRepository;
+
+
+const USAGE: &str = "Usage:
+    smp patch <db> <room> <patch-file>    Applies a terrain patch file to a room's stored terrain
+    smp find-similar <db> <room> [--walls-only]
+                                          Ranks the rooms most similar to a room
+    smp maintenance <db>                  Compacts the database and reports the space reclaimed
+    smp recompress <db> --dry-run         Reports the space saved by storing each room in its
+                                          optimal encoding
+    smp analyze-shard <db> [--threads <n>] [--deterministic]
+                                          Runs and stores every per-room analysis; with
+                                          --deterministic, outputs are written in room order
+                                          and the digest is reproducible
+    smp analyze <db> [--analyses <a,b,...>] [--rooms <room,room,...>]
+                                          Runs and stores the selected analyses (distance-
+                                          transform, regions, exits, score; all by default) over
+                                          the selected rooms (all by default)
+    smp heatmap <db> <metric> <output.png> [--theme <theme.toml>]
+                                          Renders a shard heatmap of swamp-ratio, wall-ratio,
+                                          exit-count, or maze-complexity, which is read from
+                                          the stored analyses and stored for rooms without it
+                                          (requires the render feature)
+    smp render-room <db> <room> <output.png> [--theme <theme.toml>]
+                                          Renders a room's terrain (requires the render feature)";
+
+/// The number of rooms listed by find-similar.
+const SIMILAR_ROOMS_TO_LIST: usize = 10;
+
+pub fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    let result = match arg_refs.as_slice() {
+        ["patch", db, room, patch_file] => with_repository(db, |repo| patch(repo, room, patch_file)),
+        ["find-similar", db, room] => with_repository(db, |repo| find_similar(repo, room, false)),
+        ["find-similar", db, room, "--walls-only"] => with_repository(db, |repo| find_similar(repo, room, true)),
+        ["maintenance", db] => maintenance(db),
+        ["recompress", db, "--dry-run"] => with_repository(db, |repo| recompress_dry_run(repo)),
+        ["analyze-shard", db, options @ ..] => analyze_shard(db, options),
+        ["analyze", db, options @ ..] => analyze(db, options),
+        #[cfg(feature = "render")]
+        ["heatmap", db, metric, output] => with_repository(db, |repo| heatmap(repo, metric, output, None)),
+        #[cfg(feature = "render")]
+        ["heatmap", db, metric, output, "--theme", theme] => with_repository(db, |repo| heatmap(repo, metric, output, Some(theme))),
+        #[cfg(feature = "render")]
+        ["render-room", db, room, output] => with_repository(db, |repo| render_room(repo, room, output, None)),
+        #[cfg(feature = "render")]
+        ["render-room", db, room, output, "--theme", theme] => with_repository(db, |repo| render_room(repo, room, output, Some(theme))),
+        ["recompress", _db] => Err("recompress only reports the savings for now; pass --dry-run".to_string()),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Opens a terrain database, and runs a command against the terrain of its default shard.
+fn with_repository(db: &str, command: impl FnOnce(&mut ShardDb) -> Result<(), String>) -> Result<(), String> {
+    let conn = open_db(db)?;
+    command(&mut ShardDb::new(&conn, DEFAULT_SHARD))
+}
+
+/// Opens a terrain database, bringing databases written by older versions up to date.
+fn open_db(db: &str) -> Result<Connection, String> {
+    let conn = compressed_terrain_db::open_db_file(db).map_err(|e| format!("Error opening {db}: {e}"))?;
+    compressed_terrain_db::migrate(&conn).map_err(|e| format!("Error migrating {db}: {e}"))?;
+    Ok(conn)
+}
+
+/// Applies a patch file to the stored terrain of a single room.
+fn patch(repo: &mut impl TerrainRepository, room: &str, patch_file: &str) -> Result<(), String> {
+    let room_name = parse_room_name(room)?;
+    let mut terrain = load_terrain(repo, room_name)?;
+
+    terrain_patch::apply_patch_file(&mut terrain, patch_file).map_err(|e| format!("Error applying {patch_file}: {e}"))?;
+
+    repo.put_terrain(room_name, &terrain)
+        .map_err(|e| format!("Error saving terrain for {room_name}: {e}"))?;
+
+    println!("Patched {room_name} with {patch_file}");
+    Ok(())
+}
+
+/// Lists the rooms whose terrain is most similar to a room's terrain.
+fn find_similar(repo: &impl TerrainRepository, room: &str, walls_only: bool) -> Result<(), String> {
+    let room_name = parse_room_name(room)?;
+    let target = load_terrain(repo, room_name)?;
+
+    let rooms = repo.list_rooms().map_err(|e| format!("Error listing rooms: {e}"))?;
+
+    let mut scores = Vec::new();
+    for name in rooms.into_iter().filter(|name| *name != room_name) {
+        let terrain = load_terrain(repo, name)?;
+        let score = if walls_only {
+            similarity::wall_similarity(&target, &terrain)
+        } else {
+            similarity::similarity(&target, &terrain)
+        };
+        scores.push((name, score));
+    }
+
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (name, score) in scores.into_iter().take(SIMILAR_ROOMS_TO_LIST) {
+        println!("{name}: {:.2}%", score * 100.0);
+    }
+
+    Ok(())
+}
+
+/// Compacts the database, reporting the size before and after.
+fn maintenance(db: &str) -> Result<(), String> {
+    let conn = open_db(db)?;
+    let report = compressed_terrain_db::compact_db(&conn).map_err(|e| format!("Error compacting {db}: {e}"))?;
+
+    println!("Size before: {} bytes", report.size_before);
+    println!("Size after: {} bytes", report.size_after);
+    println!("Reclaimed: {} bytes", report.bytes_reclaimed());
+    Ok(())
+}
+
+/// Reports, per room and in aggregate, how much space storing each room in its optimal encoding
+/// would save.
+fn recompress_dry_run(repo: &impl TerrainRepository) -> Result<(), String> {
+    let rooms = repo.list_rooms().map_err(|e| format!("Error listing rooms: {e}"))?;
+
+    let mut total_current = 0;
+    let mut total_optimal = 0;
+    let mut rooms_per_encoding: BTreeMap<TerrainEncoding, usize> = BTreeMap::new();
+
+    for room_name in rooms {
+        let terrain = load_terrain(repo, room_name)?;
+        let adaptive = AdaptiveRoomTerrain::new_from_compressed_terrain(&terrain);
+
+        let current_size = terrain.memory_size();
+        let optimal_size = adaptive.memory_size();
+        total_current += current_size;
+        total_optimal += optimal_size;
+        *rooms_per_encoding.entry(adaptive.encoding()).or_default() += 1;
+
+        println!("{room_name}: {} ({current_size} -> {optimal_size} bytes, saves {})", adaptive.encoding(), current_size.saturating_sub(optimal_size));
+    }
+
+    println!();
+    for (encoding, count) in rooms_per_encoding {
+        println!("Rooms optimally stored as {encoding}: {count}");
+    }
+    println!("Total size (current): {total_current} bytes");
+    println!("Total size (optimal): {total_optimal} bytes");
+    println!("Total savings: {} bytes", total_current.saturating_sub(total_optimal));
+
+    Ok(())
+}
+
+/// Runs every per-room analysis over the database and stores the results.
+fn analyze_shard(db: &str, options: &[&str]) -> Result<(), String> {
+    let mut threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut deterministic = false;
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--deterministic" => deterministic = true,
+            "--threads" => {
+                let value = options.next().ok_or("--threads requires a value")?;
+                threads = value.parse().map_err(|_| format!("Invalid thread count: {value}"))?;
+            },
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let conn = open_db(db)?;
+    let report = shard::analyze_shard(&conn, threads, deterministic).map_err(|e| format!("Error analyzing {db}: {e}"))?;
+
+    println!("Rooms analyzed: {}", report.rooms_analyzed);
+    if report.rooms_skipped > 0 {
+        println!("Rooms skipped (malformed terrain): {}", report.rooms_skipped);
+    }
+    println!("Blobs written: {}", report.blobs_written);
+    println!("Digest: {:016x}", report.digest);
+    Ok(())
+}
+
+/// Runs selected analyses over selected rooms, and stores the results.
+fn analyze(db: &str, options: &[&str]) -> Result<(), String> {
+    let mut analyses: Vec<&registry::RegisteredAnalysis> = registry::ANALYSES.iter().collect();
+    let mut rooms = None;
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--analyses" => {
+                let value = options.next().ok_or("--analyses requires a value")?;
+                analyses = registry::parse_analysis_list(value).map_err(|e| e.to_string())?;
+            },
+            "--rooms" => {
+                let value = options.next().ok_or("--rooms requires a value")?;
+                rooms = Some(value.split(',').map(str::trim).filter(|room| !room.is_empty()).map(parse_room_name).collect::<Result<Vec<_>, _>>()?);
+            },
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let conn = open_db(db)?;
+    let rooms = match rooms {
+        Some(rooms) => rooms,
+        None => ShardDb::new(&conn, DEFAULT_SHARD).list_rooms().map_err(|e| format!("Error listing rooms: {e}"))?,
+    };
+
+    let report = registry::run_analyses(&conn, &rooms, &analyses).map_err(|e| format!("Error analyzing {db}: {e}"))?;
+    for room in report.missing_rooms {
+        println!("Skipping {room}: no terrain data");
+    }
+    for summary in report.summaries {
+        println!("{}: {} rooms in {:.2?}, {} bytes stored", summary.name, summary.rooms, summary.elapsed, summary.bytes);
+    }
+    Ok(())
+}
+
+/// Renders a shard heatmap of a per-room terrain metric, or of an analysis result from the
+/// analysis store.
+#[cfg(feature = "render")]
+fn heatmap(repo: &ShardDb, metric: &str, output: &str, theme: Option<&str>) -> Result<(), String> {
+    use std::collections::HashMap;
+    use screeps::{Terrain, ROOM_AREA};
+    use screeps_map_processing::analysis::cache::AnalysisCache;
+    use screeps_map_processing::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+    use screeps_map_processing::render;
+    use screeps_map_processing::room_connectivity::exit::RoomExitsData;
+
+    /// The size of each room in the rendered image, in pixels.
+    const CELL_SIZE: u32 = 8;
+
+    let theme = load_theme(theme)?;
+    let rooms = repo.list_rooms().map_err(|e| format!("Error listing rooms: {e}"))?;
+    let analyses = AnalysisCache::new(repo.connection()).map_err(|e| format!("Error opening the analysis store: {e}"))?;
+
+    let mut values = HashMap::new();
+    for room_name in rooms {
+        let terrain = load_terrain(repo, room_name)?;
+        let bits = terrain.get_uncompressed_bits();
+        let count_tiles = |wanted: Terrain| bits.iter()
+            .filter(|bits| match **bits & 0b11 {
+                0b00 => wanted == Terrain::Plain,
+                0b10 => wanted == Terrain::Swamp,
+                _ => wanted == Terrain::Wall,
+            })
+            .count();
+
+        let value = match metric {
+            "swamp-ratio" => count_tiles(Terrain::Swamp) as f32 / ROOM_AREA as f32,
+            "wall-ratio" => count_tiles(Terrain::Wall) as f32 / ROOM_AREA as f32,
+            "exit-count" => {
+                let edge_terrain = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+                RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, room_name).num_exits() as f32
+            },
+            "maze-complexity" => analyses.maze_complexity(&terrain)
+                .map_err(|e| format!("Error reading the maze complexity of {room_name}: {e}"))?,
+            _ => return Err(format!("Unknown heatmap metric: {metric}")),
+        };
+        values.insert(room_name, value);
+    }
+
+    let image = render::render_heatmap_with_theme(&values, CELL_SIZE, &theme).ok_or("No rooms to render")?;
+    image.save_png(output).map_err(|e| format!("Error writing {output}: {e}"))?;
+
+    println!("Rendered {} rooms to {output}", values.len());
+    let stats = analyses.stats();
+    if stats.hits + stats.misses > 0 {
+        println!("Read {} stored analysis results, and computed and stored {}", stats.hits, stats.misses);
+    }
+    Ok(())
+}
+
+/// Renders the stored terrain of a single room.
+#[cfg(feature = "render")]
+fn render_room(repo: &impl TerrainRepository, room: &str, output: &str, theme: Option<&str>) -> Result<(), String> {
+    use screeps_map_processing::render;
+
+    /// The size of each tile in the rendered image, in pixels.
+    const TILE_SIZE: u32 = 4;
+
+    let theme = load_theme(theme)?;
+    let room_name = parse_room_name(room)?;
+    let terrain = load_terrain(repo, room_name)?;
+
+    let image = render::render_room(&terrain, room_name, &[], TILE_SIZE, &theme);
+    image.save_png(output).map_err(|e| format!("Error writing {output}: {e}"))?;
+
+    println!("Rendered {room_name} to {output}");
+    Ok(())
+}
+
+/// Loads a render theme file, or the default theme if there isn't one.
+#[cfg(feature = "render")]
+fn load_theme(theme: Option<&str>) -> Result<screeps_map_processing::render::RenderTheme, String> {
+    use screeps_map_processing::render::RenderTheme;
+
+    match theme {
+        Some(path) => RenderTheme::load(path).map_err(|e| format!("Error loading {path}: {e}")),
+        None => Ok(RenderTheme::default()),
+    }
+}
+
+/// Loads the stored terrain for a room, which must have some.
+fn load_terrain(repo: &impl TerrainRepository, room_name: RoomName) -> Result<CompressedRoomTerrain, String> {
+    repo.get_terrain(room_name)
+        .map_err(|e| format!("Error loading terrain for {room_name}: {e}"))?
+        .ok_or_else(|| format!("No terrain stored for {room_name}"))
+}
+
+/// Parses a room name argument.
+fn parse_room_name(room: &str) -> Result<RoomName, String> {
+    RoomName::new(room).map_err(|_| format!("Invalid room name: {room}"))
+}
@@ -0,0 +1,316 @@
+//! A single entry point for bots that need room terrain, exits, and routes.
+//!
+//! Terrain is loaded on demand through a loader function, which can read from a database, a file
+//! export, a memory segment, or anywhere else, and kept in a cache of bounded size. The exits of
+//! every room loaded stay in the connectivity graph, so routes can still use rooms whose terrain has
+//! been evicted. Cached terrain can be saved to and restored from a segment string with the
+//! [segment_codec](crate::segment_codec).
+//!
+//! This module doesn't need a database, so it builds for `wasm32` targets with the default `db`
+//! feature disabled.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use screeps::RoomName;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::room_connectivity::exit::{ExitSummary, RoomExitsData};
+use crate::room_connectivity::graph::ShardConnectivityGraph;
+use crate::room_connectivity::route::RoomRoute;
+use crate::room_connectivity::router::{find_route_with_loader, RouteOptions};
+use crate::segment_codec;
+
+/// A room's cached terrain, and when it was last used.
+struct CachedTerrain {
+    terrain: CompressedRoomTerrain,
+    last_used: u64,
+}
+
+/// A least-recently-used cache of room terrain in front of a loader function.
+struct TerrainCache<L> {
+    loader: L,
+    capacity: usize,
+    terrain: HashMap<RoomName, CachedTerrain>,
+    /// Last use -> room, for every room with cached terrain, so the oldest can be evicted cheaply
+    usage: BTreeMap<u64, RoomName>,
+    /// Rooms the loader returned None for, so they aren't asked for again
+    missing: HashSet<RoomName>,
+    clock: u64,
+}
+
+/// Read-through access to room terrain, exits, and routes, backed by a loader function and a
+/// least-recently-used terrain cache.
+pub struct BotMapApi<L> {
+    cache: TerrainCache<L>,
+    graph: ShardConnectivityGraph,
+    route_options: RouteOptions,
+}
+
+impl<L: FnMut(RoomName) -> Option<CompressedRoomTerrain>> BotMapApi<L> {
+    /// Creates an API that loads terrain with `loader`, which returns None for rooms whose terrain
+    /// isn't available, and caches the terrain of up to `capacity` rooms.
+    pub fn new(capacity: usize, loader: L) -> Self {
+        Self {
+            cache: TerrainCache {
+                loader,
+                capacity: capacity.max(1),
+                terrain: HashMap::new(),
+                usage: BTreeMap::new(),
+                missing: HashSet::new(),
+                clock: 0,
+            },
+            graph: ShardConnectivityGraph::new(),
+            route_options: RouteOptions::default(),
+        }
+    }
+
+    /// Uses the specified cost model for [route](BotMapApi::route).
+    pub fn with_route_options(self, route_options: RouteOptions) -> Self {
+        Self { route_options, ..self }
+    }
+
+    /// The terrain of a room, loading it if it isn't cached.
+    pub fn terrain(&mut self, room: RoomName) -> Option<&CompressedRoomTerrain> {
+        self.load(room)?;
+        self.cache.terrain.get(&room).map(|cached| &cached.terrain)
+    }
+
+    /// A summary of the exits along each edge of a room that has any, loading the room if it
+    /// hasn't been loaded before.
+    pub fn exits(&mut self, room: RoomName) -> Option<Vec<ExitSummary>> {
+        if !self.graph.contains_room(room) {
+            self.load(room)?;
+        }
+        let exits = self.graph.get_room_exits(room)?;
+        Some(exits.to_graph_edges().into_iter().map(|(_, _, summary)| summary).collect())
+    }
+
+    /// The cheapest route between two rooms.
+    ///
+    /// Rooms that haven't been loaded before are loaded as the search reaches them, with
+    /// [find_route_with_loader]. Only their exits are kept, so routing doesn't evict any cached
+    /// terrain.
+    pub fn route(&mut self, from: RoomName, to: RoomName) -> Option<RoomRoute> {
+        let cache = &mut self.cache;
+        find_route_with_loader(&mut self.graph, from, to, &self.route_options, |room| {
+            let terrain = cache.fetch(room)?;
+            Some(RoomExitsData::new_from_compressed_terrain(&terrain, room))
+        })
+    }
+
+    /// The connectivity graph of every room loaded so far.
+    pub fn graph(&self) -> &ShardConnectivityGraph {
+        &self.graph
+    }
+
+    /// The number of rooms with cached terrain.
+    pub fn cached_rooms(&self) -> usize {
+        self.cache.terrain.len()
+    }
+
+    /// Forgets which rooms the loader didn't have, so they're asked for again the next time
+    /// they're needed, such as after they become visible.
+    pub fn forget_missing_rooms(&mut self) {
+        self.cache.missing.clear();
+    }
+
+    /// Caches the terrain of every room in a segment string written by
+    /// [to_segment](BotMapApi::to_segment) or
+    /// [encode_room_terrains](segment_codec::encode_room_terrains), returning the number of rooms
+    /// read.
+    ///
+    /// The rooms replace any cached terrain, and are used most recently in the order they're stored.
+    pub fn load_segment(&mut self, segment: &str) -> Result<usize, MapProcessingError> {
+        let rooms = segment_codec::decode_room_terrains(segment)?;
+        let num_rooms = rooms.len();
+        for (room, terrain) in rooms {
+            self.insert(room, terrain);
+        }
+        Ok(num_rooms)
+    }
+
+    /// Encodes the terrain of every cached room as a segment string, from least to most recently
+    /// used, which can be read back with [load_segment](BotMapApi::load_segment).
+    pub fn to_segment(&self) -> String {
+        let rooms = self.cache.usage.values().filter_map(|room| Some((*room, &self.cache.terrain.get(room)?.terrain)));
+        segment_codec::encode_room_terrains(rooms)
+    }
+
+    /// Internal helper function to make sure a room's terrain is cached, loading it and its exits
+    /// if needed. Returns None if the loader doesn't have the room.
+    fn load(&mut self, room: RoomName) -> Option<()> {
+        if self.cache.touch(room) {
+            return Some(());
+        }
+        let terrain = self.cache.fetch(room)?;
+        self.insert(room, terrain);
+        Some(())
+    }
+
+    /// Internal helper function to cache a room's terrain and add its exits to the graph.
+    fn insert(&mut self, room: RoomName, terrain: CompressedRoomTerrain) {
+        self.graph.add_room(RoomExitsData::new_from_compressed_terrain(&terrain, room));
+        self.cache.insert(room, terrain);
+    }
+}
+
+impl<L: FnMut(RoomName) -> Option<CompressedRoomTerrain>> TerrainCache<L> {
+    /// Marks a room's cached terrain as just used, returning false if it isn't cached.
+    fn touch(&mut self, room: RoomName) -> bool {
+        self.clock += 1;
+        let Some(cached) = self.terrain.get_mut(&room) else {
+            return false;
+        };
+        self.usage.remove(&cached.last_used);
+        cached.last_used = self.clock;
+        self.usage.insert(self.clock, room);
+        true
+    }
+
+    /// Asks the loader for a room's terrain, without caching it, unless it's already known to be
+    /// missing.
+    fn fetch(&mut self, room: RoomName) -> Option<CompressedRoomTerrain> {
+        if self.missing.contains(&room) {
+            return None;
+        }
+        let terrain = (self.loader)(room);
+        if terrain.is_none() {
+            self.missing.insert(room);
+        }
+        terrain
+    }
+
+    /// Caches a room's terrain as the most recently used, evicting the least recently used room if
+    /// the cache is full.
+    fn insert(&mut self, room: RoomName, terrain: CompressedRoomTerrain) {
+        self.clock += 1;
+        if let Some(previous) = self.terrain.remove(&room) {
+            self.usage.remove(&previous.last_used);
+        }
+        if self.terrain.len() >= self.capacity && let Some((_, oldest)) = self.usage.pop_first() {
+            self.terrain.remove(&oldest);
+        }
+        self.missing.remove(&room);
+        self.terrain.insert(room, CachedTerrain { terrain, last_used: self.clock });
+        self.usage.insert(self.clock, room);
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn bot_map_api_loads_through_a_bounded_cache() {
+        let loads = Cell::new(0);
+        let loader = |room: RoomName| {
+            loads.set(loads.get() + 1);
+            // W1N3 doesn't exist, so routes have to go around it
+            (room != RoomName::new("W1N3").unwrap()).then(|| CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]))
+        };
+        let mut api = BotMapApi::new(2, loader).with_route_options(RouteOptions::uniform().with_unknown_room_cost(None));
+        let room = |name: &str| RoomName::new(name).unwrap();
+
+        assert!(api.terrain(room("W1N1")).is_some());
+        assert!(api.terrain(room("W1N1")).is_some());
+        assert_eq!(loads.get(), 1);
+        assert_eq!(api.exits(room("W1N1")).unwrap().len(), 4);
+        assert!(api.terrain(room("W1N3")).is_none());
+
+        api.terrain(room("W2N1"));
+        api.terrain(room("W3N1"));
+        assert_eq!(api.cached_rooms(), 2);
+        // W1N1 was evicted, but its exits are kept
+        let loads_before = loads.get();
+        assert_eq!(api.exits(room("W1N1")).unwrap().len(), 4);
+        assert_eq!(loads.get(), loads_before);
+
+        let route = api.route(room("W1N2"), room("W1N4")).unwrap();
+        assert_eq!(route.len(), 5);
+        assert_eq!(api.cached_rooms(), 2);
+    }
+
+    #[test]
+    pub fn bot_map_api_remembers_missing_rooms_and_evicts_least_recently_used() {
+        let loads = Cell::new(0);
+        let loader = |room: RoomName| {
+            loads.set(loads.get() + 1);
+            (room != RoomName::new("W1N3").unwrap()).then(|| CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]))
+        };
+        let mut api = BotMapApi::new(2, loader);
+        let room = |name: &str| RoomName::new(name).unwrap();
+
+        assert!(api.terrain(room("W1N3")).is_none());
+        assert!(api.exits(room("W1N3")).is_none());
+        assert_eq!(loads.get(), 1);
+        api.forget_missing_rooms();
+        assert!(api.terrain(room("W1N3")).is_none());
+        assert_eq!(loads.get(), 2);
+
+        // W1N1 is used again after W2N1, so W2N1 is the one evicted
+        api.terrain(room("W1N1"));
+        api.terrain(room("W2N1"));
+        api.terrain(room("W1N1"));
+        api.terrain(room("W3N1"));
+        assert_eq!(loads.get(), 5);
+        api.terrain(room("W1N1"));
+        assert_eq!(loads.get(), 5);
+        api.terrain(room("W2N1"));
+        assert_eq!(loads.get(), 6);
+        assert_eq!(api.cached_rooms(), 2);
+    }
+
+    #[test]
+    pub fn bot_map_api_route_only_loads_rooms_the_search_reaches() {
+        let requested = std::cell::RefCell::new(Vec::new());
+        let loader = |room: RoomName| {
+            requested.borrow_mut().push(room);
+            Some(CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]))
+        };
+        let mut api = BotMapApi::new(4, loader).with_route_options(RouteOptions::uniform());
+        let room = |name: &str| RoomName::new(name).unwrap();
+
+        api.terrain(room("W9N9"));
+        let route = api.route(room("W1N1"), room("W3N1")).unwrap();
+        assert_eq!(route.len(), 3);
+
+        // Rooms are loaded around the rooms the search expands, rather than a whole bounding box
+        let requested = requested.borrow();
+        assert!(requested.len() <= 1 + 5 * 3, "loaded {requested:?}");
+        assert!(!requested.contains(&room("W1N4")));
+        assert_eq!(requested.iter().filter(|r| **r == room("W2N1")).count(), 1);
+        // Routing keeps the exits of the rooms it loaded, but doesn't evict cached terrain
+        assert!(api.graph().contains_room(room("W2N1")));
+        assert_eq!(api.cached_rooms(), 1);
+    }
+
+    #[test]
+    pub fn bot_map_api_restores_cached_terrain_from_a_segment() {
+        let plains = || Some(CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]));
+        let mut api = BotMapApi::new(4, |_| plains());
+        let room = |name: &str| RoomName::new(name).unwrap();
+        api.terrain(room("W1N1"));
+        api.terrain(room("W2N1"));
+        let segment = api.to_segment();
+        assert!(segment.chars().count() <= crate::segment_codec::MAX_SEGMENT_LENGTH);
+
+        let loads = Cell::new(0);
+        let mut restored = BotMapApi::new(4, |_| {
+            loads.set(loads.get() + 1);
+            None
+        });
+        assert!(restored.terrain(room("W1N1")).is_none());
+        assert_eq!(restored.load_segment(&segment).unwrap(), 2);
+        assert!(restored.terrain(room("W1N1")).is_some());
+        assert_eq!(restored.exits(room("W2N1")).unwrap().len(), 4);
+        assert_eq!(loads.get(), 1);
+        // W1N1 was used after the segment was loaded, so it's now the most recently used
+        let saved: Vec<_> = crate::segment_codec::decode_room_terrains(&restored.to_segment()).unwrap().into_iter().map(|(room, _)| room).collect();
+        assert_eq!(saved, [room("W2N1"), room("W1N1")]);
+
+        assert!(restored.load_segment("not a segment").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Versioned byte layouts for the crate's packed types, for archives that need to be read on other
+//! platforms, or by readers that aren't written in Rust.
+//!
+//! These layouts are an export format, separate from how the terrain database stores rooms. Every
+//! encoding written by the `encode_*` functions here starts with a version byte, currently
+//! [BYTE_LAYOUT_VERSION], followed by the data for that version. Multi-byte integers are always
+//! little-endian, regardless of platform.
+//!
+//! The database blobs, such as those written by
+//! [add_exits_for_room](crate::compressed_terrain_db::add_exits_for_room) and
+//! [AdaptiveRoomTerrain::to_encoded_bytes](crate::adaptive_terrain::AdaptiveRoomTerrain::to_encoded_bytes),
+//! use the same version 1 layouts for runs and edge terrain, but have no version byte, so they
+//! can't be read with the `decode_*` functions directly.
+//!
+//! # Version 1
+//!
+//! Room exits ([encode_room_exits]): a u16 per exit, with the bits `0LLLLLLDDDPPPPPP` from most to
+//! least significant, where `P` is the start position along the edge (0-49), `D` is the
+//! [ExitDirection](screeps::ExitDirection) value (1 for top, 3 for right, 5 for bottom, 7 for left),
+//! and `L` is the length of the exit.
+//!
+//! Packed RLE runs ([encode_packed_runs]): a u16 per run, with the bits `00TTSSSSSSSSSSSS` from
+//! most to least significant, where `T` is the terrain (0 for plain, 1 for wall, 2 for swamp), and
+//! `S` is the tile index the run starts at, `y * 50 + x`. Runs are in strictly ascending order of
+//! start index.
+//!
+//! Edge terrain ([encode_edge_terrain]): 24 bytes, 6 for each edge in the order top, right, bottom,
+//! left. Each edge's bytes cover tiles 1 to 48 along the edge, left to right or top to bottom, with
+//! the first tile in the most significant bit of the first byte. A set bit is a wall, and a clear
+//! bit is walkable. The corner tiles aren't stored, since they're always walls.
+
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::error::MapProcessingError;
+use crate::room_connectivity::exit::RoomExit;
+
+/// The version byte written at the start of every encoding.
+pub const BYTE_LAYOUT_VERSION: u8 = 1;
+
+/// Encodes exits in the current layout.
+pub fn encode_room_exits(exits: &[RoomExit]) -> Vec<u8> {
+    std::iter::once(BYTE_LAYOUT_VERSION)
+        .chain(u16s_to_le_bytes(exits.iter().map(RoomExit::packed)))
+        .collect()
+}
+
+/// Decodes exits written by [encode_room_exits], validating each exit.
+pub fn decode_room_exits(bytes: &[u8]) -> Result<Vec<RoomExit>, MapProcessingError> {
+    let data = split_version("room exits", bytes)?;
+    u16s_from_le_bytes("room exits", data)?
+        .into_iter()
+        .map(|packed| Ok(RoomExit::try_new_from_packed(packed)?))
+        .collect()
+}
+
+/// Encodes packed RLE runs, as returned by
+/// [PackedRLERoomTerrain::packed_runs](crate::run_length_encoding::rle_terrain::PackedRLERoomTerrain::packed_runs),
+/// in the current layout.
+pub fn encode_packed_runs(runs: &[u16]) -> Vec<u8> {
+    std::iter::once(BYTE_LAYOUT_VERSION)
+        .chain(u16s_to_le_bytes(runs.iter().copied()))
+        .collect()
+}
+
+/// Decodes packed RLE runs written by [encode_packed_runs].
+///
+/// The runs themselves are validated when they're loaded into terrain, such as by
+/// [PackedRLERoomTerrain::try_new_from_packed_runs](crate::run_length_encoding::rle_terrain::PackedRLERoomTerrain::try_new_from_packed_runs).
+pub fn decode_packed_runs(bytes: &[u8]) -> Result<Vec<u16>, MapProcessingError> {
+    let data = split_version("RLE terrain", bytes)?;
+    u16s_from_le_bytes("RLE terrain", data)
+}
+
+/// Encodes edge terrain in the current layout.
+pub fn encode_edge_terrain(edge_terrain: &RoomEdgeTerrain) -> Vec<u8> {
+    std::iter::once(BYTE_LAYOUT_VERSION).chain(edge_terrain.get_raw_bytes()).collect()
+}
+
+/// Decodes edge terrain written by [encode_edge_terrain].
+pub fn decode_edge_terrain(bytes: &[u8]) -> Result<RoomEdgeTerrain, MapProcessingError> {
+    let data = split_version("edge terrain", bytes)?;
+    RoomEdgeTerrain::try_new_from_raw_slice(data)
+}
+
+/// Internal helper function to check the version byte of an encoding, returning the data after it.
+fn split_version<'a>(kind: &'static str, bytes: &'a [u8]) -> Result<&'a [u8], MapProcessingError> {
+    match bytes.split_first() {
+        Some((&BYTE_LAYOUT_VERSION, data)) => Ok(data),
+        Some((version, _)) => Err(MapProcessingError::InvalidData { kind, reason: format!("unsupported layout version {version}") }),
+        None => Err(MapProcessingError::InvalidLength { kind, expected: 1, found: 0 }),
+    }
+}
+
+/// Internal helper function to write u16s as little-endian bytes.
+pub(crate) fn u16s_to_le_bytes(values: impl IntoIterator<Item = u16>) -> impl Iterator<Item = u8> {
+    values.into_iter().flat_map(u16::to_le_bytes)
+}
+
+/// Internal helper function to read little-endian u16s, rejecting a trailing odd byte.
+pub(crate) fn u16s_from_le_bytes(kind: &'static str, bytes: &[u8]) -> Result<Vec<u16>, MapProcessingError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(MapProcessingError::InvalidLength { kind, expected: bytes.len() + 1, found: bytes.len() });
+    }
+    Ok(bytes.chunks_exact(2).map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]])).collect())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{ExitDirection, Terrain};
+
+    #[test]
+    pub fn byte_layouts_round_trip_with_fixed_bytes() {
+        // Exits are 0LLLLLLDDDPPPPPP: length 48, top (1), start 1
+        let exits = vec![RoomExit::new(1, 48, ExitDirection::Top), RoomExit::new(10, 5, ExitDirection::Left)];
+        let bytes = encode_room_exits(&exits);
+        assert_eq!(bytes[..3], [1, 0x41, 0x60]);
+        assert_eq!(decode_room_exits(&bytes).unwrap(), exits);
+
+        // Runs are 00TTSSSSSSSSSSSS: a wall run starting at tile 50
+        let runs = vec![0x0000, 0x1032];
+        let bytes = encode_packed_runs(&runs);
+        assert_eq!(bytes, vec![1, 0x00, 0x00, 0x32, 0x10]);
+        assert_eq!(decode_packed_runs(&bytes).unwrap(), runs);
+
+        // The first walkable tile of the top edge is the most significant bit of the first byte
+        let mut top = [Terrain::Plain; 50];
+        top[2] = Terrain::Wall;
+        let walls = [Terrain::Wall; 50];
+        let edge_terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &walls, &walls, &walls).unwrap();
+        let bytes = encode_edge_terrain(&edge_terrain);
+        assert_eq!(bytes[..3], [1, 0b0100_0000, 0]);
+        assert_eq!(bytes[7..], [0xff; 18]);
+        assert_eq!(decode_edge_terrain(&bytes).unwrap().get_raw_bytes(), edge_terrain.get_raw_bytes());
+    }
+
+    #[test]
+    pub fn byte_layouts_reject_unknown_versions_and_bad_data() {
+        assert!(decode_packed_runs(&[2, 0, 0]).is_err());
+        assert!(decode_packed_runs(&[]).is_err());
+        assert!(decode_packed_runs(&[1, 0]).is_err());
+        assert!(decode_edge_terrain(&[1; 24]).is_err());
+        // A zero-length exit
+        assert!(decode_room_exits(&[1, 0x41, 0x00]).is_err());
+    }
+}
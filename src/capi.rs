@@ -0,0 +1,221 @@
+//! A small C-compatible API for reading terrain databases from non-Rust tooling.
+//!
+//! The matching header is `include/screeps_map_processing.h`, which can be regenerated from this
+//! module with `cbindgen --config cbindgen.toml --output include/screeps_map_processing.h`. The
+//! crate is also built as a shared library, so `cargo build --release --features capi` produces
+//! one in `target/release`.
+//!
+//! Every function that can fail returns a null pointer on failure. Memory returned by this API
+//! must be released with the matching free function, never with the C allocator.
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use rusqlite::{Connection, OpenFlags};
+use screeps::RoomName;
+
+use crate::compressed_terrain_db::{self, SCHEMA_VERSION};
+
+/// An open terrain database.
+pub struct SmpDb {
+    conn: Connection,
+}
+
+/// Opens the existing terrain database at `path`, a null-terminated UTF-8 string, for reading.
+///
+/// Databases written by an older version of the crate are migrated first, which needs write access
+/// to the file. Returns null if the path isn't valid UTF-8, there's no database at the path or it
+/// can't be opened, it can't be migrated, or it was written by a newer version of the crate. The
+/// database must be closed with [smp_db_close].
+///
+/// # Safety
+///
+/// `path` must be null or point to a null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn smp_db_open(path: *const c_char) -> *mut SmpDb {
+    // Safety: the caller guarantees that path is a null-terminated string
+    let Some(path) = (unsafe { str_from_c(path) }) else {
+        return ptr::null_mut();
+    };
+    // Never created, so that a mistyped path fails instead of creating an empty database
+    let flags = OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let Ok(conn) = Connection::open_with_flags(path, flags | OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return ptr::null_mut();
+    };
+
+    let conn = match compressed_terrain_db::get_schema_version(&conn) {
+        Ok(SCHEMA_VERSION) => conn,
+        Ok(version) if version < SCHEMA_VERSION => {
+            drop(conn);
+            let Ok(conn) = Connection::open_with_flags(path, flags | OpenFlags::SQLITE_OPEN_READ_WRITE) else {
+                return ptr::null_mut();
+            };
+            if compressed_terrain_db::migrate(&conn).is_err() {
+                return ptr::null_mut();
+            }
+            conn
+        }
+        _ => return ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(SmpDb { conn }))
+}
+
+/// Closes a database opened by [smp_db_open]. Passing null does nothing.
+///
+/// # Safety
+///
+/// `db` must be null or a database returned by [smp_db_open] that hasn't been closed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn smp_db_close(db: *mut SmpDb) {
+    if !db.is_null() {
+        // Safety: the caller guarantees that db came from smp_db_open and is still open
+        drop(unsafe { Box::from_raw(db) });
+    }
+}
+
+/// Loads the terrain of a room, named by a null-terminated string like `"W1N1"`.
+///
+/// The terrain is returned as 2500 bytes in row-major order, using the same values as the game:
+/// 0 for plains, 1 for walls, and 2 for swamps. The length is written to `out_len` if it isn't
+/// null. Returns null if the room name is invalid or the room's terrain can't be loaded. The
+/// bytes must be released with [smp_bytes_free].
+///
+/// # Safety
+///
+/// `db` must be a database returned by [smp_db_open] that hasn't been closed yet, `room_name` must
+/// be null or point to a null-terminated string, and `out_len` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn smp_room_terrain(db: *const SmpDb, room_name: *const c_char, out_len: *mut usize) -> *mut u8 {
+    // Safety: the caller guarantees that db is null or an open database
+    let Some(db) = (unsafe { db.as_ref() }) else {
+        return ptr::null_mut();
+    };
+    // Safety: the caller guarantees that room_name is a null-terminated string
+    let Some(room_name) = (unsafe { str_from_c(room_name) }).and_then(|name| RoomName::new(name).ok()) else {
+        return ptr::null_mut();
+    };
+    let Ok(terrain) = compressed_terrain_db::get_terrain_for_room(&db.conn, room_name) else {
+        return ptr::null_mut();
+    };
+
+    let bytes: Box<[u8]> = terrain.get_uncompressed_bits();
+    if !out_len.is_null() {
+        // Safety: the caller guarantees that out_len is valid for writes
+        unsafe { *out_len = bytes.len() };
+    }
+    Box::into_raw(bytes) as *mut u8
+}
+
+/// Releases bytes returned by this API. Passing null does nothing.
+///
+/// # Safety
+///
+/// `bytes` must be null or a pointer returned by this API that hasn't been freed yet, and `len`
+/// must be the length that was returned with it.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn smp_bytes_free(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        // Safety: the caller guarantees that bytes and len came from a boxed slice returned by
+        // this API
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)) });
+    }
+}
+
+/// Internal helper function to borrow a null-terminated UTF-8 string, or None if it's null or
+/// not UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a null-terminated string that outlives the returned reference.
+unsafe fn str_from_c<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    // Safety: the caller guarantees that s is a null-terminated string
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CString;
+    use screeps::ROOM_AREA;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn capi_reads_room_terrain_from_a_database_file() {
+        let path = std::env::temp_dir().join(format!("smp-capi-test-{}.sqlite", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        {
+            let conn = compressed_terrain_db::open_db_file(path_str).unwrap();
+            compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+            let mut bits = [0u8; ROOM_AREA];
+            bits[3] = 1;
+            bits[4] = 2;
+            let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+            compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new("W1N1").unwrap(), &terrain).unwrap();
+        }
+
+        let c_path = CString::new(path_str).unwrap();
+        let missing_path = std::env::temp_dir().join(format!("smp-capi-test-missing-{}.sqlite", std::process::id()));
+        let c_missing_path = CString::new(missing_path.to_str().unwrap()).unwrap();
+        let room = CString::new("W1N1").unwrap();
+        let missing_room = CString::new("W2N2").unwrap();
+        unsafe {
+            assert!(smp_db_open(c_missing_path.as_ptr()).is_null());
+            assert!(!missing_path.exists());
+
+            let db = smp_db_open(c_path.as_ptr());
+            assert!(!db.is_null());
+
+            let mut len = 0;
+            let bytes = smp_room_terrain(db, room.as_ptr(), &mut len);
+            assert!(!bytes.is_null());
+            assert_eq!(len, ROOM_AREA);
+            assert_eq!(std::slice::from_raw_parts(bytes, len)[2..5], [0, 1, 2]);
+            smp_bytes_free(bytes, len);
+
+            assert!(smp_room_terrain(db, missing_room.as_ptr(), ptr::null_mut()).is_null());
+            assert!(smp_room_terrain(db, ptr::null(), ptr::null_mut()).is_null());
+            smp_db_close(db);
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    pub fn capi_migrates_databases_written_by_older_versions() {
+        let path = std::env::temp_dir().join(format!("smp-capi-migrate-test-{}.sqlite", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        let _ = std::fs::remove_file(&path);
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[2u8; ROOM_AREA]);
+        {
+            let conn = Connection::open(path_str).unwrap();
+            conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT, data BLOB);").unwrap();
+            conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W1N1', ?1)", [terrain.get_compressed_bytes()]).unwrap();
+        }
+
+        let c_path = CString::new(path_str).unwrap();
+        let room = CString::new("W1N1").unwrap();
+        unsafe {
+            let db = smp_db_open(c_path.as_ptr());
+            assert!(!db.is_null());
+            assert_eq!(compressed_terrain_db::get_schema_version(&(*db).conn).unwrap(), SCHEMA_VERSION);
+
+            let mut len = 0;
+            let bytes = smp_room_terrain(db, room.as_ptr(), &mut len);
+            assert!(!bytes.is_null());
+            assert!(std::slice::from_raw_parts(bytes, len).iter().all(|tile| *tile == 2));
+            smp_bytes_free(bytes, len);
+            smp_db_close(db);
+        }
+
+        // Databases from newer versions can't be read
+        Connection::open(path_str).unwrap().execute("UPDATE schema_version SET version = ?1", [SCHEMA_VERSION + 1]).unwrap();
+        unsafe {
+            assert!(smp_db_open(c_path.as_ptr()).is_null());
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,17 +1,23 @@
 use std::mem::size_of;
-use screeps::{LocalRoomTerrain, Terrain, RoomXY, RoomName, ROOM_USIZE};
+use screeps::{ExitDirection, LocalRoomTerrain, Terrain, RoomXY, RoomName, ROOM_USIZE};
+use screeps_utils::offline_map::OfflineRoomData;
 use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
 
 // The naive encoding is to take the tiles from 1 to 48 and encode them using a single bit each.
 // The corners of the room are always Walls, so we can ignore those for the actual data storage.
 // 48 bits is 6 bytes, meaning we need 24 bytes per edge to encode all the terrain directly.
 
 /// The errors that can be returned when parsing edge terrain data from slices.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, thiserror::Error)]
 pub enum RoomEdgeTerrainParseError {
+    #[error("top edge is not 50 tiles long")]
     TopEdgeNotLength50,
+    #[error("right edge is not 50 tiles long")]
     RightEdgeNotLength50,
+    #[error("bottom edge is not 50 tiles long")]
     BottomEdgeNotLength50,
+    #[error("left edge is not 50 tiles long")]
     LeftEdgeNotLength50,
 }
 
@@ -34,27 +40,36 @@ impl RoomEdgeTerrain {
         Self { data }
     }
 
+    /// Creates a new RoomEdgeTerrain from a slice of raw compressed data bytes, as returned by
+    /// [get_raw_bytes](RoomEdgeTerrain::get_raw_bytes).
+    ///
+    /// Returns [MapProcessingError::InvalidLength] if the slice isn't 24 bytes long.
+    pub fn try_new_from_raw_slice(data: &[u8]) -> Result<Self, MapProcessingError> {
+        let data = data.try_into().map_err(|_| MapProcessingError::InvalidLength { kind: "edge terrain", expected: 24, found: data.len() })?;
+        Ok(Self { data })
+    }
+
     /// Creates a new RoomEdgeTerrain from slices of Terrain data corresponding to each edge of a
     /// room.
     ///
     /// Each slice is expected to be 50 elements in length. Passing slices of shorter or longer
-    /// length will return Err.
+    /// length will return [MapProcessingError::EdgeTerrain].
     ///
     /// Since these are room edges, the only valid Terrain variants are Plains and Walls. Swamps
     /// are considered to be Plains and will be encoded as such. This *will* cause data loss when
     /// converting from Terrain to compressed byte data and back.
-    pub fn new_from_terrain_slices(top: &[Terrain], right: &[Terrain], bottom: &[Terrain], left: &[Terrain]) -> Result<Self, RoomEdgeTerrainParseError> {
+    pub fn new_from_terrain_slices(top: &[Terrain], right: &[Terrain], bottom: &[Terrain], left: &[Terrain]) -> Result<Self, MapProcessingError> {
         if top.len() != 50 {
-            return Err(RoomEdgeTerrainParseError::TopEdgeNotLength50);
+            return Err(RoomEdgeTerrainParseError::TopEdgeNotLength50.into());
         }
         if right.len() != 50 {
-            return Err(RoomEdgeTerrainParseError::RightEdgeNotLength50);
+            return Err(RoomEdgeTerrainParseError::RightEdgeNotLength50.into());
         }
         if bottom.len() != 50 {
-            return Err(RoomEdgeTerrainParseError::BottomEdgeNotLength50);
+            return Err(RoomEdgeTerrainParseError::BottomEdgeNotLength50.into());
         }
         if left.len() != 50 {
-            return Err(RoomEdgeTerrainParseError::LeftEdgeNotLength50);
+            return Err(RoomEdgeTerrainParseError::LeftEdgeNotLength50.into());
         }
 
         let top_slice = top.try_into().expect("should always be length 50");
@@ -130,7 +145,15 @@ impl RoomEdgeTerrain {
     }
 
     /// Creates a new RoomEdgeTerrain from compressed terrain data.
+    ///
+    /// Same as [new_from_compressed_terrain](RoomEdgeTerrain::new_from_compressed_terrain), which is
+    /// named consistently with the other terrain encodings.
     pub fn new_from_compressed_room_terrain(terrain: &CompressedRoomTerrain) -> Self {
+        Self::new_from_compressed_terrain(terrain)
+    }
+
+    /// Creates a new RoomEdgeTerrain from the edges of compressed terrain data.
+    pub fn new_from_compressed_terrain(terrain: &CompressedRoomTerrain) -> Self {
         // Extract the edges
         let mut top_edge_terrain: Vec<Terrain> = Vec::new();
         let mut right_edge_terrain: Vec<Terrain> = Vec::new();
@@ -191,6 +214,19 @@ impl RoomEdgeTerrain {
         self.data
     }
 
+    /// Serializes the edge terrain as its 24 raw bytes; same as
+    /// [get_raw_bytes](RoomEdgeTerrain::get_raw_bytes).
+    pub fn to_bytes(&self) -> [u8; 24] {
+        self.data
+    }
+
+    /// Deserializes edge terrain written by [to_bytes](RoomEdgeTerrain::to_bytes).
+    ///
+    /// Returns [MapProcessingError::InvalidLength] if the slice isn't 24 bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        Self::try_new_from_raw_slice(bytes)
+    }
+
     /// Internal helper function to compress 8 tiles of Terrain into a single byte.
     ///
     /// Valid variants are Plains and Walls. Swamps will be converted silently to Plains.
@@ -301,6 +337,17 @@ impl RoomEdgeTerrain {
         self.data[18..24].try_into().unwrap()
     }
 
+    /// Returns the compressed data for an edge of the room: one bit per tile for tiles 1 through
+    /// 48, in LTR/top-to-bottom order starting from the most significant bit, set for Walls.
+    pub fn get_edge_bytes(&self, direction: ExitDirection) -> &[u8; 6] {
+        match direction {
+            ExitDirection::Top => self.get_top_edge_bytes_slice(),
+            ExitDirection::Right => self.get_right_edge_bytes_slice(),
+            ExitDirection::Bottom => self.get_bottom_edge_bytes_slice(),
+            ExitDirection::Left => self.get_left_edge_bytes_slice(),
+        }
+    }
+
     /// Returns the Terrain data corresponding to the top edge of the room.
     pub fn get_top_edge_terrain(&self) -> [Terrain; 50] {
         Self::get_edge_terrain_from_bytes(self.get_top_edge_bytes_slice())
@@ -428,6 +475,12 @@ impl RoomEdgeTerrain {
     }
 }
 
+impl From<&OfflineRoomData> for RoomEdgeTerrain {
+    fn from(room: &OfflineRoomData) -> Self {
+        Self::new_from_local_room_terrain(&room.terrain)
+    }
+}
+
 
 #[cfg(test)]
 mod test {
@@ -1,10 +1,17 @@
-use screeps::local::xy_to_terrain_index;
-use screeps::{ROOM_SIZE, ROOM_AREA, RoomXY, Terrain};
+use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
+use screeps::{ROOM_SIZE, ROOM_AREA, ROOM_USIZE, RoomCoordinate, RoomXY, Terrain};
+use screeps_utils::offline_map::OfflineRoomData;
+
+use crate::compressed_terrain::sub_rect::SubRect;
+use crate::compressed_terrain::room_bit_grid::{RoomBitGrid, WallBitmap};
+use crate::error::MapProcessingError;
+use crate::terrain_source::TerrainCounts;
 
 /// The size of the internal data array for [CompressedRoomTerrain].
 pub const COMPRESSED_ARRAY_SIZE: usize = (ROOM_AREA / 4) as usize; // We pack 4 terrain positions into 1 byte, so our array is 4 times smaller. This should be 625 as the final value.
 
 /// Room terrain that has been compressed via bit-packing.
+#[derive(Debug, Clone)]
 pub struct CompressedRoomTerrain {
     data: Box<[u8; COMPRESSED_ARRAY_SIZE]>,
 }
@@ -26,7 +33,7 @@ impl CompressedRoomTerrain {
     }
 
     /// Gets the internal terrain byte of the specified position.
-    fn get_uncompressed_terrain_byte(&self, xy: RoomXY) -> u8 {
+    pub(crate) fn get_uncompressed_terrain_byte(&self, xy: RoomXY) -> u8 {
         // Determine the linear index of the xy coordinate in an uncompressed array of size 2500
         let uncompressed_index = xy_to_terrain_index(xy);
 
@@ -105,11 +112,260 @@ impl CompressedRoomTerrain {
         Self { data: compressed_data }
     }
 
+    /// Creates a `CompressedRoomTerrain` where every tile is `default`, except for the tiles in
+    /// `overrides`.
+    ///
+    /// Later overrides for the same tile replace earlier ones.
+    pub fn from_sparse(default: Terrain, overrides: impl IntoIterator<Item = (RoomXY, Terrain)>) -> Self {
+        let bits = default as u8 & 0b11;
+        let default_byte = Self::compress_4_bytes(&[bits; 4]);
+        let mut terrain = Self { data: Box::new([default_byte; COMPRESSED_ARRAY_SIZE]) };
+        terrain.set_many(overrides);
+        terrain
+    }
+
+    /// Creates a `CompressedRoomTerrain` from a grid of characters, one line per row: `.` for
+    /// plains, `#` for walls, and `~` for swamps.
+    ///
+    /// Surrounding whitespace on each line and blank lines at the start and end are ignored, so the
+    /// grid can be indented in a raw string. As a shorthand for uniform fills, a row shorter than
+    /// 50 tiles repeats its last tile to fill the row, and if there are fewer than 50 rows the last
+    /// row is repeated to fill the room; a single `#` is a room of walls.
+    pub fn from_ascii_art(art: &str) -> Result<Self, MapProcessingError> {
+        let invalid = |reason: String| MapProcessingError::InvalidData { kind: "ascii art terrain", reason };
+
+        let lines: Vec<&str> = art.lines().map(str::trim).collect();
+        let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+        let last = lines.iter().rposition(|line| !line.is_empty()).map_or(first, |last| last + 1);
+        let lines = &lines[first..last];
+        if lines.len() > ROOM_USIZE {
+            return Err(invalid(format!("expected at most {ROOM_USIZE} rows, found {}", lines.len())));
+        }
+
+        let mut rows = Vec::with_capacity(ROOM_USIZE);
+        for (y, line) in lines.iter().enumerate() {
+            let mut row = Vec::with_capacity(ROOM_USIZE);
+            for c in line.chars() {
+                row.push(match c {
+                    '.' => Terrain::Plain,
+                    '#' => Terrain::Wall,
+                    '~' => Terrain::Swamp,
+                    _ => return Err(invalid(format!("unexpected character {c:?} in row {y}"))),
+                });
+            }
+            let last = *row.last().ok_or_else(|| invalid(format!("row {y} is empty")))?;
+            if row.len() > ROOM_USIZE {
+                return Err(invalid(format!("expected at most {ROOM_USIZE} tiles in row {y}, found {}", row.len())));
+            }
+            row.resize(ROOM_USIZE, last);
+            rows.push(row);
+        }
+        let last_row = rows.last().cloned().ok_or_else(|| invalid("no rows".to_string()))?;
+        rows.resize(ROOM_USIZE, last_row);
+
+        let mut terrain = Self::from_sparse(Terrain::Plain, []);
+        terrain.set_many(rows.iter().flatten().enumerate().map(|(idx, tile)| (terrain_index_to_xy(idx), *tile)));
+        Ok(terrain)
+    }
+
+    /// Renders the terrain as a grid of characters, in the format read by
+    /// [from_ascii_art](CompressedRoomTerrain::from_ascii_art), with every tile written out.
+    pub fn to_ascii_art(&self) -> String {
+        let mut art = String::with_capacity(ROOM_AREA + ROOM_USIZE);
+        for (xy, tile) in self.iter_tiles() {
+            art.push(match tile {
+                Terrain::Plain => '.',
+                Terrain::Wall => '#',
+                Terrain::Swamp => '~',
+            });
+            if xy.x.u8() as usize == ROOM_USIZE - 1 {
+                art.push('\n');
+            }
+        }
+        art
+    }
+
+    /// Gets the terrain for every tile in the specified row.
+    ///
+    /// This decodes the row straight from the 13 packed bytes that hold it, which is much faster
+    /// than calling [get_xy](CompressedRoomTerrain::get_xy) for each tile.
+    pub fn get_row(&self, y: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        let start = y.u8() as usize * ROOM_USIZE;
+        let end = start + ROOM_USIZE;
+        // Odd rows start halfway through a byte, so skip the tiles from the previous row
+        let mut tiles = self.data[start / 4..end.div_ceil(4)].iter()
+            .flat_map(|byte| Self::uncompress_byte(*byte))
+            .skip(start % 4);
+
+        let mut row = [Terrain::Plain; ROOM_USIZE];
+        for (tile, bits) in row.iter_mut().zip(&mut tiles) {
+            *tile = Self::terrain_from_bits(bits);
+        }
+        row
+    }
+
+    /// Returns an iterator over the rows of the room, decoded in blocks of `chunk_rows` rows at a
+    /// time, top to bottom.
+    ///
+    /// Each block is paired with the coordinate of its first row. The last block is shorter if
+    /// `chunk_rows` doesn't evenly divide the room; `chunk_rows` is clamped to at least 1. This
+    /// lets renderers and exporters stream a room in bounded memory.
+    pub fn iter_room_tiles_chunked(&self, chunk_rows: usize) -> impl Iterator<Item = (RoomCoordinate, Vec<[Terrain; ROOM_USIZE]>)> + '_ {
+        (0..ROOM_USIZE).step_by(chunk_rows.max(1)).map(move |first| {
+            let rows = (first..(first + chunk_rows.max(1)).min(ROOM_USIZE))
+                // Safety: y is always in the range [0, 49], since it's bounded by ROOM_USIZE
+                .map(|y| self.get_row(unsafe { RoomCoordinate::unchecked_new(y as u8) }))
+                .collect();
+            // Safety: first is always in the range [0, 49], since it's bounded by ROOM_USIZE
+            (unsafe { RoomCoordinate::unchecked_new(first as u8) }, rows)
+        })
+    }
+
+    /// Gets the terrain for every tile in the specified column.
+    pub fn get_col(&self, x: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        let start = x.u8() as usize;
+        std::array::from_fn(|y| self.get_terrain_at_index(start + y * ROOM_USIZE))
+    }
+
+    /// Gets the terrain for every tile in the rectangle between two corners, inclusive, in
+    /// row-major order, without decompressing the rest of the room.
+    ///
+    /// The result is empty if `bottom_right` is above or to the left of `top_left`.
+    pub fn get_rect(&self, top_left: RoomXY, bottom_right: RoomXY) -> Vec<Terrain> {
+        SubRect::between(self, top_left, bottom_right).map(|rect| rect.to_vec()).unwrap_or_default()
+    }
+
+    /// Counts the tiles of each kind of terrain in the room, directly from the packed bytes.
+    pub fn terrain_counts(&self) -> TerrainCounts {
+        // The low bit of each tile is set for walls, and only the high bit is set for swamps
+        let (walls, swamps) = self.data.iter().fold((0, 0), |(walls, swamps), byte| {
+            let low = byte & 0b0101_0101;
+            let high = (byte >> 1) & 0b0101_0101;
+            (walls + low.count_ones() as usize, swamps + (high & !low).count_ones() as usize)
+        });
+        TerrainCounts { plain: ROOM_AREA - walls - swamps, swamp: swamps, wall: walls }
+    }
+
+    /// A mask of the walls in the room, decoded directly from the packed bytes.
+    pub fn walls_bitmask(&self) -> WallBitmap {
+        self.bitmask(Terrain::Wall)
+    }
+
+    /// A mask of the swamps in the room, decoded directly from the packed bytes.
+    pub fn swamps_bitmask(&self) -> RoomBitGrid {
+        self.bitmask(Terrain::Swamp)
+    }
+
+    /// The number of runs of identical terrain in each row, indexed by y.
+    ///
+    /// Runs are counted from transitions between neighboring tiles in the wall and swamp masks, a
+    /// whole row at a time, so this is much cheaper than run length encoding the room.
+    pub fn row_run_counts(&self) -> [u8; ROOM_USIZE] {
+        // Bit x is set if tile x differs from tile x + 1
+        const TRANSITION_MASK: u64 = (1 << (ROOM_USIZE - 1)) - 1;
+        let (walls, swamps) = (self.walls_bitmask(), self.swamps_bitmask());
+
+        std::array::from_fn(|y| {
+            let (wall_row, swamp_row) = (walls.row_bits(y as u8), swamps.row_bits(y as u8));
+            let transitions = ((wall_row ^ (wall_row >> 1)) | (swamp_row ^ (swamp_row >> 1))) & TRANSITION_MASK;
+            transitions.count_ones() as u8 + 1
+        })
+    }
+
+    /// The number of runs of identical terrain in each column, indexed by x.
+    ///
+    /// Like [row_run_counts](CompressedRoomTerrain::row_run_counts), this compares whole rows of
+    /// the wall and swamp masks at a time, so comparing the two tells an encoder whether row-major
+    /// or column-major runs would be shorter without encoding the room both ways.
+    pub fn col_run_counts(&self) -> [u8; ROOM_USIZE] {
+        let (walls, swamps) = (self.walls_bitmask(), self.swamps_bitmask());
+        let mut counts = [1u8; ROOM_USIZE];
+
+        for y in 1..ROOM_USIZE as u8 {
+            let transitions = (walls.row_bits(y) ^ walls.row_bits(y - 1)) | (swamps.row_bits(y) ^ swamps.row_bits(y - 1));
+            for (x, count) in counts.iter_mut().enumerate() {
+                *count += ((transitions >> x) & 1) as u8;
+            }
+        }
+
+        counts
+    }
+
+    /// Internal helper function to build a mask of the tiles with the specified terrain.
+    fn bitmask(&self, terrain: Terrain) -> RoomBitGrid {
+        let mut rows = [0u64; ROOM_USIZE];
+        for (byte_idx, byte) in self.data.iter().enumerate() {
+            for offset in 0..4 {
+                let idx = byte_idx * 4 + offset;
+                if Self::terrain_from_bits(byte >> (6 - 2 * offset)) == terrain {
+                    rows[idx / ROOM_USIZE] |= 1 << (idx % ROOM_USIZE);
+                }
+            }
+        }
+        RoomBitGrid::from_rows(rows)
+    }
+
+    /// Internal helper function to get the terrain at a linear terrain index.
+    fn get_terrain_at_index(&self, index: usize) -> Terrain {
+        let bitshift_amount = 6 - 2 * (index & 0b11);
+        Self::terrain_from_bits(self.data[index >> 2] >> bitshift_amount)
+    }
+
+    /// Internal helper function to convert the 2 least significant bits of a terrain byte into
+    /// [Terrain], treating `0b11` as a wall like the engine does.
+    fn terrain_from_bits(bits: u8) -> Terrain {
+        match bits & 0b11 {
+            0b00 => Terrain::Plain,
+            0b10 => Terrain::Swamp,
+            _ => Terrain::Wall,
+        }
+    }
+
+    /// Returns an iterator over every tile in the room and its terrain, in row-major order.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        self.data.iter()
+            .flat_map(|byte| Self::uncompress_byte(*byte))
+            .enumerate()
+            .map(|(idx, bits)| (terrain_index_to_xy(idx), Self::terrain_from_bits(bits)))
+    }
+
+    /// Sets the terrain at the specified position in this room.
+    pub fn set_xy(&mut self, xy: RoomXY, terrain: Terrain) {
+        self.set_uncompressed_terrain_byte(xy, terrain as u8);
+    }
+
+    /// Sets the terrain of every tile in `tiles`, in order.
+    ///
+    /// Later entries for the same tile replace earlier ones.
+    pub fn set_many(&mut self, tiles: impl IntoIterator<Item = (RoomXY, Terrain)>) {
+        for (xy, terrain) in tiles {
+            self.set_xy(xy, terrain);
+        }
+    }
+
+    /// Sets the internal terrain byte of the specified position.
+    pub(crate) fn set_uncompressed_terrain_byte(&mut self, xy: RoomXY, bits: u8) {
+        let (byte_index, internal_offset) = div_rem(xy_to_terrain_index(xy), 4);
+        let bitshift_amount = 6 - 2 * internal_offset;
+        let mask = 0b11u8 << bitshift_amount;
+        self.data[byte_index] = (self.data[byte_index] & !mask) | ((bits & 0b11) << bitshift_amount);
+    }
+
     /// Creates a `CompressedRoomTerrain` from compressed bytes of room terrain data.
     pub fn new_from_compressed_bytes(data: Box<[u8; COMPRESSED_ARRAY_SIZE]>) -> Self {
         Self { data }
     }
 
+    /// Creates a `CompressedRoomTerrain` from a slice of compressed bytes of room terrain data.
+    ///
+    /// Returns [MapProcessingError::InvalidLength] if the slice isn't [COMPRESSED_ARRAY_SIZE] bytes
+    /// long.
+    pub fn try_new_from_compressed_slice(data: &[u8]) -> Result<Self, MapProcessingError> {
+        let bytes: [u8; COMPRESSED_ARRAY_SIZE] = data.try_into()
+            .map_err(|_| MapProcessingError::InvalidLength { kind: "compressed terrain", expected: COMPRESSED_ARRAY_SIZE, found: data.len() })?;
+        Ok(Self { data: Box::new(bytes) })
+    }
+
     /// Gets a reference to the underlying compressed terrain data.
     pub fn get_compressed_bytes(&self) -> &[u8; COMPRESSED_ARRAY_SIZE] {
         &self.data
@@ -157,6 +413,23 @@ impl CompressedRoomTerrain {
     pub fn memory_size(&self) -> usize {
         size_of::<[u8; COMPRESSED_ARRAY_SIZE]>() + size_of::<Box<[u8; COMPRESSED_ARRAY_SIZE]>>()
     }
+
+    /// A 64-bit FNV-1a hash of the compressed terrain data.
+    ///
+    /// This is stable across versions and platforms, so it can be stored and compared against
+    /// later to detect terrain changes without comparing the full terrain data.
+    pub fn fingerprint(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        self.data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+    }
+}
+
+impl From<&OfflineRoomData> for CompressedRoomTerrain {
+    fn from(room: &OfflineRoomData) -> Self {
+        Self::new_from_uncompressed_bits(room.terrain.get_bits())
+    }
 }
 
 /// Calculates the quotent and remainder. Returned tuple is (quotent, remainder).
@@ -248,4 +521,155 @@ mod test {
             }
         }
     }
+
+    #[test]
+    pub fn compressed_terrain_from_sparse_matches_uncompressed_terrain() {
+        let overrides = [((3, 0), Terrain::Wall), ((0, 1), Terrain::Swamp), ((49, 49), Terrain::Wall), ((3, 0), Terrain::Plain)];
+        let terrain = CompressedRoomTerrain::from_sparse(Terrain::Plain, overrides.iter().map(|((x, y), t)| (RoomXY::checked_new(*x, *y).unwrap(), *t)));
+
+        let mut bits = [0u8; ROOM_AREA];
+        bits[50] = 2;
+        bits[2499] = 1;
+        bits[3] = 0;
+        assert_eq!(terrain.get_uncompressed_bits(), Box::new(bits));
+    }
+
+    #[test]
+    pub fn compressed_terrain_set_xy_only_changes_the_target_tile() {
+        let mut terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let xy = RoomXY::checked_new(5, 7).unwrap();
+
+        for tile in [Terrain::Wall, Terrain::Swamp, Terrain::Plain, Terrain::Swamp] {
+            terrain.set_xy(xy, tile);
+            assert_eq!(terrain.get_xy(xy), tile);
+        }
+
+        terrain.set_many([(RoomXY::checked_new(6, 7).unwrap(), Terrain::Wall), (RoomXY::checked_new(4, 7).unwrap(), Terrain::Wall)]);
+
+        let mut bits = [0u8; ROOM_AREA];
+        bits[7 * 50 + 4] = 1;
+        bits[7 * 50 + 5] = 2;
+        bits[7 * 50 + 6] = 1;
+        assert_eq!(terrain.get_uncompressed_bits(), Box::new(bits));
+    }
+
+    #[test]
+    pub fn compressed_terrain_get_row_and_col_match_get_xy() {
+        let mut bits = [0u8; ROOM_AREA];
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i % 7 % 3) as u8;
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        for i in 0..ROOM_SIZE {
+            let coord = RoomCoordinate::new(i).unwrap();
+            let row = terrain.get_row(coord);
+            let col = terrain.get_col(coord);
+            for j in 0..ROOM_SIZE {
+                let other = RoomCoordinate::new(j).unwrap();
+                assert_eq!(row[j as usize], terrain.get_xy(RoomXY::new(other, coord)));
+                assert_eq!(col[j as usize], terrain.get_xy(RoomXY::new(coord, other)));
+            }
+        }
+    }
+
+    #[test]
+    pub fn compressed_terrain_get_row_decodes_every_row_from_packed_bytes() {
+        // Includes the wall+swamp bits, and differs between the two halves of the bytes rows share
+        let bits: [u8; ROOM_AREA] = std::array::from_fn(|i| (i * 31 % 11 % 4) as u8);
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        for y in 0..ROOM_SIZE {
+            let y = RoomCoordinate::new(y).unwrap();
+            let expected: [Terrain; ROOM_USIZE] = std::array::from_fn(|x| terrain.get_xy(RoomXY::new(RoomCoordinate::new(x as u8).unwrap(), y)));
+            assert_eq!(terrain.get_row(y), expected);
+        }
+        for x in 0..ROOM_SIZE {
+            let x = RoomCoordinate::new(x).unwrap();
+            let expected: [Terrain; ROOM_USIZE] = std::array::from_fn(|y| terrain.get_xy(RoomXY::new(x, RoomCoordinate::new(y as u8).unwrap())));
+            assert_eq!(terrain.get_col(x), expected);
+        }
+    }
+
+    #[test]
+    pub fn compressed_terrain_bitmasks_match_get_xy() {
+        let mut bits = [0u8; ROOM_AREA];
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i % 13 % 4) as u8;
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let walls = terrain.walls_bitmask();
+        let swamps = terrain.swamps_bitmask();
+
+        for (xy, tile) in terrain.iter_tiles() {
+            assert_eq!(walls.get_xy(xy), tile == Terrain::Wall);
+            assert_eq!(swamps.get_xy(xy), tile == Terrain::Swamp);
+        }
+        assert_eq!(walls.popcount(), terrain.terrain_counts().wall);
+    }
+
+    #[test]
+    pub fn compressed_terrain_from_ascii_art_expands_shorthand() {
+        let terrain = CompressedRoomTerrain::from_ascii_art("
+            #
+            #..~
+            #
+        ").unwrap();
+
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        assert_eq!(terrain.get_xy(xy(20, 0)), Terrain::Wall);
+        assert_eq!(terrain.get_xy(xy(0, 1)), Terrain::Wall);
+        assert_eq!(terrain.get_xy(xy(1, 1)), Terrain::Plain);
+        assert_eq!(terrain.get_xy(xy(3, 1)), Terrain::Swamp);
+        assert_eq!(terrain.get_xy(xy(49, 1)), Terrain::Swamp);
+        assert_eq!(terrain.get_xy(xy(49, 49)), Terrain::Wall);
+        assert_eq!(terrain.terrain_counts().swamp, 47);
+
+        let reparsed = CompressedRoomTerrain::from_ascii_art(&terrain.to_ascii_art()).unwrap();
+        assert_eq!(reparsed.get_compressed_bytes(), terrain.get_compressed_bytes());
+
+        assert!(CompressedRoomTerrain::from_ascii_art("").is_err());
+        assert!(CompressedRoomTerrain::from_ascii_art("#x").is_err());
+        assert!(CompressedRoomTerrain::from_ascii_art(&".".repeat(51)).is_err());
+    }
+
+    #[test]
+    pub fn compressed_terrain_iter_room_tiles_chunked_covers_every_row() {
+        let mut bits = [0u8; ROOM_AREA];
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i % 11 % 3) as u8;
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let chunks: Vec<_> = terrain.iter_room_tiles_chunked(16).collect();
+        assert_eq!(chunks.iter().map(|(first, rows)| (first.u8(), rows.len())).collect::<Vec<_>>(), vec![(0, 16), (16, 16), (32, 16), (48, 2)]);
+
+        let rows: Vec<_> = chunks.into_iter().flat_map(|(_, rows)| rows).collect();
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(*row, terrain.get_row(RoomCoordinate::new(y as u8).unwrap()));
+        }
+        assert_eq!(terrain.iter_room_tiles_chunked(0).count(), 50);
+    }
+
+    #[test]
+    pub fn compressed_terrain_run_counts_match_decoded_runs() {
+        let mut bits = [0u8; ROOM_AREA];
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i % 17 % 7 % 3) as u8;
+        }
+        bits[7] = 3; // Treated as a wall
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let count_runs = |tiles: [Terrain; ROOM_USIZE]| (1 + tiles.iter().tuple_windows().filter(|(a, b)| a != b).count()) as u8;
+
+        let row_counts = terrain.row_run_counts();
+        let col_counts = terrain.col_run_counts();
+        for i in 0..ROOM_SIZE {
+            let coord = RoomCoordinate::new(i).unwrap();
+            assert_eq!(row_counts[i as usize], count_runs(terrain.get_row(coord)));
+            assert_eq!(col_counts[i as usize], count_runs(terrain.get_col(coord)));
+        }
+
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]);
+        assert_eq!(plains.row_run_counts(), [1; ROOM_USIZE]);
+    }
 }
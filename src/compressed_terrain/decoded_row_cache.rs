@@ -0,0 +1,121 @@
+use std::cell::OnceCell;
+use std::mem::size_of;
+use screeps::{RoomCoordinate, RoomXY, Terrain, ROOM_USIZE};
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+/// A fully decoded row of room terrain.
+pub type DecodedRow = [Terrain; ROOM_USIZE];
+
+/// Read-through cache that lazily decodes rows of a [CompressedRoomTerrain].
+///
+/// Each row is decoded from the packed bytes the first time any tile in it is read, and the
+/// decoded row is kept around for subsequent reads. Repeated access patterns (pathfinding,
+/// flood fills, distance transforms) then run at close to `LocalRoomTerrain` speed, while the
+/// compressed terrain remains the persistent representation.
+///
+/// The cache borrows the terrain it decodes, so it's intended to be short-lived: build one for
+/// the duration of an analysis and drop it afterwards.
+pub struct DecodedRowCache<'a> {
+    terrain: &'a CompressedRoomTerrain,
+    rows: [OnceCell<DecodedRow>; ROOM_USIZE],
+}
+
+impl<'a> DecodedRowCache<'a> {
+    /// Creates a new, empty cache over the provided terrain.
+    pub fn new(terrain: &'a CompressedRoomTerrain) -> Self {
+        Self {
+            terrain,
+            rows: std::array::from_fn(|_| OnceCell::new()),
+        }
+    }
+
+    /// Gets the terrain at the specified position in the room, decoding its row if needed.
+    pub fn get_xy(&self, xy: RoomXY) -> Terrain {
+        self.get_row(xy.y)[xy.x.u8() as usize]
+    }
+
+    /// Gets the decoded terrain for the specified row, decoding it if it hasn't been already.
+    pub fn get_row(&self, y: RoomCoordinate) -> &DecodedRow {
+        self.rows[y.u8() as usize].get_or_init(|| Self::decode_row(self.terrain, y))
+    }
+
+    /// Returns true if the specified row has already been decoded.
+    pub fn is_row_decoded(&self, y: RoomCoordinate) -> bool {
+        self.rows[y.u8() as usize].get().is_some()
+    }
+
+    /// The number of rows that have been decoded so far.
+    pub fn num_decoded_rows(&self) -> usize {
+        self.rows.iter().filter(|row| row.get().is_some()).count()
+    }
+
+    /// Drops all decoded rows, returning the cache to its initial empty state.
+    pub fn clear(&mut self) {
+        for row in self.rows.iter_mut() {
+            row.take();
+        }
+    }
+
+    /// A reference to the underlying compressed terrain.
+    pub fn terrain(&self) -> &'a CompressedRoomTerrain {
+        self.terrain
+    }
+
+    /// The amount of memory it takes to store this cache, not including the underlying compressed
+    /// terrain.
+    pub fn memory_size(&self) -> usize {
+        size_of::<[OnceCell<DecodedRow>; ROOM_USIZE]>() + size_of::<&CompressedRoomTerrain>()
+    }
+
+    /// Internal helper function that decodes a single row of terrain.
+    fn decode_row(terrain: &CompressedRoomTerrain, y: RoomCoordinate) -> DecodedRow {
+        terrain.get_row(y)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::constants::{ROOM_AREA, ROOM_SIZE};
+
+    #[test]
+    pub fn decoded_row_cache_get_xy_matches_compressed_terrain() {
+        // Initialize terrain to be heterogeneous
+        let mut raw_terrain_data = [0; ROOM_AREA];
+        for (i, tile) in raw_terrain_data.iter_mut().enumerate() {
+            *tile = (i % 3) as u8; // Range: 0, 1, 2 -> Plains, Wall, Swamp
+        }
+
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&raw_terrain_data);
+        let cache = DecodedRowCache::new(&terrain);
+
+        // Read every tile twice, to exercise both the decode and the cached paths
+        for _ in 0..2 {
+            for x in 0..ROOM_SIZE {
+                for y in 0..ROOM_SIZE {
+                    // Safety: x and y are both explicitly restricted to room size
+                    let xy = unsafe { RoomXY::unchecked_new(x, y) };
+                    assert_eq!(terrain.get_xy(xy), cache.get_xy(xy), "Terrain mismatch at {xy}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn decoded_row_cache_only_decodes_rows_on_demand() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]);
+        let mut cache = DecodedRowCache::new(&terrain);
+        assert_eq!(cache.num_decoded_rows(), 0);
+
+        let xy = unsafe { RoomXY::unchecked_new(10, 20) };
+        cache.get_xy(xy);
+        cache.get_xy(unsafe { RoomXY::unchecked_new(11, 20) });
+        assert_eq!(cache.num_decoded_rows(), 1);
+        assert!(cache.is_row_decoded(xy.y));
+
+        cache.clear();
+        assert_eq!(cache.num_decoded_rows(), 0);
+        assert!(!cache.is_row_decoded(xy.y));
+    }
+}
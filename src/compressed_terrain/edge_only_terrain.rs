@@ -0,0 +1,136 @@
+use screeps::{RoomName, RoomXY, Terrain};
+
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::terrain_source::TerrainSource;
+
+/// Terrain for a room where only the edges are known, such as a room scouted from its neighbors.
+///
+/// Edge tiles are stored in a [RoomEdgeTerrain], taking 24 bytes, so the room's exits are known.
+/// Interior tiles are unknown; they're reported as [Terrain::Plain] by [TerrainSource], and
+/// [is_known](EdgeOnlyRoomTerrain::is_known) can be used to tell them apart.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeOnlyRoomTerrain {
+    edges: RoomEdgeTerrain,
+}
+
+impl EdgeOnlyRoomTerrain {
+    /// Creates edge-only terrain from known edge terrain.
+    pub fn new(edges: RoomEdgeTerrain) -> Self {
+        Self { edges }
+    }
+
+    /// Creates edge-only terrain from full terrain, discarding the interior.
+    pub fn new_from_compressed_room_terrain(terrain: &CompressedRoomTerrain) -> Self {
+        Self::new(RoomEdgeTerrain::new_from_compressed_room_terrain(terrain))
+    }
+
+    /// The known edge terrain.
+    pub fn edge_terrain(&self) -> &RoomEdgeTerrain {
+        &self.edges
+    }
+
+    /// Returns true if the terrain of the tile is known, which is only the case for edge tiles.
+    pub fn is_known(&self, xy: RoomXY) -> bool {
+        xy.is_room_edge()
+    }
+
+    /// The exits of the room, for use with the connectivity graph and router.
+    pub fn exits_data(&self, room: RoomName) -> RoomExitsData {
+        RoomExitsData::new_from_compressed_edge_terrain_data(self.edges, room)
+    }
+}
+
+impl TerrainSource for EdgeOnlyRoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        self.edges.get_xy(xy).unwrap_or(Terrain::Plain)
+    }
+
+    fn memory_size(&self) -> usize {
+        self.edges.memory_size()
+    }
+}
+
+/// Terrain for a scouted room, which starts edge-only and is upgraded in place once the full
+/// terrain is available.
+pub enum ScoutedRoomTerrain {
+    EdgeOnly(EdgeOnlyRoomTerrain),
+    Full(CompressedRoomTerrain),
+}
+
+impl ScoutedRoomTerrain {
+    /// Returns true if the full terrain is known.
+    pub fn is_full(&self) -> bool {
+        matches!(self, Self::Full(_))
+    }
+
+    /// Returns true if the terrain of the tile is known.
+    pub fn is_known(&self, xy: RoomXY) -> bool {
+        match self {
+            Self::EdgeOnly(terrain) => terrain.is_known(xy),
+            Self::Full(_) => true,
+        }
+    }
+
+    /// Replaces edge-only terrain with the full terrain of the room.
+    pub fn upgrade(&mut self, terrain: CompressedRoomTerrain) {
+        *self = Self::Full(terrain);
+    }
+
+    /// The exits of the room, which are known either way.
+    pub fn exits_data(&self, room: RoomName) -> RoomExitsData {
+        let edges = match self {
+            Self::EdgeOnly(terrain) => *terrain.edge_terrain(),
+            Self::Full(terrain) => RoomEdgeTerrain::new_from_compressed_room_terrain(terrain),
+        };
+        RoomExitsData::new_from_compressed_edge_terrain_data(edges, room)
+    }
+}
+
+impl TerrainSource for ScoutedRoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        match self {
+            Self::EdgeOnly(terrain) => terrain.get_xy(xy),
+            Self::Full(terrain) => terrain.get_xy(xy),
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        match self {
+            Self::EdgeOnly(terrain) => terrain.memory_size(),
+            Self::Full(terrain) => terrain.memory_size(),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn scouted_room_terrain_upgrades_from_edge_only() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        let mut bits = [1u8; ROOM_AREA];
+        bits[10] = 0; // An exit at (10, 0)
+        bits[25 * 50 + 25] = 2;
+        let full = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W1N1").unwrap();
+
+        let mut scouted = ScoutedRoomTerrain::EdgeOnly(EdgeOnlyRoomTerrain::new_from_compressed_room_terrain(&full));
+        assert_eq!(scouted.memory_size(), 24);
+        assert_eq!(scouted.get_xy(xy(10, 0)), Terrain::Plain);
+        assert_eq!(scouted.get_xy(xy(11, 0)), Terrain::Wall);
+        assert!(!scouted.is_known(xy(25, 25)));
+        assert_eq!(scouted.get_xy(xy(25, 25)), Terrain::Plain);
+        assert_eq!(scouted.exits_data(room).num_exits(), 1);
+
+        scouted.upgrade(full);
+        assert!(scouted.is_full());
+        assert!(scouted.is_known(xy(25, 25)));
+        assert_eq!(scouted.get_xy(xy(25, 25)), Terrain::Swamp);
+        assert_eq!(scouted.exits_data(room).num_exits(), 1);
+    }
+}
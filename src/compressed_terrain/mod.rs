@@ -1,2 +1,6 @@
 pub mod compressed_terrain;
 pub mod compressed_room_edge_terrain;
+pub mod decoded_row_cache;
+pub mod edge_only_terrain;
+pub mod room_bit_grid;
+pub mod sub_rect;
@@ -0,0 +1,298 @@
+use std::mem::size_of;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign};
+use screeps::{RoomXY, ROOM_USIZE};
+
+/// The bitmask covering the 50 tile bits of a single row.
+const ROW_MASK: u64 = (1 << ROOM_USIZE) - 1;
+
+/// A 1-bit-per-tile mask over an entire room.
+///
+/// Each row is stored in its own u64, with bit `x` of row `y` corresponding to the tile at
+/// `(x, y)`. This makes neighbor operations (dilation, erosion) cheap shifts instead of per-tile
+/// lookups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomBitGrid {
+    rows: [u64; ROOM_USIZE],
+}
+
+/// A [RoomBitGrid] where set bits are walls.
+pub type WallBitmap = RoomBitGrid;
+
+impl Default for RoomBitGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RoomBitGrid {
+    /// Creates a new grid with no bits set.
+    pub fn new() -> Self {
+        Self { rows: [0; ROOM_USIZE] }
+    }
+
+    /// Creates a new grid with every bit set.
+    pub fn full() -> Self {
+        Self { rows: [ROW_MASK; ROOM_USIZE] }
+    }
+
+    /// Creates a new grid, setting each bit for which `f` returns true.
+    pub fn from_fn(mut f: impl FnMut(RoomXY) -> bool) -> Self {
+        let mut grid = Self::new();
+        for y in 0..ROOM_USIZE {
+            for x in 0..ROOM_USIZE {
+                // Safety: x and y are both in the range [0, 49]
+                let xy = unsafe { RoomXY::unchecked_new(x as u8, y as u8) };
+                if f(xy) {
+                    grid.rows[y] |= 1 << x;
+                }
+            }
+        }
+        grid
+    }
+
+    /// Creates a new grid from the raw bits of each row, as returned by
+    /// [row_bits](RoomBitGrid::row_bits). Bits past column 49 are ignored.
+    pub fn from_rows(rows: [u64; ROOM_USIZE]) -> Self {
+        Self { rows: rows.map(|row| row & ROW_MASK) }
+    }
+
+    /// Returns true if the bit for the specified tile is set.
+    pub fn get_xy(&self, xy: RoomXY) -> bool {
+        (self.rows[xy.y.u8() as usize] >> xy.x.u8()) & 1 == 1
+    }
+
+    /// Sets or clears the bit for the specified tile.
+    pub fn set_xy(&mut self, xy: RoomXY, value: bool) {
+        let row = &mut self.rows[xy.y.u8() as usize];
+        if value {
+            *row |= 1 << xy.x.u8();
+        } else {
+            *row &= !(1 << xy.x.u8());
+        }
+    }
+
+    /// The raw bits of a single row, with bit `x` corresponding to column `x`.
+    pub fn row_bits(&self, y: u8) -> u64 {
+        self.rows[y as usize]
+    }
+
+    /// Tiles set in either grid.
+    pub fn union(&self, other: &Self) -> Self {
+        self.zip_rows(other, |a, b| a | b)
+    }
+
+    /// Tiles set in both grids.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.zip_rows(other, |a, b| a & b)
+    }
+
+    /// Tiles set in this grid but not the other.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.zip_rows(other, |a, b| a & !b)
+    }
+
+    /// Tiles not set in this grid.
+    pub fn complement(&self) -> Self {
+        Self { rows: self.rows.map(|row| !row & ROW_MASK) }
+    }
+
+    /// The number of set tiles.
+    pub fn popcount(&self) -> usize {
+        self.rows.iter().map(|row| row.count_ones() as usize).sum()
+    }
+
+    /// Returns true if no tiles are set.
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Returns an iterator over the set tiles, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = RoomXY> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            let mut remaining = *row;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let x = remaining.trailing_zeros();
+                remaining &= remaining - 1;
+                // Safety: x and y are both in the range [0, 49], since only the low 50 bits of a
+                // row are ever set
+                Some(unsafe { RoomXY::unchecked_new(x as u8, y as u8) })
+            })
+        })
+    }
+
+    /// Grows the set tiles by one tile in all 8 directions.
+    ///
+    /// Tiles outside the room are treated as unset.
+    pub fn dilate(&self) -> Self {
+        let horizontal = self.rows.map(|row| (row | (row << 1) | (row >> 1)) & ROW_MASK);
+
+        let mut rows = horizontal;
+        for y in 0..ROOM_USIZE {
+            if y > 0 {
+                rows[y] |= horizontal[y - 1];
+            }
+            if y + 1 < ROOM_USIZE {
+                rows[y] |= horizontal[y + 1];
+            }
+        }
+
+        Self { rows }
+    }
+
+    /// Shrinks the set tiles by one tile in all 8 directions, keeping only tiles whose neighbors
+    /// are all set.
+    ///
+    /// Tiles outside the room are treated as set, so a full grid stays full.
+    pub fn erode(&self) -> Self {
+        self.complement().dilate().complement()
+    }
+
+    /// The amount of memory it takes to store this data.
+    pub fn memory_size(&self) -> usize {
+        size_of::<[u64; ROOM_USIZE]>()
+    }
+
+    /// Internal helper function to combine two grids row by row.
+    fn zip_rows(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        Self { rows: std::array::from_fn(|y| f(self.rows[y], other.rows[y])) }
+    }
+
+    /// Tiles set in exactly one of the grids.
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.zip_rows(other, |a, b| a ^ b)
+    }
+}
+
+/// Implements a binary operator and its assigning form for grids, by value and by reference, in
+/// terms of one of the set operations.
+macro_rules! impl_grid_op {
+    ($op:ident, $op_fn:ident, $assign:ident, $assign_fn:ident, $method:ident) => {
+        impl $op for RoomBitGrid {
+            type Output = RoomBitGrid;
+
+            fn $op_fn(self, other: Self) -> Self {
+                self.$method(&other)
+            }
+        }
+
+        impl $op<&RoomBitGrid> for &RoomBitGrid {
+            type Output = RoomBitGrid;
+
+            fn $op_fn(self, other: &RoomBitGrid) -> RoomBitGrid {
+                self.$method(other)
+            }
+        }
+
+        impl $assign for RoomBitGrid {
+            fn $assign_fn(&mut self, other: Self) {
+                *self = self.$method(&other);
+            }
+        }
+
+        impl $assign<&RoomBitGrid> for RoomBitGrid {
+            fn $assign_fn(&mut self, other: &RoomBitGrid) {
+                *self = self.$method(other);
+            }
+        }
+    };
+}
+
+impl_grid_op!(BitAnd, bitand, BitAndAssign, bitand_assign, intersection);
+impl_grid_op!(BitOr, bitor, BitOrAssign, bitor_assign, union);
+impl_grid_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, symmetric_difference);
+impl_grid_op!(Sub, sub, SubAssign, sub_assign, difference);
+
+impl Not for RoomBitGrid {
+    type Output = RoomBitGrid;
+
+    fn not(self) -> Self {
+        self.complement()
+    }
+}
+
+impl Not for &RoomBitGrid {
+    type Output = RoomBitGrid;
+
+    fn not(self) -> RoomBitGrid {
+        self.complement()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn xy(x: u8, y: u8) -> RoomXY {
+        RoomXY::checked_new(x, y).unwrap()
+    }
+
+    #[test]
+    pub fn room_bit_grid_set_operations() {
+        let a = RoomBitGrid::from_fn(|xy| xy.x.u8() < 10);
+        let b = RoomBitGrid::from_fn(|xy| xy.y.u8() < 10);
+
+        assert_eq!(a.popcount(), 500);
+        assert_eq!(a.union(&b).popcount(), 900);
+        assert_eq!(a.intersection(&b).popcount(), 100);
+        assert_eq!(a.difference(&b).popcount(), 400);
+        assert_eq!(a.complement().popcount(), 2000);
+        assert_eq!(RoomBitGrid::full().complement(), RoomBitGrid::new());
+        assert!(a.intersection(&a.complement()).is_empty());
+
+        let set: Vec<RoomXY> = a.intersection(&b).iter().collect();
+        assert_eq!(set.len(), 100);
+        assert_eq!(set[0], xy(0, 0));
+        assert_eq!(set[99], xy(9, 9));
+        assert!(set.iter().all(|p| a.get_xy(*p) && b.get_xy(*p)));
+    }
+
+    #[test]
+    pub fn room_bit_grid_operators_match_set_operations() {
+        let a = RoomBitGrid::from_fn(|xy| xy.x.u8() < 10);
+        let b = RoomBitGrid::from_fn(|xy| xy.y.u8() < 10);
+
+        assert_eq!(a & b, a.intersection(&b));
+        assert_eq!(a | b, a.union(&b));
+        assert_eq!(a - b, a.difference(&b));
+        assert_eq!((a ^ b).popcount(), 800);
+        assert_eq!(!a, a.complement());
+        assert_eq!(!&a, a.complement());
+
+        let mut c = a;
+        c |= b;
+        c &= &!b;
+        c ^= a;
+        assert_eq!(c, a & b);
+        c -= b;
+        assert_eq!(c, RoomBitGrid::new());
+    }
+
+    #[test]
+    pub fn room_bit_grid_dilate_and_erode() {
+        let mut grid = RoomBitGrid::new();
+        grid.set_xy(xy(10, 10), true);
+        grid.set_xy(xy(0, 49), true);
+
+        let dilated = grid.dilate();
+        assert_eq!(dilated.popcount(), 9 + 4);
+        assert!(dilated.get_xy(xy(9, 9)));
+        assert!(dilated.get_xy(xy(11, 11)));
+        assert!(!dilated.get_xy(xy(12, 10)));
+        assert!(dilated.get_xy(xy(1, 48)));
+
+        // Eroding the dilated single tile gets back the original tile
+        let mut single = RoomBitGrid::new();
+        single.set_xy(xy(10, 10), true);
+        assert_eq!(single.dilate().erode(), single);
+
+        // Out of bounds tiles are treated as set when eroding
+        assert_eq!(RoomBitGrid::full().erode(), RoomBitGrid::full());
+
+        grid.set_xy(xy(10, 10), false);
+        assert!(!grid.get_xy(xy(10, 10)));
+    }
+}
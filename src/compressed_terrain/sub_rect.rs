@@ -0,0 +1,209 @@
+use screeps::{RoomXY, Terrain, ROOM_SIZE};
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::terrain_source::TerrainSource;
+
+/// The number of tiles along each side of a [Quadrant].
+pub const QUADRANT_SIZE: u8 = ROOM_SIZE / 2;
+
+/// The number of tiles in a [Quadrant].
+pub const QUADRANT_AREA: usize = (QUADRANT_SIZE as usize) * (QUADRANT_SIZE as usize);
+
+/// One of the four 25x25 quarters of a room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quadrant {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Quadrant {
+    pub const ALL: [Quadrant; 4] = [Quadrant::TopLeft, Quadrant::TopRight, Quadrant::BottomLeft, Quadrant::BottomRight];
+
+    /// The top-left tile of this quadrant.
+    pub fn origin(&self) -> RoomXY {
+        let (x, y) = match self {
+            Quadrant::TopLeft => (0, 0),
+            Quadrant::TopRight => (QUADRANT_SIZE, 0),
+            Quadrant::BottomLeft => (0, QUADRANT_SIZE),
+            Quadrant::BottomRight => (QUADRANT_SIZE, QUADRANT_SIZE),
+        };
+        // Safety: x and y are both either 0 or 25
+        unsafe { RoomXY::unchecked_new(x, y) }
+    }
+
+    /// The quadrant containing the specified tile.
+    pub fn containing(xy: RoomXY) -> Self {
+        match (xy.x.u8() < QUADRANT_SIZE, xy.y.u8() < QUADRANT_SIZE) {
+            (true, true) => Quadrant::TopLeft,
+            (false, true) => Quadrant::TopRight,
+            (true, false) => Quadrant::BottomLeft,
+            (false, false) => Quadrant::BottomRight,
+        }
+    }
+}
+
+/// A rectangular view into part of a room's terrain, addressed in local coordinates.
+///
+/// Local coordinate (0, 0) is the top-left tile of the rectangle. Reads go straight to the
+/// underlying terrain, so with [CompressedRoomTerrain] only the tiles actually read are decoded.
+pub struct SubRect<'a, T: ?Sized = CompressedRoomTerrain> {
+    terrain: &'a T,
+    origin: RoomXY,
+    width: u8,
+    height: u8,
+}
+
+// Derived impls would require T itself to be Clone and Copy
+impl<T: ?Sized> Clone for SubRect<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for SubRect<'_, T> {}
+
+impl<'a, T: TerrainSource + ?Sized> SubRect<'a, T> {
+    /// Creates a view of the `width` x `height` rectangle with its top-left tile at `origin`.
+    ///
+    /// Returns None if the rectangle is empty or extends past the edge of the room.
+    pub fn new(terrain: &'a T, origin: RoomXY, width: u8, height: u8) -> Option<Self> {
+        let fits = |start: u8, len: u8| len > 0 && start as usize + len as usize <= ROOM_SIZE as usize;
+        if !fits(origin.x.u8(), width) || !fits(origin.y.u8(), height) {
+            return None;
+        }
+        Some(Self { terrain, origin, width, height })
+    }
+
+    /// Creates a view of the rectangle between two corners, inclusive.
+    ///
+    /// Returns None if `bottom_right` is above or to the left of `top_left`.
+    pub fn between(terrain: &'a T, top_left: RoomXY, bottom_right: RoomXY) -> Option<Self> {
+        let width = bottom_right.x.u8().checked_sub(top_left.x.u8())? + 1;
+        let height = bottom_right.y.u8().checked_sub(top_left.y.u8())? + 1;
+        Self::new(terrain, top_left, width, height)
+    }
+
+    /// Creates a view of one quadrant of the room.
+    pub fn quadrant(terrain: &'a T, which: Quadrant) -> Self {
+        Self { terrain, origin: which.origin(), width: QUADRANT_SIZE, height: QUADRANT_SIZE }
+    }
+
+    /// Creates a view of the square of tiles within `radius` of `center`, clipped to the room.
+    pub fn around(terrain: &'a T, center: RoomXY, radius: u8) -> Self {
+        let (top_left, bottom_right) = square_around(center, radius);
+        let width = bottom_right.x.u8() - top_left.x.u8() + 1;
+        let height = bottom_right.y.u8() - top_left.y.u8() + 1;
+        Self { terrain, origin: top_left, width, height }
+    }
+
+    /// The room tile at local coordinate (0, 0).
+    pub fn origin(&self) -> RoomXY {
+        self.origin
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+
+    /// Converts local coordinates into the corresponding room tile.
+    ///
+    /// Returns None if the coordinates are outside the rectangle.
+    pub fn to_room_xy(&self, x: u8, y: u8) -> Option<RoomXY> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        // Safety: the rectangle was checked to fit in the room when it was created
+        Some(unsafe { RoomXY::unchecked_new(self.origin.x.u8() + x, self.origin.y.u8() + y) })
+    }
+
+    /// Gets the terrain at the specified local coordinates.
+    ///
+    /// Returns None if the coordinates are outside the rectangle.
+    pub fn get_xy(&self, x: u8, y: u8) -> Option<Terrain> {
+        self.to_room_xy(x, y).map(|xy| self.terrain.get_xy(xy))
+    }
+
+    /// The terrain of every tile in the rectangle, in row-major order.
+    pub fn to_vec(&self) -> Vec<Terrain> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.get_xy(x, y))
+            .collect()
+    }
+}
+
+/// Internal helper function to find the top-left and bottom-right corners of the square of tiles
+/// within `radius` of `center`, clipped to the room.
+pub(crate) fn square_around(center: RoomXY, radius: u8) -> (RoomXY, RoomXY) {
+    let (x, y) = (center.x.u8(), center.y.u8());
+    let (min_x, min_y) = (x.saturating_sub(radius), y.saturating_sub(radius));
+    let max_x = x.saturating_add(radius).min(ROOM_SIZE - 1);
+    let max_y = y.saturating_add(radius).min(ROOM_SIZE - 1);
+    // Safety: the minimums are no greater than the coordinates of center, and the maximums are
+    // clamped to the room
+    unsafe { (RoomXY::unchecked_new(min_x, min_y), RoomXY::unchecked_new(max_x, max_y)) }
+}
+
+impl CompressedRoomTerrain {
+    /// The terrain of one quadrant of the room, in row-major order.
+    pub fn quadrant(&self, which: Quadrant) -> [Terrain; QUADRANT_AREA] {
+        let view = SubRect::quadrant(self, which);
+        std::array::from_fn(|idx| {
+            let (x, y) = ((idx % QUADRANT_SIZE as usize) as u8, (idx / QUADRANT_SIZE as usize) as u8);
+            view.get_xy(x, y).unwrap_or(Terrain::Wall)
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn sub_rect_uses_local_coordinates() {
+        // Walls in the bottom-right quadrant, and a swamp at (30, 10)
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 25..50 {
+            bits[y * 50 + 25..y * 50 + 50].fill(1);
+        }
+        bits[10 * 50 + 30] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        assert!(terrain.quadrant(Quadrant::BottomRight).iter().all(|t| *t == Terrain::Wall));
+        assert!(terrain.quadrant(Quadrant::TopLeft).iter().all(|t| *t == Terrain::Plain));
+        let top_right = terrain.quadrant(Quadrant::TopRight);
+        assert_eq!(top_right[10 * 25 + 5], Terrain::Swamp);
+
+        let around = SubRect::around(&terrain, RoomXY::checked_new(30, 10).unwrap(), 3);
+        assert_eq!((around.width(), around.height()), (7, 7));
+        assert_eq!(around.get_xy(3, 3), Some(Terrain::Swamp));
+        assert_eq!(around.get_xy(7, 0), None);
+
+        let corner = SubRect::around(&terrain, RoomXY::checked_new(48, 48).unwrap(), 3);
+        assert_eq!((corner.width(), corner.height()), (5, 5));
+        assert_eq!(corner.origin(), RoomXY::checked_new(45, 45).unwrap());
+
+        assert!(SubRect::new(&terrain, RoomXY::checked_new(40, 0).unwrap(), 11, 1).is_none());
+        assert_eq!(Quadrant::containing(RoomXY::checked_new(25, 24).unwrap()), Quadrant::TopRight);
+    }
+
+    #[test]
+    pub fn sub_rect_between_corners_is_inclusive() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let top_left = RoomXY::checked_new(3, 4).unwrap();
+        let bottom_right = RoomXY::checked_new(7, 6).unwrap();
+
+        let rect = SubRect::between(&terrain, top_left, bottom_right).unwrap();
+        assert_eq!((rect.origin(), rect.width(), rect.height()), (top_left, 5, 3));
+        assert_eq!(SubRect::between(&terrain, top_left, top_left).unwrap().to_vec().len(), 1);
+        assert!(SubRect::between(&terrain, bottom_right, top_left).is_none());
+    }
+}
@@ -1,49 +1,742 @@
 
-use rusqlite::{Connection, Error};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use rusqlite::{Connection, OptionalExtension};
 use screeps::RoomName;
-use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::adaptive_terrain::{AdaptiveRoomTerrain, TerrainEncoding};
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::{CompressedRoomTerrain, COMPRESSED_ARRAY_SIZE};
+use crate::error::MapProcessingError;
+use crate::normalize::NormalizationRules;
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::room_record::RoomRecord;
+use crate::terrain_pyramid::TerrainPyramid;
 
-pub fn open_db_file(path: &str) -> Result<Connection, Error> {
-    Connection::open(path)
+/// SQL expression for the current time, in seconds since the Unix epoch.
+const CURRENT_TIMESTAMP_SQL: &str = "CAST(strftime('%s', 'now') AS INTEGER)";
+
+/// The most rooms looked up by a single query in [get_terrains_for_rooms], well under SQLite's
+/// limit on query parameters.
+const ROOMS_PER_QUERY: usize = 500;
+
+/// The metadata key holding the normalization rules applied to the stored terrain.
+pub const NORMALIZATION_RULES_KEY: &str = "normalization_rules";
+
+/// The metadata key holding the number of duplicate terrain rows removed when the database was
+/// migrated from a version that allowed several rows for the same room.
+pub const DUPLICATE_ROOMS_REMOVED_KEY: &str = "duplicate_rooms_removed";
+
+pub fn open_db_file(path: &str) -> Result<Connection, MapProcessingError> {
+    Ok(Connection::open(path)?)
+}
+
+/// Opens a new database held entirely in memory, with every table created by [migrate].
+///
+/// The database is discarded when the connection is closed.
+pub fn open_in_memory_db() -> Result<Connection, MapProcessingError> {
+    let conn = Connection::open_in_memory()?;
+    migrate(&conn)?;
+    Ok(conn)
 }
 
-pub fn create_terrain_table_if_not_exists(conn: &Connection) -> Result<(), Error> {
+/// Opens a new private temporary database, with every table created by [migrate].
+///
+/// Unlike [open_in_memory_db], SQLite can spill the database to a temporary file if it outgrows
+/// its cache, so it suits whole-shard scripts. The database is deleted when the connection is
+/// closed.
+pub fn open_temp_db() -> Result<Connection, MapProcessingError> {
+    let conn = Connection::open("")?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// The schema version that [migrate] brings databases up to.
+pub const SCHEMA_VERSION: u32 = 7;
+
+/// A step that brings a database from one schema version to the next.
+type Migration = fn(&Connection) -> Result<(), MapProcessingError>;
+
+/// The migrations that bring a database from each schema version to the next; the migration at
+/// index `i` upgrades from version `i` to version `i + 1`.
+///
+/// Each migration must also work on databases created by versions of this crate that predate
+/// schema versioning, since those are at version 0 regardless of which tables they have.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    // 1: The terrain table, with every column added since it was introduced
+    create_terrain_table_if_not_exists,
+    // 2: The tables for data derived from terrain, and for metadata
+    |conn| {
+        create_pyramid_table_if_not_exists(conn)?;
+        create_analysis_table_if_not_exists(conn)?;
+        create_metadata_table_if_not_exists(conn)?;
+        create_room_record_table_if_not_exists(conn)?;
+        create_exits_table_if_not_exists(conn)
+    },
+    // 3: The encoding column of the terrain table
+    create_terrain_table_if_not_exists,
+    // 4: The danger score table
+    create_danger_table_if_not_exists,
+    // 5: The shard column of the pyramid, room record, and exits tables
+    |conn| {
+        create_pyramid_table_if_not_exists(conn)?;
+        create_room_record_table_if_not_exists(conn)?;
+        create_exits_table_if_not_exists(conn)
+    },
+    // 6: The shard column of the danger score table
+    create_danger_table_if_not_exists,
+    // 7: Duplicate rooms removed from the terrain table, which then holds one row per room
+    |conn| {
+        let removed = remove_duplicate_rooms(conn)?;
+        set_metadata(conn, DUPLICATE_ROOMS_REMOVED_KEY, &removed.to_string())?;
+        conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS room_terrain_shard_room ON room_terrain (shard, room_name);")?;
+
+        #[cfg(feature = "tracing")]
+        if removed > 0 {
+            tracing::warn!(removed, "removed duplicate rooms");
+        }
+
+        Ok(())
+    },
+];
+
+/// The schema version of the database, or 0 if it's never been migrated.
+pub fn get_schema_version(conn: &Connection) -> Result<u32, MapProcessingError> {
+    if !conn.table_exists(None, "schema_version")? {
+        return Ok(0);
+    }
+    let version: Option<u32> = conn.query_row("SELECT version FROM schema_version LIMIT 1", [], |row| row.get(0)).optional()?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Brings the database up to [SCHEMA_VERSION], applying each migration it's missing in a single
+/// transaction, and returns the version it was at before.
+///
+/// Returns [MapProcessingError::InvalidData] if the database was written by a newer version of
+/// this crate.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn migrate(conn: &Connection) -> Result<u32, MapProcessingError> {
+    let version = get_schema_version(conn)?;
+    if version > SCHEMA_VERSION {
+        return Err(MapProcessingError::InvalidData {
+            kind: "database",
+            reason: format!("schema version {version} is newer than the supported version {SCHEMA_VERSION}"),
+        });
+    }
+
+    let transaction = conn.unchecked_transaction()?;
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&transaction)?;
+    }
+    transaction.execute_batch("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL); DELETE FROM schema_version;")?;
+    transaction.execute("INSERT INTO schema_version (version) VALUES (?1)", [SCHEMA_VERSION])?;
+    transaction.commit()?;
+
+    #[cfg(feature = "tracing")]
+    if version < SCHEMA_VERSION {
+        tracing::info!(from = version, to = SCHEMA_VERSION, "migrated database");
+    }
+
+    Ok(version)
+}
+
+/// Creates the terrain table, or adds any missing columns to a table created by an older version.
+///
+/// Tables created by older versions may hold duplicate rows for a room, so they're only made
+/// unique per room by [migrate], which removes the duplicates first.
+pub fn create_terrain_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
     let table_exists = conn.table_exists(None, "room_terrain")?;
 
     // The existence query was successful, now actually create the table if it doesn't exist
     if !table_exists {
         // The table doesn't already exist, create it
-        let _ = conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);")?;
+        let _ = conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB, shard TEXT NOT NULL DEFAULT '');")?;
+        conn.execute_batch("CREATE UNIQUE INDEX room_terrain_shard_room ON room_terrain (shard, room_name);")?;
     }
+
+    // Bring tables created by older versions up to date
+    add_column_if_not_exists(conn, "room_terrain", "updated_at", "INTEGER")?;
+    add_column_if_not_exists(conn, "room_terrain", "fingerprint", "INTEGER")?;
+    add_column_if_not_exists(conn, "room_terrain", "room_x", "INTEGER")?;
+    add_column_if_not_exists(conn, "room_terrain", "room_y", "INTEGER")?;
+    add_column_if_not_exists(conn, "room_terrain", "shard", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_not_exists(conn, "room_terrain", "encoding", "TEXT NOT NULL DEFAULT 'compressed'")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS room_terrain_position ON room_terrain (room_x, room_y);")?;
+    backfill_fingerprints(conn)?;
+    backfill_room_positions(conn)?;
     
     Ok(())
 }
 
-pub fn add_terrain_for_room(conn: &Connection, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), Error> {
-    let params = rusqlite::named_params!{
-        ":room_name": room_name.to_string(),
-        ":data": terrain.get_compressed_bytes(),
-    };
-    conn.execute("INSERT INTO room_terrain (room_name, data) VALUES (:room_name, :data)", params).and(Ok(()))
+/// Internal helper function to remove duplicate rows for the same room, which older versions
+/// allowed, keeping the most recently inserted row for each room. Returns the number of rows
+/// removed.
+fn remove_duplicate_rooms(conn: &Connection) -> Result<usize, MapProcessingError> {
+    Ok(conn.execute("DELETE FROM room_terrain WHERE id NOT IN (SELECT MAX(id) FROM room_terrain GROUP BY shard, room_name)", [])?)
 }
 
-pub fn get_terrain_for_room(conn: &Connection, room_name: RoomName) -> Result<CompressedRoomTerrain, Error> {
-    let params = rusqlite::named_params!{
-        ":room_name": room_name.to_string(),
-    };
-    conn.query_row_and_then(
-        "SELECT data FROM room_terrain WHERE room_name = :room_name LIMIT 1",
-        params,
-        |row| row.get(0).and_then(
-            |bytes| Ok(CompressedRoomTerrain::new_from_compressed_bytes(Box::new(bytes)))
-        )
-    )
+/// Internal helper function to compute fingerprints for rows written before fingerprints were
+/// stored.
+fn backfill_fingerprints(conn: &Connection) -> Result<(), MapProcessingError> {
+    let mut stmt = conn.prepare("SELECT id, data FROM room_terrain WHERE fingerprint IS NULL AND encoding = 'compressed'")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<usize, i64>(0)?, row.get::<usize, Vec<u8>>(1)?)))?;
+
+    let mut update = conn.prepare("UPDATE room_terrain SET fingerprint = ?1 WHERE id = ?2")?;
+    for row in rows {
+        let (id, bytes) = row?;
+        // Rows with malformed terrain data are left without a fingerprint
+        if let Ok(bytes) = <[u8; COMPRESSED_ARRAY_SIZE]>::try_from(bytes) {
+            let fingerprint = CompressedRoomTerrain::new_from_compressed_bytes(Box::new(bytes)).fingerprint();
+            update.execute((fingerprint as i64, id))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Internal helper function to compute room positions for rows written before positions were
+/// stored.
+fn backfill_room_positions(conn: &Connection) -> Result<(), MapProcessingError> {
+    let mut stmt = conn.prepare("SELECT id, room_name FROM room_terrain WHERE room_x IS NULL OR room_y IS NULL")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<usize, i64>(0)?, row.get::<usize, String>(1)?)))?;
+
+    let mut update = conn.prepare("UPDATE room_terrain SET room_x = ?1, room_y = ?2 WHERE id = ?3")?;
+    for row in rows {
+        let (id, name) = row?;
+        // Rows with invalid room names are left without a position
+        if let Ok(room_name) = RoomName::new(name.as_str()) {
+            update.execute((room_name.x_coord(), room_name.y_coord(), id))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Internal helper function to create a table holding one value per room, keyed by shard and room
+/// name.
+///
+/// Tables created by older versions were keyed by room name alone; they're rebuilt with a shard
+/// column, and their rooms are moved to [DEFAULT_SHARD].
+fn create_room_table_if_not_exists(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), MapProcessingError> {
+    if conn.table_exists(None, table)? && !conn.column_exists(None, table, "shard")? {
+        conn.execute_batch(&format!("ALTER TABLE {table} RENAME TO {table}_unsharded;"))?;
+    }
+
+    conn.execute_batch(&format!("CREATE TABLE IF NOT EXISTS {table} (shard TEXT NOT NULL DEFAULT '', room_name TEXT, {column} {definition}, PRIMARY KEY (shard, room_name));"))?;
+
+    if conn.table_exists(None, format!("{table}_unsharded").as_str())? {
+        conn.execute_batch(&format!("INSERT INTO {table} (room_name, {column}) SELECT room_name, {column} FROM {table}_unsharded; DROP TABLE {table}_unsharded;"))?;
+    }
+
+    Ok(())
+}
+
+/// Internal helper function to add a column to an existing table, if it's missing.
+fn add_column_if_not_exists(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), MapProcessingError> {
+    if !conn.column_exists(None, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition};"))?;
+    }
+    Ok(())
+}
+
+/// The shard that rows from single-shard databases belong to, and that the free functions in this
+/// module read and write. Use [ShardDb] to work with other shards.
+pub const DEFAULT_SHARD: &str = "";
+
+/// Stores the terrain for a room. Returns an error if terrain is already stored for the room; see
+/// [upsert_terrain_for_room] to replace it.
+pub fn add_terrain_for_room(conn: &Connection, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).add_terrain_for_room(room_name, terrain)
+}
+
+/// Stores the terrain for a room, replacing any terrain already stored for it and deleting the
+/// pyramid, room record, and exits derived from the old terrain.
+pub fn upsert_terrain_for_room(conn: &Connection, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).upsert_terrain_for_room(room_name, terrain)
+}
+
+/// Deletes the stored terrain for a room, along with its pyramid, room record, and exits, returning
+/// true if there was any terrain.
+pub fn delete_terrain_for_room(conn: &Connection, room_name: RoomName) -> Result<bool, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).delete_terrain_for_room(room_name)
+}
+
+/// Replaces the stored terrain for a room, returning the number of rows that were updated.
+pub fn update_terrain_for_room(conn: &Connection, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<usize, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).update_terrain_for_room(room_name, terrain)
+}
+
+/// Gets the stored terrain for a room, converting it to [CompressedRoomTerrain] if it was stored in
+/// another encoding.
+pub fn get_terrain_for_room(conn: &Connection, room_name: RoomName) -> Result<CompressedRoomTerrain, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_terrain_for_room(room_name)
+}
+
+/// Gets the stored terrain for each of the rooms that has any.
+///
+/// The rooms are looked up with a few large queries rather than one query per room, which makes
+/// this much faster for loading a neighborhood of rooms at once, such as for pathfinding. Terrain
+/// stored in other encodings is converted to [CompressedRoomTerrain].
+pub fn get_terrains_for_rooms(conn: &Connection, rooms: &[RoomName]) -> Result<HashMap<RoomName, CompressedRoomTerrain>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_terrains_for_rooms(rooms)
+}
+
+/// Stores the terrain for a room in whichever encoding it's in, such as the smallest encoding
+/// picked by [AdaptiveRoomTerrain::new_from_compressed_terrain]. Returns an error if terrain is
+/// already stored for the room.
+pub fn add_encoded_terrain_for_room(conn: &Connection, room_name: RoomName, terrain: &AdaptiveRoomTerrain) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).add_encoded_terrain_for_room(room_name, terrain)
+}
+
+/// Gets the stored terrain for a room in the encoding it was stored in.
+pub fn get_terrain_any_encoding(conn: &Connection, room_name: RoomName) -> Result<AdaptiveRoomTerrain, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_terrain_any_encoding(room_name)
+}
+
+/// Internal helper function to decode terrain stored with the named encoding into
+/// [CompressedRoomTerrain], without converting terrain that's already compressed.
+fn decode_compressed_terrain(encoding: &str, bytes: &[u8]) -> Result<CompressedRoomTerrain, MapProcessingError> {
+    if encoding == TerrainEncoding::Compressed.name() {
+        CompressedRoomTerrain::try_new_from_compressed_slice(bytes)
+    } else {
+        Ok(decode_terrain(encoding, bytes)?.to_compressed_terrain())
+    }
+}
+
+/// Internal helper function to decode terrain stored with the named encoding.
+fn decode_terrain(encoding: &str, bytes: &[u8]) -> Result<AdaptiveRoomTerrain, MapProcessingError> {
+    let encoding = TerrainEncoding::from_name(encoding)
+        .ok_or_else(|| MapProcessingError::InvalidData { kind: "room terrain", reason: format!("unknown encoding: {encoding}") })?;
+    AdaptiveRoomTerrain::from_encoded_bytes(encoding, bytes)
+}
+
+/// When the terrain for a room was last written, in seconds since the Unix epoch.
+///
+/// Returns None if the room has no terrain, or its terrain was written before update times were
+/// tracked.
+pub fn get_terrain_updated_at(conn: &Connection, room_name: RoomName) -> Result<Option<i64>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_terrain_updated_at(room_name)
+}
+
+/// Overrides the time the terrain for a room was last written, in seconds since the Unix epoch.
+pub fn set_terrain_updated_at(conn: &Connection, room_name: RoomName, timestamp: i64) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).set_terrain_updated_at(room_name, timestamp)
+}
+
+/// Gets the rooms whose terrain was last written before the timestamp, in seconds since the Unix
+/// epoch.
+///
+/// Rooms written before update times were tracked are always included.
+pub fn get_rooms_older_than(conn: &Connection, timestamp: i64) -> Result<Vec<RoomName>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_rooms_older_than(timestamp)
 }
 
-pub fn get_rooms_with_terrain(conn: &Connection) -> Result<Vec<RoomName>, Error> {
-    let mut stmt = conn.prepare("SELECT room_name FROM room_terrain")?;
+/// Gets the terrain fingerprint of every room, as computed by
+/// [CompressedRoomTerrain::fingerprint], without loading any terrain data.
+///
+/// This is useful for finding which rooms differ from a freshly parsed map dump.
+pub fn get_room_fingerprints(conn: &Connection) -> Result<HashMap<RoomName, u64>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_room_fingerprints()
+}
+
+/// Gets every room with stored terrain, ordered by room x coordinate, then room y coordinate.
+pub fn get_rooms_with_terrain(conn: &Connection) -> Result<Vec<RoomName>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_rooms_with_terrain()
+}
+
+/// Calls `f` with the name and terrain of every room with stored terrain, in the same order as
+/// [get_rooms_with_terrain], until it returns [ControlFlow::Break].
+///
+/// Every room is read by a single query as it's needed, rather than querying each room
+/// separately, so this is the fastest way to process a whole shard. Terrain stored in other
+/// encodings is converted to [CompressedRoomTerrain], and rows with invalid room names are skipped.
+/// Terrain that can't be decoded is passed to `f` as an error, so callers can decide whether to
+/// skip the room or stop.
+///
+/// Returns the value `f` broke with, or None if it saw every room.
+pub fn iter_room_terrains<B>(conn: &Connection, f: impl FnMut(RoomName, Result<CompressedRoomTerrain, MapProcessingError>) -> ControlFlow<B>) -> Result<Option<B>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).iter_room_terrains(f)
+}
+
+/// Gets up to `limit` rooms with stored terrain that come after `after`, in the same order as
+/// [get_rooms_with_terrain].
+///
+/// Passing None for `after` starts from the first room. This is a resumable cursor: long-running
+/// jobs can record the last room of each page, and pass it back in to continue after an
+/// interruption. `after` doesn't need to have stored terrain itself.
+///
+/// Rows with invalid room names are skipped without counting toward `limit`, so a page is only
+/// shorter than `limit` once there are no more rooms.
+pub fn iter_rooms_from(conn: &Connection, after: Option<RoomName>, limit: usize) -> Result<Vec<RoomName>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).iter_rooms_from(after, limit)
+}
+
+/// Gets every shard with stored terrain, in alphabetical order.
+pub fn get_shards(conn: &Connection) -> Result<Vec<String>, MapProcessingError> {
+    let mut stmt = conn.prepare("SELECT DISTINCT shard FROM room_terrain ORDER BY shard")?;
     let rows = stmt.query_map([], |row| row.get::<usize, String>(0))?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// A handle for reading and writing the terrain of a single shard.
+///
+/// Each method matches the free function of the same name, which works with [DEFAULT_SHARD].
+#[derive(Debug, Clone, Copy)]
+pub struct ShardDb<'a> {
+    conn: &'a Connection,
+    shard: &'a str,
+}
+
+impl<'a> ShardDb<'a> {
+    /// Creates a handle for the terrain of a shard, such as `"shard3"`.
+    pub fn new(conn: &'a Connection, shard: &'a str) -> Self {
+        Self { conn, shard }
+    }
+
+    /// The shard this handle reads and writes.
+    pub fn shard(&self) -> &'a str {
+        self.shard
+    }
+
+    /// The database connection this handle uses.
+    pub fn connection(&self) -> &'a Connection {
+        self.conn
+    }
+
+    /// See [add_terrain_for_room](crate::compressed_terrain_db::add_terrain_for_room).
+    pub fn add_terrain_for_room(&self, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":data": terrain.get_compressed_bytes(),
+            ":fingerprint": terrain.fingerprint() as i64,
+            ":room_x": room_name.x_coord(),
+            ":room_y": room_name.y_coord(),
+        };
+        self.conn.execute(&format!("INSERT INTO room_terrain (shard, room_name, data, fingerprint, room_x, room_y, updated_at) VALUES (:shard, :room_name, :data, :fingerprint, :room_x, :room_y, {CURRENT_TIMESTAMP_SQL})"), params)?;
+        Ok(())
+    }
+
+    /// See [upsert_terrain_for_room](crate::compressed_terrain_db::upsert_terrain_for_room).
+    pub fn upsert_terrain_for_room(&self, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":data": terrain.get_compressed_bytes(),
+            ":fingerprint": terrain.fingerprint() as i64,
+            ":room_x": room_name.x_coord(),
+            ":room_y": room_name.y_coord(),
+        };
+        self.in_transaction(|| {
+            self.delete_derived_room_data(room_name)?;
+            self.conn.execute(&format!("INSERT INTO room_terrain (shard, room_name, data, fingerprint, room_x, room_y, updated_at) VALUES (:shard, :room_name, :data, :fingerprint, :room_x, :room_y, {CURRENT_TIMESTAMP_SQL}) \
+                ON CONFLICT (shard, room_name) DO UPDATE SET data = excluded.data, encoding = excluded.encoding, fingerprint = excluded.fingerprint, updated_at = excluded.updated_at"), params)?;
+            Ok(())
+        })
+    }
+
+    /// See [delete_terrain_for_room](crate::compressed_terrain_db::delete_terrain_for_room).
+    pub fn delete_terrain_for_room(&self, room_name: RoomName) -> Result<bool, MapProcessingError> {
+        self.in_transaction(|| {
+            self.delete_derived_room_data(room_name)?;
+            let deleted = self.conn.execute("DELETE FROM room_terrain WHERE shard = ?1 AND room_name = ?2", [self.shard, &room_name.to_string()])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Internal helper function to delete the pyramid, room record, and exits stored for a room,
+    /// which would be stale once its terrain is replaced or deleted.
+    fn delete_derived_room_data(&self, room_name: RoomName) -> Result<(), MapProcessingError> {
+        let room_name = room_name.to_string();
+        for table in ["room_terrain_pyramid", "room_record", "room_exits"] {
+            if self.conn.table_exists(None, table)? {
+                self.conn.execute(&format!("DELETE FROM {table} WHERE shard = ?1 AND room_name = ?2"), [self.shard, &room_name])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Internal helper function to run several writes in one transaction, or in the caller's
+    /// transaction if one is already open.
+    fn in_transaction<T>(&self, write: impl FnOnce() -> Result<T, MapProcessingError>) -> Result<T, MapProcessingError> {
+        if !self.conn.is_autocommit() {
+            return write();
+        }
+        let transaction = self.conn.unchecked_transaction()?;
+        let result = write()?;
+        transaction.commit()?;
+        Ok(result)
+    }
+
+    /// See [update_terrain_for_room](crate::compressed_terrain_db::update_terrain_for_room).
+    pub fn update_terrain_for_room(&self, room_name: RoomName, terrain: &CompressedRoomTerrain) -> Result<usize, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":data": terrain.get_compressed_bytes(),
+            ":fingerprint": terrain.fingerprint() as i64,
+        };
+        Ok(self.conn.execute(&format!("UPDATE room_terrain SET data = :data, encoding = 'compressed', fingerprint = :fingerprint, updated_at = {CURRENT_TIMESTAMP_SQL} WHERE shard = :shard AND room_name = :room_name"), params)?)
+    }
+
+    /// See [get_terrain_for_room](crate::compressed_terrain_db::get_terrain_for_room).
+    pub fn get_terrain_for_room(&self, room_name: RoomName) -> Result<CompressedRoomTerrain, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+        };
+        let (bytes, encoding): (Vec<u8>, String) = self.conn.query_row("SELECT data, encoding FROM room_terrain WHERE shard = :shard AND room_name = :room_name LIMIT 1", params, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        decode_compressed_terrain(&encoding, &bytes)
+    }
+
+    /// See [get_terrains_for_rooms](crate::compressed_terrain_db::get_terrains_for_rooms).
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(shard = self.shard, rooms = rooms.len())))]
+    pub fn get_terrains_for_rooms(&self, rooms: &[RoomName]) -> Result<HashMap<RoomName, CompressedRoomTerrain>, MapProcessingError> {
+        let mut res = HashMap::with_capacity(rooms.len());
+
+        for chunk in rooms.chunks(ROOMS_PER_QUERY) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!("SELECT room_name, data, encoding FROM room_terrain WHERE shard = ? AND room_name IN ({placeholders})"))?;
+            let names: Vec<String> = chunk.iter().map(RoomName::to_string).collect();
+            let params = std::iter::once(self.shard).chain(names.iter().map(String::as_str));
+            let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+
+            while let Some(row) = rows.next()? {
+                let Ok(room_name) = RoomName::new(row.get::<usize, String>(0)?.as_str()) else {
+                    continue;
+                };
+                let (bytes, encoding): (Vec<u8>, String) = (row.get(1)?, row.get(2)?);
+                res.insert(room_name, decode_compressed_terrain(&encoding, &bytes)?);
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(found = res.len(), "loaded terrain for rooms");
+
+        Ok(res)
+    }
+
+    /// See [add_encoded_terrain_for_room](crate::compressed_terrain_db::add_encoded_terrain_for_room).
+    pub fn add_encoded_terrain_for_room(&self, room_name: RoomName, terrain: &AdaptiveRoomTerrain) -> Result<(), MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":data": terrain.to_encoded_bytes(),
+            ":encoding": terrain.encoding().name(),
+            ":fingerprint": terrain.to_compressed_terrain().fingerprint() as i64,
+            ":room_x": room_name.x_coord(),
+            ":room_y": room_name.y_coord(),
+        };
+        self.conn.execute(&format!("INSERT INTO room_terrain (shard, room_name, data, encoding, fingerprint, room_x, room_y, updated_at) VALUES (:shard, :room_name, :data, :encoding, :fingerprint, :room_x, :room_y, {CURRENT_TIMESTAMP_SQL})"), params)?;
+        Ok(())
+    }
+
+    /// See [get_terrain_any_encoding](crate::compressed_terrain_db::get_terrain_any_encoding).
+    pub fn get_terrain_any_encoding(&self, room_name: RoomName) -> Result<AdaptiveRoomTerrain, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+        };
+        let (bytes, encoding): (Vec<u8>, String) = self.conn.query_row("SELECT data, encoding FROM room_terrain WHERE shard = :shard AND room_name = :room_name LIMIT 1", params, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        decode_terrain(&encoding, &bytes)
+    }
+
+    /// See [get_terrain_updated_at](crate::compressed_terrain_db::get_terrain_updated_at).
+    pub fn get_terrain_updated_at(&self, room_name: RoomName) -> Result<Option<i64>, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+        };
+        let updated_at = self.conn.query_row("SELECT updated_at FROM room_terrain WHERE shard = :shard AND room_name = :room_name LIMIT 1", params, |row| row.get(0)).optional()?;
+        Ok(updated_at.flatten())
+    }
+
+    /// See [set_terrain_updated_at](crate::compressed_terrain_db::set_terrain_updated_at).
+    pub fn set_terrain_updated_at(&self, room_name: RoomName, timestamp: i64) -> Result<(), MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":updated_at": timestamp,
+        };
+        self.conn.execute("UPDATE room_terrain SET updated_at = :updated_at WHERE shard = :shard AND room_name = :room_name", params)?;
+        Ok(())
+    }
+
+    /// See [get_rooms_older_than](crate::compressed_terrain_db::get_rooms_older_than).
+    pub fn get_rooms_older_than(&self, timestamp: i64) -> Result<Vec<RoomName>, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":timestamp": timestamp,
+        };
+        let mut stmt = self.conn.prepare("SELECT room_name FROM room_terrain WHERE shard = :shard AND (updated_at IS NULL OR updated_at < :timestamp)")?;
+        let rows = stmt.query_map(params, |row| row.get::<usize, String>(0))?;
+        room_names_from_rows(rows)
+    }
+
+    /// See [get_room_fingerprints](crate::compressed_terrain_db::get_room_fingerprints).
+    pub fn get_room_fingerprints(&self) -> Result<HashMap<RoomName, u64>, MapProcessingError> {
+        let mut stmt = self.conn.prepare("SELECT room_name, fingerprint FROM room_terrain WHERE shard = ?1 AND fingerprint IS NOT NULL")?;
+        let rows = stmt.query_map([self.shard], |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, i64>(1)?)))?;
+
+        let mut res = HashMap::new();
+
+        for row in rows {
+            let (name, fingerprint) = row?;
+            if let Ok(name) = RoomName::new(name.as_str()) {
+                res.insert(name, fingerprint as u64);
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// See [get_rooms_with_terrain](crate::compressed_terrain_db::get_rooms_with_terrain).
+    pub fn get_rooms_with_terrain(&self) -> Result<Vec<RoomName>, MapProcessingError> {
+        let mut stmt = self.conn.prepare("SELECT room_name FROM room_terrain WHERE shard = ?1 ORDER BY room_x, room_y")?;
+        let rows = stmt.query_map([self.shard], |row| row.get::<usize, String>(0))?;
+        room_names_from_rows(rows)
+    }
+
+    /// See [iter_room_terrains](crate::compressed_terrain_db::iter_room_terrains).
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(shard = self.shard)))]
+    pub fn iter_room_terrains<B>(&self, mut f: impl FnMut(RoomName, Result<CompressedRoomTerrain, MapProcessingError>) -> ControlFlow<B>) -> Result<Option<B>, MapProcessingError> {
+        let mut stmt = self.conn.prepare("SELECT room_name, data, encoding FROM room_terrain WHERE shard = ?1 ORDER BY room_x, room_y")?;
+        let mut rows = stmt.query([self.shard])?;
+        #[cfg(feature = "tracing")]
+        let mut rooms = 0;
+
+        while let Some(row) = rows.next()? {
+            let Ok(room_name) = RoomName::new(row.get::<usize, String>(0)?.as_str()) else {
+                continue;
+            };
+            let (bytes, encoding): (Vec<u8>, String) = (row.get(1)?, row.get(2)?);
+            let terrain = decode_compressed_terrain(&encoding, &bytes);
+            #[cfg(feature = "tracing")]
+            {
+                rooms += 1;
+            }
+            if let ControlFlow::Break(value) = f(room_name, terrain) {
+                return Ok(Some(value));
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(rooms, "read terrain for every room");
+
+        Ok(None)
+    }
+
+    /// See [iter_rooms_from](crate::compressed_terrain_db::iter_rooms_from).
+    pub fn iter_rooms_from(&self, after: Option<RoomName>, limit: usize) -> Result<Vec<RoomName>, MapProcessingError> {
+        let mut stmt = self.conn.prepare("SELECT room_name, room_x, room_y FROM room_terrain WHERE shard = :shard AND (room_x, room_y) > (:after_x, :after_y) ORDER BY room_x, room_y LIMIT :limit")?;
+        let (mut after_x, mut after_y) = after.map(|room| (room.x_coord(), room.y_coord())).unwrap_or((i32::MIN, i32::MIN));
+        let mut res = Vec::new();
+
+        // Keep reading past rows with invalid names until the page is full or the rows run out
+        while res.len() < limit {
+            let remaining = limit - res.len();
+            let params = rusqlite::named_params!{
+                ":shard": self.shard,
+                ":after_x": after_x,
+                ":after_y": after_y,
+                ":limit": remaining as i64,
+            };
+            let mut rows = stmt.query(params)?;
+            let mut rows_read = 0;
+            while let Some(row) = rows.next()? {
+                rows_read += 1;
+                (after_x, after_y) = (row.get(1)?, row.get(2)?);
+                if let Ok(room_name) = RoomName::new(row.get::<usize, String>(0)?.as_str()) {
+                    res.push(room_name);
+                }
+            }
+            if rows_read < remaining {
+                break;
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// See [add_pyramid_for_room](crate::compressed_terrain_db::add_pyramid_for_room).
+    pub fn add_pyramid_for_room(&self, room_name: RoomName, pyramid: &TerrainPyramid) -> Result<(), MapProcessingError> {
+        self.set_room_data("room_terrain_pyramid", room_name, &pyramid.to_bytes())
+    }
+
+    /// See [get_pyramid_for_room](crate::compressed_terrain_db::get_pyramid_for_room).
+    pub fn get_pyramid_for_room(&self, room_name: RoomName) -> Result<TerrainPyramid, MapProcessingError> {
+        let bytes = self.get_room_data("room_terrain_pyramid", room_name)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        TerrainPyramid::try_from_slice(&bytes)
+    }
+
+    /// See [add_room_record](crate::compressed_terrain_db::add_room_record).
+    pub fn add_room_record(&self, record: &RoomRecord) -> Result<(), MapProcessingError> {
+        self.set_room_data("room_record", record.room(), &record.to_bytes()?)
+    }
+
+    /// See [get_room_record](crate::compressed_terrain_db::get_room_record).
+    pub fn get_room_record(&self, room_name: RoomName) -> Result<Option<RoomRecord>, MapProcessingError> {
+        self.get_room_data("room_record", room_name)?.map(|bytes| RoomRecord::from_bytes(&bytes)).transpose()
+    }
+
+    /// See [add_exits_for_room](crate::compressed_terrain_db::add_exits_for_room).
+    pub fn add_exits_for_room(&self, exits: &RoomExitsData) -> Result<(), MapProcessingError> {
+        self.set_room_data("room_exits", exits.room(), &exits.edge_terrain_data().to_bytes())
+    }
+
+    /// See [get_exits_for_room](crate::compressed_terrain_db::get_exits_for_room).
+    pub fn get_exits_for_room(&self, room_name: RoomName) -> Result<RoomExitsData, MapProcessingError> {
+        let bytes = self.get_room_data("room_exits", room_name)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let edges = RoomEdgeTerrain::from_bytes(&bytes)?;
+        Ok(RoomExitsData::new_from_compressed_edge_terrain_data(edges, room_name))
+    }
 
+    /// See [set_danger_score](crate::compressed_terrain_db::set_danger_score).
+    pub fn set_danger_score(&self, room_name: RoomName, score: f32) -> Result<(), MapProcessingError> {
+        self.conn.execute("INSERT OR REPLACE INTO room_danger (shard, room_name, score) VALUES (?1, ?2, ?3)", (self.shard, room_name.to_string(), score))?;
+        Ok(())
+    }
+
+    /// See [get_danger_scores](crate::compressed_terrain_db::get_danger_scores).
+    pub fn get_danger_scores(&self) -> Result<HashMap<RoomName, f32>, MapProcessingError> {
+        let mut stmt = self.conn.prepare("SELECT room_name, score FROM room_danger WHERE shard = ?1")?;
+        let rows = stmt.query_map([self.shard], |row| Ok((row.get::<usize, String>(0)?, row.get::<usize, f32>(1)?)))?;
+
+        let mut scores = HashMap::new();
+        for row in rows {
+            let (name, score) = row?;
+            // Rows with invalid room names are skipped
+            if let Ok(room_name) = RoomName::new(name.as_str()) {
+                scores.insert(room_name, score);
+            }
+        }
+        Ok(scores)
+    }
+
+    /// Internal helper function to store a room's data in a table created by
+    /// [create_room_table_if_not_exists], replacing any existing data for the room.
+    fn set_room_data(&self, table: &str, room_name: RoomName, data: &[u8]) -> Result<(), MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+            ":data": data,
+        };
+        self.conn.execute(&format!("INSERT OR REPLACE INTO {table} (shard, room_name, data) VALUES (:shard, :room_name, :data)"), params)?;
+        Ok(())
+    }
+
+    /// Internal helper function to load a room's data from a table created by
+    /// [create_room_table_if_not_exists], if there is any.
+    fn get_room_data(&self, table: &str, room_name: RoomName) -> Result<Option<Vec<u8>>, MapProcessingError> {
+        let params = rusqlite::named_params!{
+            ":shard": self.shard,
+            ":room_name": room_name.to_string(),
+        };
+        Ok(self.conn.query_row(&format!("SELECT data FROM {table} WHERE shard = :shard AND room_name = :room_name"), params, |row| row.get(0)).optional()?)
+    }
+}
+
+/// Internal helper function to collect room names from query rows, skipping invalid names.
+fn room_names_from_rows(rows: impl Iterator<Item = rusqlite::Result<String>>) -> Result<Vec<RoomName>, MapProcessingError> {
     let mut res = Vec::new();
 
     for names_result in rows {
@@ -55,3 +748,572 @@ pub fn get_rooms_with_terrain(conn: &Connection) -> Result<Vec<RoomName>, Error>
     Ok(res)
 }
 
+/// The database size before and after a [compact_db] run, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionReport {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+impl CompactionReport {
+    /// The number of bytes reclaimed by compaction.
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+/// Rebuilds the database to reclaim dead pages (VACUUM) and refreshes query planner statistics
+/// (ANALYZE).
+///
+/// Archives churned by repeated imports can grow significantly from dead pages.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn compact_db(conn: &Connection) -> Result<CompactionReport, MapProcessingError> {
+    let size_before = get_db_size(conn)?;
+    conn.execute_batch("VACUUM; ANALYZE;")?;
+    let size_after = get_db_size(conn)?;
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(size_before, size_after, "compacted database");
+
+    Ok(CompactionReport { size_before, size_after })
+}
+
+/// The size of the database, in bytes, as the number of pages times the page size.
+pub fn get_db_size(conn: &Connection) -> Result<u64, MapProcessingError> {
+    let page_count: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(page_count * page_size)
+}
+
+pub fn create_pyramid_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    create_room_table_if_not_exists(conn, "room_terrain_pyramid", "data", "BLOB")
+}
+
+/// Stores the downsampled terrain for a room, replacing any existing pyramid for it.
+pub fn add_pyramid_for_room(conn: &Connection, room_name: RoomName, pyramid: &TerrainPyramid) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).add_pyramid_for_room(room_name, pyramid)
+}
+
+pub fn get_pyramid_for_room(conn: &Connection, room_name: RoomName) -> Result<TerrainPyramid, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_pyramid_for_room(room_name)
+}
+
+/// Creates the table of cached analysis results, keyed by terrain fingerprint, analysis name, and
+/// analysis parameters.
+pub fn create_analysis_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS analysis_blob (fingerprint INTEGER NOT NULL, analysis TEXT NOT NULL, params TEXT NOT NULL, data BLOB, PRIMARY KEY (fingerprint, analysis, params));")?;
+    Ok(())
+}
+
+/// Stores an analysis result, replacing any existing result with the same key.
+pub fn set_analysis_blob(conn: &Connection, fingerprint: u64, analysis: &str, params: &str, data: &[u8]) -> Result<(), MapProcessingError> {
+    let params = rusqlite::named_params!{
+        ":fingerprint": fingerprint as i64,
+        ":analysis": analysis,
+        ":params": params,
+        ":data": data,
+    };
+    conn.execute("INSERT OR REPLACE INTO analysis_blob (fingerprint, analysis, params, data) VALUES (:fingerprint, :analysis, :params, :data)", params)?;
+    Ok(())
+}
+
+/// Gets a stored analysis result, if there is one.
+pub fn get_analysis_blob(conn: &Connection, fingerprint: u64, analysis: &str, params: &str) -> Result<Option<Vec<u8>>, MapProcessingError> {
+    let params = rusqlite::named_params!{
+        ":fingerprint": fingerprint as i64,
+        ":analysis": analysis,
+        ":params": params,
+    };
+    Ok(conn.query_row("SELECT data FROM analysis_blob WHERE fingerprint = :fingerprint AND analysis = :analysis AND params = :params", params, |row| row.get(0)).optional()?)
+}
+
+pub fn create_metadata_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS metadata (key TEXT PRIMARY KEY, value TEXT);")?;
+    Ok(())
+}
+
+pub fn set_metadata(conn: &Connection, key: &str, value: &str) -> Result<(), MapProcessingError> {
+    let params = rusqlite::named_params!{
+        ":key": key,
+        ":value": value,
+    };
+    conn.execute("INSERT OR REPLACE INTO metadata (key, value) VALUES (:key, :value)", params)?;
+    Ok(())
+}
+
+pub fn get_metadata(conn: &Connection, key: &str) -> Result<Option<String>, MapProcessingError> {
+    let params = rusqlite::named_params!{
+        ":key": key,
+    };
+    Ok(conn.query_row("SELECT value FROM metadata WHERE key = :key", params, |row| row.get(0)).optional()?)
+}
+
+/// Records the normalization rules that were applied to the terrain stored in this database.
+pub fn set_normalization_rules(conn: &Connection, rules: &NormalizationRules) -> Result<(), MapProcessingError> {
+    set_metadata(conn, NORMALIZATION_RULES_KEY, &rules.to_string())
+}
+
+/// The normalization rules that were applied to the terrain stored in this database, if they were
+/// recorded.
+///
+/// Unknown rule names are reported as [MapProcessingError::InvalidData].
+pub fn get_normalization_rules(conn: &Connection) -> Result<Option<NormalizationRules>, MapProcessingError> {
+    match get_metadata(conn, NORMALIZATION_RULES_KEY)? {
+        Some(names) => NormalizationRules::from_names(&names).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Creates the table of room records, keyed by shard and room name.
+pub fn create_room_record_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    create_room_table_if_not_exists(conn, "room_record", "data", "BLOB")
+}
+
+/// Stores a room record, replacing any existing record for the room.
+pub fn add_room_record(conn: &Connection, record: &RoomRecord) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).add_room_record(record)
+}
+
+/// Loads the record for a room, if there is one.
+pub fn get_room_record(conn: &Connection, room_name: RoomName) -> Result<Option<RoomRecord>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_room_record(room_name)
+}
+
+/// Creates the table of precomputed room exits, stored as 24 bytes of edge terrain per room.
+pub fn create_exits_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    create_room_table_if_not_exists(conn, "room_exits", "data", "BLOB")
+}
+
+/// Stores the exits for a room, replacing any existing exits for it.
+pub fn add_exits_for_room(conn: &Connection, exits: &RoomExitsData) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).add_exits_for_room(exits)
+}
+
+/// Loads the exits for a room stored by [add_exits_for_room].
+pub fn get_exits_for_room(conn: &Connection, room_name: RoomName) -> Result<RoomExitsData, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_exits_for_room(room_name)
+}
+
+/// Creates the table of room danger scores, keyed by shard and room name.
+pub fn create_danger_table_if_not_exists(conn: &Connection) -> Result<(), MapProcessingError> {
+    create_room_table_if_not_exists(conn, "room_danger", "score", "REAL")
+}
+
+/// Stores the danger score for a room, as computed by
+/// [danger_scores](crate::analysis::danger::danger_scores), replacing any existing score for it.
+pub fn set_danger_score(conn: &Connection, room_name: RoomName, score: f32) -> Result<(), MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).set_danger_score(room_name, score)
+}
+
+/// Gets the danger score of every room stored by [set_danger_score].
+pub fn get_danger_scores(conn: &Connection) -> Result<HashMap<RoomName, f32>, MapProcessingError> {
+    ShardDb::new(conn, DEFAULT_SHARD).get_danger_scores()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{RoomXY, Terrain, ROOM_AREA};
+
+    #[test]
+    pub fn get_rooms_older_than_uses_update_times() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Simulate a table created before update times were tracked
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W0N0', x'00')", []).unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let old_room = RoomName::new("W1N1").unwrap();
+        let new_room = RoomName::new("W2N2").unwrap();
+        add_terrain_for_room(&conn, old_room, &terrain).unwrap();
+        add_terrain_for_room(&conn, new_room, &terrain).unwrap();
+        set_terrain_updated_at(&conn, old_room, 1000).unwrap();
+
+        assert!(get_terrain_updated_at(&conn, new_room).unwrap().is_some());
+        assert_eq!(get_terrain_updated_at(&conn, RoomName::new("W0N0").unwrap()).unwrap(), None);
+        assert_eq!(get_rooms_older_than(&conn, 2000).unwrap(), vec![RoomName::new("W0N0").unwrap(), old_room]);
+    }
+
+    #[test]
+    pub fn get_room_fingerprints_includes_backfilled_rooms() {
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        assert_ne!(plains.fingerprint(), walls.fingerprint());
+
+        let conn = Connection::open_in_memory().unwrap();
+        // Simulate a row written before fingerprints were stored
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W0N0', ?1)", [walls.get_compressed_bytes()]).unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+
+        let room = RoomName::new("W1N1").unwrap();
+        add_terrain_for_room(&conn, room, &plains).unwrap();
+
+        let fingerprints = get_room_fingerprints(&conn).unwrap();
+        assert_eq!(fingerprints.len(), 2);
+        assert_eq!(fingerprints[&room], plains.fingerprint());
+        assert_eq!(fingerprints[&RoomName::new("W0N0").unwrap()], walls.fingerprint());
+
+        update_terrain_for_room(&conn, room, &walls).unwrap();
+        assert_eq!(get_room_fingerprints(&conn).unwrap()[&room], walls.fingerprint());
+    }
+
+    #[test]
+    pub fn upsert_terrain_for_room_replaces_existing_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Simulate a table from a version that allowed duplicate rooms
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        let old_terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let new_terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        for terrain in [&old_terrain, &new_terrain] {
+            conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W1N1', ?1)", [terrain.get_compressed_bytes()]).unwrap();
+        }
+        migrate(&conn).unwrap();
+        assert_eq!(get_metadata(&conn, DUPLICATE_ROOMS_REMOVED_KEY).unwrap(), Some("1".to_string()));
+
+        let room = RoomName::new("W1N1").unwrap();
+        assert_eq!(get_rooms_with_terrain(&conn).unwrap(), vec![room]);
+        assert_eq!(get_terrain_for_room(&conn, room).unwrap().fingerprint(), new_terrain.fingerprint());
+        assert!(add_terrain_for_room(&conn, room, &old_terrain).is_err());
+
+        upsert_terrain_for_room(&conn, room, &old_terrain).unwrap();
+        assert_eq!(get_terrain_for_room(&conn, room).unwrap().fingerprint(), old_terrain.fingerprint());
+        assert_eq!(get_room_fingerprints(&conn).unwrap()[&room], old_terrain.fingerprint());
+
+        assert!(delete_terrain_for_room(&conn, room).unwrap());
+        assert!(!delete_terrain_for_room(&conn, room).unwrap());
+        assert!(get_terrain_for_room(&conn, room).is_err());
+        upsert_terrain_for_room(&conn, room, &new_terrain).unwrap();
+        assert_eq!(get_rooms_with_terrain(&conn).unwrap(), vec![room]);
+    }
+
+    #[test]
+    pub fn delete_terrain_for_room_deletes_derived_room_data() {
+        let conn = open_in_memory_db().unwrap();
+        let room = RoomName::new("W1N1").unwrap();
+        let other_room = RoomName::new("W2N1").unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        for room in [room, other_room] {
+            add_terrain_for_room(&conn, room, &terrain).unwrap();
+            add_pyramid_for_room(&conn, room, &TerrainPyramid::new_from_compressed_terrain(&terrain)).unwrap();
+            add_exits_for_room(&conn, &RoomExitsData::new_from_compressed_terrain(&terrain, room)).unwrap();
+            add_room_record(&conn, &RoomRecord::new_from_compressed_terrain(room, &terrain)).unwrap();
+        }
+
+        assert!(delete_terrain_for_room(&conn, room).unwrap());
+        assert!(get_pyramid_for_room(&conn, room).is_err());
+        assert!(get_exits_for_room(&conn, room).is_err());
+        assert!(get_room_record(&conn, room).unwrap().is_none());
+
+        assert!(get_pyramid_for_room(&conn, other_room).is_ok());
+        assert!(get_exits_for_room(&conn, other_room).is_ok());
+        assert!(get_room_record(&conn, other_room).unwrap().is_some());
+    }
+
+    #[test]
+    pub fn upsert_terrain_for_room_deletes_derived_room_data() {
+        let conn = open_in_memory_db().unwrap();
+        let room = RoomName::new("W1N1").unwrap();
+        let other_room = RoomName::new("W2N1").unwrap();
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        for room in [room, other_room] {
+            add_terrain_for_room(&conn, room, &plains).unwrap();
+            add_pyramid_for_room(&conn, room, &TerrainPyramid::new_from_compressed_terrain(&plains)).unwrap();
+            add_exits_for_room(&conn, &RoomExitsData::new_from_compressed_terrain(&plains, room)).unwrap();
+            add_room_record(&conn, &RoomRecord::new_from_compressed_terrain(room, &plains)).unwrap();
+        }
+
+        upsert_terrain_for_room(&conn, room, &walls).unwrap();
+        assert_eq!(get_terrain_for_room(&conn, room).unwrap().fingerprint(), walls.fingerprint());
+        assert!(get_pyramid_for_room(&conn, room).is_err());
+        assert!(get_exits_for_room(&conn, room).is_err());
+        assert!(get_room_record(&conn, room).unwrap().is_none());
+
+        assert!(get_pyramid_for_room(&conn, other_room).is_ok());
+        assert!(get_exits_for_room(&conn, other_room).is_ok());
+        assert!(get_room_record(&conn, other_room).unwrap().is_some());
+
+        // Writes inside a caller's transaction join it instead of failing to open their own
+        let transaction = conn.unchecked_transaction().unwrap();
+        upsert_terrain_for_room(&conn, other_room, &walls).unwrap();
+        transaction.rollback().unwrap();
+        assert_eq!(get_terrain_for_room(&conn, other_room).unwrap().fingerprint(), plains.fingerprint());
+        assert!(get_exits_for_room(&conn, other_room).is_ok());
+    }
+
+    #[test]
+    pub fn encoded_terrain_round_trips_through_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+        // Room corners are always walls, which wildcard RLE relies on
+        let corners = [(0, 0), (49, 0), (0, 49), (49, 49)].map(|(x, y)| (RoomXY::checked_new(x, y).unwrap(), Terrain::Wall));
+        let plains = CompressedRoomTerrain::from_sparse(Terrain::Plain, corners);
+        let room = RoomName::new("W1N1").unwrap();
+
+        for encoding in TerrainEncoding::ALL {
+            add_encoded_terrain_for_room(&conn, room, &AdaptiveRoomTerrain::new_with_encoding(&plains, encoding)).unwrap();
+            assert_eq!(get_terrain_any_encoding(&conn, room).unwrap().encoding(), encoding);
+            assert_eq!(get_terrain_for_room(&conn, room).unwrap().fingerprint(), plains.fingerprint());
+            assert_eq!(get_room_fingerprints(&conn).unwrap()[&room], plains.fingerprint());
+            assert!(delete_terrain_for_room(&conn, room).unwrap());
+        }
+
+        // Writing compressed terrain over an encoded row resets its encoding
+        add_encoded_terrain_for_room(&conn, room, &AdaptiveRoomTerrain::new_from_compressed_terrain(&plains)).unwrap();
+        upsert_terrain_for_room(&conn, room, &plains).unwrap();
+        assert_eq!(get_terrain_any_encoding(&conn, room).unwrap().encoding(), TerrainEncoding::Compressed);
+
+        conn.execute("UPDATE room_terrain SET encoding = 'nope'", []).unwrap();
+        assert!(get_terrain_any_encoding(&conn, room).is_err());
+    }
+
+    #[test]
+    pub fn shard_db_scopes_rooms_per_shard() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Simulate a single-shard table, whose rooms move to the default shard
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W1N1', ?1)", [plains.get_compressed_bytes()]).unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+
+        let room = RoomName::new("W1N1").unwrap();
+        let shard3 = ShardDb::new(&conn, "shard3");
+        shard3.add_terrain_for_room(room, &walls).unwrap();
+        shard3.add_terrain_for_room(RoomName::new("W2N2").unwrap(), &walls).unwrap();
+
+        assert_eq!(get_shards(&conn).unwrap(), vec![DEFAULT_SHARD.to_string(), "shard3".to_string()]);
+        assert_eq!(get_rooms_with_terrain(&conn).unwrap(), vec![room]);
+        assert_eq!(get_terrain_for_room(&conn, room).unwrap().fingerprint(), plains.fingerprint());
+        assert_eq!(shard3.get_terrain_for_room(room).unwrap().fingerprint(), walls.fingerprint());
+        assert_eq!(shard3.get_rooms_with_terrain().unwrap().len(), 2);
+        assert_eq!(shard3.get_room_fingerprints().unwrap()[&room], walls.fingerprint());
+
+        assert!(shard3.delete_terrain_for_room(room).unwrap());
+        assert!(get_terrain_for_room(&conn, room).is_ok());
+        assert!(ShardDb::new(&conn, "shard0").get_rooms_with_terrain().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn shard_db_scopes_derived_room_data_per_shard() {
+        // A version 4 database, whose exits table is keyed by room name alone
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..4] {
+            migration(&conn).unwrap();
+        }
+        conn.execute_batch("DROP TABLE room_exits; CREATE TABLE room_exits (room_name TEXT PRIMARY KEY, data BLOB);").unwrap();
+        let room = RoomName::new("W1N1").unwrap();
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let open_exits = RoomExitsData::new_from_compressed_terrain(&plains, room);
+        conn.execute("INSERT INTO room_exits (room_name, data) VALUES ('W1N1', ?1)", [open_exits.edge_terrain_data().to_bytes()]).unwrap();
+
+        conn.execute_batch("CREATE TABLE schema_version (version INTEGER NOT NULL); INSERT INTO schema_version (version) VALUES (4);").unwrap();
+        assert_eq!(migrate(&conn).unwrap(), 4);
+        assert!(conn.column_exists(None, "room_exits", "shard").unwrap());
+        assert_eq!(get_exits_for_room(&conn, room).unwrap().iter_exits().count(), open_exits.iter_exits().count());
+
+        let shard3 = ShardDb::new(&conn, "shard3");
+        assert!(shard3.get_exits_for_room(room).is_err());
+        assert!(shard3.get_pyramid_for_room(room).is_err());
+
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        shard3.add_exits_for_room(&RoomExitsData::new_from_compressed_terrain(&walls, room)).unwrap();
+        shard3.add_pyramid_for_room(room, &TerrainPyramid::new_from_compressed_terrain(&walls)).unwrap();
+        add_pyramid_for_room(&conn, room, &TerrainPyramid::new_from_compressed_terrain(&plains)).unwrap();
+
+        assert_eq!(shard3.get_exits_for_room(room).unwrap().iter_exits().count(), 0);
+        assert_eq!(get_exits_for_room(&conn, room).unwrap().iter_exits().count(), open_exits.iter_exits().count());
+        assert_eq!(shard3.get_pyramid_for_room(room).unwrap(), TerrainPyramid::new_from_compressed_terrain(&walls));
+        assert_eq!(get_pyramid_for_room(&conn, room).unwrap(), TerrainPyramid::new_from_compressed_terrain(&plains));
+    }
+
+    #[test]
+    pub fn danger_scores_are_scoped_per_shard() {
+        // A version 5 database, whose danger table is keyed by room name alone
+        let conn = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..5] {
+            migration(&conn).unwrap();
+        }
+        conn.execute_batch("DROP TABLE room_danger; CREATE TABLE room_danger (room_name TEXT PRIMARY KEY, score REAL); INSERT INTO room_danger (room_name, score) VALUES ('W1N1', 0.5);").unwrap();
+        conn.execute_batch("CREATE TABLE schema_version (version INTEGER NOT NULL); INSERT INTO schema_version (version) VALUES (5);").unwrap();
+        assert_eq!(migrate(&conn).unwrap(), 5);
+
+        let room = RoomName::new("W1N1").unwrap();
+        let shard3 = ShardDb::new(&conn, "shard3");
+        assert!(shard3.get_danger_scores().unwrap().is_empty());
+        shard3.set_danger_score(room, 2.0).unwrap();
+
+        assert_eq!(get_danger_scores(&conn).unwrap(), HashMap::from([(room, 0.5)]));
+        assert_eq!(shard3.get_danger_scores().unwrap(), HashMap::from([(room, 2.0)]));
+    }
+
+    #[test]
+    pub fn migrate_upgrades_unversioned_databases() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W1N1', ?1)", [terrain.get_compressed_bytes()]).unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), 0);
+
+        assert_eq!(migrate(&conn).unwrap(), 0);
+        assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let room = RoomName::new("W1N1").unwrap();
+        assert_eq!(get_room_fingerprints(&conn).unwrap()[&room], terrain.fingerprint());
+        assert!(conn.table_exists(None, "room_exits").unwrap());
+        assert!(conn.table_exists(None, "room_danger").unwrap());
+
+        assert_eq!(migrate(&conn).unwrap(), SCHEMA_VERSION);
+        conn.execute("UPDATE schema_version SET version = ?1", [SCHEMA_VERSION + 1]).unwrap();
+        assert!(migrate(&conn).is_err());
+    }
+
+    #[test]
+    pub fn open_in_memory_and_temp_dbs_are_migrated() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let room = RoomName::new("W1N1").unwrap();
+        for conn in [open_in_memory_db().unwrap(), open_temp_db().unwrap()] {
+            assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+            add_terrain_for_room(&conn, room, &terrain).unwrap();
+            set_danger_score(&conn, room, 1.0).unwrap();
+            assert_eq!(get_rooms_with_terrain(&conn).unwrap(), vec![room]);
+        }
+    }
+
+    #[test]
+    pub fn exits_round_trip_through_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_exits_table_if_not_exists(&conn).unwrap();
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..50].fill(1); // Wall off the top edge
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W1N1").unwrap();
+        let exits = RoomExitsData::new_from_compressed_terrain(&terrain, room);
+        add_exits_for_room(&conn, &exits).unwrap();
+
+        let loaded = get_exits_for_room(&conn, room).unwrap();
+        assert_eq!(loaded.room(), room);
+        assert_eq!(loaded.iter_exits().collect::<Vec<_>>(), exits.iter_exits().collect::<Vec<_>>());
+        assert!(loaded.top_edge_exits().is_empty());
+        assert!(get_exits_for_room(&conn, RoomName::new("W2N2").unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn pyramids_round_trip_through_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_pyramid_table_if_not_exists(&conn).unwrap();
+
+        let room = RoomName::new("W1N1").unwrap();
+        let walls = TerrainPyramid::new_from_compressed_terrain(&CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]));
+        let swamps = TerrainPyramid::new_from_compressed_terrain(&CompressedRoomTerrain::new_from_uncompressed_bits(&[2u8; ROOM_AREA]));
+
+        add_pyramid_for_room(&conn, room, &walls).unwrap();
+        add_pyramid_for_room(&conn, room, &swamps).unwrap();
+        assert_eq!(get_pyramid_for_room(&conn, room).unwrap(), swamps);
+    }
+
+    #[test]
+    pub fn get_terrain_for_room_rejects_malformed_data() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W0N0', x'00')", []).unwrap();
+
+        let result = get_terrain_for_room(&conn, RoomName::new("W0N0").unwrap());
+        assert!(matches!(result, Err(MapProcessingError::InvalidLength { expected: COMPRESSED_ARRAY_SIZE, found: 1, .. })));
+        assert!(matches!(get_terrain_for_room(&conn, RoomName::new("W1N1").unwrap()), Err(MapProcessingError::Database(_))));
+    }
+
+    #[test]
+    pub fn get_terrains_for_rooms_loads_rooms_in_chunks() {
+        let conn = open_in_memory_db().unwrap();
+        let shard = ShardDb::new(&conn, "shard0");
+        let rooms: Vec<RoomName> = (0..30).flat_map(|x| (0..20).map(move |y| RoomName::new(&format!("E{x}S{y}")).unwrap())).collect();
+        for (idx, room) in rooms.iter().enumerate() {
+            let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[(idx % 3) as u8; ROOM_AREA]);
+            shard.add_terrain_for_room(*room, &terrain).unwrap();
+        }
+        add_terrain_for_room(&conn, rooms[0], &CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA])).unwrap();
+
+        let mut wanted = rooms.clone();
+        wanted.push(RoomName::new("W1N1").unwrap());
+        let terrains = shard.get_terrains_for_rooms(&wanted).unwrap();
+        assert!(rooms.len() > ROOMS_PER_QUERY);
+        assert_eq!(terrains.len(), rooms.len());
+        assert_eq!(terrains[&rooms[0]].get_uncompressed_bits()[0], 0);
+        assert_eq!(terrains[&rooms[ROOMS_PER_QUERY + 2]].get_uncompressed_bits()[0], ((ROOMS_PER_QUERY + 2) % 3) as u8);
+
+        assert_eq!(get_terrains_for_rooms(&conn, &rooms).unwrap().len(), 1);
+        assert!(get_terrains_for_rooms(&conn, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn iter_room_terrains_reads_every_room_in_order() {
+        let conn = open_in_memory_db().unwrap();
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let swamps = CompressedRoomTerrain::new_from_uncompressed_bits(&[2u8; ROOM_AREA]);
+        add_terrain_for_room(&conn, RoomName::new("E1N1").unwrap(), &plains).unwrap();
+        add_encoded_terrain_for_room(&conn, RoomName::new("W1N1").unwrap(), &AdaptiveRoomTerrain::new_from_compressed_terrain(&swamps)).unwrap();
+        ShardDb::new(&conn, "shard1").add_terrain_for_room(RoomName::new("W5N5").unwrap(), &plains).unwrap();
+
+        let mut seen = Vec::new();
+        let stopped = iter_room_terrains(&conn, |room_name, terrain| {
+            seen.push((room_name.to_string(), terrain.unwrap().fingerprint()));
+            ControlFlow::<()>::Continue(())
+        }).unwrap();
+        assert_eq!(stopped, None);
+        assert_eq!(seen, vec![("W1N1".to_string(), swamps.fingerprint()), ("E1N1".to_string(), plains.fingerprint())]);
+
+        let mut calls = 0;
+        let first = iter_room_terrains(&conn, |room_name, _| {
+            calls += 1;
+            ControlFlow::Break(room_name)
+        }).unwrap();
+        assert_eq!(first, Some(RoomName::new("W1N1").unwrap()));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    pub fn iter_rooms_from_pages_through_rooms_in_position_order() {
+        let conn = Connection::open_in_memory().unwrap();
+        // Simulate a row written before room positions were stored
+        conn.execute_batch("CREATE TABLE room_terrain (id INTEGER PRIMARY KEY, room_name TEXT,  data BLOB);").unwrap();
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('E1N1', x'00')", []).unwrap();
+        create_terrain_table_if_not_exists(&conn).unwrap();
+
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        for name in ["E0S0", "W1N1", "E0N1", "W1S0"] {
+            add_terrain_for_room(&conn, RoomName::new(name).unwrap(), &terrain).unwrap();
+        }
+
+        let names = |rooms: Vec<RoomName>| rooms.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        let expected = ["W1N1", "W1S0", "E0N1", "E0S0", "E1N1"];
+        assert_eq!(names(get_rooms_with_terrain(&conn).unwrap()), expected);
+
+        let first_page = iter_rooms_from(&conn, None, 2).unwrap();
+        assert_eq!(names(first_page.clone()), expected[..2]);
+        let second_page = iter_rooms_from(&conn, first_page.last().copied(), 2).unwrap();
+        assert_eq!(names(second_page.clone()), expected[2..4]);
+        let last_page = iter_rooms_from(&conn, second_page.last().copied(), 2).unwrap();
+        assert_eq!(names(last_page), expected[4..]);
+    }
+
+    #[test]
+    pub fn iter_rooms_from_skips_invalid_names_without_shortening_pages() {
+        let conn = open_in_memory_db().unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        for name in ["W1N1", "W1S0", "E0N1"] {
+            add_terrain_for_room(&conn, RoomName::new(name).unwrap(), &terrain).unwrap();
+        }
+        // Rows with invalid names between W1N1 and W1S0
+        conn.execute("INSERT INTO room_terrain (room_name, data, room_x, room_y) VALUES ('bogus1', x'00', -2, -1), ('bogus2', x'00', -2, -1)", []).unwrap();
+
+        let names = |rooms: Vec<RoomName>| rooms.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+        let first_page = iter_rooms_from(&conn, None, 1).unwrap();
+        assert_eq!(names(first_page.clone()), ["W1N1"]);
+        let second_page = iter_rooms_from(&conn, first_page.last().copied(), 1).unwrap();
+        assert_eq!(names(second_page.clone()), ["W1S0"]);
+        assert_eq!(names(iter_rooms_from(&conn, None, 10).unwrap()), ["W1N1", "W1S0", "E0N1"]);
+    }
+}
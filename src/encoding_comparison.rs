@@ -0,0 +1,389 @@
+//! Compares how much memory each terrain encoding needs for the rooms in a terrain database, and
+//! plans which encoding to keep each room in under a memory budget.
+
+use std::collections::HashMap;
+
+use screeps::{RoomName, ROOM_AREA};
+
+use crate::adaptive_terrain::TerrainEncoding;
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::run_length_encoding::rle_terrain::{PackedRLERoomTerrain, RLERoomTerrain, WildcardRLERoomTerrain};
+use crate::terrain_repository::TerrainRepository;
+
+/// The size of a single room's terrain in each encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoomEncodingSizes {
+    pub room: RoomName,
+    pub compressed_size: usize,
+    /// The size of the unpacked u16 run length encoding, for reference; it's never the smallest.
+    pub rle_size: usize,
+    pub packed_rle_size: usize,
+    pub wildcard_rle_size: usize,
+    pub rle_runs: usize,
+    pub packed_rle_runs: usize,
+    pub wildcard_rle_runs: usize,
+}
+
+impl RoomEncodingSizes {
+    /// Measures a room's terrain in each encoding.
+    pub fn new_from_compressed_terrain(room: RoomName, terrain: &CompressedRoomTerrain) -> Self {
+        let rle = RLERoomTerrain::new_from_compressed_terrain(terrain);
+        let packed = PackedRLERoomTerrain::new_from_compressed_terrain(terrain);
+        let wildcard = WildcardRLERoomTerrain::new_from_compressed_terrain(terrain);
+
+        Self {
+            room,
+            compressed_size: terrain.memory_size(),
+            rle_size: rle.memory_size(),
+            packed_rle_size: packed.memory_size(),
+            wildcard_rle_size: wildcard.memory_size(),
+            rle_runs: rle.num_runs(),
+            packed_rle_runs: packed.num_runs(),
+            wildcard_rle_runs: wildcard.num_runs(),
+        }
+    }
+
+    /// The size of the room's terrain in the specified encoding, in bytes.
+    pub fn size_for(&self, encoding: TerrainEncoding) -> usize {
+        match encoding {
+            TerrainEncoding::Compressed => self.compressed_size,
+            TerrainEncoding::PackedRLE => self.packed_rle_size,
+            TerrainEncoding::WildcardRLE => self.wildcard_rle_size,
+        }
+    }
+
+    /// The smallest encoding for the room, with ties broken by [TerrainEncoding::smallest], so it's
+    /// always the encoding [AdaptiveRoomTerrain](crate::adaptive_terrain::AdaptiveRoomTerrain)
+    /// would store the room in.
+    pub fn optimal_encoding(&self) -> TerrainEncoding {
+        TerrainEncoding::smallest(|encoding| self.size_for(encoding))
+    }
+
+    /// The size of the room's terrain in its smallest encoding, in bytes.
+    pub fn optimal_size(&self) -> usize {
+        self.size_for(self.optimal_encoding())
+    }
+}
+
+/// Summary statistics for the number of runs rooms need in a run length encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RunDistribution {
+    pub min: usize,
+    pub median: usize,
+    pub max: usize,
+}
+
+impl RunDistribution {
+    /// Summarizes a list of per-room run counts; an empty list summarizes to all zeroes.
+    pub fn new_from_run_counts(mut runs: Vec<usize>) -> Self {
+        runs.sort_unstable();
+        Self {
+            min: runs.first().copied().unwrap_or(0),
+            median: runs.get(runs.len() / 2).copied().unwrap_or(0),
+            max: runs.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+/// The results of comparing terrain encodings across a set of rooms.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EncodingComparisonReport {
+    /// The sizes for each room compared.
+    pub rooms: Vec<RoomEncodingSizes>,
+    /// The rooms that were skipped because their terrain was missing or couldn't be decoded.
+    pub skipped_rooms: Vec<RoomName>,
+    pub packed_rle_runs: RunDistribution,
+    pub wildcard_rle_runs: RunDistribution,
+}
+
+impl EncodingComparisonReport {
+    /// Builds a report from the sizes of each room.
+    pub fn new_from_rooms(rooms: Vec<RoomEncodingSizes>) -> Self {
+        let packed_rle_runs = RunDistribution::new_from_run_counts(rooms.iter().map(|room| room.packed_rle_runs).collect());
+        let wildcard_rle_runs = RunDistribution::new_from_run_counts(rooms.iter().map(|room| room.wildcard_rle_runs).collect());
+
+        Self { rooms, skipped_rooms: Vec::new(), packed_rle_runs, wildcard_rle_runs }
+    }
+
+    /// The number of rooms compared.
+    pub fn rooms_processed(&self) -> usize {
+        self.rooms.len()
+    }
+
+    /// The number of rooms skipped because their terrain was missing or couldn't be decoded.
+    pub fn rooms_skipped(&self) -> usize {
+        self.skipped_rooms.len()
+    }
+
+    /// The number of rooms whose smallest encoding is `encoding`.
+    pub fn rooms_optimal_for(&self, encoding: TerrainEncoding) -> usize {
+        self.rooms.iter().filter(|room| room.optimal_encoding() == encoding).count()
+    }
+
+    /// The storage needed for the rooms whose smallest encoding is `encoding`, in bytes.
+    pub fn storage_needed_for(&self, encoding: TerrainEncoding) -> usize {
+        self.rooms.iter()
+            .filter(|room| room.optimal_encoding() == encoding)
+            .map(|room| room.size_for(encoding))
+            .sum()
+    }
+
+    /// The storage needed if every room was stored uncompressed, one byte per tile.
+    pub fn uncompressed_storage_needed(&self) -> usize {
+        self.rooms.len() * ROOM_AREA
+    }
+
+    /// The storage needed if every room was stored as [CompressedRoomTerrain].
+    pub fn compressed_only_storage_needed(&self) -> usize {
+        self.rooms.iter().map(|room| room.compressed_size).sum()
+    }
+
+    /// The storage needed if every room was stored in its smallest encoding.
+    pub fn total_storage_needed(&self) -> usize {
+        self.rooms.iter().map(|room| room.optimal_size()).sum()
+    }
+}
+
+/// Compares the size of each terrain encoding for every room in a terrain repository, such as a
+/// terrain database.
+///
+/// Rooms are compared in the order returned by [list_rooms](TerrainRepository::list_rooms). Rooms
+/// whose terrain is missing or can't be decoded are skipped and listed in
+/// [skipped_rooms](EncodingComparisonReport::skipped_rooms); only failing to list the rooms is an
+/// error.
+pub fn compare_encodings(repo: &impl TerrainRepository) -> Result<EncodingComparisonReport, MapProcessingError> {
+    let room_names = repo.list_rooms()?;
+
+    // Load every room at once, falling back to loading rooms one at a time if a bad room fails the
+    // bulk load
+    let terrains: Vec<(RoomName, Option<CompressedRoomTerrain>)> = match repo.get_terrains(&room_names) {
+        Ok(terrains) => {
+            let mut terrains: HashMap<RoomName, CompressedRoomTerrain> = terrains.into_iter().collect();
+            room_names.iter().map(|room_name| (*room_name, terrains.remove(room_name))).collect()
+        },
+        Err(_) => room_names.iter().map(|room_name| (*room_name, repo.get_terrain(*room_name).ok().flatten())).collect(),
+    };
+
+    let mut rooms = Vec::new();
+    let mut skipped_rooms = Vec::new();
+    for (room_name, terrain) in terrains {
+        match terrain {
+            Some(terrain) => rooms.push(RoomEncodingSizes::new_from_compressed_terrain(room_name, &terrain)),
+            None => skipped_rooms.push(room_name),
+        }
+    }
+
+    let mut report = EncodingComparisonReport::new_from_rooms(rooms);
+    report.skipped_rooms = skipped_rooms;
+    Ok(report)
+}
+
+/// How a room is kept under a [StoragePlan].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoomStorage {
+    /// The full terrain, in the specified encoding.
+    Full(TerrainEncoding),
+    /// Only the edge terrain, as a [RoomEdgeTerrain], which is enough to know the room's exits.
+    EdgeOnly,
+    /// Nothing; the budget didn't have room for it.
+    Omitted,
+}
+
+impl RoomStorage {
+    /// The memory needed to keep a room this way, in bytes.
+    pub fn size_for(&self, sizes: &RoomEncodingSizes) -> usize {
+        match self {
+            RoomStorage::Full(encoding) => sizes.size_for(*encoding),
+            RoomStorage::EdgeOnly => edge_only_size(),
+            RoomStorage::Omitted => 0,
+        }
+    }
+}
+
+/// The choice of how to keep each room, so that the total fits in a memory budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePlan {
+    /// The budget the plan was made for, in bytes.
+    pub budget_bytes: usize,
+    /// How each room is kept, in the order the rooms were provided.
+    pub rooms: Vec<(RoomName, RoomStorage)>,
+    /// The total memory used by the plan, in bytes.
+    pub total_size: usize,
+}
+
+impl StoragePlan {
+    /// Plans how to keep each room within `budget_bytes`, maximizing coverage.
+    ///
+    /// Every room is kept edge-only first, so that exits are known for as many rooms as possible.
+    /// The remaining budget then upgrades rooms to their smallest full encoding, cheapest upgrades
+    /// first, which maximizes the number of rooms with full terrain. If the budget can't fit every
+    /// room edge-only, the rooms listed first are kept.
+    pub fn new_from_sizes(rooms: &[RoomEncodingSizes], budget_bytes: usize) -> Self {
+        let edge_size = edge_only_size();
+        let mut remaining = budget_bytes;
+        let mut storage: Vec<RoomStorage> = rooms.iter()
+            .map(|_| {
+                if remaining >= edge_size {
+                    remaining -= edge_size;
+                    RoomStorage::EdgeOnly
+                } else {
+                    RoomStorage::Omitted
+                }
+            })
+            .collect();
+
+        let mut upgrades: Vec<(usize, usize)> = rooms.iter().enumerate()
+            .filter(|(idx, _)| storage[*idx] == RoomStorage::EdgeOnly)
+            .map(|(idx, sizes)| (sizes.optimal_size().saturating_sub(edge_size), idx))
+            .collect();
+        upgrades.sort_unstable();
+
+        for (cost, idx) in upgrades {
+            if cost > remaining {
+                break;
+            }
+            remaining -= cost;
+            storage[idx] = RoomStorage::Full(rooms[idx].optimal_encoding());
+        }
+
+        let total_size = rooms.iter().zip(&storage).map(|(sizes, storage)| storage.size_for(sizes)).sum();
+        let rooms = rooms.iter().zip(storage).map(|(sizes, storage)| (sizes.room, storage)).collect();
+
+        Self { budget_bytes, rooms, total_size }
+    }
+
+    /// How the specified room is kept, or None if it wasn't part of the plan.
+    pub fn storage_for(&self, room: RoomName) -> Option<RoomStorage> {
+        self.rooms.iter().find(|(name, _)| *name == room).map(|(_, storage)| *storage)
+    }
+
+    /// The number of rooms kept with their full terrain.
+    pub fn num_full_rooms(&self) -> usize {
+        self.rooms.iter().filter(|(_, storage)| matches!(storage, RoomStorage::Full(_))).count()
+    }
+
+    /// The number of rooms kept edge-only.
+    pub fn num_edge_only_rooms(&self) -> usize {
+        self.rooms.iter().filter(|(_, storage)| *storage == RoomStorage::EdgeOnly).count()
+    }
+
+    /// The number of rooms left out of the plan.
+    pub fn num_omitted_rooms(&self) -> usize {
+        self.rooms.iter().filter(|(_, storage)| *storage == RoomStorage::Omitted).count()
+    }
+}
+
+/// Plans how to keep every room in a terrain repository within `budget_bytes`.
+///
+/// See [StoragePlan::new_from_sizes] for how rooms are chosen.
+pub fn storage_plan(repo: &impl TerrainRepository, budget_bytes: usize) -> Result<StoragePlan, MapProcessingError> {
+    let report = compare_encodings(repo)?;
+    Ok(StoragePlan::new_from_sizes(&report.rooms, budget_bytes))
+}
+
+/// Internal helper function returning the memory needed to keep a room edge-only.
+fn edge_only_size() -> usize {
+    RoomEdgeTerrain::new_from_raw_bytes([0; 24]).memory_size()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "db")]
+    use rusqlite::Connection;
+    #[cfg(feature = "db")]
+    use crate::compressed_terrain_db;
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn compare_encodings_reports_per_room_winners() {
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+
+        // A room of plains compresses down to very few runs, while a checkerboard has a run for
+        // every tile
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let mut bits = [0u8; ROOM_AREA];
+        for (idx, bit) in bits.iter_mut().enumerate() {
+            *bit = ((idx + idx / 50) % 2) as u8;
+        }
+        let checkerboard = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new("W1N1").unwrap(), &plains).unwrap();
+        compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new("W2N1").unwrap(), &checkerboard).unwrap();
+
+        let report = compare_encodings(&conn).unwrap();
+        assert_eq!(report.rooms_processed(), 2);
+        assert_eq!(report.rooms_optimal_for(TerrainEncoding::Compressed), 1);
+        assert_eq!(report.storage_needed_for(TerrainEncoding::Compressed), checkerboard.memory_size());
+        assert_eq!(report.compressed_only_storage_needed(), 2 * plains.memory_size());
+        assert!(report.total_storage_needed() < report.compressed_only_storage_needed());
+        assert_eq!(report.uncompressed_storage_needed(), 2 * ROOM_AREA);
+        assert!(report.packed_rle_runs.min < report.packed_rle_runs.max);
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn compare_encodings_skips_undecodable_rooms() {
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new("W1N1").unwrap(), &plains).unwrap();
+        conn.execute("INSERT INTO room_terrain (room_name, data) VALUES ('W2N1', x'00')", []).unwrap();
+
+        let report = compare_encodings(&conn).unwrap();
+        assert_eq!(report.rooms_processed(), 1);
+        assert_eq!(report.rooms_skipped(), 1);
+        assert_eq!(report.skipped_rooms, vec![RoomName::new("W2N1").unwrap()]);
+        assert_eq!(report.rooms[0].room, RoomName::new("W1N1").unwrap());
+    }
+
+    #[test]
+    pub fn room_encoding_sizes_break_ties_toward_rle() {
+        let sizes = |compressed_size, packed_rle_size, wildcard_rle_size| RoomEncodingSizes {
+            room: RoomName::new("W1N1").unwrap(),
+            compressed_size,
+            rle_size: 1000,
+            packed_rle_size,
+            wildcard_rle_size,
+            rle_runs: 0,
+            packed_rle_runs: 0,
+            wildcard_rle_runs: 0,
+        };
+
+        assert_eq!(sizes(100, 200, 200).optimal_encoding(), TerrainEncoding::Compressed);
+        assert_eq!(sizes(100, 100, 200).optimal_encoding(), TerrainEncoding::PackedRLE);
+        assert_eq!(sizes(100, 200, 100).optimal_encoding(), TerrainEncoding::WildcardRLE);
+        assert_eq!(sizes(200, 100, 100).optimal_encoding(), TerrainEncoding::WildcardRLE);
+    }
+
+    #[test]
+    pub fn storage_plan_upgrades_cheapest_rooms_first() {
+        let sizes = |name: &str, optimal: usize| RoomEncodingSizes {
+            room: RoomName::new(name).unwrap(),
+            compressed_size: 633,
+            rle_size: 2 * optimal,
+            packed_rle_size: optimal,
+            wildcard_rle_size: optimal + 10,
+            rle_runs: 0,
+            packed_rle_runs: 0,
+            wildcard_rle_runs: 0,
+        };
+        let rooms = [sizes("W1N1", 500), sizes("W2N1", 100), sizes("W3N1", 200)];
+
+        // Room for every room edge-only, plus the two cheapest upgrades
+        let plan = StoragePlan::new_from_sizes(&rooms, 3 * 24 + 76 + 176);
+        assert_eq!(plan.storage_for(RoomName::new("W1N1").unwrap()), Some(RoomStorage::EdgeOnly));
+        assert_eq!(plan.storage_for(RoomName::new("W2N1").unwrap()), Some(RoomStorage::Full(TerrainEncoding::PackedRLE)));
+        assert_eq!(plan.storage_for(RoomName::new("W3N1").unwrap()), Some(RoomStorage::Full(TerrainEncoding::PackedRLE)));
+        assert_eq!(plan.total_size, plan.budget_bytes);
+
+        // Not even room for every room edge-only
+        let plan = StoragePlan::new_from_sizes(&rooms, 50);
+        assert_eq!((plan.num_full_rooms(), plan.num_edge_only_rooms(), plan.num_omitted_rooms()), (0, 2, 1));
+        assert_eq!(plan.storage_for(RoomName::new("W3N1").unwrap()), Some(RoomStorage::Omitted));
+    }
+}
@@ -0,0 +1,38 @@
+//! Provides the crate-wide error type.
+
+use thiserror::Error;
+
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrainParseError;
+use crate::map_import::MapImportError;
+use crate::room_connectivity::exit::RoomExitValidationError;
+use crate::terrain_patch::TerrainPatchError;
+
+/// The errors that can be returned by fallible operations across the crate.
+///
+/// Module-specific error types are wrapped rather than replaced, so callers that care about the
+/// details can still match on them.
+#[derive(Debug, Error)]
+pub enum MapProcessingError {
+    /// A database operation failed.
+    #[error("database error: {0}")]
+    #[cfg(feature = "db")]
+    Database(#[from] rusqlite::Error),
+    /// Edge terrain slices couldn't be parsed.
+    #[error("invalid edge terrain: {0}")]
+    EdgeTerrain(#[from] RoomEdgeTerrainParseError),
+    /// Exit parameters failed validation.
+    #[error("invalid room exit: {0}")]
+    RoomExit(#[from] RoomExitValidationError),
+    /// A map dump couldn't be loaded.
+    #[error(transparent)]
+    MapImport(#[from] MapImportError),
+    /// A terrain patch couldn't be loaded.
+    #[error(transparent)]
+    TerrainPatch(#[from] TerrainPatchError),
+    /// Serialized data is the wrong size.
+    #[error("invalid {kind} data: expected {expected} bytes, found {found}")]
+    InvalidLength { kind: &'static str, expected: usize, found: usize },
+    /// Serialized data holds a value that can't be decoded.
+    #[error("invalid {kind} data: {reason}")]
+    InvalidData { kind: &'static str, reason: String },
+}
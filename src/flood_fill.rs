@@ -0,0 +1,106 @@
+//! Provides breadth-first flood fills over a room, with configurable obstacles.
+//!
+//! Fills spread in all 8 directions from one or more seed tiles. Seeds are always part of the
+//! fill, even if they're obstacles themselves, so a fill can start from a structure like a spawn.
+
+use std::collections::VecDeque;
+use screeps::RoomXY;
+
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+use crate::pathfinding::UNREACHABLE;
+use crate::room_matrix::RoomMatrix;
+
+/// Finds every tile reachable from the seeds without crossing a tile for which `is_obstacle`
+/// returns true.
+pub fn flood_fill(seeds: &[RoomXY], is_obstacle: impl Fn(RoomXY) -> bool) -> RoomBitGrid {
+    let distances = flood_fill_distances(seeds, is_obstacle);
+    RoomBitGrid::from_fn(|xy| distances.get(xy) != UNREACHABLE)
+}
+
+/// Finds every tile reachable from the seeds without crossing a tile set in `obstacles`.
+///
+/// This works a whole row at a time by repeatedly dilating the filled area, so it's much faster
+/// than [flood_fill] when the obstacles are already available as a mask.
+pub fn flood_fill_mask(seeds: &[RoomXY], obstacles: &RoomBitGrid) -> RoomBitGrid {
+    let mut filled = RoomBitGrid::new();
+    for seed in seeds {
+        filled.set_xy(*seed, true);
+    }
+
+    let passable = obstacles.complement();
+    loop {
+        let grown = filled.union(&filled.dilate().intersection(&passable));
+        if grown == filled {
+            return filled;
+        }
+        filled = grown;
+    }
+}
+
+/// Calculates the number of steps from the nearest seed to every tile reachable without crossing
+/// a tile for which `is_obstacle` returns true.
+///
+/// Seeds have a distance of 0, and tiles that can't be reached have a distance of
+/// [UNREACHABLE].
+pub fn flood_fill_distances(seeds: &[RoomXY], is_obstacle: impl Fn(RoomXY) -> bool) -> RoomMatrix<u16> {
+    let mut output = RoomMatrix::new(UNREACHABLE);
+    let mut queue = VecDeque::new();
+
+    for seed in seeds {
+        if output.get(*seed) != 0 {
+            output.set(*seed, 0);
+            queue.push_back(*seed);
+        }
+    }
+
+    while let Some(xy) = queue.pop_front() {
+        let distance = output.get(xy) + 1;
+        for neighbor in xy.neighbors() {
+            if output.get(neighbor) == UNREACHABLE && !is_obstacle(neighbor) {
+                output.set(neighbor, distance);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    output
+}
+
+/// Calculates the number of steps from the nearest seed to every tile reachable without crossing
+/// a tile set in `obstacles`.
+///
+/// See [flood_fill_distances].
+pub fn flood_fill_distances_mask(seeds: &[RoomXY], obstacles: &RoomBitGrid) -> RoomMatrix<u16> {
+    flood_fill_distances(seeds, |xy| obstacles.get_xy(xy))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn flood_fill_stops_at_obstacles() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        // A wall down column 20, with a single gap at (20, 30)
+        let walls = RoomBitGrid::from_fn(|tile| tile.x.u8() == 20 && tile.y.u8() != 30);
+
+        let filled = flood_fill_mask(&[xy(5, 5)], &walls);
+        assert_eq!(filled, flood_fill(&[xy(5, 5)], |tile| walls.get_xy(tile)));
+        assert_eq!(filled.popcount(), 2500 - 49);
+
+        let closed = walls.union(&RoomBitGrid::from_fn(|tile| tile == xy(20, 30)));
+        let filled = flood_fill_mask(&[xy(5, 5)], &closed);
+        assert_eq!(filled.popcount(), 20 * 50);
+        assert!(!filled.get_xy(xy(21, 5)));
+
+        let distances = flood_fill_distances_mask(&[xy(19, 30)], &walls);
+        assert_eq!(distances.get(xy(19, 30)), 0);
+        assert_eq!(distances.get(xy(21, 30)), 2);
+        assert_eq!(distances.get(xy(21, 10)), 21);
+        assert_eq!(distances.get(xy(20, 10)), UNREACHABLE);
+
+        // Seeds are filled even when they're obstacles
+        assert!(flood_fill_mask(&[xy(20, 0)], &closed).get_xy(xy(19, 0)));
+    }
+}
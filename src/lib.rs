@@ -1,4 +1,34 @@
+pub mod adaptive_terrain;
+pub mod analysis;
+#[cfg(feature = "bot-api")]
+pub mod bot_api;
+pub mod byte_layout;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod compressed_terrain;
+#[cfg(feature = "db")]
 pub mod compressed_terrain_db;
+pub mod encoding_comparison;
+pub mod error;
+pub mod flood_fill;
+pub mod map_data_hub;
+pub mod map_import;
+pub mod normalize;
+pub mod overlaid_terrain;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod pathfinding;
 pub mod room_connectivity;
+pub mod room_matrix;
+pub mod room_record;
 pub mod run_length_encoding;
+pub mod sampling;
+pub mod segment_codec;
+#[cfg(feature = "serde")]
+mod serde_support;
+pub mod terrain_patch;
+pub mod terrain_pyramid;
+pub mod terrain_repository;
+pub mod terrain_source;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
@@ -0,0 +1,161 @@
+//! Keeps room terrain and everything derived from it consistent as terrain changes.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use screeps::RoomName;
+
+use crate::analysis::exit_reachability::ExitReachability;
+use crate::analysis::maze_complexity::maze_complexity;
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::room_connectivity::graph::ShardConnectivityGraph;
+
+/// What was invalidated by a call to [update_room_terrain](MapDataHub::update_room_terrain).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoomUpdate {
+    /// The room's terrain changed, so its cached analyses were dropped.
+    pub terrain_changed: bool,
+    /// The room's edge terrain changed, so its exits and graph node were rebuilt.
+    pub exits_changed: bool,
+}
+
+/// The derived data kept for a single room.
+struct RoomEntry {
+    terrain: CompressedRoomTerrain,
+    edges: RoomEdgeTerrain,
+    exit_reachability: OnceCell<ExitReachability>,
+    maze_complexity: OnceCell<f32>,
+}
+
+impl RoomEntry {
+    fn new(terrain: CompressedRoomTerrain) -> Self {
+        let edges = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        Self { terrain, edges, exit_reachability: OnceCell::new(), maze_complexity: OnceCell::new() }
+    }
+}
+
+/// Owns the terrain of a set of rooms along with the data derived from it: edge terrain, exits,
+/// the connectivity graph, and per-room analyses.
+///
+/// Updating a room's terrain through the hub invalidates exactly the data that depends on it.
+/// Analyses are computed lazily the first time they're requested, and kept until the room's
+/// terrain changes. Exits and the graph node are only rebuilt if the room's edges changed, since
+/// interior edits can't affect them.
+#[derive(Default)]
+pub struct MapDataHub {
+    rooms: HashMap<RoomName, RoomEntry>,
+    graph: ShardConnectivityGraph,
+}
+
+impl MapDataHub {
+    /// Creates an empty hub.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the terrain of a room, adding it if it isn't in the hub yet, and invalidates the data
+    /// derived from the old terrain.
+    pub fn update_room_terrain(&mut self, room: RoomName, new_terrain: CompressedRoomTerrain) -> RoomUpdate {
+        let new_entry = RoomEntry::new(new_terrain);
+
+        let update = match self.rooms.get(&room) {
+            Some(entry) if entry.terrain.get_compressed_bytes() == new_entry.terrain.get_compressed_bytes() => {
+                return RoomUpdate { terrain_changed: false, exits_changed: false };
+            }
+            Some(entry) => RoomUpdate {
+                terrain_changed: true,
+                exits_changed: entry.edges.get_raw_bytes() != new_entry.edges.get_raw_bytes(),
+            },
+            None => RoomUpdate { terrain_changed: true, exits_changed: true },
+        };
+
+        if update.exits_changed {
+            self.graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(new_entry.edges, room));
+        }
+        self.rooms.insert(room, new_entry);
+        update
+    }
+
+    /// Removes a room and everything derived from it, returning its terrain if it was present.
+    pub fn remove_room(&mut self, room: RoomName) -> Option<CompressedRoomTerrain> {
+        self.graph.remove_room(room);
+        self.rooms.remove(&room).map(|entry| entry.terrain)
+    }
+
+    /// Returns true if the room is in the hub.
+    pub fn contains_room(&self, room: RoomName) -> bool {
+        self.rooms.contains_key(&room)
+    }
+
+    /// The terrain of a room, if it's in the hub.
+    pub fn terrain(&self, room: RoomName) -> Option<&CompressedRoomTerrain> {
+        self.rooms.get(&room).map(|entry| &entry.terrain)
+    }
+
+    /// The edge terrain of a room, if it's in the hub.
+    pub fn edge_terrain(&self, room: RoomName) -> Option<&RoomEdgeTerrain> {
+        self.rooms.get(&room).map(|entry| &entry.edges)
+    }
+
+    /// The exits of a room, if it's in the hub.
+    pub fn exits(&self, room: RoomName) -> Option<&RoomExitsData> {
+        self.graph.get_room_exits(room)
+    }
+
+    /// The connectivity graph of every room in the hub.
+    pub fn graph(&self) -> &ShardConnectivityGraph {
+        &self.graph
+    }
+
+    /// The exit reachability of a room, computed if it isn't cached already.
+    pub fn exit_reachability(&self, room: RoomName) -> Option<&ExitReachability> {
+        let entry = self.rooms.get(&room)?;
+        Some(entry.exit_reachability.get_or_init(|| ExitReachability::new_from_compressed_terrain(&entry.terrain)))
+    }
+
+    /// The maze complexity score of a room, computed if it isn't cached already.
+    pub fn maze_complexity(&self, room: RoomName) -> Option<f32> {
+        let entry = self.rooms.get(&room)?;
+        Some(*entry.maze_complexity.get_or_init(|| maze_complexity(&entry.terrain)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{RoomXY, Terrain, ROOM_AREA};
+
+    #[test]
+    pub fn map_data_hub_invalidates_only_dependent_data() {
+        let room = RoomName::new("W1N1").unwrap();
+        let mut hub = MapDataHub::new();
+
+        let open = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        assert_eq!(hub.update_room_terrain(room, open), RoomUpdate { terrain_changed: true, exits_changed: true });
+        assert_eq!(hub.exits(room).unwrap().num_exits(), 4);
+        let open_complexity = hub.maze_complexity(room).unwrap();
+
+        // Unchanged terrain keeps everything
+        let open = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        assert_eq!(hub.update_room_terrain(room, open), RoomUpdate { terrain_changed: false, exits_changed: false });
+
+        // An interior wall changes the analyses but not the exits
+        let mut walled = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        walled.set_many((1..49).map(|y| (RoomXY::checked_new(25, y).unwrap(), Terrain::Wall)));
+        assert_eq!(hub.update_room_terrain(room, walled), RoomUpdate { terrain_changed: true, exits_changed: false });
+        assert_ne!(hub.maze_complexity(room).unwrap(), open_complexity);
+        assert_eq!(hub.exits(room).unwrap().num_exits(), 4);
+
+        // Walling off an edge changes the exits too
+        let mut closed = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        closed.set_many((0..50).map(|x| (RoomXY::checked_new(x, 0).unwrap(), Terrain::Wall)));
+        assert_eq!(hub.update_room_terrain(room, closed), RoomUpdate { terrain_changed: true, exits_changed: true });
+        assert_eq!(hub.exits(room).unwrap().num_exits(), 3);
+
+        assert!(hub.remove_room(room).is_some());
+        assert!(hub.exits(room).is_none());
+        assert!(hub.graph().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! Provides a tolerant loader for shard map JSON dumps.
+//!
+//! Unlike `screeps_utils::offline_map::load_shard_map_json`, this doesn't abort on the first
+//! malformed room. Rooms that can't be parsed are reported individually, rooms without terrain
+//! are listed separately, and unknown fields are ignored.
+//!
+//! Two layouts are accepted:
+//! - The standard shard dump: an object with a `rooms` array
+//! - A bare array of rooms, as produced by some private server tooling
+//!
+//! Terrain can optionally be normalized with a [NormalizationRules] set as it's imported.
+
+use std::fmt;
+use screeps::{RoomName, ROOM_AREA};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::normalize::NormalizationRules;
+
+/// The errors that prevent a map dump from being loaded at all.
+#[derive(Debug, Error)]
+pub enum MapImportError {
+    /// The file couldn't be read.
+    #[error("unable to read map file: {0}")]
+    Io(#[source] std::io::Error),
+    /// The file isn't valid JSON.
+    #[error("map file is not valid JSON: {0}")]
+    Json(#[source] serde_json::Error),
+    /// The JSON is valid, but isn't a list of rooms or an object containing a `rooms` list.
+    #[error("map file is not a recognized map dump layout")]
+    UnrecognizedLayout,
+}
+
+/// The reasons an individual room entry can fail to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoomImportFailureReason {
+    /// The entry isn't a JSON object.
+    NotAnObject,
+    /// The entry has no `room` (or `name`) field.
+    MissingRoomName,
+    /// The room name couldn't be parsed.
+    InvalidRoomName(String),
+    /// The terrain field isn't a string.
+    TerrainNotAString,
+    /// The terrain string isn't 2500 characters long.
+    InvalidTerrainLength(usize),
+    /// The terrain string contains a character other than `0`-`3`.
+    InvalidTerrainCharacter(char),
+}
+
+/// A room entry that couldn't be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomImportFailure {
+    /// The position of the entry in the rooms list.
+    pub index: usize,
+    /// The room name, if one could be read from the entry.
+    pub room_name: Option<String>,
+    pub reason: RoomImportFailureReason,
+}
+
+impl fmt::Display for RoomImportFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.room_name.as_deref().unwrap_or("<unknown>");
+        write!(f, "room entry {} ({name}): {:?}", self.index, self.reason)
+    }
+}
+
+/// The result of importing a map dump.
+#[derive(Default)]
+pub struct MapImport {
+    /// Rooms that were successfully parsed, along with their terrain.
+    pub rooms: Vec<(RoomName, CompressedRoomTerrain)>,
+    /// Rooms that were present in the dump, but had no terrain.
+    pub rooms_without_terrain: Vec<RoomName>,
+    /// Room entries that couldn't be parsed.
+    pub failures: Vec<RoomImportFailure>,
+}
+
+/// Loads a map dump from the specified file, without normalizing terrain.
+pub fn load_map_json<P: AsRef<std::path::Path>>(path: P) -> Result<MapImport, MapProcessingError> {
+    load_map_json_with_rules(path, &NormalizationRules::none())
+}
+
+/// Loads a map dump from the specified file, applying the normalization rules to each room's
+/// terrain.
+pub fn load_map_json_with_rules<P: AsRef<std::path::Path>>(path: P, rules: &NormalizationRules) -> Result<MapImport, MapProcessingError> {
+    let contents = std::fs::read_to_string(path).map_err(MapImportError::Io)?;
+    parse_map_json_with_rules(&contents, rules)
+}
+
+/// Parses a map dump from a JSON string, without normalizing terrain.
+pub fn parse_map_json(json: &str) -> Result<MapImport, MapProcessingError> {
+    parse_map_json_with_rules(json, &NormalizationRules::none())
+}
+
+/// Parses a map dump from a JSON string, applying the normalization rules to each room's terrain.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = json.len())))]
+pub fn parse_map_json_with_rules(json: &str, rules: &NormalizationRules) -> Result<MapImport, MapProcessingError> {
+    let value: Value = serde_json::from_str(json).map_err(MapImportError::Json)?;
+
+    let entries = match &value {
+        Value::Array(entries) => entries,
+        Value::Object(map) => match map.get("rooms") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err(MapImportError::UnrecognizedLayout.into()),
+        },
+        _ => return Err(MapImportError::UnrecognizedLayout.into()),
+    };
+
+    let mut import = MapImport::default();
+
+    for (index, entry) in entries.iter().enumerate() {
+        match parse_room_entry(entry, rules) {
+            Ok((room_name, Some(terrain))) => import.rooms.push((room_name, terrain)),
+            Ok((room_name, None)) => import.rooms_without_terrain.push(room_name),
+            Err((room_name, reason)) => import.failures.push(RoomImportFailure { index, room_name, reason }),
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(rooms = import.rooms.len(), rooms_without_terrain = import.rooms_without_terrain.len(), failures = import.failures.len(), "parsed map dump");
+
+    Ok(import)
+}
+
+/// Internal helper function that parses a single room entry.
+///
+/// On failure, returns the room name (if it could be read) along with the failure reason.
+fn parse_room_entry(entry: &Value, rules: &NormalizationRules) -> Result<(RoomName, Option<CompressedRoomTerrain>), (Option<String>, RoomImportFailureReason)> {
+    let object = entry.as_object().ok_or((None, RoomImportFailureReason::NotAnObject))?;
+
+    let raw_name = object.get("room").or_else(|| object.get("name"))
+        .and_then(|v| v.as_str())
+        .ok_or((None, RoomImportFailureReason::MissingRoomName))?;
+
+    let room_name = RoomName::new(raw_name)
+        .map_err(|_| (Some(raw_name.to_string()), RoomImportFailureReason::InvalidRoomName(raw_name.to_string())))?;
+
+    let terrain = match object.get("terrain") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(parse_terrain_string(s, rules).map_err(|reason| (Some(raw_name.to_string()), reason))?),
+        Some(_) => return Err((Some(raw_name.to_string()), RoomImportFailureReason::TerrainNotAString)),
+    };
+
+    Ok((room_name, terrain))
+}
+
+/// Internal helper function that parses and normalizes a 2500-character terrain string.
+fn parse_terrain_string(s: &str, rules: &NormalizationRules) -> Result<CompressedRoomTerrain, RoomImportFailureReason> {
+    let length = s.chars().count();
+    if length != ROOM_AREA {
+        return Err(RoomImportFailureReason::InvalidTerrainLength(length));
+    }
+
+    let mut bits = [0u8; ROOM_AREA];
+    for (bit, c) in bits.iter_mut().zip(s.chars()) {
+        *bit = match c {
+            '0' => 0,
+            '1' => 1,
+            '2' => 2,
+            '3' => 3,
+            _ => return Err(RoomImportFailureReason::InvalidTerrainCharacter(c)),
+        };
+    }
+
+    rules.apply(&mut bits);
+
+    Ok(CompressedRoomTerrain::new_from_uncompressed_bits(&bits))
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{RoomXY, Terrain};
+
+    fn terrain_string(c: char) -> String {
+        std::iter::repeat_n(c, ROOM_AREA).collect()
+    }
+
+    #[test]
+    pub fn parse_map_json_accepts_shard_dump_layout() {
+        let json = format!(r#"{{"description": "test", "extra": 1, "rooms": [{{"room": "W1N1", "status": "normal", "terrain": "{}", "objects": []}}]}}"#, terrain_string('1'));
+        let import = parse_map_json(&json).unwrap();
+
+        assert_eq!(import.rooms.len(), 1);
+        assert!(import.failures.is_empty());
+
+        let (name, terrain) = &import.rooms[0];
+        assert_eq!(*name, RoomName::new("W1N1").unwrap());
+        assert_eq!(terrain.get_xy(unsafe { RoomXY::unchecked_new(10, 10) }), Terrain::Wall);
+    }
+
+    #[test]
+    pub fn parse_map_json_accepts_room_list_layout() {
+        let json = format!(r#"[{{"name": "E1S1", "terrain": "{}"}}, {{"room": "E2S1"}}]"#, terrain_string('2'));
+        let import = parse_map_json(&json).unwrap();
+
+        assert_eq!(import.rooms.len(), 1);
+        assert_eq!(import.rooms_without_terrain, vec![RoomName::new("E2S1").unwrap()]);
+        assert!(import.failures.is_empty());
+    }
+
+    #[test]
+    pub fn parse_map_json_reports_per_room_failures_without_aborting() {
+        let json = format!(
+            r#"[{{"room": "W1N1", "terrain": "012"}}, {{"room": "bogus", "terrain": "{0}"}}, 5, {{"terrain": "{0}"}}, {{"room": "W2N1", "terrain": "{0}"}}]"#,
+            terrain_string('0'),
+        );
+        let import = parse_map_json(&json).unwrap();
+
+        assert_eq!(import.rooms.len(), 1);
+        let reasons: Vec<_> = import.failures.iter().map(|f| (f.index, f.reason.clone())).collect();
+        assert_eq!(reasons, vec![
+            (0, RoomImportFailureReason::InvalidTerrainLength(3)),
+            (1, RoomImportFailureReason::InvalidRoomName("bogus".to_string())),
+            (2, RoomImportFailureReason::NotAnObject),
+            (3, RoomImportFailureReason::MissingRoomName),
+        ]);
+    }
+
+    #[test]
+    pub fn parse_map_json_with_rules_normalizes_terrain() {
+        let json = format!(r#"[{{"room": "W1N1", "terrain": "{}"}}]"#, terrain_string('2'));
+        let import = parse_map_json_with_rules(&json, &NormalizationRules::standard()).unwrap();
+
+        let (_, terrain) = &import.rooms[0];
+        assert_eq!(terrain.get_xy(unsafe { RoomXY::unchecked_new(0, 0) }), Terrain::Wall);
+        assert_eq!(terrain.get_xy(unsafe { RoomXY::unchecked_new(10, 0) }), Terrain::Plain);
+        assert_eq!(terrain.get_xy(unsafe { RoomXY::unchecked_new(10, 10) }), Terrain::Swamp);
+    }
+
+    #[test]
+    pub fn parse_map_json_rejects_unknown_layouts() {
+        assert!(matches!(parse_map_json(r#"{"foo": []}"#), Err(MapProcessingError::MapImport(MapImportError::UnrecognizedLayout))));
+        assert!(matches!(parse_map_json("not json"), Err(MapProcessingError::MapImport(MapImportError::Json(_)))));
+    }
+}
@@ -0,0 +1,243 @@
+//! Provides composable normalization rules for raw room terrain data.
+//!
+//! Raw terrain from map dumps and private servers doesn't always match what the game engine
+//! actually does with it: swamp+wall tiles behave as walls, edge swamps behave as plains, and
+//! corners are always walls. These rules can be applied to raw terrain bits before compression,
+//! so that the stored terrain reflects the effective terrain.
+//!
+//! A rule set can be converted to and from a compact bitmask or a comma-separated list of rule
+//! names, so that the rules applied to an archive can be recorded alongside it.
+
+use std::fmt;
+use screeps::ROOM_AREA;
+
+use crate::error::MapProcessingError;
+
+const PLAIN_BITS: u8 = 0b00;
+const WALL_BITS: u8 = 0b01;
+const SWAMP_BITS: u8 = 0b10;
+const SWAMP_WALL_BITS: u8 = 0b11;
+
+/// An individual normalization rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationRule {
+    /// Converts swamp+wall (`0b11`) tiles into walls.
+    CollapseSwampWalls,
+    /// Converts swamps on room edges into plains.
+    EdgeSwampsToPlains,
+    /// Forces the four room corners to be walls.
+    CornersToWalls,
+    /// Converts exits that are a single tile wide into walls.
+    CloseSingleTileExits,
+}
+
+impl NormalizationRule {
+    /// All rules, in the order they're applied.
+    pub const ALL: [NormalizationRule; 4] = [
+        NormalizationRule::CollapseSwampWalls,
+        NormalizationRule::EdgeSwampsToPlains,
+        NormalizationRule::CornersToWalls,
+        NormalizationRule::CloseSingleTileExits,
+    ];
+
+    /// The bit used to represent this rule in a [NormalizationRules] bitmask.
+    pub fn bit(&self) -> u8 {
+        match self {
+            NormalizationRule::CollapseSwampWalls => 0b0001,
+            NormalizationRule::EdgeSwampsToPlains => 0b0010,
+            NormalizationRule::CornersToWalls => 0b0100,
+            NormalizationRule::CloseSingleTileExits => 0b1000,
+        }
+    }
+
+    /// The stable name of this rule, used when recording rule sets as text.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NormalizationRule::CollapseSwampWalls => "collapse-swamp-walls",
+            NormalizationRule::EdgeSwampsToPlains => "edge-swamps-to-plains",
+            NormalizationRule::CornersToWalls => "corners-to-walls",
+            NormalizationRule::CloseSingleTileExits => "close-single-tile-exits",
+        }
+    }
+
+    /// Looks up a rule by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+
+    /// Applies this rule to raw, row-major terrain bits.
+    pub fn apply(&self, bits: &mut [u8; ROOM_AREA]) {
+        match self {
+            NormalizationRule::CollapseSwampWalls => {
+                for bit in bits.iter_mut() {
+                    if *bit & SWAMP_WALL_BITS == SWAMP_WALL_BITS {
+                        *bit = WALL_BITS;
+                    }
+                }
+            },
+            NormalizationRule::EdgeSwampsToPlains => {
+                for idx in edge_indices() {
+                    if bits[idx] & SWAMP_WALL_BITS == SWAMP_BITS {
+                        bits[idx] = PLAIN_BITS;
+                    }
+                }
+            },
+            NormalizationRule::CornersToWalls => {
+                for idx in [0, 49, 49 * 50, ROOM_AREA - 1] {
+                    bits[idx] = WALL_BITS;
+                }
+            },
+            NormalizationRule::CloseSingleTileExits => {
+                for edge in edges() {
+                    for offset in 1..=48 {
+                        let is_open = |o: usize| bits[edge[o]] & WALL_BITS == 0;
+                        if is_open(offset) && !is_open(offset - 1) && !is_open(offset + 1) {
+                            bits[edge[offset]] = WALL_BITS;
+                        }
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// A set of normalization rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NormalizationRules {
+    bits: u8,
+}
+
+impl NormalizationRules {
+    /// A rule set that doesn't change anything.
+    pub fn none() -> Self {
+        Self { bits: 0 }
+    }
+
+    /// The rules that make stored terrain match how the game engine treats it, without changing
+    /// room connectivity: collapsing swamp+walls, converting edge swamps to plains, and forcing
+    /// corners to be walls.
+    pub fn standard() -> Self {
+        Self::none()
+            .with(NormalizationRule::CollapseSwampWalls)
+            .with(NormalizationRule::EdgeSwampsToPlains)
+            .with(NormalizationRule::CornersToWalls)
+    }
+
+    /// Creates a rule set from its bitmask representation, ignoring any unknown bits.
+    pub fn from_bits(bits: u8) -> Self {
+        let known = NormalizationRule::ALL.iter().fold(0, |acc, rule| acc | rule.bit());
+        Self { bits: bits & known }
+    }
+
+    /// Parses a rule set from a comma-separated list of rule names.
+    ///
+    /// Unknown rule names are reported as [MapProcessingError::InvalidData].
+    pub fn from_names(names: &str) -> Result<Self, MapProcessingError> {
+        let mut rules = Self::none();
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let rule = NormalizationRule::from_name(name)
+                .ok_or_else(|| MapProcessingError::InvalidData { kind: "normalization rules", reason: format!("unknown normalization rule: {name}") })?;
+            rules = rules.with(rule);
+        }
+        Ok(rules)
+    }
+
+    /// The bitmask representation of this rule set.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Returns a copy of this rule set with the specified rule added.
+    pub fn with(self, rule: NormalizationRule) -> Self {
+        Self { bits: self.bits | rule.bit() }
+    }
+
+    /// Returns true if this rule set contains the specified rule.
+    pub fn contains(&self, rule: NormalizationRule) -> bool {
+        self.bits & rule.bit() != 0
+    }
+
+    /// Returns an iterator over the rules in this set, in the order they're applied.
+    pub fn iter(&self) -> impl Iterator<Item = NormalizationRule> + '_ {
+        NormalizationRule::ALL.into_iter().filter(|rule| self.contains(*rule))
+    }
+
+    /// Applies every rule in this set to raw, row-major terrain bits.
+    pub fn apply(&self, bits: &mut [u8; ROOM_AREA]) {
+        for rule in self.iter() {
+            rule.apply(bits);
+        }
+    }
+}
+
+impl fmt::Display for NormalizationRules {
+    /// Formats the rule set as a comma-separated list of rule names.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.iter().map(|rule| rule.name()).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// Internal helper function returning the terrain indices of each edge, in LTR/top-to-bottom
+/// order.
+fn edges() -> [[usize; 50]; 4] {
+    [
+        std::array::from_fn(|x| x),             // Top
+        std::array::from_fn(|y| y * 50 + 49),   // Right
+        std::array::from_fn(|x| 49 * 50 + x),   // Bottom
+        std::array::from_fn(|y| y * 50),        // Left
+    ]
+}
+
+/// Internal helper function returning the terrain indices of every edge tile.
+fn edge_indices() -> impl Iterator<Item = usize> {
+    edges().into_iter().flatten()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn normalization_rules_round_trip_through_names_and_bits() {
+        let rules = NormalizationRules::standard();
+        assert_eq!(rules.to_string(), "collapse-swamp-walls,edge-swamps-to-plains,corners-to-walls");
+        assert_eq!(NormalizationRules::from_names(&rules.to_string()).unwrap(), rules);
+        assert_eq!(NormalizationRules::from_bits(rules.bits()), rules);
+        assert_eq!(NormalizationRules::from_names("bogus").unwrap_err().to_string(), "invalid normalization rules data: unknown normalization rule: bogus");
+        assert_eq!(NormalizationRules::from_names("").unwrap(), NormalizationRules::none());
+    }
+
+    #[test]
+    pub fn standard_rules_normalize_raw_terrain() {
+        // All swamp+walls
+        let mut bits = [SWAMP_WALL_BITS; ROOM_AREA];
+        bits[25] = SWAMP_BITS; // Top edge swamp
+        bits[25 * 50 + 25] = SWAMP_BITS; // Interior swamp
+        bits[0] = PLAIN_BITS; // Corner
+
+        NormalizationRules::standard().apply(&mut bits);
+
+        assert_eq!(bits[0], WALL_BITS, "corner should be a wall");
+        assert_eq!(bits[25], PLAIN_BITS, "edge swamp should be a plain");
+        assert_eq!(bits[25 * 50 + 25], SWAMP_BITS, "interior swamp should be untouched");
+        assert_eq!(bits[10 * 50 + 10], WALL_BITS, "swamp+wall should be a wall");
+    }
+
+    #[test]
+    pub fn close_single_tile_exits_only_closes_single_tile_exits() {
+        let mut bits = [WALL_BITS; ROOM_AREA];
+        bits[10] = PLAIN_BITS; // Single-tile exit on the top edge
+        bits[20] = PLAIN_BITS; // Two-tile exit on the top edge
+        bits[21] = PLAIN_BITS;
+        bits[5 * 50] = SWAMP_BITS; // Single-tile exit on the left edge
+
+        NormalizationRules::none().with(NormalizationRule::CloseSingleTileExits).apply(&mut bits);
+
+        assert_eq!(bits[10], WALL_BITS);
+        assert_eq!(bits[5 * 50], WALL_BITS);
+        assert_eq!(bits[20], PLAIN_BITS);
+        assert_eq!(bits[21], PLAIN_BITS);
+    }
+}
@@ -0,0 +1,118 @@
+//! Provides a terrain view that layers known structures over the natural terrain.
+
+use std::collections::HashMap;
+use screeps::{RoomXY, Terrain};
+
+use crate::terrain_source::TerrainSource;
+
+/// A [TerrainSource] that reports override terrain for some tiles, and the wrapped terrain for
+/// every other tile.
+///
+/// This lets pathfinding and planning account for structures without modifying the stored
+/// terrain: constructed walls can be reported as walls, and roads as plains, since a road makes
+/// both swamps and walls walkable at road speed.
+#[derive(Debug, Clone)]
+pub struct OverlaidTerrain<T> {
+    base: T,
+    overrides: HashMap<RoomXY, Terrain>,
+}
+
+impl<T: TerrainSource> OverlaidTerrain<T> {
+    /// Creates an overlay with no overrides.
+    pub fn new(base: T) -> Self {
+        Self { base, overrides: HashMap::new() }
+    }
+
+    /// Reports `terrain` for the tile, replacing any existing override.
+    pub fn set_override(&mut self, xy: RoomXY, terrain: Terrain) {
+        self.overrides.insert(xy, terrain);
+    }
+
+    /// Removes the override for a tile, returning it if there was one.
+    pub fn remove_override(&mut self, xy: RoomXY) -> Option<Terrain> {
+        self.overrides.remove(&xy)
+    }
+
+    /// Marks a tile as blocked by a structure, such as a constructed wall.
+    pub fn add_wall(&mut self, xy: RoomXY) {
+        self.set_override(xy, Terrain::Wall);
+    }
+
+    /// Marks a tile as having a road.
+    pub fn add_road(&mut self, xy: RoomXY) {
+        self.set_override(xy, Terrain::Plain);
+    }
+
+    /// The override for a tile, if it has one.
+    pub fn get_override(&self, xy: RoomXY) -> Option<Terrain> {
+        self.overrides.get(&xy).copied()
+    }
+
+    /// Every overridden tile, along with its override terrain, in no particular order.
+    pub fn overrides(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        self.overrides.iter().map(|(xy, terrain)| (*xy, *terrain))
+    }
+
+    /// Removes every override.
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+    }
+
+    /// The wrapped terrain.
+    pub fn base(&self) -> &T {
+        &self.base
+    }
+
+    /// Unwraps the overlay, discarding the overrides.
+    pub fn into_base(self) -> T {
+        self.base
+    }
+}
+
+impl<T: TerrainSource> TerrainSource for OverlaidTerrain<T> {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        self.get_override(xy).unwrap_or_else(|| self.base.get_xy(xy))
+    }
+
+    fn memory_size(&self) -> usize {
+        self.base.memory_size() + self.overrides.capacity() * size_of::<(RoomXY, Terrain)>() + size_of::<HashMap<RoomXY, Terrain>>()
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        self.base.iter_tiles().map(|(xy, tile)| (xy, self.get_override(xy).unwrap_or(tile)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+    use crate::pathfinding::{path_costs_from, TerrainCosts, UNREACHABLE};
+
+    #[test]
+    pub fn overlaid_terrain_changes_path_costs_without_changing_base() {
+        // A wall across the room at y = 10, with a swamp gap at x = 5
+        let mut bits = [0u8; ROOM_AREA];
+        bits[10 * 50..11 * 50].fill(1);
+        bits[10 * 50 + 5] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let start = RoomXY::checked_new(5, 9).unwrap();
+        let goal = RoomXY::checked_new(5, 11).unwrap();
+        let gap = RoomXY::checked_new(5, 10).unwrap();
+
+        let mut overlay = OverlaidTerrain::new(&terrain);
+        assert_eq!(path_costs_from(&overlay, start, &TerrainCosts::default()).get(goal), 6);
+
+        overlay.add_road(gap);
+        assert_eq!(path_costs_from(&overlay, start, &TerrainCosts::default()).get(goal), 2);
+
+        overlay.add_wall(gap);
+        assert_eq!(path_costs_from(&overlay, start, &TerrainCosts::default()).get(goal), UNREACHABLE);
+        assert_eq!(overlay.base().get_xy(gap), Terrain::Swamp);
+
+        overlay.remove_override(gap);
+        assert_eq!(overlay.get_xy(gap), Terrain::Swamp);
+    }
+}
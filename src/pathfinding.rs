@@ -0,0 +1,464 @@
+//! Provides terrain-aware pathing primitives for working within a single room.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use screeps::{RoomXY, Terrain};
+use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
+
+use crate::analysis::maze_complexity::maze_complexity;
+use crate::analysis::quad::{quad_passable_mask, quad_tiles};
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::room_connectivity::exit::{RoomExit, edge_offset_to_xy};
+use crate::room_matrix::RoomMatrix;
+use crate::terrain_source::TerrainSource;
+
+/// The path cost reported for tiles that can't be reached.
+pub const UNREACHABLE: u16 = u16::MAX;
+
+/// The cost of moving onto each kind of walkable terrain. Walls are always impassable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainCosts {
+    pub plain_cost: u16,
+    pub swamp_cost: u16,
+}
+
+impl TerrainCosts {
+    /// The cost of moving onto the specified terrain, or None if it's impassable.
+    pub fn cost(&self, terrain: Terrain) -> Option<u16> {
+        match terrain {
+            Terrain::Plain => Some(self.plain_cost),
+            Terrain::Swamp => Some(self.swamp_cost),
+            Terrain::Wall => None,
+        }
+    }
+
+    /// The number of ticks it takes a creep to move onto each kind of terrain, without roads.
+    ///
+    /// `fatigue_parts` is the number of body parts that generate fatigue, which is every part
+    /// except MOVE and empty CARRY parts. Returns None if the creep has no MOVE parts, since it
+    /// can't move at all.
+    pub fn for_body(move_parts: u32, fatigue_parts: u32) -> Option<Self> {
+        if move_parts == 0 {
+            return None;
+        }
+        // Each MOVE part removes 2 fatigue per tick, and a creep can't move while fatigued
+        let ticks = |fatigue_per_part: u32| (fatigue_parts * fatigue_per_part).div_ceil(2 * move_parts).clamp(1, u16::MAX as u32) as u16;
+        Some(Self { plain_cost: ticks(2), swamp_cost: ticks(10) })
+    }
+}
+
+impl Default for TerrainCosts {
+    /// The same defaults as the in-game PathFinder: plains cost 1, swamps cost 5.
+    fn default() -> Self {
+        Self { plain_cost: 1, swamp_cost: 5 }
+    }
+}
+
+/// Calculates the minimum path cost from `from` to every tile in the room.
+///
+/// Movement is 8-directional, and the cost of a step is the cost of the tile being moved onto.
+/// The starting tile has a cost of 0, and tiles that can't be reached have a cost of
+/// [UNREACHABLE]. Costs saturate just below [UNREACHABLE].
+pub fn path_costs_from(terrain: &impl TerrainSource, from: RoomXY, costs: &TerrainCosts) -> RoomMatrix<u16> {
+    multi_source_bfs(terrain, &[from], costs)
+}
+
+/// Calculates the minimum path cost from the nearest of `sources` to every tile in the room.
+///
+/// This is the same as [path_costs_from], but with every source tile starting at a cost of 0. It's
+/// useful for things like distance from all sources and the controller at once, or distance from
+/// every exit tile.
+pub fn multi_source_bfs(terrain: &impl TerrainSource, sources: &[RoomXY], costs: &TerrainCosts) -> RoomMatrix<u16> {
+    let mut output = RoomMatrix::new(UNREACHABLE);
+    let mut heap = BinaryHeap::new();
+
+    for source in sources {
+        output.set(*source, 0);
+        heap.push(Reverse((0u16, xy_to_terrain_index(*source))));
+    }
+
+    while let Some(Reverse((cost, idx))) = heap.pop() {
+        let xy = terrain_index_to_xy(idx);
+        if cost > output.get(xy) {
+            // We've already found a cheaper way to this tile
+            continue;
+        }
+
+        for neighbor in xy.neighbors() {
+            if let Some(step_cost) = costs.cost(terrain.get_xy(neighbor)) {
+                let new_cost = cost.saturating_add(step_cost).min(UNREACHABLE - 1);
+                if new_cost < output.get(neighbor) {
+                    output.set(neighbor, new_cost);
+                    heap.push(Reverse((new_cost, xy_to_terrain_index(neighbor))));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Recovers a cheapest path to `to` from the path costs computed by [path_costs_from] or
+/// [multi_source_bfs], using the same terrain and costs.
+///
+/// The returned path starts at a source tile and ends at `to`, including both. Returns None if
+/// `to` is unreachable, or if either terrain cost is 0, since tiles reached by zero-cost steps
+/// can't be told apart from the sources.
+pub fn trace_path(path_costs: &RoomMatrix<u16>, terrain: &impl TerrainSource, to: RoomXY, costs: &TerrainCosts) -> Option<Vec<RoomXY>> {
+    if path_costs.get(to) == UNREACHABLE || costs.plain_cost == 0 || costs.swamp_cost == 0 {
+        return None;
+    }
+
+    let mut path = vec![to];
+    let mut current = to;
+
+    while path_costs.get(current) > 0 {
+        // The cost of a step is the cost of the tile being moved onto, so the previous tile is any
+        // neighbor whose cost plus the cost of this tile is the cost of this tile
+        let current_cost = path_costs.get(current);
+        let step_cost = costs.cost(terrain.get_xy(current))?;
+        current = current.neighbors().into_iter()
+            .find(|n| {
+                let n_cost = path_costs.get(*n);
+                n_cost < current_cost && n_cost.saturating_add(step_cost).min(UNREACHABLE - 1) == current_cost
+            })?;
+        path.push(current);
+    }
+
+    path.reverse();
+    Some(path)
+}
+
+/// An edge of a [terrain_mst].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerrainMstEdge {
+    /// The index of the point this edge starts from.
+    pub from: usize,
+    /// The index of the point this edge goes to.
+    pub to: usize,
+    /// The total path cost of this edge.
+    pub cost: u16,
+    /// The tiles of the path, from `points[from]` to `points[to]`, including both.
+    pub path: Vec<RoomXY>,
+}
+
+/// Calculates a minimum spanning tree over the points, where the weight of each edge is the
+/// terrain path cost between its two points, using the default [TerrainCosts].
+///
+/// This is the skeleton of an economical road network connecting points of interest like spawns,
+/// sources, and the controller. Points that can't reach each other are left in separate trees.
+pub fn terrain_mst(terrain: &CompressedRoomTerrain, points: &[RoomXY]) -> Vec<TerrainMstEdge> {
+    let costs = TerrainCosts::default();
+    let path_costs: Vec<RoomMatrix<u16>> = points.iter().map(|p| path_costs_from(terrain, *p, &costs)).collect();
+
+    // Prim's algorithm, restarting from the next unconnected point when a tree can't grow further
+    let mut in_tree = vec![false; points.len()];
+    let mut edges = Vec::new();
+
+    for root in 0..points.len() {
+        if in_tree[root] {
+            continue;
+        }
+        in_tree[root] = true;
+
+        loop {
+            let cheapest = (0..points.len())
+                .filter(|from| in_tree[*from])
+                .flat_map(|from| (0..points.len()).filter(|to| !in_tree[*to]).map(move |to| (from, to)))
+                .map(|(from, to)| (path_costs[from].get(points[to]), from, to))
+                .filter(|(cost, _, _)| *cost != UNREACHABLE)
+                .min();
+
+            let Some((cost, from, to)) = cheapest else {
+                break;
+            };
+
+            in_tree[to] = true;
+            let path = trace_path(&path_costs[from], terrain, points[to], &costs).unwrap_or_default();
+            edges.push(TerrainMstEdge { from, to, cost, path });
+        }
+    }
+
+    edges
+}
+
+/// Calculates the minimum path cost from the nearest tile of an exit to every tile in the room.
+///
+/// This works as a flow field toward the exit: repeatedly stepping to the lowest-cost neighbor
+/// from any reachable tile leads to the exit.
+pub fn exit_flow_field(terrain: &impl TerrainSource, exit: &RoomExit, costs: &TerrainCosts) -> RoomMatrix<u16> {
+    let direction = exit.exit_direction();
+    let sources: Vec<RoomXY> = (exit.start()..=exit.end()).map(|offset| edge_offset_to_xy(direction, offset)).collect();
+    multi_source_bfs(terrain, &sources, costs)
+}
+
+/// Finds a cheapest path for a 2x2 formation, given as the positions of its top-left creep.
+///
+/// The formation moves as a unit, so each step costs the most expensive of the four tiles moved
+/// onto, and every step must keep all four tiles off of walls (see
+/// [quad_passable_mask](crate::analysis::quad::quad_passable_mask)). The returned path includes both
+/// `from` and `to`. Returns None if there's no such path.
+pub fn quad_path(terrain: &impl TerrainSource, from: RoomXY, to: RoomXY, costs: &TerrainCosts) -> Option<Vec<RoomXY>> {
+    let passable = quad_passable_mask(terrain);
+    if !passable.get_xy(from) || !passable.get_xy(to) {
+        return None;
+    }
+
+    let step_cost = |anchor: RoomXY| {
+        quad_tiles(anchor)?.iter().map(|tile| costs.cost(terrain.get_xy(*tile))).try_fold(0, |max, cost| Some(max.max(cost?)))
+    };
+    let min_step_cost = costs.plain_cost.min(costs.swamp_cost);
+    let heuristic = |xy: RoomXY| (xy.get_range_to(to) as u16).saturating_mul(min_step_cost);
+
+    let mut path_costs = RoomMatrix::new(UNREACHABLE);
+    let mut came_from: RoomMatrix<Option<RoomXY>> = RoomMatrix::new(None);
+    let mut heap = BinaryHeap::new();
+
+    path_costs.set(from, 0);
+    heap.push(Reverse((heuristic(from), 0u16, xy_to_terrain_index(from))));
+
+    while let Some(Reverse((_, cost, idx))) = heap.pop() {
+        let xy = terrain_index_to_xy(idx);
+        if xy == to {
+            let mut path = vec![to];
+            while let Some(previous) = came_from.get(*path.last()?) {
+                path.push(previous);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost > path_costs.get(xy) {
+            // We've already found a cheaper way to this tile
+            continue;
+        }
+
+        for neighbor in xy.neighbors().into_iter().filter(|n| passable.get_xy(*n)) {
+            let Some(step) = step_cost(neighbor) else {
+                continue;
+            };
+            let new_cost = cost.saturating_add(step).min(UNREACHABLE - 1);
+            if new_cost < path_costs.get(neighbor) {
+                path_costs.set(neighbor, new_cost);
+                came_from.set(neighbor, Some(xy));
+                heap.push(Reverse((new_cost.saturating_add(heuristic(neighbor)), new_cost, xy_to_terrain_index(neighbor))));
+            }
+        }
+    }
+
+    None
+}
+
+/// How much each point of [maze_complexity] widens the search beyond a straight line.
+const MAZE_DETOUR_WEIGHT: f32 = 8.0;
+
+/// How much swamps widen the search, since swamps make the straight-line heuristic underestimate.
+const SWAMP_DETOUR_WEIGHT: f32 = 4.0;
+
+/// Estimates the number of tiles the in-game PathFinder will expand to find a path between two
+/// tiles of the room, with its default plain and swamp costs.
+///
+/// The estimate starts from the straight-line range, and widens it by the room's
+/// [maze_complexity] and swamp ratio. It's capped at the number of walkable tiles, since each tile
+/// is expanded at most once. This is a rough budgeting heuristic for deciding whether a search is
+/// worth its CPU, not a prediction of the exact count.
+///
+/// This computes the maze complexity from scratch; use
+/// [estimate_pathfinder_ops_with_complexity] to reuse a stored score, such as from
+/// [AnalysisCache::maze_complexity](crate::analysis::cache::AnalysisCache::maze_complexity).
+pub fn estimate_pathfinder_ops(terrain: &CompressedRoomTerrain, from: RoomXY, to: RoomXY) -> u32 {
+    estimate_pathfinder_ops_with_complexity(terrain, from, to, maze_complexity(terrain))
+}
+
+/// Estimates PathFinder expansions like [estimate_pathfinder_ops], using a precomputed
+/// [maze_complexity] score for the room.
+pub fn estimate_pathfinder_ops_with_complexity(terrain: &CompressedRoomTerrain, from: RoomXY, to: RoomXY, maze_complexity: f32) -> u32 {
+    let bits = terrain.get_uncompressed_bits();
+    let swamp_tiles = bits.iter().filter(|b| **b & 0b11 == 0b10).count();
+    let open_tiles = bits.iter().filter(|b| **b & 0b01 == 0).count();
+    if open_tiles == 0 {
+        return 0;
+    }
+
+    let swamp_ratio = swamp_tiles as f32 / open_tiles as f32;
+    let straight_line = from.get_range_to(to) as f32 + 1.0;
+    let estimate = straight_line * (1.0 + MAZE_DETOUR_WEIGHT * maze_complexity) * (1.0 + SWAMP_DETOUR_WEIGHT * swamp_ratio);
+
+    (estimate.round() as u32).min(open_tiles as u32)
+}
+
+/// Picks the tile of an exit that is cheapest to reach from `from`, using the default
+/// [TerrainCosts].
+///
+/// Ties are broken in favor of the tile closest to the middle of the exit. If no exit tile is
+/// reachable, this returns the middle tile of the exit.
+pub fn choose_exit_tile(terrain: &CompressedRoomTerrain, exit: &RoomExit, from: RoomXY) -> RoomXY {
+    let path_costs = path_costs_from(terrain, from, &TerrainCosts::default());
+    let direction = exit.exit_direction();
+    let midpoint = exit.start() + exit.len() / 2;
+
+    let best_offset = (exit.start()..=exit.end())
+        .min_by_key(|offset| (path_costs.get(edge_offset_to_xy(direction, *offset)), offset.abs_diff(midpoint)))
+        .unwrap_or(midpoint);
+
+    edge_offset_to_xy(direction, best_offset)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{ExitDirection, ROOM_AREA};
+
+    #[test]
+    pub fn path_costs_from_accounts_for_swamps_and_walls() {
+        // Plains everywhere, except a wall column at x = 10 with a single gap at y = 25, and a
+        // swamp at (5, 5)
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..50 {
+            if y != 25 {
+                bits[y * 50 + 10] = 1;
+            }
+        }
+        bits[5 * 50 + 5] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let from = unsafe { RoomXY::unchecked_new(4, 4) };
+        let costs = path_costs_from(&terrain, from, &TerrainCosts::default());
+
+        assert_eq!(costs.get(from), 0);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(5, 4) }), 1);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(5, 5) }), 5, "swamp should cost 5");
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(10, 0) }), UNREACHABLE, "walls should be unreachable");
+
+        // Getting past the wall requires going through the gap at (10, 25), 21 rows down
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(10, 25) }), 21);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(11, 4) }), 42);
+    }
+
+    #[test]
+    pub fn multi_source_bfs_uses_nearest_source() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let sources = [unsafe { RoomXY::unchecked_new(10, 10) }, unsafe { RoomXY::unchecked_new(40, 10) }];
+        let costs = multi_source_bfs(&terrain, &sources, &TerrainCosts::default());
+
+        assert_eq!(costs.get(sources[0]), 0);
+        assert_eq!(costs.get(sources[1]), 0);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(13, 10) }), 3);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(37, 12) }), 3);
+        assert_eq!(costs.get(unsafe { RoomXY::unchecked_new(25, 10) }), 15);
+        assert!(costs.iter().all(|(_, c)| c != UNREACHABLE));
+    }
+
+    #[test]
+    pub fn trace_path_rejects_zero_cost_terrain() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let from = unsafe { RoomXY::unchecked_new(10, 10) };
+        let to = unsafe { RoomXY::unchecked_new(13, 10) };
+
+        let costs = TerrainCosts::default();
+        let path = trace_path(&path_costs_from(&terrain, from, &costs), &terrain, to, &costs).unwrap();
+        assert_eq!((path.first(), path.last(), path.len()), (Some(&from), Some(&to), 4));
+
+        let free_plains = TerrainCosts { plain_cost: 0, swamp_cost: 5 };
+        assert_eq!(trace_path(&path_costs_from(&terrain, from, &free_plains), &terrain, to, &free_plains), None);
+    }
+
+    #[test]
+    pub fn terrain_mst_connects_points_with_cheapest_paths() {
+        // Plains everywhere, with a swamp column at x = 20 except for a gap at y = 10
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..50 {
+            if y != 10 {
+                bits[y * 50 + 20] = 2;
+            }
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let points = [
+            unsafe { RoomXY::unchecked_new(10, 10) },
+            unsafe { RoomXY::unchecked_new(30, 10) },
+            unsafe { RoomXY::unchecked_new(10, 15) },
+        ];
+        let edges = terrain_mst(&terrain, &points);
+
+        assert_eq!(edges.len(), 2);
+        assert_eq!((edges[0].from, edges[0].to, edges[0].cost), (0, 2, 5));
+        assert_eq!((edges[1].from, edges[1].to, edges[1].cost), (0, 1, 20));
+
+        for edge in &edges {
+            assert_eq!(edge.path.first(), Some(&points[edge.from]));
+            assert_eq!(edge.path.last(), Some(&points[edge.to]));
+            assert_eq!(edge.path.len(), edge.cost as usize + 1, "path should only cross plains");
+        }
+    }
+
+    #[test]
+    pub fn quad_path_needs_two_tile_gaps() {
+        // A wall column at x = 10, with a 1-tile gap at y = 10 and a 2-tile gap at y = 40..=41
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..50 {
+            if y != 10 && y != 40 && y != 41 {
+                bits[y * 50 + 10] = 1;
+            }
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let from = RoomXY::checked_new(5, 10).unwrap();
+        let to = RoomXY::checked_new(15, 10).unwrap();
+
+        let path = quad_path(&terrain, from, to, &TerrainCosts::default()).unwrap();
+        assert_eq!(path.first(), Some(&from));
+        assert_eq!(path.last(), Some(&to));
+        assert!(path.iter().any(|xy| xy.x.u8() == 10 && xy.y.u8() == 40));
+        assert!(path.windows(2).all(|w| w[0].get_range_to(w[1]) == 1));
+
+        assert_eq!(quad_path(&terrain, from, RoomXY::checked_new(49, 10).unwrap(), &TerrainCosts::default()), None);
+    }
+
+    #[test]
+    pub fn estimate_pathfinder_ops_grows_with_swamps_and_distance() {
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let swamps = CompressedRoomTerrain::new_from_uncompressed_bits(&[2u8; ROOM_AREA]);
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+
+        let near = estimate_pathfinder_ops(&plains, xy(10, 10), xy(12, 10));
+        let far = estimate_pathfinder_ops(&plains, xy(10, 10), xy(40, 10));
+        let far_swamp = estimate_pathfinder_ops(&swamps, xy(10, 10), xy(40, 10));
+        assert!(near < far, "{near} >= {far}");
+        assert!(far < far_swamp, "{far} >= {far_swamp}");
+        assert!(far_swamp <= ROOM_AREA as u32);
+        assert_eq!(estimate_pathfinder_ops(&walls, xy(10, 10), xy(40, 10)), 0);
+    }
+
+    #[test]
+    pub fn estimate_pathfinder_ops_reuses_precomputed_complexity() {
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..40 {
+            bits[y * 50 + 25] = 1;
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        let (from, to) = (xy(10, 10), xy(40, 10));
+
+        let score = maze_complexity(&terrain);
+        assert_eq!(estimate_pathfinder_ops_with_complexity(&terrain, from, to, score), estimate_pathfinder_ops(&terrain, from, to));
+        assert!(estimate_pathfinder_ops_with_complexity(&terrain, from, to, score + 1.0) > estimate_pathfinder_ops(&terrain, from, to));
+    }
+
+    #[test]
+    pub fn choose_exit_tile_prefers_cheapest_tile_over_midpoint() {
+        // Plains everywhere, with the top edge open from 1 to 48
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let exit = RoomExit::new(1, 48, ExitDirection::Top);
+
+        // Coming from the far left, the closest exit tile should be on the left side of the span
+        let from = unsafe { RoomXY::unchecked_new(2, 20) };
+        let tile = choose_exit_tile(&terrain, &exit, from);
+        assert_eq!(tile, unsafe { RoomXY::unchecked_new(22, 0) });
+
+        // Directly below the midpoint, the midpoint itself should be chosen
+        let from = unsafe { RoomXY::unchecked_new(25, 20) };
+        let tile = choose_exit_tile(&terrain, &exit, from);
+        assert_eq!(tile, unsafe { RoomXY::unchecked_new(25, 0) });
+    }
+}
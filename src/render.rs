@@ -0,0 +1,494 @@
+//! Provides rendering of map data to images.
+//!
+//! Requires the `render` feature.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use screeps::{RoomName, RoomXY, Terrain, ROOM_AREA, ROOM_SIZE};
+use screeps::local::xy_to_terrain_index;
+
+use crate::room_connectivity::router::RoomKind;
+use crate::terrain_source::TerrainSource;
+
+/// An RGB color.
+pub type Rgb = [u8; 3];
+
+/// The color used for rooms without a value.
+pub const MISSING_ROOM_COLOR: Rgb = [32, 32, 32];
+
+/// The color used for the lowest value in a heatmap.
+pub const HEATMAP_LOW_COLOR: Rgb = [0, 0, 255];
+
+/// The color used for the highest value in a heatmap.
+pub const HEATMAP_HIGH_COLOR: Rgb = [255, 0, 0];
+
+/// An 8-bit RGB image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    /// Creates a new image filled with a single color.
+    pub fn new(width: u32, height: u32, color: Rgb) -> Self {
+        let pixels = color.repeat((width * height) as usize);
+        Self { width, height, pixels }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Gets the color of a pixel, or None if it's outside the image.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<Rgb> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let idx = ((y * self.width + x) * 3) as usize;
+        Some([self.pixels[idx], self.pixels[idx + 1], self.pixels[idx + 2]])
+    }
+
+    /// Fills a rectangle with a single color, clipped to the image.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                let idx = ((py * self.width + px) * 3) as usize;
+                self.pixels[idx..idx + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// The raw pixel data, as row-major RGB triples.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Encodes this image as a PNG.
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()
+    }
+
+    /// Encodes this image as a PNG file.
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+/// The colors used to render map data.
+///
+/// Themes can be loaded from TOML with [from_toml_str](RenderTheme::from_toml_str) or
+/// [load](RenderTheme::load). Every field is optional, and fields that aren't set keep their
+/// default:
+///
+/// ```toml
+/// [terrain]
+/// plain = "#2b2b2b"
+/// swamp = "#232713"
+/// wall = [17, 17, 17]
+///
+/// [exits]
+/// color = "#7a7a7a"
+///
+/// [heatmap]
+/// low = "#0000ff"
+/// high = "#ff0000"
+/// missing = "#202020"
+///
+/// [overlay]
+/// color = "#00ff00"
+/// opacity = 0.5
+///
+/// [room_kinds]
+/// strength = 0.2
+/// highway = "#4060a0"
+/// source_keeper = "#a04040"
+/// ```
+///
+/// Colors are `"#rrggbb"` strings or `[r, g, b]` arrays. Room kinds are `normal`, `highway`,
+/// `crossroads`, `source_keeper`, and `center`; rooms of kinds without a tint aren't tinted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderTheme {
+    pub plain: Rgb,
+    pub swamp: Rgb,
+    pub wall: Rgb,
+    /// The color of walkable tiles along the room edges.
+    pub exit: Rgb,
+    pub heatmap_low: Rgb,
+    pub heatmap_high: Rgb,
+    /// The color of rooms without a value or terrain.
+    pub missing_room: Rgb,
+    /// The color blended over tiles in an overlay.
+    pub overlay: Rgb,
+    /// How much of the overlay color is blended in, from 0 to 1.
+    pub overlay_opacity: f32,
+    /// The tint blended over each tile in rooms of each kind.
+    pub room_kind_tints: HashMap<RoomKind, Rgb>,
+    /// How much of the room kind tint is blended in, from 0 to 1.
+    pub room_kind_tint_strength: f32,
+}
+
+impl Default for RenderTheme {
+    fn default() -> Self {
+        Self {
+            plain: [43, 43, 43],
+            swamp: [35, 39, 19],
+            wall: [17, 17, 17],
+            exit: [122, 122, 122],
+            heatmap_low: HEATMAP_LOW_COLOR,
+            heatmap_high: HEATMAP_HIGH_COLOR,
+            missing_room: MISSING_ROOM_COLOR,
+            overlay: [0, 255, 0],
+            overlay_opacity: 0.5,
+            room_kind_tints: HashMap::from([
+                (RoomKind::Highway, [64, 96, 160]),
+                (RoomKind::Crossroads, [64, 96, 160]),
+                (RoomKind::SourceKeeper, [160, 64, 64]),
+                (RoomKind::Center, [160, 128, 64]),
+            ]),
+            room_kind_tint_strength: 0.2,
+        }
+    }
+}
+
+impl RenderTheme {
+    /// Parses a theme from TOML, starting from the default theme.
+    pub fn from_toml_str(text: &str) -> Result<Self, RenderThemeError> {
+        let table: toml::Table = text.parse().map_err(RenderThemeError::Toml)?;
+        let mut theme = Self::default();
+
+        for (section, fields) in &table {
+            let fields = fields.as_table().ok_or_else(|| RenderThemeError::invalid(section, "expected a table"))?;
+            for (key, value) in fields {
+                let field = format!("{section}.{key}");
+                match (section.as_str(), key.as_str()) {
+                    ("terrain", "plain") => theme.plain = parse_color(&field, value)?,
+                    ("terrain", "swamp") => theme.swamp = parse_color(&field, value)?,
+                    ("terrain", "wall") => theme.wall = parse_color(&field, value)?,
+                    ("exits", "color") => theme.exit = parse_color(&field, value)?,
+                    ("heatmap", "low") => theme.heatmap_low = parse_color(&field, value)?,
+                    ("heatmap", "high") => theme.heatmap_high = parse_color(&field, value)?,
+                    ("heatmap", "missing") => theme.missing_room = parse_color(&field, value)?,
+                    ("overlay", "color") => theme.overlay = parse_color(&field, value)?,
+                    ("overlay", "opacity") => theme.overlay_opacity = parse_fraction(&field, value)?,
+                    ("room_kinds", "strength") => theme.room_kind_tint_strength = parse_fraction(&field, value)?,
+                    ("room_kinds", kind) => {
+                        let kind = room_kind_from_name(kind).ok_or_else(|| RenderThemeError::invalid(&field, "unknown room kind"))?;
+                        theme.room_kind_tints.insert(kind, parse_color(&field, value)?);
+                    },
+                    _ => return Err(RenderThemeError::invalid(&field, "unknown field")),
+                }
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Loads a theme from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RenderThemeError> {
+        let text = std::fs::read_to_string(path).map_err(RenderThemeError::Io)?;
+        Self::from_toml_str(&text)
+    }
+}
+
+/// The errors that prevent a theme from being loaded.
+#[derive(Debug)]
+pub enum RenderThemeError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't valid TOML.
+    Toml(toml::de::Error),
+    /// A field is unknown, or its value is invalid.
+    InvalidField { field: String, reason: &'static str },
+}
+
+impl RenderThemeError {
+    fn invalid(field: &str, reason: &'static str) -> Self {
+        Self::InvalidField { field: field.to_string(), reason }
+    }
+}
+
+impl fmt::Display for RenderThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "unable to read theme file: {e}"),
+            Self::Toml(e) => write!(f, "theme file is not valid TOML: {e}"),
+            Self::InvalidField { field, reason } => write!(f, "theme field {field} is invalid: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderThemeError {}
+
+/// Internal helper function to parse a `"#rrggbb"` string or `[r, g, b]` array.
+fn parse_color(field: &str, value: &toml::Value) -> Result<Rgb, RenderThemeError> {
+    let invalid = || RenderThemeError::invalid(field, "expected a \"#rrggbb\" string or an [r, g, b] array");
+    match value {
+        toml::Value::String(hex) => {
+            let hex = hex.strip_prefix('#').filter(|hex| hex.len() == 6).ok_or_else(invalid)?;
+            let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid());
+            Ok([channel(0)?, channel(2)?, channel(4)?])
+        },
+        toml::Value::Array(channels) if channels.len() == 3 => {
+            let channel = |i: usize| channels[i].as_integer().and_then(|c| u8::try_from(c).ok()).ok_or_else(invalid);
+            Ok([channel(0)?, channel(1)?, channel(2)?])
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Internal helper function to parse a number from 0 to 1.
+fn parse_fraction(field: &str, value: &toml::Value) -> Result<f32, RenderThemeError> {
+    let fraction = match value {
+        toml::Value::Float(f) => *f,
+        toml::Value::Integer(i) => *i as f64,
+        _ => return Err(RenderThemeError::invalid(field, "expected a number")),
+    };
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(RenderThemeError::invalid(field, "expected a number from 0 to 1"));
+    }
+    Ok(fraction as f32)
+}
+
+/// Internal helper function to parse a room kind from its name in a theme.
+fn room_kind_from_name(name: &str) -> Option<RoomKind> {
+    match name {
+        "normal" => Some(RoomKind::Normal),
+        "highway" => Some(RoomKind::Highway),
+        "crossroads" => Some(RoomKind::Crossroads),
+        "source_keeper" => Some(RoomKind::SourceKeeper),
+        "center" => Some(RoomKind::Center),
+        _ => None,
+    }
+}
+
+/// Renders a shard-level heatmap, coloring each room by its value.
+///
+/// Rooms are laid out by their map position, with each room taking up a `cell_size` square of
+/// pixels. The image covers the bounding box of every room with a value. Values are scaled
+/// linearly from [HEATMAP_LOW_COLOR] at the smallest value to [HEATMAP_HIGH_COLOR] at the largest;
+/// rooms inside the bounding box without a value are drawn as [MISSING_ROOM_COLOR].
+///
+/// Returns None if there are no values.
+pub fn render_heatmap(values: &HashMap<RoomName, f32>, cell_size: u32) -> Option<RgbImage> {
+    render_heatmap_with_theme(values, cell_size, &RenderTheme::default())
+}
+
+/// Renders a shard-level heatmap like [render_heatmap], using the heatmap and missing room colors
+/// of a theme.
+pub fn render_heatmap_with_theme(values: &HashMap<RoomName, f32>, cell_size: u32, theme: &RenderTheme) -> Option<RgbImage> {
+    let (min_x, min_y, rooms_wide, rooms_high) = room_bounds(values.keys())?;
+
+    let min_value = values.values().copied().fold(f32::INFINITY, f32::min);
+    let max_value = values.values().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = max_value - min_value;
+
+    let mut image = RgbImage::new(rooms_wide * cell_size, rooms_high * cell_size, theme.missing_room);
+
+    for (room, value) in values {
+        let fraction = if range > 0.0 { (value - min_value) / range } else { 0.5 };
+        let color = lerp_color(theme.heatmap_low, theme.heatmap_high, fraction);
+        let x = (room.x_coord() - min_x) as u32 * cell_size;
+        let y = (room.y_coord() - min_y) as u32 * cell_size;
+        image.fill_rect(x, y, cell_size, cell_size, color);
+    }
+
+    Some(image)
+}
+
+/// Renders the terrain of a single room, with `tile_size` pixels per tile.
+///
+/// Tiles are drawn in the theme's terrain colors, tinted by the room's kind. Walkable edge tiles
+/// are drawn in the exit color, and `overlay` tiles, such as the output of an analysis, are blended
+/// with the overlay color.
+pub fn render_room(terrain: &impl TerrainSource, room: RoomName, overlay: &[RoomXY], tile_size: u32, theme: &RenderTheme) -> RgbImage {
+    let size = ROOM_SIZE as u32 * tile_size;
+    let mut image = RgbImage::new(size, size, theme.missing_room);
+    draw_room(&mut image, (0, 0), terrain, room, overlay, tile_size, theme);
+    image
+}
+
+/// Renders the terrain of many rooms, laid out by their map position like [render_heatmap], with
+/// each room drawn like [render_room] without an overlay.
+///
+/// Returns None if there are no rooms.
+pub fn render_rooms<T: TerrainSource>(rooms: &HashMap<RoomName, T>, tile_size: u32, theme: &RenderTheme) -> Option<RgbImage> {
+    let (min_x, min_y, rooms_wide, rooms_high) = room_bounds(rooms.keys())?;
+    let room_size = ROOM_SIZE as u32 * tile_size;
+    let mut image = RgbImage::new(rooms_wide * room_size, rooms_high * room_size, theme.missing_room);
+
+    for (room, terrain) in rooms {
+        let x = (room.x_coord() - min_x) as u32 * room_size;
+        let y = (room.y_coord() - min_y) as u32 * room_size;
+        draw_room(&mut image, (x, y), terrain, *room, &[], tile_size, theme);
+    }
+
+    Some(image)
+}
+
+/// The room kinds shown by [render_legend], in the order their swatches are drawn.
+pub const LEGEND_ROOM_KINDS: [RoomKind; 5] = [RoomKind::Normal, RoomKind::Highway, RoomKind::Crossroads, RoomKind::SourceKeeper, RoomKind::Center];
+
+/// Renders a legend for a theme, with `swatch_size` pixel square swatches.
+///
+/// The top row has a swatch for each of [LEGEND_ROOM_KINDS], left to right, in the color a plain
+/// tile is drawn in rooms of that kind. The bottom row is the heatmap scale, running from the low
+/// color on the left to the high color on the right.
+pub fn render_legend(theme: &RenderTheme, swatch_size: u32) -> RgbImage {
+    let width = LEGEND_ROOM_KINDS.len() as u32 * swatch_size;
+    let mut image = RgbImage::new(width, swatch_size * 2, theme.missing_room);
+
+    for (i, kind) in LEGEND_ROOM_KINDS.iter().enumerate() {
+        let color = match theme.room_kind_tints.get(kind) {
+            Some(tint) => lerp_color(theme.plain, *tint, theme.room_kind_tint_strength),
+            None => theme.plain,
+        };
+        image.fill_rect(i as u32 * swatch_size, 0, swatch_size, swatch_size, color);
+    }
+
+    for x in 0..width {
+        let fraction = if width > 1 { x as f32 / (width - 1) as f32 } else { 0.5 };
+        image.fill_rect(x, swatch_size, 1, swatch_size, lerp_color(theme.heatmap_low, theme.heatmap_high, fraction));
+    }
+
+    image
+}
+
+/// Internal helper function to draw a room's tiles with their top left corner at `origin`.
+fn draw_room(image: &mut RgbImage, (x, y): (u32, u32), terrain: &impl TerrainSource, room: RoomName, overlay: &[RoomXY], tile_size: u32, theme: &RenderTheme) {
+    let tint = theme.room_kind_tints.get(&RoomKind::for_room(room));
+    let last = ROOM_SIZE - 1;
+    let mut overlaid = vec![false; ROOM_AREA];
+    for xy in overlay {
+        overlaid[xy_to_terrain_index(*xy)] = true;
+    }
+
+    for (xy, tile) in terrain.iter_tiles() {
+        let on_edge = xy.x.u8() == 0 || xy.x.u8() == last || xy.y.u8() == 0 || xy.y.u8() == last;
+        let mut color = match tile {
+            Terrain::Wall => theme.wall,
+            _ if on_edge => theme.exit,
+            Terrain::Plain => theme.plain,
+            Terrain::Swamp => theme.swamp,
+        };
+        if let Some(tint) = tint {
+            color = lerp_color(color, *tint, theme.room_kind_tint_strength);
+        }
+        if overlaid[xy_to_terrain_index(xy)] {
+            color = lerp_color(color, theme.overlay, theme.overlay_opacity);
+        }
+        image.fill_rect(x + xy.x.u8() as u32 * tile_size, y + xy.y.u8() as u32 * tile_size, tile_size, tile_size, color);
+    }
+}
+
+/// Internal helper function to get the top left room coordinates, and the width and height in
+/// rooms, of the bounding box of some rooms. Returns None if there are no rooms.
+fn room_bounds<'a>(rooms: impl Iterator<Item = &'a RoomName> + Clone) -> Option<(i32, i32, u32, u32)> {
+    let min_x = rooms.clone().map(|r| r.x_coord()).min()?;
+    let max_x = rooms.clone().map(|r| r.x_coord()).max()?;
+    let min_y = rooms.clone().map(|r| r.y_coord()).min()?;
+    let max_y = rooms.map(|r| r.y_coord()).max()?;
+    Some((min_x, min_y, (max_x - min_x + 1) as u32, (max_y - min_y + 1) as u32))
+}
+
+/// Linearly interpolates between two colors, with `fraction` clamped to [0, 1].
+pub fn lerp_color(from: Rgb, to: Rgb, fraction: f32) -> Rgb {
+    let fraction = fraction.clamp(0.0, 1.0);
+    std::array::from_fn(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * fraction).round() as u8)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn render_heatmap_lays_out_rooms_by_position() {
+        let values: HashMap<RoomName, f32> = [("W1N1", 0.0), ("W0N1", 10.0), ("W0N0", 5.0)].into_iter()
+            .map(|(name, value)| (RoomName::new(name).unwrap(), value))
+            .collect();
+        let image = render_heatmap(&values, 4).unwrap();
+
+        assert_eq!((image.width(), image.height()), (8, 8));
+        assert_eq!(image.get_pixel(0, 0), Some(HEATMAP_LOW_COLOR)); // W1N1
+        assert_eq!(image.get_pixel(7, 3), Some(HEATMAP_HIGH_COLOR)); // W0N1
+        assert_eq!(image.get_pixel(4, 4), Some([128, 0, 128])); // W0N0
+        assert_eq!(image.get_pixel(0, 4), Some(MISSING_ROOM_COLOR)); // W1N0
+
+        let mut png_bytes = Vec::new();
+        image.write_png(&mut png_bytes).unwrap();
+        assert_eq!(&png_bytes[1..4], b"PNG");
+
+        assert_eq!(render_heatmap(&HashMap::new(), 4), None);
+    }
+
+    #[test]
+    pub fn render_theme_loads_from_toml_and_colors_rooms() {
+        let theme = RenderTheme::from_toml_str(r##"
+            [terrain]
+            plain = "#102030"
+            wall = [1, 2, 3]
+
+            [exits]
+            color = "#ffffff"
+
+            [overlay]
+            color = "#000000"
+            opacity = 1
+
+            [room_kinds]
+            strength = 0.5
+            normal = "#000000"
+        "##).unwrap();
+        assert_eq!(theme.plain, [0x10, 0x20, 0x30]);
+        assert_eq!(theme.swamp, RenderTheme::default().swamp);
+
+        let mut bits = [0u8; ROOM_AREA];
+        bits[0] = 1;
+        let terrain = crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let overlay = [RoomXY::checked_new(20, 20).unwrap()];
+        let image = render_room(&terrain, RoomName::new("W1N1").unwrap(), &overlay, 2, &theme);
+        assert_eq!((image.width(), image.height()), (100, 100));
+        assert_eq!(image.get_pixel(1, 1), Some([1, 1, 2])); // Wall corner, tinted
+        assert_eq!(image.get_pixel(2, 0), Some([128, 128, 128])); // Exit
+        assert_eq!(image.get_pixel(10, 10), Some([8, 16, 24])); // Plain
+        assert_eq!(image.get_pixel(41, 41), Some([0, 0, 0])); // Overlay
+
+        let rooms = HashMap::from([(RoomName::new("W1N1").unwrap(), terrain)]);
+        assert_eq!(render_rooms(&rooms, 1, &RenderTheme::default()).unwrap().width(), 50);
+
+        assert!(RenderTheme::from_toml_str("[terrain]\nplain = \"#12345\"").is_err());
+        assert!(RenderTheme::from_toml_str("[terrain]\nlava = \"#123456\"").is_err());
+        assert!(RenderTheme::from_toml_str("[overlay]\nopacity = 2.0").is_err());
+        assert!(RenderTheme::from_toml_str("[room_kinds]\nportal = [0, 0, 0]").is_err());
+    }
+
+    #[test]
+    pub fn render_legend_draws_room_kinds_and_heatmap_scale() {
+        let theme = RenderTheme::default();
+        let image = render_legend(&theme, 4);
+        assert_eq!((image.width(), image.height()), (20, 8));
+
+        assert_eq!(image.get_pixel(0, 0), Some(theme.plain)); // Normal rooms aren't tinted
+        let highway = lerp_color(theme.plain, theme.room_kind_tints[&RoomKind::Highway], theme.room_kind_tint_strength);
+        assert_eq!(image.get_pixel(5, 3), Some(highway));
+        let center = lerp_color(theme.plain, theme.room_kind_tints[&RoomKind::Center], theme.room_kind_tint_strength);
+        assert_eq!(image.get_pixel(19, 0), Some(center));
+
+        assert_eq!(image.get_pixel(0, 4), Some(theme.heatmap_low));
+        assert_eq!(image.get_pixel(19, 7), Some(theme.heatmap_high));
+    }
+}
@@ -0,0 +1,118 @@
+//! Provides a builder for constructing a [ShardConnectivityGraph] from multiple threads.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use screeps::RoomName;
+
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::room_connectivity::graph::{MapTopology, ShardConnectivityGraph};
+use crate::terrain_source::TerrainSource;
+
+/// The rooms added to a single shard of a [GraphBuilder], along with their border annotations.
+type BuilderShard = HashMap<RoomName, (RoomExitsData, Option<[f32; 4]>)>;
+
+/// Collects per-room exits data from any number of threads, then finalizes it into a
+/// [ShardConnectivityGraph].
+///
+/// Rooms are sharded by their x coordinate, with a separate lock per shard, so threads working on
+/// different columns of the map don't contend with each other.
+#[derive(Debug)]
+pub struct GraphBuilder {
+    shards: Vec<Mutex<BuilderShard>>,
+    topology: MapTopology,
+}
+
+impl GraphBuilder {
+    /// Creates an empty builder with the specified number of shards, which is usually a small
+    /// multiple of the number of threads that will be adding rooms.
+    pub fn new(num_shards: usize) -> Self {
+        Self::with_topology(num_shards, MapTopology::default())
+    }
+
+    /// Creates an empty builder for a map with the specified topology.
+    pub fn with_topology(num_shards: usize, topology: MapTopology) -> Self {
+        let shards = (0..num_shards.max(1)).map(|_| Mutex::default()).collect();
+        Self { shards, topology }
+    }
+
+    /// Adds a room, replacing any existing exits data for it.
+    pub fn add_room(&self, exits: RoomExitsData) {
+        self.insert_room(exits, None);
+    }
+
+    /// Adds a room along with its full terrain; see
+    /// [ShardConnectivityGraph::add_room_with_terrain].
+    pub fn add_room_with_terrain(&self, exits: RoomExitsData, terrain: &impl TerrainSource) {
+        let fractions = exits.border_swamp_fractions(terrain);
+        self.insert_room(exits, Some(fractions));
+    }
+
+    /// The number of rooms added so far.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    /// Returns true if no rooms have been added.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Consumes the builder, returning the graph of every room added to it.
+    pub fn finish(self) -> ShardConnectivityGraph {
+        let mut graph = ShardConnectivityGraph::with_topology(self.topology);
+        for shard in self.shards {
+            let rooms = shard.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+            for (exits, border_swamp) in rooms.into_values() {
+                graph.insert_room(exits, border_swamp);
+            }
+        }
+        graph
+    }
+
+    /// Internal helper function to add a room to the shard for its x coordinate.
+    fn insert_room(&self, exits: RoomExitsData, border_swamp: Option<[f32; 4]>) {
+        let shard_idx = exits.room().x_coord().rem_euclid(self.shards.len() as i32) as usize;
+        lock(&self.shards[shard_idx]).insert(exits.room(), (exits, border_swamp));
+    }
+}
+
+/// Internal helper function to lock a shard, ignoring poisoning since every insert leaves the
+/// shard in a consistent state.
+fn lock(shard: &Mutex<BuilderShard>) -> std::sync::MutexGuard<'_, BuilderShard> {
+    shard.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+    use crate::room_connectivity::graph::exits_with_open_edges;
+
+    #[test]
+    pub fn graph_builder_collects_rooms_from_multiple_threads() {
+        let names: Vec<String> = (0..8).flat_map(|x| (0..8).map(move |y| format!("W{x}N{y}"))).collect();
+        let builder = GraphBuilder::new(4);
+
+        thread::scope(|scope| {
+            for chunk in names.chunks(16) {
+                let builder = &builder;
+                scope.spawn(move || {
+                    for name in chunk {
+                        builder.add_room(exits_with_open_edges(name, [true; 4]));
+                    }
+                });
+            }
+        });
+        assert_eq!(builder.len(), 64);
+
+        let graph = builder.finish();
+        let sequential: ShardConnectivityGraph = names.iter()
+            .map(|name| exits_with_open_edges(name, [true; 4]))
+            .collect();
+        assert_eq!(graph.len(), sequential.len());
+        let room = RoomName::new("W3N3").unwrap();
+        assert_eq!(graph.neighbors(room), sequential.neighbors(room));
+        assert_eq!(graph.neighbors(room).len(), 4);
+    }
+}
@@ -0,0 +1,153 @@
+//! Provides a 1 byte summary of which edges of a room connect to its neighbors.
+
+#[cfg(feature = "db")]
+use std::collections::HashMap;
+#[cfg(feature = "db")]
+use std::ops::ControlFlow;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::ExitDirection;
+#[cfg(feature = "db")]
+use screeps::RoomName;
+
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+#[cfg(feature = "db")]
+use crate::error::MapProcessingError;
+use crate::room_connectivity::exit::RoomExitsData;
+use crate::room_connectivity::graph::{EXIT_DIRECTIONS, has_exits_toward};
+
+/// Which edges of a room have exits to a neighboring room, as 4 bit flags.
+///
+/// This is much smaller than [RoomExitsData] when only room-level connectivity is needed, such as
+/// when storing connectivity for a whole shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RoomConnectivity(u8);
+
+impl RoomConnectivity {
+    pub const TOP: Self = Self(0b0001);
+    pub const RIGHT: Self = Self(0b0010);
+    pub const BOTTOM: Self = Self(0b0100);
+    pub const LEFT: Self = Self(0b1000);
+
+    const ALL_BITS: u8 = 0b1111;
+
+    /// No connected edges.
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Every edge connected.
+    pub fn all() -> Self {
+        Self(Self::ALL_BITS)
+    }
+
+    /// Creates flags from their bit representation, or None if any bits other than the lowest 4
+    /// are set.
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        (bits & !Self::ALL_BITS == 0).then_some(Self(bits))
+    }
+
+    /// The bit representation of these flags.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// The flag for an edge.
+    pub fn for_direction(direction: ExitDirection) -> Self {
+        match direction {
+            ExitDirection::Top => Self::TOP,
+            ExitDirection::Right => Self::RIGHT,
+            ExitDirection::Bottom => Self::BOTTOM,
+            ExitDirection::Left => Self::LEFT,
+        }
+    }
+
+    /// Returns true if the edge in the specified direction is connected.
+    pub fn is_connected(&self, direction: ExitDirection) -> bool {
+        self.0 & Self::for_direction(direction).0 != 0
+    }
+
+    /// Marks the edge in the specified direction as connected or not.
+    pub fn set_connected(&mut self, direction: ExitDirection, connected: bool) {
+        let flag = Self::for_direction(direction).0;
+        if connected {
+            self.0 |= flag;
+        } else {
+            self.0 &= !flag;
+        }
+    }
+
+    /// The number of connected edges.
+    pub fn num_connected(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    /// Returns an iterator over the connected edges, in clockwise order starting from the top.
+    pub fn directions(&self) -> impl Iterator<Item = ExitDirection> + use<> {
+        let flags = *self;
+        EXIT_DIRECTIONS.into_iter().filter(move |direction| flags.is_connected(*direction))
+    }
+}
+
+impl From<&RoomExitsData> for RoomConnectivity {
+    fn from(exits: &RoomExitsData) -> Self {
+        let mut flags = Self::empty();
+        for direction in EXIT_DIRECTIONS {
+            flags.set_connected(direction, has_exits_toward(exits, direction));
+        }
+        flags
+    }
+}
+
+/// Calculates the connectivity of every room with terrain in the database.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+#[cfg(feature = "db")]
+pub fn get_room_connectivity_from_db(conn: &Connection) -> Result<HashMap<RoomName, RoomConnectivity>, MapProcessingError> {
+    let mut connectivity = HashMap::new();
+    let failed = compressed_terrain_db::iter_room_terrains(conn, |room, terrain| {
+        let terrain = match terrain {
+            Ok(terrain) => terrain,
+            Err(e) => return ControlFlow::Break(e),
+        };
+        let exits = RoomExitsData::new_from_compressed_terrain(&terrain, room);
+        connectivity.insert(room, RoomConnectivity::from(&exits));
+        ControlFlow::Continue(())
+    })?;
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    Ok(connectivity)
+}
+
+
+#[cfg(all(test, feature = "db"))]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+    use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+    #[test]
+    pub fn room_connectivity_matches_room_exits() {
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..50].fill(1); // Wall off the top edge
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W1N1").unwrap();
+        compressed_terrain_db::add_terrain_for_room(&conn, room, &terrain).unwrap();
+
+        let connectivity = get_room_connectivity_from_db(&conn).unwrap();
+        let flags = connectivity[&room];
+        assert_eq!(flags.bits(), 0b1110);
+        assert!(!flags.is_connected(ExitDirection::Top));
+        assert_eq!(flags.directions().collect::<Vec<_>>(), vec![ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left]);
+        assert_eq!(flags.num_connected(), 3);
+
+        assert_eq!(RoomConnectivity::from_bits(0b1_0000), None);
+        let mut flags = RoomConnectivity::empty();
+        flags.set_connected(ExitDirection::Left, true);
+        assert_eq!(flags, RoomConnectivity::LEFT);
+        assert_eq!(std::mem::size_of::<RoomConnectivity>(), 1);
+    }
+}
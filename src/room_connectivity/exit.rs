@@ -1,7 +1,27 @@
-use screeps::{ExitDirection, Terrain, RoomName};
+use std::sync::OnceLock;
+use screeps::{ExitDirection, LocalCostMatrix, Terrain, RoomName, RoomXY};
+use screeps_utils::offline_map::OfflineRoomData;
 
 use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
-
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::terrain_source::TerrainSource;
+use crate::room_connectivity::graph::{EXIT_DIRECTIONS, MapTopology, neighbor_room};
+
+
+/// The errors that can be returned when validating exit parameters.
+#[derive(Debug, Copy, Clone, PartialEq, thiserror::Error)]
+pub enum RoomExitValidationError {
+    /// The exit has a length of zero.
+    #[error("exit has a length of zero")]
+    ZeroLength,
+    /// The exit starts beyond the last tile of the edge, or extends past it.
+    #[error("exit extends past the edge of the room")]
+    ExceedsEdge,
+    /// The packed exit direction bits are not a valid ExitDirection.
+    #[error("exit direction is invalid")]
+    InvalidDirection,
+}
 
 /// Compact representation of an entire exit along a room edge.
 ///
@@ -52,13 +72,49 @@ impl RoomExit {
         Self { packed: final_packed }
     }
 
+    /// Creates a new RoomExit from the packed representation, validating that it describes an exit
+    /// that can actually exist on a room edge.
+    pub fn try_new_from_packed(packed: u16) -> Result<Self, RoomExitValidationError> {
+        let dir_bits = (packed & Self::EXIT_DIRECTION_BITMASK) >> Self::EXIT_DIRECTION_OFFSET;
+        if !matches!(dir_bits, 1 | 3 | 5 | 7) {
+            return Err(RoomExitValidationError::InvalidDirection);
+        }
+
+        let exit = Self { packed };
+        Self::validate_span(exit.start(), exit.len())?;
+
+        Ok(exit)
+    }
+
     /// Creates a new RoomExit from the start and length parameters.
+    ///
+    /// Note: This does not validate the parameters; see [try_new](RoomExit::try_new).
     pub fn new(start: u8, length: u8, direction: ExitDirection) -> Self {
         let packed = Self::get_packed_from_parameters(start, length, direction);
 
         Self { packed }
     }
 
+    /// Creates a new RoomExit from the start and length parameters, rejecting zero-length exits and
+    /// exits that extend past the end of the edge.
+    pub fn try_new(start: u8, length: u8, direction: ExitDirection) -> Result<Self, RoomExitValidationError> {
+        Self::validate_span(start, length)?;
+        Ok(Self::new(start, length, direction))
+    }
+
+    /// Internal helper function to validate that an exit span fits within a room edge.
+    fn validate_span(start: u8, length: u8) -> Result<(), RoomExitValidationError> {
+        if length == 0 {
+            return Err(RoomExitValidationError::ZeroLength);
+        }
+
+        if start as usize + length as usize > 50 {
+            return Err(RoomExitValidationError::ExceedsEdge);
+        }
+
+        Ok(())
+    }
+
     /// Helper function to get the packed representation from the start and length parameters.
     pub fn get_packed_from_parameters(start: u8, length: u8, direction: ExitDirection) -> u16 {
         let direction_val = direction as u16;
@@ -99,6 +155,82 @@ impl RoomExit {
         }
     }
 
+    /// The absolute positions of every tile of this exit, in LTR/top-to-bottom order, for an exit
+    /// of the specified room.
+    #[cfg(feature = "game-types")]
+    pub fn to_positions(&self, room: RoomName) -> Vec<screeps::Position> {
+        let direction = self.exit_direction();
+        (self.start()..=self.end())
+            .map(|offset| {
+                let xy = edge_offset_to_xy(direction, offset);
+                screeps::Position::new(xy.x, xy.y, room)
+            })
+            .collect()
+    }
+
+    /// Calls `f` with the position of every tile of this exit, in LTR/top-to-bottom order, without
+    /// allocating.
+    pub fn for_each_tile(&self, mut f: impl FnMut(RoomXY)) {
+        let direction = self.exit_direction();
+        for offset in self.start()..=self.end() {
+            f(edge_offset_to_xy(direction, offset));
+        }
+    }
+
+    /// Returns an iterator over the position of every tile of this exit, in LTR/top-to-bottom
+    /// order.
+    pub fn tiles(&self) -> impl Iterator<Item = RoomXY> + use<> {
+        let direction = self.exit_direction();
+        (self.start()..=self.end()).map(move |offset| edge_offset_to_xy(direction, offset))
+    }
+
+    /// Returns an iterator over the tiles that this exit leads to in the neighboring room, for an
+    /// exit of the specified room, paired with the name of the neighboring room.
+    ///
+    /// Tiles are in the same order as [tiles](RoomExit::tiles), so each entry tile is directly
+    /// across the border from the exit tile at the same position. The iterator is empty if the
+    /// neighboring room doesn't exist.
+    pub fn neighbor_entry_tiles(&self, room: RoomName) -> impl Iterator<Item = (RoomName, RoomXY)> + use<> {
+        let direction = self.exit_direction();
+        let entry_direction = opposite_exit_direction(direction);
+        let neighbor = neighbor_room(room, direction);
+        (self.start()..=self.end()).filter_map(move |offset| Some((neighbor?, edge_offset_to_xy(entry_direction, offset))))
+    }
+
+    /// The fraction of swamp tiles on the two rows or columns just inside this exit, from 0 to 1.
+    ///
+    /// Creeps crossing the border have to move through these tiles, so a high fraction means the
+    /// crossing is slow even though the exit exists.
+    pub fn swamp_fraction(&self, terrain: &impl TerrainSource) -> f32 {
+        let (swamps, total) = self.inner_swamp_counts(terrain);
+        if total == 0 { 0.0 } else { swamps as f32 / total as f32 }
+    }
+
+    /// Internal helper function to count the swamp tiles, and all tiles, on the two rows or columns
+    /// just inside this exit.
+    fn inner_swamp_counts(&self, terrain: &impl TerrainSource) -> (u32, u32) {
+        let direction = self.exit_direction();
+        let mut counts = (0, 0);
+        for offset in self.start()..=self.end() {
+            for depth in 1..=2 {
+                let edge_xy = edge_offset_to_xy(direction, offset);
+                // Safety: edge coordinates are 0 or 49 and depth is at most 2, so the inner
+                // coordinates are always in the range [1, 48]
+                let xy = unsafe {
+                    match direction {
+                        ExitDirection::Top => RoomXY::unchecked_new(edge_xy.x.u8(), depth),
+                        ExitDirection::Right => RoomXY::unchecked_new(49 - depth, edge_xy.y.u8()),
+                        ExitDirection::Bottom => RoomXY::unchecked_new(edge_xy.x.u8(), 49 - depth),
+                        ExitDirection::Left => RoomXY::unchecked_new(depth, edge_xy.y.u8()),
+                    }
+                };
+                counts.0 += (terrain.get_xy(xy) == Terrain::Swamp) as u32;
+                counts.1 += 1;
+            }
+        }
+        counts
+    }
+
     /// The packed representation of this exit.
     pub fn packed(&self) -> u16 {
         self.packed
@@ -113,85 +245,122 @@ impl RoomExit {
     ///
     /// Returned ordering is: Top, Right, Bottom, Left
     pub fn get_exits_from_edge_terrain(terrain: &RoomEdgeTerrain) -> (Vec<Self>, Vec<Self>, Vec<Self>, Vec<Self>) {
-        let top_terrain = terrain.get_top_edge_terrain();
-        let right_terrain = terrain.get_right_edge_terrain();
-        let bottom_terrain = terrain.get_bottom_edge_terrain();
-        let left_terrain = terrain.get_left_edge_terrain();
-
-        let top_exits = Self::get_exits_from_single_edge(&top_terrain, ExitDirection::Top);
-        let right_exits = Self::get_exits_from_single_edge(&right_terrain, ExitDirection::Right);
-        let bottom_exits = Self::get_exits_from_single_edge(&bottom_terrain, ExitDirection::Bottom);
-        let left_exits = Self::get_exits_from_single_edge(&left_terrain, ExitDirection::Left);
+        let top_exits = Self::get_exits_from_edge_bytes(terrain.get_edge_bytes(ExitDirection::Top), ExitDirection::Top);
+        let right_exits = Self::get_exits_from_edge_bytes(terrain.get_edge_bytes(ExitDirection::Right), ExitDirection::Right);
+        let bottom_exits = Self::get_exits_from_edge_bytes(terrain.get_edge_bytes(ExitDirection::Bottom), ExitDirection::Bottom);
+        let left_exits = Self::get_exits_from_edge_bytes(terrain.get_edge_bytes(ExitDirection::Left), ExitDirection::Left);
 
         (top_exits, right_exits, bottom_exits, left_exits)
     }
 
+    /// Extracts every exit from the compressed room edge terrain as a single list.
+    ///
+    /// Exits are in clockwise order: Top, Right, Bottom, then Left, and in LTR/top-to-bottom order
+    /// along each edge. This matches the indexing of [RoomExitsData::get_exit_by_index].
+    pub fn get_all_exits(terrain: &RoomEdgeTerrain) -> Vec<Self> {
+        [ExitDirection::Top, ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left].into_iter()
+            .flat_map(|direction| Self::get_exits_from_edge_bytes(terrain.get_edge_bytes(direction), direction))
+            .collect()
+    }
+
     /// Utility function that processes edge terrain into a list of exits.
     ///
     /// Returned vector can be empty if the edge is entirely Walls, and thus has no exits.
     pub fn get_exits_from_single_edge(terrain: &[Terrain; 50], direction: ExitDirection) -> Vec<Self> {
-        let mut exits = Vec::new();
+        // Bit i is set if tile i of the edge is not a wall; unlike the compressed edge bytes, raw
+        // terrain data can have exits on the corner tiles
+        let open = terrain.iter().enumerate()
+            .filter(|(_, t)| **t != Terrain::Wall)
+            .fold(0u64, |bits, (i, _)| bits | (1 << i));
 
-        // These are how we track the current exit that we're processing;
-        // - length will always be non-zero if we're currently processing an exit, and gets reset to
-        //   0 once the exit is finalized and pushed onto the output vector
-        // - start can be any value from 0 to 49; on MMO it won't ever be 0 or 49, but if we're
-        //   using raw terrain data, it can happen
-        let mut current_exit_start = 0;
-        let mut current_exit_length = 0;
-
-        for i in 0..50 {
-            if terrain[i] == Terrain::Wall {
-                // If we've hit a wall, then if we were previously tracking an exit, it's done and
-                // we need to store it
-                if current_exit_length > 0 {
-                    let exit = Self::new(current_exit_start, current_exit_length, direction);
-                    exits.push(exit);
-                    current_exit_start = 0;
-                    current_exit_length = 0;
-                }
+        Self::get_exits_from_open_bits(open, direction)
+    }
 
-                continue;
-            }
+    /// Utility function that processes a compressed edge, as stored by [RoomEdgeTerrain], into a
+    /// list of exits.
+    ///
+    /// This scans runs of open tiles with bit intrinsics instead of decoding each tile, so it's the
+    /// fast path for scanning exits across many rooms. Returned vector can be empty if the edge is
+    /// entirely Walls, and thus has no exits.
+    pub fn get_exits_from_edge_bytes(bytes: &[u8; 6], direction: ExitDirection) -> Vec<Self> {
+        Self::get_exits_from_open_bits(Self::open_bits_from_edge_bytes(bytes), direction)
+    }
 
-            // At this point, we know we're on a exit, so we need to determine if we're on the
-            // first tile of the exit or not
-            let is_new_exit = {
-                if i == 0 {
-                    // If we're on the first tile, then it's guaranteed to be a new exit
-                    true
-                } else {
-                    // If the previous tile was a Wall, then this tile is the start of a new exit
-                    terrain[i-1] == Terrain::Wall
-                }
-            };
+    /// Calls `f` with each exit along a compressed edge, as stored by [RoomEdgeTerrain], without
+    /// allocating.
+    ///
+    /// Exits are visited in the same order as [get_exits_from_edge_bytes](RoomExit::get_exits_from_edge_bytes).
+    pub fn for_each_exit_in_edge_bytes(bytes: &[u8; 6], direction: ExitDirection, f: impl FnMut(Self)) {
+        Self::for_each_exit_in_open_bits(Self::open_bits_from_edge_bytes(bytes), direction, f);
+    }
 
-            // If we're on a new exit, adjust our tracking variables appropriately
-            if is_new_exit {
-                current_exit_start = i as u8;
-                current_exit_length = 1;
-            } else {
-                current_exit_length += 1;
-            }
-        }
+    /// Internal helper function to count the exits along a compressed edge without building them.
+    fn count_exits_in_edge_bytes(bytes: &[u8; 6]) -> usize {
+        let open = Self::open_bits_from_edge_bytes(bytes);
+        // Each exit starts at an open tile whose previous tile isn't open
+        (open & !(open << 1)).count_ones() as usize
+    }
 
-        // Catch a final exit that ends on the 50th tile; this won't happen on MMO, but it could
-        // happen theoretically with raw edge terrain.
-        if current_exit_length > 0 {
-            let exit = Self::new(current_exit_start, current_exit_length, direction);
-            exits.push(exit);
-        }
+    /// Internal helper function to convert a compressed edge into a bitmask of open edge tiles,
+    /// with tile i at bit i.
+    fn open_bits_from_edge_bytes(bytes: &[u8; 6]) -> u64 {
+        // The bytes hold tiles 1 through 48, MSB first, with set bits for walls. Reversing the bits
+        // puts tile 1 at bit 63 - 47 = 16, so shifting down by 15 puts tile i at bit i.
+        let walls = bytes.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64).reverse_bits() >> 15;
+        const EDGE_INTERIOR_MASK: u64 = ((1 << 48) - 1) << 1;
+
+        !walls & EDGE_INTERIOR_MASK
+    }
 
+    /// Internal helper function to convert a bitmask of open edge tiles, with tile i at bit i, into
+    /// a list of exits.
+    fn get_exits_from_open_bits(open: u64, direction: ExitDirection) -> Vec<Self> {
+        let mut exits = Vec::new();
+        Self::for_each_exit_in_open_bits(open, direction, |exit| exits.push(exit));
         exits
     }
+
+    /// Internal helper function to call `f` with each exit in a bitmask of open edge tiles, with
+    /// tile i at bit i.
+    fn for_each_exit_in_open_bits(mut open: u64, direction: ExitDirection, mut f: impl FnMut(Self)) {
+        while open != 0 {
+            let start = open.trailing_zeros();
+            let length = (!(open >> start)).trailing_zeros();
+
+            let exit = Self::new(start as u8, length as u8, direction);
+            debug_assert_eq!(Self::try_new_from_packed(exit.packed()), Ok(exit), "generated an invalid exit");
+            f(exit);
+
+            // Clear the run we just processed
+            open &= !(((1u64 << length) - 1) << start);
+        }
+    }
+}
+
+/// A summary of the exits along one edge of a room, for use as a connectivity edge weight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExitSummary {
+    /// The edge the exits are on.
+    pub direction: ExitDirection,
+    /// The number of separate exits along the edge.
+    pub num_exits: u8,
+    /// The total number of exit tiles along the edge.
+    pub total_width: u8,
+    /// The number of tiles in the widest exit along the edge.
+    pub widest_exit: u8,
 }
 
 /// Compactly stores information about all the exits in a room.
-#[derive(Debug, Clone, Copy)]
+///
+/// The edge terrain is the persistent form of the data; the exits along each edge are derived
+/// from its packed bytes the first time they're needed, and cached for later lookups.
+#[derive(Debug, Clone)]
 pub struct RoomExitsData {
     /// Unfortunately, there really isn't any way to store this better than just 24 raw bytes of
     /// compressed edge data.
     data: RoomEdgeTerrain,
+    /// The exits along each edge, in clockwise order starting from the top, once they've been read
+    edge_exits: [OnceLock<Vec<RoomExit>>; 4],
 
     room: RoomName,
     num_top_exits: usize,
@@ -201,14 +370,20 @@ pub struct RoomExitsData {
 }
 
 impl RoomExitsData {
+    /// Creates the exits data for a room directly from its full terrain.
+    pub fn new_from_compressed_terrain(terrain: &CompressedRoomTerrain, room: RoomName) -> Self {
+        Self::new_from_compressed_edge_terrain_data(RoomEdgeTerrain::new_from_compressed_terrain(terrain), room)
+    }
+
     pub fn new_from_compressed_edge_terrain_data(data: RoomEdgeTerrain, room: RoomName) -> Self {
-        let num_top_exits = RoomExit::get_exits_from_single_edge(&data.get_top_edge_terrain(), ExitDirection::Top).len();
-        let num_right_exits = RoomExit::get_exits_from_single_edge(&data.get_right_edge_terrain(), ExitDirection::Right).len();
-        let num_bottom_exits = RoomExit::get_exits_from_single_edge(&data.get_bottom_edge_terrain(), ExitDirection::Bottom).len();
-        let num_left_exits = RoomExit::get_exits_from_single_edge(&data.get_left_edge_terrain(), ExitDirection::Left).len();
+        let num_top_exits = RoomExit::count_exits_in_edge_bytes(data.get_edge_bytes(ExitDirection::Top));
+        let num_right_exits = RoomExit::count_exits_in_edge_bytes(data.get_edge_bytes(ExitDirection::Right));
+        let num_bottom_exits = RoomExit::count_exits_in_edge_bytes(data.get_edge_bytes(ExitDirection::Bottom));
+        let num_left_exits = RoomExit::count_exits_in_edge_bytes(data.get_edge_bytes(ExitDirection::Left));
 
         Self {
             data,
+            edge_exits: Default::default(),
             room,
             num_top_exits,
             num_right_exits,
@@ -222,24 +397,45 @@ impl RoomExitsData {
         self.data.memory_size()
     }
 
+    /// The exits, if any, along the specified edge of the room.
+    ///
+    /// The first call for each edge reads the exits straight from the packed edge bytes, without
+    /// expanding the edge terrain; later calls return the cached exits.
+    pub fn edge_exits(&self, direction: ExitDirection) -> &[RoomExit] {
+        let edge_index = EXIT_DIRECTIONS.iter().position(|d| *d == direction).unwrap_or(0);
+        self.edge_exits[edge_index].get_or_init(|| RoomExit::get_exits_from_edge_bytes(self.data.get_edge_bytes(direction), direction))
+    }
+
+    /// A bitmask of the exit tiles along the specified edge of the room, where bit `k` is set if
+    /// the tile at offset `k` along the edge is an exit tile.
+    ///
+    /// Offsets run left to right along the top and bottom edges, and top to bottom along the left
+    /// and right edges, matching [RoomExit::start]. Only bits 1 to 48 can be set, since the corners
+    /// are always walls. This answers per-tile and per-span questions without building any exits:
+    /// the number of exit tiles from offset `a` up to but not including `b` is
+    /// `(bits & ((1 << b) - (1 << a))).count_ones()`.
+    pub fn edge_exit_bits(&self, direction: ExitDirection) -> u64 {
+        RoomExit::open_bits_from_edge_bytes(self.data.get_edge_bytes(direction))
+    }
+
     /// The exits, if any, along the top edge of the room.
     pub fn top_edge_exits(&self) -> Vec<RoomExit> {
-        RoomExit::get_exits_from_single_edge(&self.data.get_top_edge_terrain(), ExitDirection::Top)
+        self.edge_exits(ExitDirection::Top).to_vec()
     }
 
     /// The exits, if any, along the right edge of the room.
     pub fn right_edge_exits(&self) -> Vec<RoomExit> {
-        RoomExit::get_exits_from_single_edge(&self.data.get_right_edge_terrain(), ExitDirection::Right)
+        self.edge_exits(ExitDirection::Right).to_vec()
     }
 
     /// The exits, if any, along the bottom edge of the room.
     pub fn bottom_edge_exits(&self) -> Vec<RoomExit> {
-        RoomExit::get_exits_from_single_edge(&self.data.get_bottom_edge_terrain(), ExitDirection::Bottom)
+        self.edge_exits(ExitDirection::Bottom).to_vec()
     }
 
     /// The exits, if any, along the left edge of the room.
     pub fn left_edge_exits(&self) -> Vec<RoomExit> {
-        RoomExit::get_exits_from_single_edge(&self.data.get_left_edge_terrain(), ExitDirection::Left)
+        self.edge_exits(ExitDirection::Left).to_vec()
     }
 
     /// The number of exits along the top edge of the room.
@@ -270,6 +466,18 @@ impl RoomExitsData {
         self.num_left_exits
     }
 
+    /// The number of exits along the specified edge of the room.
+    ///
+    /// This is more efficient than constructing all of the exits, if you just need the exit count.
+    pub fn num_exits_toward(&self, direction: ExitDirection) -> usize {
+        match direction {
+            ExitDirection::Top => self.num_top_exits,
+            ExitDirection::Right => self.num_right_exits,
+            ExitDirection::Bottom => self.num_bottom_exits,
+            ExitDirection::Left => self.num_left_exits,
+        }
+    }
+
     /// The total number of exits along all edges of the room.
     ///
     /// This is more efficient than constructing all of the exits, if you just need the exit count.
@@ -282,6 +490,27 @@ impl RoomExitsData {
         &self.data
     }
 
+    /// Serializes the exits data as the packed room name, as a little-endian u16, followed by the
+    /// 24 bytes of edge terrain. The exits themselves are recomputed when deserializing.
+    pub fn to_bytes(&self) -> [u8; 26] {
+        let mut bytes = [0; 26];
+        bytes[..2].copy_from_slice(&self.room.packed_repr().to_le_bytes());
+        bytes[2..].copy_from_slice(&self.data.to_bytes());
+        bytes
+    }
+
+    /// Deserializes exits data written by [to_bytes](RoomExitsData::to_bytes).
+    ///
+    /// Returns [MapProcessingError::InvalidLength] if the slice isn't 26 bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        if bytes.len() != 26 {
+            return Err(MapProcessingError::InvalidLength { kind: "exits data", expected: 26, found: bytes.len() });
+        }
+        let room = RoomName::from_packed(u16::from_le_bytes([bytes[0], bytes[1]]));
+        let data = RoomEdgeTerrain::from_bytes(&bytes[2..])?;
+        Ok(Self::new_from_compressed_edge_terrain_data(data, room))
+    }
+
     /// Returns true if the top edge has exits and has a neighbor to the north, false otherwise.
     ///
     /// This is more efficient than `self.top_edge_exits().len()` if you're just wanting
@@ -388,8 +617,8 @@ impl RoomExitsData {
                 //   extract the correct one
                 if self.num_top_exits > 0 {
                     if index <= max_idx_top {
-                        // Index is one of these exits, generate them and return the correct one
-                        let exits = self.top_edge_exits();
+                        // Index is one of these exits, get them and return the correct one
+                        let exits = self.edge_exits(ExitDirection::Top);
                         let local_index = index - min_idx_top;
                         return exits.get(local_index).copied();
                     }
@@ -400,8 +629,8 @@ impl RoomExitsData {
                 //   extract the correct one
                 if self.num_right_exits > 0 {
                     if index <= max_idx_right {
-                        // Index is one of these exits, generate them and return the correct one
-                        let exits = self.right_edge_exits();
+                        // Index is one of these exits, get them and return the correct one
+                        let exits = self.edge_exits(ExitDirection::Right);
                         let local_index = index - min_idx_right;
                         return exits.get(local_index).copied();
                     }
@@ -412,8 +641,8 @@ impl RoomExitsData {
                 //   extract the correct one
                 if self.num_bottom_exits > 0 {
                     if index <= max_idx_bottom {
-                        // Index is one of these exits, generate them and return the correct one
-                        let exits = self.bottom_edge_exits();
+                        // Index is one of these exits, get them and return the correct one
+                        let exits = self.edge_exits(ExitDirection::Bottom);
                         let local_index = index - min_idx_bottom;
                         return exits.get(local_index).copied();
                     }
@@ -425,8 +654,8 @@ impl RoomExitsData {
                 //   extract the correct one
                 if self.num_left_exits > 0 {
                     if index <= max_idx_left {
-                        // Index is one of these exits, generate them and return the correct one
-                        let exits = self.left_edge_exits();
+                        // Index is one of these exits, get them and return the correct one
+                        let exits = self.edge_exits(ExitDirection::Left);
                         let local_index = index - min_idx_left;
                         return exits.get(local_index).copied();
                     }
@@ -444,9 +673,108 @@ impl RoomExitsData {
         self.room
     }
 
+    /// The packed representations of every exit in the room, in the same order as
+    /// [iter](RoomExitsData::iter).
+    ///
+    /// See [from_packed_exits](RoomExitsData::from_packed_exits) to reload them.
+    pub fn packed_exits(&self) -> Vec<u16> {
+        let mut packed = Vec::with_capacity(self.num_exits());
+        self.for_each_exit(|exit| packed.push(exit.packed()));
+        packed
+    }
+
+    /// Rebuilds exits data for a room from a list of packed exits, as returned by
+    /// [packed_exits](RoomExitsData::packed_exits).
+    ///
+    /// Every exit is validated, and exits can't include the corner tiles of an edge, since those
+    /// are always walls. Only walls are tracked along edges, so this round trips exactly, but any
+    /// swamp exit tiles are reported as plains by [edge_terrain_data](RoomExitsData::edge_terrain_data).
+    pub fn from_packed_exits(room: RoomName, packed_exits: &[u16]) -> Result<Self, MapProcessingError> {
+        let mut edges = [[Terrain::Wall; 50]; 4];
+        for packed in packed_exits {
+            let exit = RoomExit::try_new_from_packed(*packed)?;
+            if exit.start() == 0 || exit.end() == 49 {
+                return Err(MapProcessingError::InvalidData { kind: "room exits", reason: format!("exit {packed:#06x} includes a corner tile") });
+            }
+
+            let edge_index = EXIT_DIRECTIONS.iter().position(|direction| *direction == exit.exit_direction()).unwrap_or(0);
+            edges[edge_index][exit.start() as usize..=exit.end() as usize].fill(Terrain::Plain);
+        }
+
+        let [top, right, bottom, left] = &edges;
+        let data = RoomEdgeTerrain::new_from_terrain_slices(top, right, bottom, left)?;
+        Ok(Self::new_from_compressed_edge_terrain_data(data, room))
+    }
+
+    /// Returns an iterator over all the exits in the room, in clockwise order by edge (top, right,
+    /// bottom, left), and then in LTR/top-to-bottom order along each edge.
+    ///
+    /// This is the same order as [get_exit_by_index](RoomExitsData::get_exit_by_index), but reads
+    /// each edge's exits once instead of looking up each index.
+    pub fn iter_exits(&self) -> impl Iterator<Item = RoomExit> + '_ {
+        EXIT_DIRECTIONS.into_iter().flat_map(|direction| self.edge_exits(direction).iter().copied())
+    }
+
     /// Returns an iterator over all the exits in the room.
     pub fn iter(&self) -> RoomExitsIter {
-        RoomExitsIter::new(*self)
+        RoomExitsIter::new(self.clone())
+    }
+
+    /// Calls `f` with every exit in the room, in the same order as [iter](RoomExitsData::iter),
+    /// without allocating.
+    pub fn for_each_exit(&self, mut f: impl FnMut(RoomExit)) {
+        for direction in [ExitDirection::Top, ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left] {
+            RoomExit::for_each_exit_in_edge_bytes(self.data.get_edge_bytes(direction), direction, &mut f);
+        }
+    }
+
+    /// The connectivity edges leading out of this room, as `(room, neighbor, summary)` tuples.
+    ///
+    /// There's at most one edge per direction, and edges toward rooms that can't exist (past the
+    /// edge of the world) are skipped. Edges only describe this room's side of each border; see
+    /// [ShardConnectivityGraph](crate::room_connectivity::graph::ShardConnectivityGraph) for
+    /// connectivity that accounts for both sides.
+    pub fn to_graph_edges(&self) -> Vec<(RoomName, RoomName, ExitSummary)> {
+        self.to_graph_edges_with_topology(MapTopology::Bounded)
+    }
+
+    /// The connectivity edges leading out of this room, with neighbors determined by the specified
+    /// map topology.
+    ///
+    /// See [to_graph_edges](RoomExitsData::to_graph_edges).
+    pub fn to_graph_edges_with_topology(&self, topology: MapTopology) -> Vec<(RoomName, RoomName, ExitSummary)> {
+        EXIT_DIRECTIONS.into_iter()
+            .filter_map(|direction| {
+                let neighbor = topology.neighbor_room(self.room, direction)?;
+
+                let mut summary = ExitSummary { direction, num_exits: 0, total_width: 0, widest_exit: 0 };
+                RoomExit::for_each_exit_in_edge_bytes(self.data.get_edge_bytes(direction), direction, |exit| {
+                    summary.num_exits += 1;
+                    summary.total_width += exit.len();
+                    summary.widest_exit = summary.widest_exit.max(exit.len());
+                });
+
+                (summary.num_exits > 0).then_some((self.room, neighbor, summary))
+            })
+            .collect()
+    }
+
+    /// The fraction of swamp tiles just inside the exits along each edge, in clockwise order
+    /// starting from the top, weighted by exit width; see [RoomExit::swamp_fraction].
+    ///
+    /// Edges without exits have a fraction of 0. `terrain` must be the full terrain of this room.
+    pub fn border_swamp_fractions(&self, terrain: &impl TerrainSource) -> [f32; 4] {
+        EXIT_DIRECTIONS.map(|direction| {
+            let (swamps, total) = self.edge_exits(direction).iter()
+                .map(|exit| exit.inner_swamp_counts(terrain))
+                .fold((0, 0), |(swamps, total), counts| (swamps + counts.0, total + counts.1));
+            if total == 0 { 0.0 } else { swamps as f32 / total as f32 }
+        })
+    }
+
+    /// Calls `f` with the position of every exit tile in the room, without allocating.
+    pub fn for_each_exit_tile(&self, mut f: impl FnMut(RoomXY)) {
+        self.for_each_exit(|exit| exit.for_each_tile(&mut f));
     }
 }
 
@@ -458,10 +786,11 @@ pub struct RoomExitsIter {
 
 impl RoomExitsIter {
     fn new(data: RoomExitsData) -> Self {
+        let length = data.num_exits();
         Self {
             data,
             current_index: 0,
-            length: data.num_exits(),
+            length,
         }
     }
 }
@@ -500,6 +829,109 @@ pub fn left_room(room: RoomName) -> Option<RoomName> {
     room.checked_add((-1, 0))
 }
 
+/// Utility function to convert an offset along a room edge into the corresponding tile.
+///
+/// Offsets are in the same LTR/top-to-bottom order used by [RoomExit::start]; offsets greater than
+/// 49 are clamped to 49.
+pub fn edge_offset_to_xy(direction: ExitDirection, offset: u8) -> RoomXY {
+    let offset = offset.min(49);
+    // Safety: offset is clamped to 49 above, and 0 and 49 are both valid coordinates
+    unsafe {
+        match direction {
+            ExitDirection::Top => RoomXY::unchecked_new(offset, 0),
+            ExitDirection::Right => RoomXY::unchecked_new(49, offset),
+            ExitDirection::Bottom => RoomXY::unchecked_new(offset, 49),
+            ExitDirection::Left => RoomXY::unchecked_new(0, offset),
+        }
+    }
+}
+
+/// The cost matrix value for tiles that can't be walked on.
+const IMPASSABLE_COST: u8 = 255;
+
+/// The exits of a room that remain usable when the tiles marked impassable (255) in a cost matrix
+/// are blocked, such as by enemy ramparts on border tiles.
+///
+/// Exits are trimmed or split around blocked tiles, and exits that are fully blocked are removed.
+/// The result can be added to a [ShardConnectivityGraph](crate::room_connectivity::graph::ShardConnectivityGraph)
+/// in place of the room's terrain exits to route around the restrictions.
+pub fn restricted_exits(exits: &RoomExitsData, cost_matrix: &LocalCostMatrix) -> RoomExitsData {
+    let edges = exits.edge_terrain_data();
+    let restrict = |direction: ExitDirection, mut edge: [Terrain; 50]| {
+        for (offset, tile) in edge.iter_mut().enumerate() {
+            if cost_matrix.get(edge_offset_to_xy(direction, offset as u8)) == IMPASSABLE_COST {
+                *tile = Terrain::Wall;
+            }
+        }
+        edge
+    };
+
+    let top = restrict(ExitDirection::Top, edges.get_top_edge_terrain());
+    let right = restrict(ExitDirection::Right, edges.get_right_edge_terrain());
+    let bottom = restrict(ExitDirection::Bottom, edges.get_bottom_edge_terrain());
+    let left = restrict(ExitDirection::Left, edges.get_left_edge_terrain());
+
+    let restricted = RoomEdgeTerrain::new_from_terrain_slices(&top, &right, &bottom, &left).expect("edges should always be length 50");
+    RoomExitsData::new_from_compressed_edge_terrain_data(restricted, exits.room())
+}
+
+/// The exits that actually connect a room to its neighbor in the specified direction, as pairs of
+/// the room's exit and the matching exit on the neighbor's opposite edge.
+///
+/// Each pair covers the tiles that are open on both sides of the border, so exits in one room that
+/// lead into walls in the other are trimmed or dropped.
+pub fn paired_exits(edges: &RoomEdgeTerrain, neighbor_edges: &RoomEdgeTerrain, direction: ExitDirection) -> Vec<(RoomExit, RoomExit)> {
+    let opposite = opposite_exit_direction(direction);
+    let open = RoomExit::open_bits_from_edge_bytes(edges.get_edge_bytes(direction))
+        & RoomExit::open_bits_from_edge_bytes(neighbor_edges.get_edge_bytes(opposite));
+
+    RoomExit::get_exits_from_open_bits(open, direction)
+        .into_iter()
+        .map(|exit| (exit, RoomExit::new(exit.start(), exit.len(), opposite)))
+        .collect()
+}
+
+/// Utility function to return the edge of `room` that leads into `neighbor`, if the two rooms are
+/// adjacent.
+pub fn exit_direction_to_neighbor(room: RoomName, neighbor: RoomName) -> Option<ExitDirection> {
+    match (neighbor.x_coord() - room.x_coord(), neighbor.y_coord() - room.y_coord()) {
+        (0, -1) => Some(ExitDirection::Top),
+        (1, 0) => Some(ExitDirection::Right),
+        (0, 1) => Some(ExitDirection::Bottom),
+        (-1, 0) => Some(ExitDirection::Left),
+        _ => None,
+    }
+}
+
+/// The exit tiles of `room` that lead into `toward`, like `Room.find(FIND_EXIT_*)` in game.
+///
+/// Tiles are ordered the same way as the exits along the edge. Returns an empty list if the rooms
+/// aren't adjacent, the edge has no exits, or `exits` isn't the exits data for `room`.
+pub fn exit_positions_toward(room: RoomName, toward: RoomName, exits: &RoomExitsData) -> Vec<RoomXY> {
+    let Some(direction) = exit_direction_to_neighbor(room, toward).filter(|_| exits.room() == room) else {
+        return Vec::new();
+    };
+    exits.edge_exits(direction).iter().flat_map(RoomExit::tiles).collect()
+}
+
+impl From<&OfflineRoomData> for RoomExitsData {
+    fn from(room: &OfflineRoomData) -> Self {
+        Self::new_from_compressed_edge_terrain_data(RoomEdgeTerrain::from(room), room.room_name)
+    }
+}
+
+/// Utility function to return the edge directly across the border from the given edge.
+///
+/// For example, exiting a room via its top edge enters the neighboring room via its bottom edge.
+pub fn opposite_exit_direction(direction: ExitDirection) -> ExitDirection {
+    match direction {
+        ExitDirection::Top => ExitDirection::Bottom,
+        ExitDirection::Right => ExitDirection::Left,
+        ExitDirection::Bottom => ExitDirection::Top,
+        ExitDirection::Left => ExitDirection::Right,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -528,6 +960,65 @@ mod test {
         }
     }
 
+    #[test]
+    pub fn room_exit_try_new_rejects_degenerate_exits() {
+        assert_eq!(RoomExit::try_new(10, 0, ExitDirection::Top), Err(RoomExitValidationError::ZeroLength));
+        assert_eq!(RoomExit::try_new(49, 2, ExitDirection::Left), Err(RoomExitValidationError::ExceedsEdge));
+        assert_eq!(RoomExit::try_new(63, 63, ExitDirection::Left), Err(RoomExitValidationError::ExceedsEdge));
+        assert_eq!(RoomExit::try_new(0, 50, ExitDirection::Right), Ok(RoomExit::new(0, 50, ExitDirection::Right)));
+
+        let valid = RoomExit::new(5, 10, ExitDirection::Bottom);
+        assert_eq!(RoomExit::try_new_from_packed(valid.packed()), Ok(valid));
+        assert_eq!(RoomExit::try_new_from_packed(valid.packed() & RoomExit::EXIT_DIRECTION_INVERTED_BITMASK), Err(RoomExitValidationError::InvalidDirection));
+        assert_eq!(RoomExit::try_new_from_packed(5), Err(RoomExitValidationError::InvalidDirection));
+        assert_eq!(RoomExit::try_new_from_packed(1 << RoomExit::EXIT_DIRECTION_OFFSET | 5), Err(RoomExitValidationError::ZeroLength));
+    }
+
+    #[test]
+    #[cfg(feature = "game-types")]
+    pub fn room_exit_to_positions_uses_edge_tiles_of_room() {
+        let room = RoomName::new("W5N5").unwrap();
+        let positions = RoomExit::new(10, 3, ExitDirection::Right).to_positions(room);
+
+        let expected: Vec<(u8, u8)> = vec![(49, 10), (49, 11), (49, 12)];
+        assert_eq!(positions.iter().map(|p| (p.x().u8(), p.y().u8())).collect::<Vec<_>>(), expected);
+        assert!(positions.iter().all(|p| p.room_name() == room));
+    }
+
+    #[test]
+    pub fn room_exit_get_exits_from_edge_bytes_matches_terrain_scan() {
+        let mut patterns: Vec<[u8; 6]> = vec![[0; 6], [0xFF; 6], [0x80, 0, 0, 0, 0, 0x01], [0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE]];
+        for byte in 0..=255u8 {
+            patterns.push([byte, !byte, byte, byte.rotate_left(3), 0, byte]);
+        }
+
+        for bytes in patterns {
+            let terrain = RoomEdgeTerrain::get_edge_terrain_from_bytes(&bytes);
+            assert_eq!(
+                RoomExit::get_exits_from_edge_bytes(&bytes, ExitDirection::Left),
+                RoomExit::get_exits_from_single_edge(&terrain, ExitDirection::Left),
+                "Exits differ for {bytes:?}",
+            );
+        }
+    }
+
+    #[test]
+    pub fn room_exit_get_all_exits_matches_exit_indices() {
+        let mut edge = [Terrain::Wall; 50];
+        edge[5..10].fill(Terrain::Plain);
+        edge[20..22].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&edge, &wall_edge, &edge, &edge).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        let exits = RoomExit::get_all_exits(&terrain);
+        assert_eq!(exits.len(), 6);
+        for (i, exit) in exits.iter().enumerate() {
+            assert_eq!(exits_data.get_exit_by_index(i), Some(*exit));
+        }
+        assert_eq!(exits[2], RoomExit::new(5, 5, ExitDirection::Bottom));
+    }
+
     #[test]
     pub fn room_exit_new_from_packed_matches_original_data() {
         let directions = [ExitDirection::Top, ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left];
@@ -972,4 +1463,334 @@ mod test {
         assert_eq!(exit.len(), 48, "Exit length invalid");
         assert_eq!(exit.exit_direction(), ExitDirection::Left, "Exit direction invalid");
     }
+
+    #[test]
+    pub fn room_exits_data_from_offline_room_data_matches_terrain() {
+        use screeps::{LocalRoomTerrain, ROOM_AREA};
+        use screeps::game::map::RoomStatus;
+        use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+        // Walls everywhere except for a 3 tile gap in the middle of the top edge
+        let mut bits = Box::new([Terrain::Wall as u8; ROOM_AREA]);
+        bits[24..27].fill(Terrain::Plain as u8);
+        let room = OfflineRoomData {
+            room_name: RoomName::new("W1N1").unwrap(),
+            status: RoomStatus::Normal,
+            bus: false,
+            terrain: LocalRoomTerrain::new_from_bits(bits),
+            objects: Vec::new(),
+        };
+
+        let terrain = CompressedRoomTerrain::from(&room);
+        assert_eq!(terrain.get_xy(RoomXY::checked_new(25, 0).unwrap()), Terrain::Plain);
+        assert_eq!(terrain.get_xy(RoomXY::checked_new(25, 1).unwrap()), Terrain::Wall);
+
+        let edge_terrain = RoomEdgeTerrain::from(&room);
+        let expected_edge_terrain = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        for direction in [ExitDirection::Top, ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left] {
+            assert_eq!(edge_terrain.get_edge_bytes(direction), expected_edge_terrain.get_edge_bytes(direction));
+        }
+
+        let exits_data = RoomExitsData::from(&room);
+        assert_eq!(exits_data.room(), room.room_name);
+        assert_eq!(exits_data.num_exits(), 1);
+        assert_eq!(exits_data.top_edge_exits(), vec![RoomExit::new(24, 3, ExitDirection::Top)]);
+    }
+
+    #[test]
+    pub fn exit_positions_toward_lists_edge_tiles() {
+        let mut edge = [Terrain::Wall; 50];
+        edge[10..13].fill(Terrain::Plain);
+        edge[20] = Terrain::Plain;
+        let closed = [Terrain::Wall; 50];
+        let edge_terrain = RoomEdgeTerrain::new_from_terrain_slices(&closed, &edge, &closed, &closed).unwrap();
+        let room = RoomName::new("W2N2").unwrap();
+        let exits = RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, room);
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+
+        // W1N2 is east of W2N2
+        assert_eq!(exit_positions_toward(room, RoomName::new("W1N2").unwrap(), &exits), vec![xy(49, 10), xy(49, 11), xy(49, 12), xy(49, 20)]);
+        assert!(exit_positions_toward(room, RoomName::new("W2N3").unwrap(), &exits).is_empty());
+        assert!(exit_positions_toward(room, RoomName::new("W5N5").unwrap(), &exits).is_empty());
+        assert!(exit_positions_toward(RoomName::new("W3N2").unwrap(), room, &exits).is_empty());
+    }
+
+    #[test]
+    pub fn room_exits_data_round_trips_through_bytes() {
+        let mut edge = [Terrain::Wall; 50];
+        edge[10..20].fill(Terrain::Plain);
+        let edge_terrain = RoomEdgeTerrain::new_from_terrain_slices(&edge, &edge, &[Terrain::Wall; 50], &edge).unwrap();
+        assert_eq!(RoomEdgeTerrain::from_bytes(&edge_terrain.to_bytes()).unwrap().get_raw_bytes(), edge_terrain.get_raw_bytes());
+
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, RoomName::new("E12S34").unwrap());
+        let decoded = RoomExitsData::from_bytes(&exits_data.to_bytes()).unwrap();
+        assert_eq!(decoded.room(), exits_data.room());
+        assert_eq!(decoded.iter_exits().collect::<Vec<_>>(), exits_data.iter_exits().collect::<Vec<_>>());
+        assert_eq!(decoded.num_exits(), 3);
+
+        assert!(RoomExitsData::from_bytes(&exits_data.to_bytes()[..25]).is_err());
+        assert!(RoomEdgeTerrain::from_bytes(&[0; 23]).is_err());
+    }
+
+    #[test]
+    pub fn room_exits_data_new_from_compressed_terrain_matches_edge_pipeline() {
+        use screeps::ROOM_AREA;
+
+        let mut bits = [Terrain::Wall as u8; ROOM_AREA];
+        bits[10..20].fill(Terrain::Plain as u8);
+        bits[49 * 50 + 30] = Terrain::Swamp as u8;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W1N1").unwrap();
+
+        let direct = RoomExitsData::new_from_compressed_terrain(&terrain, room);
+        let edges = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        let expected = RoomExitsData::new_from_compressed_edge_terrain_data(edges, room);
+        assert_eq!(direct.iter_exits().collect::<Vec<_>>(), expected.iter_exits().collect::<Vec<_>>());
+        assert_eq!(direct.num_exits(), 2);
+        assert_eq!(RoomEdgeTerrain::new_from_compressed_terrain(&terrain).get_edge_bytes(ExitDirection::Bottom), edges.get_edge_bytes(ExitDirection::Bottom));
+    }
+
+    #[test]
+    pub fn room_exits_data_for_each_exit_tile_visits_every_exit_tile() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        top[10] = Terrain::Swamp;
+        let mut left = [Terrain::Wall; 50];
+        left[40..42].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &wall_edge, &wall_edge, &left).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        let mut exits = Vec::new();
+        exits_data.for_each_exit(|exit| exits.push(exit));
+        assert_eq!(exits, exits_data.iter().collect::<Vec<_>>());
+
+        let mut tiles = Vec::new();
+        exits_data.for_each_exit_tile(|xy| tiles.push((xy.x.u8(), xy.y.u8())));
+        assert_eq!(tiles, [(3, 0), (4, 0), (5, 0), (10, 0), (0, 40), (0, 41)]);
+    }
+
+    #[test]
+    pub fn room_exits_data_to_graph_edges_summarizes_each_direction() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        top[10..20].fill(Terrain::Plain);
+        let open_edge = [Terrain::Plain; 50];
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &wall_edge, &wall_edge, &open_edge).unwrap();
+
+        let room = RoomName::new("W1N1").unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, room);
+        assert_eq!(exits_data.to_graph_edges(), vec![
+            (room, RoomName::new("W1N2").unwrap(), ExitSummary { direction: ExitDirection::Top, num_exits: 2, total_width: 13, widest_exit: 10 }),
+            (room, RoomName::new("W2N1").unwrap(), ExitSummary { direction: ExitDirection::Left, num_exits: 1, total_width: 48, widest_exit: 48 }),
+        ]);
+    }
+
+    #[test]
+    pub fn room_exits_data_packed_exits_round_trip() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        top[10] = Terrain::Swamp;
+        let mut right = [Terrain::Wall; 50];
+        right[1..49].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &right, &wall_edge, &wall_edge).unwrap();
+        let room = RoomName::new("W1N1").unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, room);
+
+        let packed = exits_data.packed_exits();
+        assert_eq!(packed.len(), 3);
+
+        let reloaded = RoomExitsData::from_packed_exits(room, &packed).unwrap();
+        assert_eq!(reloaded.room(), room);
+        assert_eq!(reloaded.packed_exits(), packed);
+        assert_eq!(reloaded.edge_terrain_data().get_raw_bytes(), terrain.get_raw_bytes());
+
+        let corner_exit = RoomExit::new(0, 3, ExitDirection::Left).packed();
+        assert!(matches!(RoomExitsData::from_packed_exits(room, &[corner_exit]), Err(MapProcessingError::InvalidData { .. })));
+        assert!(matches!(RoomExitsData::from_packed_exits(room, &[0]), Err(MapProcessingError::RoomExit(_))));
+    }
+
+    #[test]
+    pub fn room_exits_data_edge_exit_bits_match_exits() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        top[48] = Terrain::Plain;
+        let mut right = [Terrain::Wall; 50];
+        right[1..49].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &right, &wall_edge, &wall_edge).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        assert_eq!(exits_data.edge_exit_bits(ExitDirection::Top), 0b111000 | (1 << 48));
+        assert_eq!(exits_data.edge_exit_bits(ExitDirection::Right), ((1 << 48) - 1) << 1);
+        assert_eq!(exits_data.edge_exit_bits(ExitDirection::Bottom), 0);
+
+        for direction in [ExitDirection::Top, ExitDirection::Right] {
+            let bits = exits_data.edge_exit_bits(direction);
+            let width: u32 = exits_data.edge_exits(direction).iter().map(|exit| exit.len() as u32).sum();
+            assert_eq!(bits.count_ones(), width);
+        }
+        let span = |a: u32, b: u32| (1u64 << b) - (1u64 << a);
+        assert_eq!((exits_data.edge_exit_bits(ExitDirection::Top) & span(4, 10)).count_ones(), 2);
+    }
+
+    #[test]
+    pub fn restricted_exits_split_and_remove_blocked_exits() {
+        let mut top = [Terrain::Wall; 50];
+        top[10..20].fill(Terrain::Plain);
+        let mut left = [Terrain::Wall; 50];
+        left[30..33].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &wall_edge, &wall_edge, &left).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        let mut cost_matrix = LocalCostMatrix::new();
+        cost_matrix.set(RoomXY::checked_new(14, 0).unwrap(), 255);
+        cost_matrix.set(RoomXY::checked_new(15, 0).unwrap(), 200);
+        for y in 30..33 {
+            cost_matrix.set(RoomXY::checked_new(0, y).unwrap(), 255);
+        }
+
+        let restricted = restricted_exits(&exits_data, &cost_matrix);
+        assert_eq!(restricted.room(), exits_data.room());
+        assert_eq!(restricted.iter().collect::<Vec<_>>(), vec![
+            RoomExit::new(10, 4, ExitDirection::Top),
+            RoomExit::new(15, 5, ExitDirection::Top),
+        ]);
+        assert!(!restricted.connected_to_left_neighbor());
+    }
+
+    #[test]
+    pub fn paired_exits_intersects_both_sides_of_a_border() {
+        let mut left_room = [Terrain::Wall; 50];
+        let mut right_room = [Terrain::Wall; 50];
+        left_room[5..15].fill(Terrain::Plain);
+        left_room[30..35].fill(Terrain::Swamp);
+        right_room[10..20].fill(Terrain::Plain);
+        let walls = [Terrain::Wall; 50];
+        let edges = RoomEdgeTerrain::new_from_terrain_slices(&walls, &left_room, &walls, &walls).unwrap();
+        let neighbor_edges = RoomEdgeTerrain::new_from_terrain_slices(&walls, &walls, &walls, &right_room).unwrap();
+
+        assert_eq!(paired_exits(&edges, &neighbor_edges, ExitDirection::Right), vec![
+            (RoomExit::new(10, 5, ExitDirection::Right), RoomExit::new(10, 5, ExitDirection::Left)),
+        ]);
+        assert_eq!(paired_exits(&edges, &neighbor_edges, ExitDirection::Top), vec![]);
+    }
+
+    #[test]
+    pub fn room_exit_tiles_pair_with_neighbor_entry_tiles() {
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        let exit = RoomExit::new(10, 3, ExitDirection::Right);
+        assert_eq!(exit.tiles().collect::<Vec<_>>(), vec![xy(49, 10), xy(49, 11), xy(49, 12)]);
+
+        let neighbor = RoomName::new("E2N1").unwrap();
+        let entries: Vec<_> = exit.neighbor_entry_tiles(RoomName::new("E1N1").unwrap()).collect();
+        assert_eq!(entries, vec![(neighbor, xy(0, 10)), (neighbor, xy(0, 11)), (neighbor, xy(0, 12))]);
+
+        let edge_of_world = RoomExit::new(5, 2, ExitDirection::Top);
+        assert_eq!(edge_of_world.neighbor_entry_tiles(RoomName::new("W0N127").unwrap()).count(), 0);
+    }
+
+    #[test]
+    pub fn room_exits_data_derives_edge_exits_from_edge_bytes() {
+        fn assert_clone_and_sync<T: Clone + Sync>() {}
+        assert_clone_and_sync::<RoomExitsData>();
+
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        top[20..30].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &wall_edge, &wall_edge, &wall_edge).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        let first = exits_data.top_edge_exits();
+        assert_eq!(first, [RoomExit::new(3, 3, ExitDirection::Top), RoomExit::new(20, 10, ExitDirection::Top)]);
+        assert_eq!(first, exits_data.edge_exits(ExitDirection::Top));
+        assert_eq!(exits_data.get_exit_by_index(1), Some(first[1]));
+        assert!(exits_data.left_edge_exits().is_empty());
+        assert_eq!(exits_data.iter().count(), 2);
+    }
+
+    #[test]
+    pub fn room_exit_count_exits_in_edge_bytes_matches_exit_list() {
+        let mut bytes = [0u8; 6];
+        for seed in 0..2000u64 {
+            let value = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 16;
+            bytes.copy_from_slice(&value.to_be_bytes()[2..]);
+            if seed % 7 == 0 {
+                bytes = [0xFF; 6];
+                bytes[(seed % 6) as usize] = 0x18;
+            }
+            let exits = RoomExit::get_exits_from_edge_bytes(&bytes, ExitDirection::Top);
+            assert_eq!(RoomExit::count_exits_in_edge_bytes(&bytes), exits.len(), "{bytes:?}");
+        }
+        assert_eq!(RoomExit::count_exits_in_edge_bytes(&[0; 6]), 1);
+        assert_eq!(RoomExit::count_exits_in_edge_bytes(&[0xFF; 6]), 0);
+        assert_eq!(RoomExit::count_exits_in_edge_bytes(&[0x55; 6]), 24);
+    }
+
+    #[test]
+    pub fn room_exits_data_caches_edge_exits_on_first_use() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &wall_edge, &wall_edge, &wall_edge).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+        assert!(exits_data.edge_exits.iter().all(|cache| cache.get().is_none()));
+
+        assert_eq!(exits_data.get_exit_by_index(0), Some(RoomExit::new(3, 3, ExitDirection::Top)));
+        assert!(exits_data.edge_exits[0].get().is_some());
+
+        // Later lookups, including through clones, reuse the cached exits instead of recomputing them
+        let cached = exits_data.edge_exits(ExitDirection::Top).as_ptr();
+        assert_eq!(exits_data.edge_exits(ExitDirection::Top).as_ptr(), cached);
+        assert_eq!(exits_data.iter().count(), 1);
+        assert_eq!(exits_data.edge_exits(ExitDirection::Top).as_ptr(), cached);
+        assert!(exits_data.clone().edge_exits[0].get().is_some());
+    }
+
+    #[test]
+    pub fn room_exits_data_iter_exits_matches_get_exit_by_index() {
+        let mut top = [Terrain::Wall; 50];
+        top[3..6].fill(Terrain::Plain);
+        let mut right = [Terrain::Wall; 50];
+        right[10..12].fill(Terrain::Plain);
+        right[20..22].fill(Terrain::Plain);
+        let mut left = [Terrain::Wall; 50];
+        left[40..45].fill(Terrain::Plain);
+        let wall_edge = [Terrain::Wall; 50];
+        let terrain = RoomEdgeTerrain::new_from_terrain_slices(&top, &right, &wall_edge, &left).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new("W1N1").unwrap());
+
+        let exits: Vec<_> = exits_data.iter_exits().collect();
+        assert_eq!(exits.len(), exits_data.num_exits());
+        assert_eq!(exits, (0..exits.len()).filter_map(|idx| exits_data.get_exit_by_index(idx)).collect::<Vec<_>>());
+        assert_eq!(exits_data.num_exits_toward(ExitDirection::Right), 2);
+        assert_eq!(exits_data.num_exits_toward(ExitDirection::Bottom), 0);
+    }
+
+    #[test]
+    pub fn room_exit_swamp_fraction_uses_inner_tiles() {
+        use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+        let xy = |x, y| RoomXY::checked_new(x, y).unwrap();
+        // A swampy strip inside the top edge from x = 10 to 14, and a swamp on the top edge itself
+        let mut tiles: Vec<_> = (10..15).map(|x| (xy(x, 1), Terrain::Swamp)).collect();
+        tiles.push((xy(20, 0), Terrain::Swamp));
+        tiles.push((xy(0, 0), Terrain::Wall));
+        tiles.push((xy(49, 0), Terrain::Wall));
+        let terrain = CompressedRoomTerrain::from_sparse(Terrain::Plain, tiles);
+
+        assert_eq!(RoomExit::new(10, 5, ExitDirection::Top).swamp_fraction(&terrain), 0.5);
+        assert_eq!(RoomExit::new(20, 5, ExitDirection::Top).swamp_fraction(&terrain), 0.0);
+        assert_eq!(RoomExit::new(10, 5, ExitDirection::Left).swamp_fraction(&terrain), 0.0);
+
+        let edges = RoomEdgeTerrain::new_from_compressed_room_terrain(&terrain);
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(edges, RoomName::new("W1N1").unwrap());
+        let fractions = exits_data.border_swamp_fractions(&terrain);
+        assert_eq!(fractions[0], 5.0 / 96.0);
+        assert_eq!(fractions[1], 0.0);
+    }
 }
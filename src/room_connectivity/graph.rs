@@ -0,0 +1,598 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+#[cfg(feature = "db")]
+use std::ops::ControlFlow;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::{ExitDirection, RoomName};
+
+#[cfg(feature = "db")]
+use crate::analysis::cache::EXIT_REACHABILITY_ANALYSIS;
+use crate::analysis::exit_reachability::ExitReachability;
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+use crate::error::MapProcessingError;
+use crate::terrain_source::TerrainSource;
+use crate::room_connectivity::portal::{PortalMetadata, parse_portal_edges_json};
+use crate::room_connectivity::exit::{RoomExit, RoomExitsData, paired_exits, top_room, right_room, bottom_room, left_room};
+
+/// The four exit directions, in clockwise order starting from the top.
+pub const EXIT_DIRECTIONS: [ExitDirection; 4] = [ExitDirection::Top, ExitDirection::Right, ExitDirection::Bottom, ExitDirection::Left];
+
+/// Connectivity between the rooms of a shard, built from the exits of each room.
+///
+/// Two rooms are connected if they're adjacent, and both have exits on their shared border.
+/// Adjacency follows the graph's [MapTopology], which is bounded unless specified otherwise.
+///
+/// Portal edges can also be added, to connect rooms that aren't adjacent. They're kept separate
+/// from the terrain connectivity, and used by the [router](crate::room_connectivity::router).
+#[derive(Debug, Clone, Default)]
+pub struct ShardConnectivityGraph {
+    rooms: HashMap<RoomName, RoomExitsData>,
+    topology: MapTopology,
+    /// Source room -> (destination room, metadata) for each portal edge
+    portals: HashMap<RoomName, Vec<(RoomName, PortalMetadata)>>,
+    /// Room -> swamp fraction inside the exits of each edge, for rooms added with their terrain
+    border_swamp: HashMap<RoomName, [f32; 4]>,
+    /// Room -> danger score, for rooms that have been rated
+    danger: HashMap<RoomName, f32>,
+    /// Room -> which of its exits can reach each other, for rooms whose reachability is known
+    exit_reachability: HashMap<RoomName, ExitReachability>,
+}
+
+impl ShardConnectivityGraph {
+    /// Creates an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty graph for a map with the specified topology.
+    pub fn with_topology(topology: MapTopology) -> Self {
+        Self { topology, ..Self::default() }
+    }
+
+    /// Creates a graph of every room with terrain in the database.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    #[cfg(feature = "db")]
+    pub fn new_from_db(conn: &Connection) -> Result<Self, MapProcessingError> {
+        let mut graph = Self::new();
+        let failed = compressed_terrain_db::iter_room_terrains(conn, |room, terrain| {
+            match terrain {
+                Ok(terrain) => graph.add_room(RoomExitsData::new_from_compressed_terrain(&terrain, room)),
+                Err(e) => return ControlFlow::Break(e),
+            }
+            ControlFlow::Continue(())
+        })?;
+        if let Some(e) = failed {
+            return Err(e);
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(rooms = graph.rooms().count(), "built connectivity graph");
+
+        Ok(graph)
+    }
+
+    /// The topology of the map, which determines which rooms are adjacent.
+    pub fn topology(&self) -> MapTopology {
+        self.topology
+    }
+
+    /// Adds a room to the graph, replacing any existing exits data for it.
+    ///
+    /// Any border annotations from [add_room_with_terrain](ShardConnectivityGraph::add_room_with_terrain)
+    /// are cleared, since they were computed for the replaced room.
+    pub fn add_room(&mut self, exits: RoomExitsData) {
+        self.insert_room(exits, None);
+    }
+
+    /// Adds a room to the graph along with its full terrain, which is used to annotate each border
+    /// with how swampy it is to cross; see [border_swamp_fraction](ShardConnectivityGraph::border_swamp_fraction).
+    pub fn add_room_with_terrain(&mut self, exits: RoomExitsData, terrain: &impl TerrainSource) {
+        let fractions = exits.border_swamp_fractions(terrain);
+        self.insert_room(exits, Some(fractions));
+    }
+
+    /// Internal helper function to add a room along with its border annotations, if it has any.
+    pub(crate) fn insert_room(&mut self, exits: RoomExitsData, border_swamp: Option<[f32; 4]>) {
+        match border_swamp {
+            Some(fractions) => self.border_swamp.insert(exits.room(), fractions),
+            None => self.border_swamp.remove(&exits.room()),
+        };
+        self.rooms.insert(exits.room(), exits);
+    }
+
+    /// Removes a room from the graph, returning its exits data if it was present.
+    pub fn remove_room(&mut self, room: RoomName) -> Option<RoomExitsData> {
+        self.border_swamp.remove(&room);
+        self.danger.remove(&room);
+        self.exit_reachability.remove(&room);
+        self.rooms.remove(&room)
+    }
+
+    /// The fraction of swamp tiles just inside the exits along an edge of a room, from 0 to 1; see
+    /// [RoomExit::swamp_fraction](crate::room_connectivity::exit::RoomExit::swamp_fraction).
+    ///
+    /// This is 0 for rooms that weren't added with [add_room_with_terrain](ShardConnectivityGraph::add_room_with_terrain).
+    pub fn border_swamp_fraction(&self, room: RoomName, direction: ExitDirection) -> f32 {
+        let Some(fractions) = self.border_swamp.get(&room) else {
+            return 0.0;
+        };
+        match direction {
+            ExitDirection::Top => fractions[0],
+            ExitDirection::Right => fractions[1],
+            ExitDirection::Bottom => fractions[2],
+            ExitDirection::Left => fractions[3],
+        }
+    }
+
+    /// Sets the danger score of a room, such as one computed by
+    /// [danger_scores](crate::analysis::danger::danger_scores).
+    ///
+    /// The room doesn't need to be in the graph yet.
+    pub fn set_room_danger(&mut self, room: RoomName, score: f32) {
+        self.danger.insert(room, score);
+    }
+
+    /// The danger score of a room, or 0 if it hasn't been rated.
+    pub fn room_danger(&self, room: RoomName) -> f32 {
+        self.danger.get(&room).copied().unwrap_or(0.0)
+    }
+
+    /// Sets which of a room's exits can reach each other within the room, such as the result of
+    /// [ExitReachability::new_from_compressed_terrain].
+    ///
+    /// The room doesn't need to be in the graph yet, but the reachability is ignored unless it has
+    /// the same number of exits as the room's exits data.
+    pub fn set_exit_reachability(&mut self, room: RoomName, reachability: ExitReachability) {
+        self.exit_reachability.insert(room, reachability);
+    }
+
+    /// Loads the exit reachability of every room in the graph that has it stored in the
+    /// `analysis_blob` table, as written by [AnalysisCache](crate::analysis::cache::AnalysisCache)
+    /// or [analyze_shard](crate::analysis::shard::analyze_shard), returning the number of rooms
+    /// loaded.
+    ///
+    /// Stored results are matched to rooms by their current terrain fingerprint, so results for
+    /// outdated terrain are never loaded.
+    #[cfg(feature = "db")]
+    pub fn load_exit_reachability(&mut self, conn: &Connection) -> Result<usize, MapProcessingError> {
+        let fingerprints = compressed_terrain_db::get_room_fingerprints(conn)?;
+        let mut loaded = 0;
+
+        for (room, fingerprint) in fingerprints {
+            if !self.contains_room(room) {
+                continue;
+            }
+            if let Some(bytes) = compressed_terrain_db::get_analysis_blob(conn, fingerprint, EXIT_REACHABILITY_ANALYSIS, "")? {
+                self.set_exit_reachability(room, ExitReachability::from_bytes(&bytes));
+                loaded += 1;
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// The exits along an edge of a room, each with its [region](ExitReachability::region), or None
+    /// if the room's exit reachability isn't known.
+    pub fn exit_regions_toward(&self, room: RoomName, direction: ExitDirection) -> Option<Vec<(RoomExit, u8)>> {
+        let exits = self.rooms.get(&room)?;
+        let reachability = self.exit_reachability.get(&room)?;
+        if reachability.num_exits() != exits.num_exits() {
+            return None;
+        }
+
+        // Exits are indexed through every edge in clockwise order, starting from the top
+        let first_index: usize = EXIT_DIRECTIONS.iter()
+            .take_while(|edge| **edge != direction)
+            .map(|edge| exits.num_exits_toward(*edge))
+            .sum();
+        exits.edge_exits(direction).iter()
+            .enumerate()
+            .map(|(idx, exit)| Some((*exit, reachability.region(first_index + idx)?)))
+            .collect()
+    }
+
+    /// Returns true if the room is in the graph.
+    pub fn contains_room(&self, room: RoomName) -> bool {
+        self.rooms.contains_key(&room)
+    }
+
+    /// The exits data for a room, if it's in the graph.
+    pub fn get_room_exits(&self, room: RoomName) -> Option<&RoomExitsData> {
+        self.rooms.get(&room)
+    }
+
+    /// Returns an iterator over every room in the graph, in no particular order.
+    pub fn rooms(&self) -> impl Iterator<Item = RoomName> + '_ {
+        self.rooms.keys().copied()
+    }
+
+    /// The number of rooms in the graph.
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    /// Returns true if the graph has no rooms.
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+
+    /// Adds a one-way portal edge from one room to another, replacing any existing portal edge
+    /// between them that leads to the same shard.
+    ///
+    /// Neither room needs to be in the graph.
+    pub fn add_portal_edge(&mut self, from: RoomName, to: RoomName, metadata: PortalMetadata) {
+        let edges = self.portals.entry(from).or_default();
+        edges.retain(|(destination, existing)| *destination != to || existing.shard != metadata.shard);
+        edges.push((to, metadata));
+    }
+
+    /// Adds every portal edge in a JSON list, in the format described in the
+    /// [portal](crate::room_connectivity::portal) module docs, returning the number of edges added.
+    pub fn load_portal_edges_json(&mut self, json: &str) -> Result<usize, MapProcessingError> {
+        let edges = parse_portal_edges_json(json)?;
+        let num_edges = edges.len();
+        for edge in edges {
+            self.add_portal_edge(edge.from, edge.to, edge.metadata);
+        }
+        Ok(num_edges)
+    }
+
+    /// The portal edges leading out of a room, as `(destination, metadata)` pairs.
+    pub fn portal_edges(&self, room: RoomName) -> &[(RoomName, PortalMetadata)] {
+        self.portals.get(&room).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns true if there's a portal edge from one room to another on the same shard.
+    pub fn has_portal_edge(&self, from: RoomName, to: RoomName) -> bool {
+        self.portal_edges(from).iter().any(|(destination, metadata)| *destination == to && !metadata.is_intershard())
+    }
+
+    /// The rooms connected to a room, along with the direction of each from the room.
+    ///
+    /// Only rooms that are in the graph are returned.
+    pub fn neighbors(&self, room: RoomName) -> Vec<(ExitDirection, RoomName)> {
+        self.neighbors_with_min_exit_width(room, 1)
+    }
+
+    /// The rooms connected to a room by borders with at least `min_width` exit tiles in total,
+    /// along with the direction of each from the room.
+    ///
+    /// This is useful for keeping large squads away from narrow chokepoints. Only rooms that are in
+    /// the graph are returned.
+    pub fn neighbors_with_min_exit_width(&self, room: RoomName, min_width: u32) -> Vec<(ExitDirection, RoomName)> {
+        EXIT_DIRECTIONS.into_iter()
+            .filter_map(|direction| {
+                let neighbor = self.topology.neighbor_room(room, direction)?;
+                (self.border_exit_width(room, direction)? >= min_width.max(1)).then_some((direction, neighbor))
+            })
+            .collect()
+    }
+
+    /// The total number of exit tiles along the border between a room and its neighbor in the
+    /// specified direction.
+    ///
+    /// Only tiles that are open on both sides of the border are counted, since exits into a wall on
+    /// the other side can't be used; see [paired_exits]. Returns None if either room isn't in the
+    /// graph.
+    pub fn border_exit_width(&self, room: RoomName, direction: ExitDirection) -> Option<u32> {
+        let exits = self.rooms.get(&room)?;
+        let neighbor_exits = self.rooms.get(&self.topology.neighbor_room(room, direction)?)?;
+        let pairs = paired_exits(exits.edge_terrain_data(), neighbor_exits.edge_terrain_data(), direction);
+        Some(pairs.iter().map(|(exit, _)| exit.len() as u32).sum())
+    }
+
+    /// The number of room-to-room steps from a room to every room reachable from it, including 0
+    /// for the room itself.
+    ///
+    /// Returns an empty map if the room isn't in the graph.
+    pub fn distances_from(&self, room: RoomName) -> HashMap<RoomName, u32> {
+        self.breadth_first_search(room, None).0
+    }
+
+    /// The shortest list of rooms leading from one room to another, including both ends, or None
+    /// if there's no path between them.
+    pub fn shortest_path(&self, from: RoomName, to: RoomName) -> Option<Vec<RoomName>> {
+        let (distances, came_from) = self.breadth_first_search(from, Some(to));
+        distances.get(&to)?;
+
+        let mut path = vec![to];
+        while let Some(previous) = came_from.get(path.last()?) {
+            path.push(*previous);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Internal helper function to search outward from a room, stopping early once `target` is
+    /// found, returning the distance to and predecessor of every room reached.
+    fn breadth_first_search(&self, from: RoomName, target: Option<RoomName>) -> (HashMap<RoomName, u32>, HashMap<RoomName, RoomName>) {
+        let mut distances = HashMap::new();
+        let mut came_from = HashMap::new();
+        if !self.contains_room(from) {
+            return (distances, came_from);
+        }
+
+        distances.insert(from, 0);
+        let mut queue = VecDeque::from([from]);
+        while let Some(room) = queue.pop_front() {
+            if Some(room) == target {
+                break;
+            }
+            let distance = distances[&room];
+            for (_, neighbor) in self.neighbors(room) {
+                if let Entry::Vacant(entry) = distances.entry(neighbor) {
+                    entry.insert(distance + 1);
+                    came_from.insert(neighbor, room);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        (distances, came_from)
+    }
+
+    /// Returns true if the room is in the graph and is connected to exactly one neighbor.
+    pub fn is_dead_end(&self, room: RoomName) -> bool {
+        self.neighbors(room).len() == 1
+    }
+
+    /// The number of rooms in the dead-end branch that ends in a room, or None if the room isn't a
+    /// dead end.
+    ///
+    /// The branch is followed from the room through rooms with exactly two neighbors, and stops
+    /// before the first room with more. If the branch ends in another dead end instead, that room
+    /// is counted too.
+    pub fn cul_de_sac_depth(&self, room: RoomName) -> Option<u32> {
+        let [(_, mut current)] = self.neighbors(room)[..] else {
+            return None;
+        };
+        let mut previous = room;
+        let mut depth = 1;
+
+        // Bounded by the number of rooms, in case a tiny toroidal map makes the branch a loop
+        while depth <= self.len() as u32 {
+            let neighbors = self.neighbors(current);
+            match neighbors[..] {
+                [_] => return Some(depth + 1),
+                [(_, a), (_, b)] => {
+                    let next = if a == previous { b } else { a };
+                    previous = current;
+                    current = next;
+                    depth += 1;
+                }
+                _ => return Some(depth),
+            }
+        }
+
+        Some(depth)
+    }
+
+    /// The amount of memory used to store the exits data, in bytes.
+    pub fn memory_size(&self) -> usize {
+        self.rooms.values().map(|exits| exits.memory_size() + std::mem::size_of::<RoomName>()).sum()
+    }
+}
+
+impl Extend<RoomExitsData> for ShardConnectivityGraph {
+    fn extend<T: IntoIterator<Item = RoomExitsData>>(&mut self, iter: T) {
+        for exits in iter {
+            self.add_room(exits);
+        }
+    }
+}
+
+impl FromIterator<RoomExitsData> for ShardConnectivityGraph {
+    fn from_iter<T: IntoIterator<Item = RoomExitsData>>(iter: T) -> Self {
+        let mut graph = Self::new();
+        graph.extend(iter);
+        graph
+    }
+}
+
+/// Every dead-end room in the graph, in no particular order.
+///
+/// See [is_dead_end](ShardConnectivityGraph::is_dead_end).
+pub fn dead_end_rooms(graph: &ShardConnectivityGraph) -> Vec<RoomName> {
+    graph.rooms().filter(|room| graph.is_dead_end(*room)).collect()
+}
+
+/// Returns true if the room has exits along the edge in the specified direction.
+pub fn has_exits_toward(exits: &RoomExitsData, direction: ExitDirection) -> bool {
+    match direction {
+        ExitDirection::Top => exits.connected_to_top_neighbor(),
+        ExitDirection::Right => exits.connected_to_right_neighbor(),
+        ExitDirection::Bottom => exits.connected_to_bottom_neighbor(),
+        ExitDirection::Left => exits.connected_to_left_neighbor(),
+    }
+}
+
+/// The total number of exit tiles along the edge in the specified direction.
+pub fn exit_width_toward(exits: &RoomExitsData, direction: ExitDirection) -> u32 {
+    if !has_exits_toward(exits, direction) {
+        return 0;
+    }
+    exits.edge_exit_bits(direction).count_ones()
+}
+
+/// The room adjacent to a room in the specified direction, if it exists.
+pub fn neighbor_room(room: RoomName, direction: ExitDirection) -> Option<RoomName> {
+    match direction {
+        ExitDirection::Top => top_room(room),
+        ExitDirection::Right => right_room(room),
+        ExitDirection::Bottom => bottom_room(room),
+        ExitDirection::Left => left_room(room),
+    }
+}
+
+/// The shape of a shard's map, which determines how rooms along its border connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MapTopology {
+    /// Rooms along the border of the map have no neighbors past it, as on the official servers.
+    #[default]
+    Bounded,
+    /// The map wraps around at its borders, so rooms on opposite borders are adjacent.
+    ///
+    /// The map is `width` by `height` rooms, centered on the origin; a 20 by 20 map runs from W9
+    /// to E9 and from N9 to S9. Rooms outside of the map are treated as if they'd been wrapped into
+    /// it.
+    Toroidal { width: u32, height: u32 },
+}
+
+impl MapTopology {
+    /// The room adjacent to a room in the specified direction, if it exists.
+    pub fn neighbor_room(&self, room: RoomName, direction: ExitDirection) -> Option<RoomName> {
+        let offset = match direction {
+            ExitDirection::Top => (0, -1),
+            ExitDirection::Right => (1, 0),
+            ExitDirection::Bottom => (0, 1),
+            ExitDirection::Left => (-1, 0),
+        };
+        self.offset_room(room, offset)
+    }
+
+    /// The room `offset` rooms away from a room, as (x, y) with y increasing southward, if it
+    /// exists.
+    pub fn offset_room(&self, room: RoomName, offset: (i32, i32)) -> Option<RoomName> {
+        let MapTopology::Toroidal { width, height } = *self else {
+            return room.checked_add(offset);
+        };
+
+        let x = wrap_coord(room.x_coord() + offset.0, width);
+        let y = wrap_coord(room.y_coord() + offset.1, height);
+        room.checked_add((x - room.x_coord(), y - room.y_coord()))
+    }
+
+    /// The linear distance between two rooms, the larger of their horizontal and vertical
+    /// distances, as used for observer and terminal ranges. On toroidal maps this takes the
+    /// shorter way around each axis.
+    pub fn linear_distance(&self, from: RoomName, to: RoomName) -> u32 {
+        let dx = (to.x_coord() - from.x_coord()).unsigned_abs();
+        let dy = (to.y_coord() - from.y_coord()).unsigned_abs();
+        match *self {
+            MapTopology::Bounded => dx.max(dy),
+            MapTopology::Toroidal { width, height } => {
+                let around = |delta: u32, size: u32| {
+                    let delta = delta % size.max(1);
+                    delta.min(size.max(1) - delta)
+                };
+                around(dx, width).max(around(dy, height))
+            }
+        }
+    }
+
+    /// The edge of `room` that leads into `neighbor`, if the two rooms are adjacent.
+    pub fn direction_to_neighbor(&self, room: RoomName, neighbor: RoomName) -> Option<ExitDirection> {
+        EXIT_DIRECTIONS.into_iter().find(|direction| self.neighbor_room(room, *direction) == Some(neighbor))
+    }
+}
+
+/// Internal helper function to wrap a room coordinate into a toroidal map of the specified size,
+/// centered on the origin.
+fn wrap_coord(coord: i32, size: u32) -> i32 {
+    let size = size.max(1) as i32;
+    (coord + size / 2).rem_euclid(size) - size / 2
+}
+
+/// Exits data for a room with exits on the specified edges, in top, right, bottom, left order, and
+/// walls on the others. Shared by the tests of the connectivity modules.
+#[cfg(test)]
+pub(crate) fn exits_with_open_edges(room: &str, open: [bool; 4]) -> RoomExitsData {
+    use screeps::Terrain;
+    use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+
+    let mut open_edge = [Terrain::Plain; 50];
+    open_edge[0] = Terrain::Wall;
+    open_edge[49] = Terrain::Wall;
+    let closed_edge = [Terrain::Wall; 50];
+    let edge = |i: usize| if open[i] { open_edge } else { closed_edge };
+
+    let terrain = RoomEdgeTerrain::new_from_terrain_slices(&edge(0), &edge(1), &edge(2), &edge(3)).unwrap();
+    RoomExitsData::new_from_compressed_edge_terrain_data(terrain, RoomName::new(room).unwrap())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn shard_connectivity_graph_requires_exits_on_both_sides_of_a_border() {
+        let mut graph = ShardConnectivityGraph::new();
+        graph.add_room(exits_with_open_edges("W1N1", [true, true, true, true]));
+        graph.add_room(exits_with_open_edges("W0N1", [false, false, false, true])); // Open toward W1N1
+        graph.add_room(exits_with_open_edges("W1N0", [false, true, false, false])); // Closed toward W1N1
+
+        let neighbors = graph.neighbors(RoomName::new("W1N1").unwrap());
+        assert_eq!(neighbors, vec![(ExitDirection::Right, RoomName::new("W0N1").unwrap())]);
+        assert_eq!(graph.neighbors(RoomName::new("W1N0").unwrap()), vec![]);
+        assert_eq!(graph.len(), 3);
+    }
+
+    #[test]
+    pub fn shard_connectivity_graph_filters_neighbors_by_exit_width() {
+        let mut graph = ShardConnectivityGraph::new();
+        graph.add_room(exits_with_open_edges("W1N1", [true, true, true, true]));
+        graph.add_room(exits_with_open_edges("W0N1", [true, true, true, true]));
+        let room = RoomName::new("W1N1").unwrap();
+
+        assert_eq!(graph.border_exit_width(room, ExitDirection::Right), Some(48));
+        assert_eq!(graph.border_exit_width(room, ExitDirection::Left), None);
+        assert_eq!(graph.neighbors_with_min_exit_width(room, 48).len(), 1);
+        assert_eq!(graph.neighbors_with_min_exit_width(room, 49).len(), 0);
+    }
+
+    #[test]
+    pub fn shard_connectivity_graph_measures_dead_ends() {
+        // W2N1 is a junction, with a one-room branch to the left and above, and a two-room branch
+        // to the right
+        let mut graph = ShardConnectivityGraph::new();
+        for room in ["W3N1", "W2N1", "W1N1", "W0N1", "W2N2"] {
+            graph.add_room(exits_with_open_edges(room, [true, true, true, true]));
+        }
+        let room = |name| RoomName::new(name).unwrap();
+
+        let mut dead_ends = dead_end_rooms(&graph);
+        dead_ends.sort_by_key(|room| room.to_string());
+        assert_eq!(dead_ends, vec![room("W0N1"), room("W2N2"), room("W3N1")]);
+        assert!(!graph.is_dead_end(room("W1N1")));
+
+        assert_eq!(graph.cul_de_sac_depth(room("W0N1")), Some(2));
+        assert_eq!(graph.cul_de_sac_depth(room("W3N1")), Some(1));
+        assert_eq!(graph.cul_de_sac_depth(room("W2N1")), None);
+
+        graph.remove_room(room("W2N2"));
+        graph.remove_room(room("W3N1"));
+        assert_eq!(graph.cul_de_sac_depth(room("W2N1")), Some(3));
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn shard_connectivity_graph_finds_shortest_paths() {
+        use screeps::ROOM_AREA;
+        use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+        // An L-shaped chain of rooms, plus an isolated room
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_terrain_table_if_not_exists(&conn).unwrap();
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]);
+        for room in ["W2N2", "W1N2", "W1N1", "W5N5"] {
+            compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new(room).unwrap(), &terrain).unwrap();
+        }
+        let room = |name| RoomName::new(name).unwrap();
+
+        let graph = ShardConnectivityGraph::new_from_db(&conn).unwrap();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.shortest_path(room("W2N2"), room("W1N1")), Some(vec![room("W2N2"), room("W1N2"), room("W1N1")]));
+        assert_eq!(graph.shortest_path(room("W2N2"), room("W2N2")), Some(vec![room("W2N2")]));
+        assert_eq!(graph.shortest_path(room("W2N2"), room("W5N5")), None);
+        assert_eq!(graph.distances_from(room("W1N1")).get(&room("W2N2")), Some(&2));
+        assert!(graph.distances_from(room("W9N9")).is_empty());
+
+        let collected: ShardConnectivityGraph = ["W2N2", "W1N2"].into_iter()
+            .map(|name| exits_with_open_edges(name, [true, true, true, true]))
+            .collect();
+        assert_eq!(collected.neighbors(room("W2N2")), vec![(ExitDirection::Right, room("W1N2"))]);
+    }
+}
@@ -1 +1,9 @@
+pub mod builder;
+pub mod connectivity;
 pub mod exit;
+pub mod graph;
+pub mod portal;
+pub mod route;
+pub mod router;
+pub mod scouting;
+pub mod validation;
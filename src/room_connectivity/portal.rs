@@ -0,0 +1,84 @@
+//! Provides portal edges, which connect rooms that aren't adjacent.
+//!
+//! Portals aren't part of room terrain, so they have to be supplied separately, usually as JSON:
+//!
+//! ```json
+//! [
+//!     { "from": "W5N5", "to": "E15S5" },
+//!     { "from": "W10N0", "to": "W10N0", "shard": "shard1", "decay_time": 123456 }
+//! ]
+//! ```
+//!
+//! Each entry is a one-way edge; portals that work in both directions need an entry each way.
+
+use screeps::RoomName;
+use serde_json::Value;
+
+use crate::error::MapProcessingError;
+
+/// Details about a portal edge.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PortalMetadata {
+    /// The shard the portal leads to, or None if it leads elsewhere on the same shard.
+    pub shard: Option<String>,
+    /// The game tick the portal decays at, or None if it's stable.
+    pub decay_time: Option<u32>,
+}
+
+impl PortalMetadata {
+    /// Returns true if the portal leads to another shard.
+    pub fn is_intershard(&self) -> bool {
+        self.shard.is_some()
+    }
+}
+
+/// A one-way portal from one room to another.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PortalEdge {
+    pub from: RoomName,
+    pub to: RoomName,
+    pub metadata: PortalMetadata,
+}
+
+/// Parses a JSON list of portal edges, in the format described in the [module docs](self).
+pub fn parse_portal_edges_json(json: &str) -> Result<Vec<PortalEdge>, MapProcessingError> {
+    let invalid = |reason: String| MapProcessingError::InvalidData { kind: "portal", reason };
+    let value: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let entries = value.as_array().ok_or_else(|| invalid("expected a list of portals".to_string()))?;
+
+    entries.iter().enumerate()
+        .map(|(idx, entry)| {
+            let room = |field: &str| {
+                let name = entry.get(field).and_then(Value::as_str)
+                    .ok_or_else(|| invalid(format!("entry {idx} has no `{field}` room")))?;
+                RoomName::new(name).map_err(|_| invalid(format!("entry {idx} has an invalid `{field}` room: {name}")))
+            };
+            let shard = entry.get("shard").and_then(Value::as_str).map(str::to_string);
+            let decay_time = entry.get("decay_time").and_then(Value::as_u64).map(|tick| tick as u32);
+
+            Ok(PortalEdge { from: room("from")?, to: room("to")?, metadata: PortalMetadata { shard, decay_time } })
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn parse_portal_edges_json_reads_metadata() {
+        let edges = parse_portal_edges_json(r#"[
+            { "from": "W5N5", "to": "E15S5" },
+            { "from": "W10N0", "to": "W10N0", "shard": "shard1", "decay_time": 123456 }
+        ]"#).unwrap();
+
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].to, RoomName::new("E15S5").unwrap());
+        assert!(!edges[0].metadata.is_intershard());
+        assert_eq!(edges[1].metadata, PortalMetadata { shard: Some("shard1".to_string()), decay_time: Some(123456) });
+
+        assert!(parse_portal_edges_json(r#"[{ "from": "W5N5" }]"#).is_err());
+        assert!(parse_portal_edges_json(r#"{ "from": "W5N5", "to": "W6N6" }"#).is_err());
+    }
+}
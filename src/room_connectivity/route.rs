@@ -0,0 +1,238 @@
+use std::mem::size_of;
+use screeps::{ExitDirection, RoomName};
+
+use crate::room_connectivity::exit::opposite_exit_direction;
+use crate::room_connectivity::graph::{MapTopology, ShardConnectivityGraph};
+
+/// A single room along a [RoomRoute], along with the borders used to enter and leave it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RouteStep {
+    room: RoomName,
+    entry: Option<ExitDirection>,
+    exit: Option<ExitDirection>,
+}
+
+impl RouteStep {
+    const ENTRY_OFFSET: u32 = 3;
+    const ROOM_OFFSET: u32 = 16;
+    const DIRECTION_BITMASK: u32 = 0b111;
+
+    /// Creates a new route step.
+    pub fn new(room: RoomName, entry: Option<ExitDirection>, exit: Option<ExitDirection>) -> Self {
+        Self { room, entry, exit }
+    }
+
+    /// Creates a route step from its packed representation.
+    ///
+    /// Returns None if either of the direction fields holds an invalid value.
+    ///
+    /// Format:
+    /// RRRRRRRRRRRRRRRR0000000000EEEXXX
+    ///
+    /// - R: The packed representation of the room name
+    /// - E: The entry edge, as an ExitDirection value, or 0 if there is no entry edge
+    /// - X: The exit edge, as an ExitDirection value, or 0 if there is no exit edge
+    pub fn new_from_packed(packed: u32) -> Option<Self> {
+        let room = RoomName::from_packed((packed >> Self::ROOM_OFFSET) as u16);
+        let entry = Self::unpack_direction((packed >> Self::ENTRY_OFFSET) & Self::DIRECTION_BITMASK)?;
+        let exit = Self::unpack_direction(packed & Self::DIRECTION_BITMASK)?;
+        Some(Self { room, entry, exit })
+    }
+
+    /// The room this step is in.
+    pub fn room(&self) -> RoomName {
+        self.room
+    }
+
+    /// The edge of the room that the route enters through; None for the first room of a route, or
+    /// a room entered through a portal.
+    pub fn entry(&self) -> Option<ExitDirection> {
+        self.entry
+    }
+
+    /// The edge of the room that the route leaves through; None for the last room of a route, or
+    /// a room left through a portal.
+    pub fn exit(&self) -> Option<ExitDirection> {
+        self.exit
+    }
+
+    /// The packed representation of this step.
+    pub fn packed(&self) -> u32 {
+        let room_bits = (self.room.packed_repr() as u32) << Self::ROOM_OFFSET;
+        let entry_bits = Self::pack_direction(self.entry) << Self::ENTRY_OFFSET;
+        let exit_bits = Self::pack_direction(self.exit);
+        room_bits | entry_bits | exit_bits
+    }
+
+    /// Internal helper function to pack an optional direction into 3 bits.
+    fn pack_direction(direction: Option<ExitDirection>) -> u32 {
+        direction.map(|d| d as u32).unwrap_or(0)
+    }
+
+    /// Internal helper function to unpack 3 bits into an optional direction.
+    ///
+    /// The outer Option is None if the bits are invalid.
+    fn unpack_direction(bits: u32) -> Option<Option<ExitDirection>> {
+        match bits {
+            0 => Some(None),
+            1 => Some(Some(ExitDirection::Top)),
+            3 => Some(Some(ExitDirection::Right)),
+            5 => Some(Some(ExitDirection::Bottom)),
+            7 => Some(Some(ExitDirection::Left)),
+            _ => None,
+        }
+    }
+}
+
+/// An ordered list of rooms to travel through, along with the borders used in each room.
+///
+/// Unlike a bare list of room names, this tells callers which edge to aim for in each room of the
+/// route.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RoomRoute {
+    steps: Vec<RouteStep>,
+}
+
+impl RoomRoute {
+    /// Creates a route from an ordered list of rooms, deriving the entry and exit edges from the
+    /// position of each room relative to its neighbors in the list.
+    ///
+    /// Returns None if any two consecutive rooms in the list are not adjacent.
+    pub fn new_from_rooms(rooms: &[RoomName]) -> Option<Self> {
+        Self::new_from_rooms_with_topology(rooms, MapTopology::Bounded)
+    }
+
+    /// Creates a route from an ordered list of rooms, where adjacency follows the specified map
+    /// topology, so routes on a wrap-around map can cross its seams.
+    ///
+    /// Returns None if any two consecutive rooms in the list are not adjacent.
+    pub fn new_from_rooms_with_topology(rooms: &[RoomName], topology: MapTopology) -> Option<Self> {
+        let mut steps: Vec<RouteStep> = rooms.iter().map(|room| RouteStep::new(*room, None, None)).collect();
+
+        for i in 1..steps.len() {
+            let direction = topology.direction_to_neighbor(steps[i-1].room, steps[i].room)?;
+            steps[i-1].exit = Some(direction);
+            steps[i].entry = Some(opposite_exit_direction(direction));
+        }
+
+        Some(Self { steps })
+    }
+
+    /// Creates a route from an ordered list of rooms, where each room is adjacent to the next under
+    /// the graph's topology, or has a portal edge to it on the same shard.
+    ///
+    /// Steps connected by a portal have no exit or entry edge. Returns None if any two consecutive
+    /// rooms in the list are neither adjacent nor connected by a portal.
+    pub fn new_from_rooms_in_graph(rooms: &[RoomName], graph: &ShardConnectivityGraph) -> Option<Self> {
+        let mut steps: Vec<RouteStep> = rooms.iter().map(|room| RouteStep::new(*room, None, None)).collect();
+
+        for i in 1..steps.len() {
+            match graph.topology().direction_to_neighbor(steps[i-1].room, steps[i].room) {
+                Some(direction) => {
+                    steps[i-1].exit = Some(direction);
+                    steps[i].entry = Some(opposite_exit_direction(direction));
+                }
+                None if graph.has_portal_edge(steps[i-1].room, steps[i].room) => {}
+                None => return None,
+            }
+        }
+
+        Some(Self { steps })
+    }
+
+    /// Creates a route from its packed representation, as returned by [packed](RoomRoute::packed).
+    ///
+    /// Returns None if any of the packed steps is invalid.
+    pub fn new_from_packed(packed: &[u32]) -> Option<Self> {
+        let steps = packed.iter().map(|p| RouteStep::new_from_packed(*p)).collect::<Option<Vec<_>>>()?;
+        Some(Self { steps })
+    }
+
+    /// The packed representation of this route, one u32 per room.
+    pub fn packed(&self) -> Vec<u32> {
+        self.steps.iter().map(|step| step.packed()).collect()
+    }
+
+    /// The steps of this route, in travel order.
+    pub fn steps(&self) -> &[RouteStep] {
+        &self.steps
+    }
+
+    /// The rooms of this route, in travel order.
+    pub fn rooms(&self) -> impl Iterator<Item = RoomName> + '_ {
+        self.steps.iter().map(|step| step.room)
+    }
+
+    /// The number of rooms in this route, including the starting room.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns true if this route has no rooms.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Returns true if the route passes through the specified room.
+    pub fn contains_room(&self, room: RoomName) -> bool {
+        self.steps.iter().any(|step| step.room == room)
+    }
+
+    /// Returns the step for the specified room, if the route passes through it.
+    pub fn get_step(&self, room: RoomName) -> Option<&RouteStep> {
+        self.steps.iter().find(|step| step.room == room)
+    }
+
+    /// The amount of memory it takes to store this data.
+    pub fn memory_size(&self) -> usize {
+        self.steps.len() * size_of::<RouteStep>() + size_of::<Vec<RouteStep>>()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn room_route_new_from_rooms_derives_borders() {
+        let rooms: Vec<RoomName> = ["W1N1", "W0N1", "W0N2", "W1N2"].iter().map(|n| RoomName::new(n).unwrap()).collect();
+        let route = RoomRoute::new_from_rooms(&rooms).unwrap();
+
+        assert_eq!(route.len(), 4);
+        assert_eq!(route.rooms().collect::<Vec<_>>(), rooms);
+
+        let expected = [
+            (None, Some(ExitDirection::Right)),
+            (Some(ExitDirection::Left), Some(ExitDirection::Top)),
+            (Some(ExitDirection::Bottom), Some(ExitDirection::Left)),
+            (Some(ExitDirection::Right), None),
+        ];
+        for (step, (entry, exit)) in route.steps().iter().zip(expected) {
+            assert_eq!(step.entry(), entry, "Entry mismatch for {}", step.room());
+            assert_eq!(step.exit(), exit, "Exit mismatch for {}", step.room());
+        }
+
+        assert!(route.contains_room(RoomName::new("W0N2").unwrap()));
+        assert!(!route.contains_room(RoomName::new("W5N5").unwrap()));
+    }
+
+    #[test]
+    pub fn room_route_new_from_rooms_rejects_non_adjacent_rooms() {
+        let rooms: Vec<RoomName> = ["W1N1", "W3N1"].iter().map(|n| RoomName::new(n).unwrap()).collect();
+        assert_eq!(RoomRoute::new_from_rooms(&rooms), None);
+    }
+
+    #[test]
+    pub fn room_route_packed_round_trips() {
+        let rooms: Vec<RoomName> = ["E0S0", "E1S0", "E1S1"].iter().map(|n| RoomName::new(n).unwrap()).collect();
+        let route = RoomRoute::new_from_rooms(&rooms).unwrap();
+
+        let packed = route.packed();
+        assert_eq!(packed.len(), 3);
+        assert_eq!(RoomRoute::new_from_packed(&packed), Some(route));
+
+        // Invalid direction bits are rejected
+        assert_eq!(RouteStep::new_from_packed(packed[0] | (0b010 << 3)), None);
+    }
+}
@@ -0,0 +1,611 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use screeps::{ExitDirection, RoomName};
+use screeps_utils::map::{room_type_for_name, RoomType};
+
+use crate::room_connectivity::exit::{RoomExit, RoomExitsData, opposite_exit_direction};
+use crate::room_connectivity::graph::{ShardConnectivityGraph, EXIT_DIRECTIONS, exit_width_toward};
+use crate::room_connectivity::route::RoomRoute;
+
+/// The kind of a room, as determined by its position in its sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoomKind {
+    /// A room with a controller.
+    Normal,
+    /// A highway room, between sectors.
+    Highway,
+    /// A highway intersection, at the corner of a sector.
+    Crossroads,
+    /// A room with source keepers.
+    SourceKeeper,
+    /// The center room of a sector.
+    Center,
+}
+
+impl RoomKind {
+    /// Determines the kind of a room from its name.
+    pub fn for_room(room: RoomName) -> Self {
+        match room_type_for_name(room) {
+            RoomType::Normal => RoomKind::Normal,
+            RoomType::Highway => RoomKind::Highway,
+            RoomType::HighwayIntersection => RoomKind::Crossroads,
+            RoomType::Keeper => RoomKind::SourceKeeper,
+            RoomType::Center => RoomKind::Center,
+        }
+    }
+}
+
+/// The cost model used by [find_route].
+///
+/// Each room entered along a route adds the cost for its kind; None marks a room as impassable.
+/// Rooms missing from the graph use `unknown_room_cost` instead, unless their kind is impassable,
+/// and are assumed to have exits on every edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteOptions {
+    pub normal_cost: Option<u32>,
+    pub highway_cost: Option<u32>,
+    pub crossroads_cost: Option<u32>,
+    pub source_keeper_cost: Option<u32>,
+    pub center_cost: Option<u32>,
+    pub unknown_room_cost: Option<u32>,
+    /// The minimum total exit width of a border for the route to cross it, counting only tiles
+    /// open on both sides; borders of rooms missing from the graph are only checked on the known
+    /// side.
+    pub min_exit_width: u32,
+    /// The extra cost of crossing a border whose exits are all swamp on both sides, scaled down by
+    /// the fraction of swamp just inside the exits; see
+    /// [border_swamp_fraction](ShardConnectivityGraph::border_swamp_fraction).
+    pub swamp_border_penalty: u32,
+    /// The extra cost of entering a room per point of its
+    /// [room_danger](ShardConnectivityGraph::room_danger), rounded to the nearest whole cost.
+    pub danger_penalty: u32,
+    /// Whether routes may only leave a room through exits that can be reached from the exits they
+    /// entered it by; see [with_exit_reachability](RouteOptions::with_exit_reachability).
+    pub respect_exit_reachability: bool,
+    /// The maximum number of rooms to expand before giving up.
+    pub max_rooms: usize,
+}
+
+impl RouteOptions {
+    /// The default limit on the number of rooms expanded by a search.
+    pub const DEFAULT_MAX_ROOMS: usize = 10_000;
+
+    /// A cost model for general shard travel: highways are cheap, source keeper rooms are
+    /// expensive, and center rooms are very expensive. Unknown rooms are impassable.
+    pub fn shard_travel() -> Self {
+        Self {
+            normal_cost: Some(2),
+            highway_cost: Some(1),
+            crossroads_cost: Some(1),
+            source_keeper_cost: Some(10),
+            center_cost: Some(25),
+            unknown_room_cost: None,
+            min_exit_width: 1,
+            swamp_border_penalty: 0,
+            danger_penalty: 0,
+            respect_exit_reachability: false,
+            max_rooms: Self::DEFAULT_MAX_ROOMS,
+        }
+    }
+
+    /// Like [shard_travel](RouteOptions::shard_travel), but never routes through source keeper or
+    /// center rooms.
+    pub fn avoid_source_keepers() -> Self {
+        Self {
+            source_keeper_cost: None,
+            center_cost: None,
+            ..Self::shard_travel()
+        }
+    }
+
+    /// Like [shard_travel](RouteOptions::shard_travel), but steers clear of rooms with high
+    /// [danger scores](crate::analysis::danger).
+    pub fn cautious_travel() -> Self {
+        Self::shard_travel().with_danger_penalty(2)
+    }
+
+    /// Every room costs the same, so routes minimize the number of rooms traveled through.
+    pub fn uniform() -> Self {
+        Self {
+            normal_cost: Some(1),
+            highway_cost: Some(1),
+            crossroads_cost: Some(1),
+            source_keeper_cost: Some(1),
+            center_cost: Some(1),
+            unknown_room_cost: None,
+            min_exit_width: 1,
+            swamp_border_penalty: 0,
+            danger_penalty: 0,
+            respect_exit_reachability: false,
+            max_rooms: Self::DEFAULT_MAX_ROOMS,
+        }
+    }
+
+    /// Sets the cost of entering rooms that aren't in the graph.
+    pub fn with_unknown_room_cost(self, cost: Option<u32>) -> Self {
+        Self { unknown_room_cost: cost, ..self }
+    }
+
+    /// Only crosses borders with at least `width` exit tiles in total.
+    pub fn with_min_exit_width(self, width: u32) -> Self {
+        Self { min_exit_width: width, ..self }
+    }
+
+    /// Penalizes crossing swampy borders by up to `penalty`.
+    pub fn with_swamp_border_penalty(self, penalty: u32) -> Self {
+        Self { swamp_border_penalty: penalty, ..self }
+    }
+
+    /// Adds `penalty` to the cost of entering a room for each point of its danger score.
+    pub fn with_danger_penalty(self, penalty: u32) -> Self {
+        Self { danger_penalty: penalty, ..self }
+    }
+
+    /// Only leaves a room through exits that can be reached, within the room, from the exits it was
+    /// entered by, so routes never pass through rooms that are split by walls.
+    ///
+    /// This uses the reachability set with
+    /// [set_exit_reachability](ShardConnectivityGraph::set_exit_reachability) or
+    /// [load_exit_reachability](ShardConnectivityGraph::load_exit_reachability). Rooms without it,
+    /// the starting room, and rooms entered through portals can be left through any exit. Routes
+    /// may pass through a room more than once, in different regions.
+    pub fn with_exit_reachability(self, respect: bool) -> Self {
+        Self { respect_exit_reachability: respect, ..self }
+    }
+
+    /// The cost of entering a room of the specified kind, or None if it's impassable.
+    pub fn cost_for_kind(&self, kind: RoomKind) -> Option<u32> {
+        match kind {
+            RoomKind::Normal => self.normal_cost,
+            RoomKind::Highway => self.highway_cost,
+            RoomKind::Crossroads => self.crossroads_cost,
+            RoomKind::SourceKeeper => self.source_keeper_cost,
+            RoomKind::Center => self.center_cost,
+        }
+    }
+
+    /// The cost of entering a room, or None if it's impassable.
+    pub fn room_cost(&self, graph: &ShardConnectivityGraph, room: RoomName) -> Option<u32> {
+        let kind_cost = self.cost_for_kind(RoomKind::for_room(room))?;
+        let base_cost = if graph.contains_room(room) {
+            kind_cost
+        } else {
+            self.unknown_room_cost?
+        };
+        let danger_cost = (graph.room_danger(room) * self.danger_penalty as f32).round() as u32;
+        Some(base_cost.saturating_add(danger_cost))
+    }
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        Self::shard_travel()
+    }
+}
+
+/// A room along a route search, and the [region](crate::analysis::exit_reachability::ExitReachability::region)
+/// of its exits it was entered in, or None if it can be left through any exit.
+type RouteState = (RoomName, Option<u8>);
+
+/// Finds the cheapest route between two rooms under the cost model in `options`.
+///
+/// The starting room is free to leave, regardless of its kind. Returns None if there's no route, or
+/// if the search expands more than `options.max_rooms` rooms without reaching the destination.
+pub fn find_route(graph: &ShardConnectivityGraph, from: RoomName, to: RoomName, options: &RouteOptions) -> Option<RoomRoute> {
+    search_route(&mut &*graph, from, to, options)
+}
+
+/// Finds the cheapest route between two rooms like [find_route], loading rooms into the graph as
+/// the search reaches them.
+///
+/// Before a room is expanded, `load` is called for it and each of its neighbors that aren't in the
+/// graph yet, and returns the room's exits, or None if the room isn't known. Each room is only
+/// asked for once per search, and rooms that aren't known are treated as unknown by the cost model.
+pub fn find_route_with_loader(
+    graph: &mut ShardConnectivityGraph,
+    from: RoomName,
+    to: RoomName,
+    options: &RouteOptions,
+    load: impl FnMut(RoomName) -> Option<RoomExitsData>,
+) -> Option<RoomRoute> {
+    search_route(&mut LoadingRouteGraph { graph, load, requested: HashSet::new() }, from, to, options)
+}
+
+/// Internal trait for the graph a route search reads, which may grow as the search expands.
+trait RouteGraph {
+    fn graph(&self) -> &ShardConnectivityGraph;
+
+    /// Called before the search expands a room.
+    fn expanding(&mut self, _room: RoomName) {}
+}
+
+impl RouteGraph for &ShardConnectivityGraph {
+    fn graph(&self) -> &ShardConnectivityGraph {
+        self
+    }
+}
+
+/// A graph that loads rooms around each room the search expands.
+struct LoadingRouteGraph<'a, F> {
+    graph: &'a mut ShardConnectivityGraph,
+    load: F,
+    /// Rooms the loader has already been asked for
+    requested: HashSet<RoomName>,
+}
+
+impl<F: FnMut(RoomName) -> Option<RoomExitsData>> RouteGraph for LoadingRouteGraph<'_, F> {
+    fn graph(&self) -> &ShardConnectivityGraph {
+        self.graph
+    }
+
+    fn expanding(&mut self, room: RoomName) {
+        let topology = self.graph.topology();
+        let neighbors = EXIT_DIRECTIONS.map(|direction| topology.neighbor_room(room, direction));
+        for room in std::iter::once(room).chain(neighbors.into_iter().flatten()) {
+            if !self.graph.contains_room(room) && self.requested.insert(room) && let Some(exits) = (self.load)(room) {
+                self.graph.add_room(exits);
+            }
+        }
+    }
+}
+
+/// Internal helper function to run the route search over a graph.
+fn search_route(route_graph: &mut impl RouteGraph, from: RoomName, to: RoomName, options: &RouteOptions) -> Option<RoomRoute> {
+    let mut best_costs: HashMap<RouteState, u32> = HashMap::new();
+    let mut came_from: HashMap<RouteState, RouteState> = HashMap::new();
+    let mut open = BinaryHeap::new();
+    let mut rooms_expanded = 0;
+
+    best_costs.insert((from, None), 0);
+    open.push(Reverse((0, from.packed_repr(), None)));
+
+    while let Some(Reverse((cost, packed, region))) = open.pop() {
+        let state = (RoomName::from_packed(packed), region);
+        if state.0 == to {
+            let mut states = vec![state];
+            while let Some(previous) = came_from.get(states.last()?) {
+                states.push(*previous);
+            }
+            let rooms: Vec<RoomName> = states.into_iter().rev().map(|(room, _)| room).collect();
+            return RoomRoute::new_from_rooms_in_graph(&rooms, route_graph.graph());
+        }
+
+        if best_costs.get(&state).is_some_and(|best| *best < cost) {
+            continue; // Stale entry
+        }
+
+        rooms_expanded += 1;
+        if rooms_expanded > options.max_rooms {
+            return None;
+        }
+
+        route_graph.expanding(state.0);
+        for (neighbor, room_cost) in route_neighbors(route_graph.graph(), state, options) {
+            let neighbor_cost = cost.saturating_add(room_cost);
+            if best_costs.get(&neighbor).is_none_or(|best| neighbor_cost < *best) {
+                best_costs.insert(neighbor, neighbor_cost);
+                came_from.insert(neighbor, state);
+                open.push(Reverse((neighbor_cost, neighbor.0.packed_repr(), neighbor.1)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Internal helper function returning the rooms reachable in one step from a room, including
+/// through portals on the same shard, along with the cost of entering each.
+fn route_neighbors(graph: &ShardConnectivityGraph, (room, region): RouteState, options: &RouteOptions) -> Vec<(RouteState, u32)> {
+    let min_width = options.min_exit_width.max(1);
+    let one_side_wide_enough = |room: RoomName, direction: ExitDirection| {
+        graph.get_room_exits(room).is_none_or(|exits| exit_width_toward(exits, direction) >= min_width)
+    };
+    // Only tiles open on both sides of a border can be crossed; if either room is missing from
+    // the graph, fall back to checking whichever side is known
+    let wide_enough = |room: RoomName, neighbor: RoomName, direction: ExitDirection| {
+        match graph.border_exit_width(room, direction) {
+            Some(width) => width >= min_width,
+            None => one_side_wide_enough(room, direction) && one_side_wide_enough(neighbor, opposite_exit_direction(direction)),
+        }
+    };
+
+    let swamp_penalty = |room: RoomName, neighbor: RoomName, direction: ExitDirection| {
+        let swampiness = (graph.border_swamp_fraction(room, direction) + graph.border_swamp_fraction(neighbor, opposite_exit_direction(direction))) / 2.0;
+        (swampiness * options.swamp_border_penalty as f32).round() as u32
+    };
+
+    let mut neighbors = Vec::new();
+
+    for direction in EXIT_DIRECTIONS {
+        let Some(neighbor) = graph.topology().neighbor_room(room, direction) else {
+            continue;
+        };
+        if !wide_enough(room, neighbor, direction) {
+            continue;
+        }
+        let Some(cost) = options.room_cost(graph, neighbor) else {
+            continue;
+        };
+
+        let cost = cost.saturating_add(swamp_penalty(room, neighbor, direction));
+        if options.respect_exit_reachability {
+            for entry_region in entry_regions(graph, room, region, neighbor, direction) {
+                neighbors.push(((neighbor, entry_region), cost));
+            }
+        } else {
+            neighbors.push(((neighbor, None), cost));
+        }
+    }
+
+    let portals = graph.portal_edges(room).iter()
+        .filter(|(_, metadata)| !metadata.is_intershard())
+        .filter_map(|(destination, _)| Some(((*destination, None), options.room_cost(graph, *destination)?)));
+    neighbors.extend(portals);
+
+    neighbors
+}
+
+/// Internal helper function returning the regions of `neighbor` that can be entered by leaving
+/// `room` through its exits in `direction` that are in `region`.
+///
+/// Rooms without known exit reachability can be left through any exit, and are entered in the
+/// None region.
+fn entry_regions(graph: &ShardConnectivityGraph, room: RoomName, region: Option<u8>, neighbor: RoomName, direction: ExitDirection) -> Vec<Option<u8>> {
+    let leaving = graph.exit_regions_toward(room, direction);
+    let can_leave_by = |entry: Option<&RoomExit>| match (&leaving, region) {
+        (Some(exits), Some(region)) => exits.iter()
+            .any(|(exit, exit_region)| *exit_region == region && entry.is_none_or(|entry| exit.start() <= entry.end() && entry.start() <= exit.end())),
+        _ => true,
+    };
+
+    let Some(entries) = graph.exit_regions_toward(neighbor, opposite_exit_direction(direction)) else {
+        return if can_leave_by(None) { vec![None] } else { Vec::new() };
+    };
+
+    let mut regions = Vec::new();
+    for (entry, entry_region) in entries {
+        if can_leave_by(Some(&entry)) && !regions.contains(&Some(entry_region)) {
+            regions.push(Some(entry_region));
+        }
+    }
+    regions
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{Terrain, ROOM_AREA};
+    use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+    use crate::room_connectivity::exit::RoomExitsData;
+    use crate::room_connectivity::graph::{exits_with_open_edges, MapTopology};
+    use crate::room_connectivity::portal::PortalMetadata;
+
+    /// A graph of rooms with exits on every edge.
+    fn open_graph(rooms: &[&str]) -> ShardConnectivityGraph {
+        open_graph_with_topology(rooms, MapTopology::Bounded)
+    }
+
+    /// A graph of rooms with exits on every edge, on a map with the specified topology.
+    fn open_graph_with_topology(rooms: &[&str], topology: MapTopology) -> ShardConnectivityGraph {
+        let mut graph = ShardConnectivityGraph::with_topology(topology);
+        for room in rooms {
+            graph.add_room(exits_with_open_edges(room, [true; 4]));
+        }
+        graph
+    }
+
+    fn room_names(route: &RoomRoute) -> Vec<String> {
+        route.rooms().map(|room| room.to_string()).collect()
+    }
+
+    #[test]
+    pub fn room_kind_for_room_matches_sector_layout() {
+        let kind = |name: &str| RoomKind::for_room(RoomName::new(name).unwrap());
+        assert_eq!(kind("W1N1"), RoomKind::Normal);
+        assert_eq!(kind("W0N1"), RoomKind::Highway);
+        assert_eq!(kind("W0N0"), RoomKind::Crossroads);
+        assert_eq!(kind("W4N4"), RoomKind::SourceKeeper);
+        assert_eq!(kind("W5N5"), RoomKind::Center);
+    }
+
+    #[test]
+    pub fn find_route_uses_room_kind_costs() {
+        // E1N1 is a normal room between two highway rooms; going around it takes 4 highway rooms
+        let graph = open_graph(&["E1N2", "E1N1", "E1N0", "E0N2", "E0N1", "E0N0"]);
+        let from = RoomName::new("E1N2").unwrap();
+        let to = RoomName::new("E1N0").unwrap();
+
+        let route = find_route(&graph, from, to, &RouteOptions::shard_travel()).unwrap();
+        assert_eq!(room_names(&route), ["E1N2", "E1N1", "E1N0"]);
+
+        let expensive_normal = RouteOptions { normal_cost: Some(5), ..RouteOptions::shard_travel() };
+        let route = find_route(&graph, from, to, &expensive_normal).unwrap();
+        assert_eq!(room_names(&route), ["E1N2", "E0N2", "E0N1", "E0N0", "E1N0"]);
+
+        let impassable_normal = RouteOptions { normal_cost: None, ..RouteOptions::uniform() };
+        let route = find_route(&graph, from, to, &impassable_normal).unwrap();
+        assert!(!route.contains_room(RoomName::new("E1N1").unwrap()));
+
+        assert!(find_route(&graph, from, to, &RouteOptions::shard_travel().with_min_exit_width(48)).is_some());
+        assert_eq!(find_route(&graph, from, to, &RouteOptions::shard_travel().with_min_exit_width(49)), None);
+    }
+
+    #[test]
+    pub fn find_route_min_exit_width_counts_tiles_open_on_both_sides() {
+        let mut graph = open_graph(&["W1N3", "W1N2", "W1N1", "W2N3", "W2N2", "W2N1"]);
+        let from = RoomName::new("W1N3").unwrap();
+        let to = RoomName::new("W1N1").unwrap();
+
+        // Both sides of the W1N3/W1N2 border have 20 exit tiles, but only 10 of them line up
+        let mut edge = [Terrain::Plain; 50];
+        edge[0] = Terrain::Wall;
+        edge[49] = Terrain::Wall;
+        let mut upper_bottom = [Terrain::Wall; 50];
+        upper_bottom[15..35].fill(Terrain::Plain);
+        let mut lower_top = [Terrain::Wall; 50];
+        lower_top[5..25].fill(Terrain::Plain);
+        let upper = RoomEdgeTerrain::new_from_terrain_slices(&edge, &edge, &upper_bottom, &edge).unwrap();
+        let lower = RoomEdgeTerrain::new_from_terrain_slices(&lower_top, &edge, &edge, &edge).unwrap();
+        graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(upper, from));
+        graph.add_room(RoomExitsData::new_from_compressed_edge_terrain_data(lower, RoomName::new("W1N2").unwrap()));
+
+        let route = find_route(&graph, from, to, &RouteOptions::uniform().with_min_exit_width(10)).unwrap();
+        assert_eq!(room_names(&route), ["W1N3", "W1N2", "W1N1"]);
+
+        let route = find_route(&graph, from, to, &RouteOptions::uniform().with_min_exit_width(15)).unwrap();
+        assert_eq!(room_names(&route).len(), 5);
+        assert_eq!(room_names(&route)[1], "W2N3");
+    }
+
+    #[test]
+    pub fn find_route_uses_unknown_room_cost() {
+        let graph = open_graph(&["W4N5", "W3N5"]);
+        let from = RoomName::new("W4N5").unwrap();
+        let to = RoomName::new("W2N5").unwrap();
+
+        assert_eq!(find_route(&graph, from, to, &RouteOptions::default()), None);
+
+        let options = RouteOptions::default().with_unknown_room_cost(Some(3));
+        let route = find_route(&graph, from, to, &options).unwrap();
+        assert_eq!(room_names(&route), ["W4N5", "W3N5", "W2N5"]);
+
+        // W4N5 is a source keeper room, and W5N5 is a center room
+        let avoid = RouteOptions::avoid_source_keepers().with_unknown_room_cost(Some(3));
+        assert_eq!(find_route(&graph, RoomName::new("W3N5").unwrap(), RoomName::new("W5N5").unwrap(), &avoid), None);
+    }
+
+    #[test]
+    pub fn find_route_crosses_toroidal_seams() {
+        // A 20 by 20 map runs from W9 to E9, so W9N1 and E9N1 share a border across the seam
+        let rooms = ["W9N1", "E9N1", "E9N9", "E9S9"];
+        let from = RoomName::new("W9N1").unwrap();
+        let to = RoomName::new("E9N1").unwrap();
+
+        assert_eq!(find_route(&open_graph(&rooms), from, to, &RouteOptions::uniform()), None);
+
+        let graph = open_graph_with_topology(&rooms, MapTopology::Toroidal { width: 20, height: 20 });
+        let route = find_route(&graph, from, to, &RouteOptions::uniform()).unwrap();
+        assert_eq!(room_names(&route), ["W9N1", "E9N1"]);
+        assert_eq!(route.steps()[0].exit(), Some(ExitDirection::Left));
+        assert_eq!(route.steps()[1].entry(), Some(ExitDirection::Right));
+
+        let neighbors = graph.neighbors(RoomName::new("E9N9").unwrap());
+        assert_eq!(neighbors, vec![(ExitDirection::Top, RoomName::new("E9S9").unwrap())]);
+    }
+
+    #[test]
+    pub fn find_route_uses_portals() {
+        let mut graph = open_graph(&["W1N1", "W9N9"]);
+        let from = RoomName::new("W1N1").unwrap();
+        let to = RoomName::new("W9N9").unwrap();
+        assert_eq!(find_route(&graph, from, to, &RouteOptions::uniform()), None);
+
+        graph.add_portal_edge(from, to, PortalMetadata { shard: Some("shard1".to_string()), decay_time: None });
+        assert_eq!(find_route(&graph, from, to, &RouteOptions::uniform()), None);
+
+        assert_eq!(graph.load_portal_edges_json(r#"[{ "from": "W1N1", "to": "W9N9" }]"#).unwrap(), 1);
+        let route = find_route(&graph, from, to, &RouteOptions::uniform()).unwrap();
+        assert_eq!(room_names(&route), ["W1N1", "W9N9"]);
+        assert_eq!(route.steps()[0].exit(), None);
+        assert_eq!(route.steps()[1].entry(), None);
+        assert_eq!(graph.portal_edges(from).len(), 2);
+    }
+
+    #[test]
+    pub fn find_route_penalizes_swampy_borders() {
+        use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+        // Two equally long routes around W2N2; the route through W3N2 crosses swampy borders
+        let mut graph = open_graph(&["W2N1", "W1N1", "W3N2", "W1N2", "W2N3", "W1N3", "W3N1", "W3N3"]);
+        let from = RoomName::new("W2N1").unwrap();
+        let to = RoomName::new("W2N3").unwrap();
+        let swamp_border = RoomName::new("W3N2").unwrap();
+
+        let route = find_route(&graph, from, to, &RouteOptions::uniform()).unwrap();
+        assert_eq!(room_names(&route), ["W2N1", "W3N1", "W3N2", "W3N3", "W2N3"]);
+
+        let swamp = CompressedRoomTerrain::new_from_uncompressed_bits(&[2; ROOM_AREA]);
+        let exits = graph.get_room_exits(swamp_border).unwrap().clone();
+        graph.add_room_with_terrain(exits.clone(), &swamp);
+        assert_eq!(graph.border_swamp_fraction(swamp_border, ExitDirection::Top), 1.0);
+
+        // Without a penalty the swampy borders don't matter
+        let route = find_route(&graph, from, to, &RouteOptions::uniform()).unwrap();
+        assert_eq!(room_names(&route), ["W2N1", "W3N1", "W3N2", "W3N3", "W2N3"]);
+
+        let route = find_route(&graph, from, to, &RouteOptions::uniform().with_swamp_border_penalty(10)).unwrap();
+        assert_eq!(room_names(&route), ["W2N1", "W1N1", "W1N2", "W1N3", "W2N3"]);
+
+        // Replacing the room without terrain clears its border annotations
+        graph.add_room(exits);
+        assert_eq!(graph.border_swamp_fraction(swamp_border, ExitDirection::Top), 0.0);
+        let route = find_route(&graph, from, to, &RouteOptions::uniform().with_swamp_border_penalty(10)).unwrap();
+        assert_eq!(room_names(&route), ["W2N1", "W3N1", "W3N2", "W3N3", "W2N3"]);
+    }
+
+    #[test]
+    pub fn find_route_avoids_dangerous_rooms() {
+        // E1N1 is the direct route, but is rated as dangerous
+        let mut graph = open_graph(&["E1N2", "E1N1", "E1N0", "E0N2", "E0N1", "E0N0"]);
+        let from = RoomName::new("E1N2").unwrap();
+        let to = RoomName::new("E1N0").unwrap();
+        graph.set_room_danger(RoomName::new("E1N1").unwrap(), 2.6);
+
+        let route = find_route(&graph, from, to, &RouteOptions::shard_travel()).unwrap();
+        assert_eq!(room_names(&route), ["E1N2", "E1N1", "E1N0"]);
+        assert_eq!(RouteOptions::cautious_travel().room_cost(&graph, RoomName::new("E1N1").unwrap()), Some(2 + 5));
+        let route = find_route(&graph, from, to, &RouteOptions::cautious_travel()).unwrap();
+        assert_eq!(room_names(&route), ["E1N2", "E0N2", "E0N1", "E0N0", "E1N0"]);
+    }
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn find_route_respects_exit_reachability() {
+        use crate::analysis::cache::AnalysisCache;
+        use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+        use crate::compressed_terrain_db;
+
+        // W2N1 only has exits on its left and right edges, and a wall down the middle splits them
+        let mut open = [0u8; ROOM_AREA];
+        for corner in [0, 49, 49 * 50, ROOM_AREA - 1] {
+            open[corner] = 1;
+        }
+        let mut split = [0u8; ROOM_AREA];
+        for (idx, tile) in split.iter_mut().enumerate() {
+            let (x, y) = (idx % 50, idx / 50);
+            if y == 0 || y == 49 || x == 25 {
+                *tile = 1;
+            }
+        }
+        let open = CompressedRoomTerrain::new_from_uncompressed_bits(&open);
+        let split = CompressedRoomTerrain::new_from_uncompressed_bits(&split);
+
+        let conn = compressed_terrain_db::open_in_memory_db().unwrap();
+        let cache = AnalysisCache::new(&conn).unwrap();
+        for name in ["W3N1", "W2N1", "W1N1", "W3N2", "W2N2", "W1N2"] {
+            let terrain = if name == "W2N1" { &split } else { &open };
+            compressed_terrain_db::add_terrain_for_room(&conn, RoomName::new(name).unwrap(), terrain).unwrap();
+            cache.exit_reachability(terrain).unwrap();
+        }
+
+        let mut graph = ShardConnectivityGraph::new_from_db(&conn).unwrap();
+        let from = RoomName::new("W3N1").unwrap();
+        let to = RoomName::new("W1N1").unwrap();
+        let route = find_route(&graph, from, to, &RouteOptions::uniform()).unwrap();
+        assert_eq!(room_names(&route), ["W3N1", "W2N1", "W1N1"]);
+
+        // Without stored reachability, every room is assumed to be connected
+        let options = RouteOptions::uniform().with_exit_reachability(true);
+        assert_eq!(find_route(&graph, from, to, &options), Some(route));
+
+        assert_eq!(graph.load_exit_reachability(&conn).unwrap(), 6);
+        let route = find_route(&graph, from, to, &options).unwrap();
+        assert_eq!(room_names(&route), ["W3N1", "W3N2", "W2N2", "W1N2", "W1N1"]);
+
+        // Either half of W2N1 can still be reached
+        assert!(find_route(&graph, from, RoomName::new("W2N1").unwrap(), &options).is_some());
+        let route = find_route(&graph, to, RoomName::new("W2N1").unwrap(), &options).unwrap();
+        assert_eq!(route.len(), 2);
+    }
+}
@@ -0,0 +1,159 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use screeps::RoomName;
+
+use crate::room_connectivity::exit::opposite_exit_direction;
+use crate::room_connectivity::graph::{ShardConnectivityGraph, EXIT_DIRECTIONS, has_exits_toward};
+
+/// Ranks the unknown rooms bordering the known area around `origin` by how worthwhile they are to
+/// scout next, highest priority first.
+///
+/// Known rooms connected to the origin are searched outward through the graph, and every room
+/// outside `known_rooms` that a searched room has exits toward is a target. Each target is scored
+/// by its connectivity degree divided by its hop distance from the origin, so close rooms that open
+/// up the most of the map come first. A target's degree is the number of edges with exits if it's
+/// in the graph, and otherwise the number of searched rooms with exits toward it.
+///
+/// Ties are broken by hop distance, then by room name, so the order is deterministic.
+pub fn scout_targets(graph: &ShardConnectivityGraph, known_rooms: &HashSet<RoomName>, origin: RoomName) -> Vec<(RoomName, f32)> {
+    let mut distances = HashMap::from([(origin, 0u32)]);
+    let mut queue = VecDeque::from([origin]);
+    // Target room -> (hop distance, number of searched rooms with exits toward it)
+    let mut targets: HashMap<RoomName, (u32, u32)> = HashMap::new();
+
+    while let Some(room) = queue.pop_front() {
+        let distance = distances[&room];
+        let Some(exits) = graph.get_room_exits(room) else {
+            continue;
+        };
+
+        for direction in EXIT_DIRECTIONS {
+            let Some(neighbor) = graph.topology().neighbor_room(room, direction) else {
+                continue;
+            };
+            if !has_exits_toward(exits, direction) {
+                continue;
+            }
+
+            if known_rooms.contains(&neighbor) {
+                let connected = graph.get_room_exits(neighbor)
+                    .is_some_and(|neighbor_exits| has_exits_toward(neighbor_exits, opposite_exit_direction(direction)));
+                if connected && !distances.contains_key(&neighbor) {
+                    distances.insert(neighbor, distance + 1);
+                    queue.push_back(neighbor);
+                }
+            } else {
+                let target = targets.entry(neighbor).or_insert((distance + 1, 0));
+                target.1 += 1;
+            }
+        }
+    }
+
+    let mut ranked: Vec<(RoomName, u32, f32)> = targets.into_iter()
+        .map(|(room, (distance, bordering))| {
+            let degree = match graph.get_room_exits(room) {
+                Some(exits) => EXIT_DIRECTIONS.into_iter().filter(|direction| has_exits_toward(exits, *direction)).count() as u32,
+                None => bordering,
+            };
+            (room, distance, degree as f32 / distance as f32)
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.2.total_cmp(&a.2)
+            .then(a.1.cmp(&b.1))
+            .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+    });
+
+    ranked.into_iter().map(|(room, _, score)| (room, score)).collect()
+}
+
+
+/// Picks a small set of rooms in the graph such that every room in the graph is within `range` of
+/// one of them, by [linear distance](crate::room_connectivity::graph::MapTopology::linear_distance),
+/// for planning where to place observers.
+///
+/// This is a greedy set cover: each step picks the room that covers the most rooms not yet
+/// covered, breaking ties by room name, so the result is deterministic but not always minimal.
+/// Rooms are returned in the order they were picked.
+pub fn minimum_observation_set(graph: &ShardConnectivityGraph, range: u32) -> Vec<RoomName> {
+    let range = range as i32;
+    let topology = graph.topology();
+    let coverage = |room: RoomName| -> HashSet<RoomName> {
+        (-range..=range)
+            .flat_map(|dx| (-range..=range).map(move |dy| (dx, dy)))
+            .filter_map(|offset| topology.offset_room(room, offset))
+            .filter(|covered| graph.contains_room(*covered))
+            .collect()
+    };
+
+    let mut uncovered: HashSet<RoomName> = graph.rooms().collect();
+    // Max-heap of (number of newly covered rooms, room name); the counts only ever go down as
+    // rooms are covered, so stale entries are re-counted lazily when they reach the top
+    let mut heap: BinaryHeap<(usize, Reverse<String>, RoomName)> = graph.rooms()
+        .map(|room| (coverage(room).len(), Reverse(room.to_string()), room))
+        .collect();
+
+    let mut observers = Vec::new();
+    while !uncovered.is_empty() {
+        let Some((count, name, room)) = heap.pop() else {
+            break;
+        };
+        let covered: Vec<RoomName> = coverage(room).into_iter().filter(|covered| uncovered.contains(covered)).collect();
+        if covered.len() < count {
+            heap.push((covered.len(), name, room));
+            continue;
+        }
+
+        for covered in covered {
+            uncovered.remove(&covered);
+        }
+        observers.push(room);
+    }
+
+    observers
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::room_connectivity::graph::exits_with_open_edges;
+
+    #[test]
+    pub fn scout_targets_ranks_unknown_rooms_by_degree_and_distance() {
+        let room = |name: &str| RoomName::new(name).unwrap();
+        let mut graph = ShardConnectivityGraph::new();
+        graph.add_room(exits_with_open_edges("W2N2", [false, false, false, true])); // Origin, open to the left
+        graph.add_room(exits_with_open_edges("W3N2", [true, true, false, false])); // Known, open up and right
+        graph.add_room(exits_with_open_edges("W3N3", [true, true, true, true])); // Unknown, but seen from afar
+        let known_rooms = HashSet::from([room("W2N2"), room("W3N2")]);
+
+        let targets = scout_targets(&graph, &known_rooms, room("W2N2"));
+
+        // W3N3 is above W3N2, two hops away, with four exits
+        assert_eq!(targets, vec![(room("W3N3"), 2.0)]);
+
+        // Once the edges are walled off, there's nothing left to scout
+        graph.add_room(exits_with_open_edges("W3N2", [false, true, false, false]));
+        assert!(scout_targets(&graph, &known_rooms, room("W2N2")).is_empty());
+    }
+
+    #[test]
+    pub fn minimum_observation_set_covers_every_room() {
+        let room = |name: &str| RoomName::new(name).unwrap();
+        let graph: ShardConnectivityGraph = (0..10)
+            .map(|x| exits_with_open_edges(&format!("W{x}N0"), [true, true, true, true]))
+            .collect();
+
+        let observers = minimum_observation_set(&graph, 2);
+        assert_eq!(observers, vec![room("W2N0"), room("W7N0")]);
+        assert!(graph.rooms().all(|target| observers.iter().any(|observer| graph.topology().linear_distance(*observer, target) <= 2)));
+
+        assert_eq!(minimum_observation_set(&graph, 10).len(), 1);
+        assert!(minimum_observation_set(&ShardConnectivityGraph::new(), 10).is_empty());
+
+        let toroidal = crate::room_connectivity::graph::MapTopology::Toroidal { width: 20, height: 20 };
+        assert_eq!(toroidal.linear_distance(room("W9N0"), room("E9N0")), 1);
+    }
+}
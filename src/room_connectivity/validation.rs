@@ -0,0 +1,96 @@
+//! Cross-checks offline connectivity against exits reported by the game.
+//!
+//! The expected input is a JSON object mapping room names to the result of
+//! `Game.map.describeExits` for that room, which maps direction constants to neighboring room
+//! names:
+//!
+//! ```json
+//! { "W1N1": { "1": "W1N2", "3": "W0N1" }, "W2N1": null }
+//! ```
+
+use screeps::{ExitDirection, RoomName};
+use serde_json::Value;
+
+use crate::error::MapProcessingError;
+use crate::room_connectivity::graph::{ShardConnectivityGraph, EXIT_DIRECTIONS, has_exits_toward};
+
+/// A border where the graph and the game disagree about whether a room has an exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExitDiscrepancy {
+    pub room: RoomName,
+    pub direction: ExitDirection,
+    /// The neighbor the game reports in this direction, if any.
+    pub game: Option<RoomName>,
+    /// The neighbor the graph connects to in this direction, if any.
+    pub graph: Option<RoomName>,
+}
+
+/// Compares the exits of every room in a `describeExits` dump with the exits in the graph, and
+/// returns every border where they disagree, ordered by room and then direction.
+///
+/// When both rooms of a border are in the graph, the graph side counts as connected if the border
+/// has exit tiles that are open on both sides; otherwise it's connected if the room has exits
+/// toward its neighbor. Rooms in the dump that aren't in the graph are skipped, and a `null` entry
+/// means the game reports no exits for the room.
+pub fn validate_against_describe_exits(graph: &ShardConnectivityGraph, json: &str) -> Result<Vec<ExitDiscrepancy>, MapProcessingError> {
+    let invalid = |reason: String| MapProcessingError::InvalidData { kind: "describeExits", reason };
+    let value: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+    let rooms = value.as_object().ok_or_else(|| invalid("expected an object of rooms".to_string()))?;
+
+    let mut discrepancies = Vec::new();
+    for (name, described) in rooms {
+        let room = RoomName::new(name).map_err(|_| invalid(format!("invalid room name: {name}")))?;
+        let Some(exits) = graph.get_room_exits(room) else {
+            continue;
+        };
+
+        for direction in EXIT_DIRECTIONS {
+            let game = match described.get((direction as u8).to_string()) {
+                Some(Value::String(neighbor)) => Some(RoomName::new(neighbor).map_err(|_| invalid(format!("invalid room name: {neighbor}")))?),
+                _ => None,
+            };
+
+            let neighbor = graph.topology().neighbor_room(room, direction);
+            let connected = match neighbor.filter(|neighbor| graph.contains_room(*neighbor)) {
+                Some(_) => graph.border_exit_width(room, direction).is_some_and(|width| width > 0),
+                None => has_exits_toward(exits, direction),
+            };
+            let graph_neighbor = neighbor.filter(|_| connected);
+
+            if game != graph_neighbor {
+                discrepancies.push(ExitDiscrepancy { room, direction, game, graph: graph_neighbor });
+            }
+        }
+    }
+
+    discrepancies.sort_by_key(|discrepancy| (discrepancy.room.to_string(), discrepancy.direction as u8));
+    Ok(discrepancies)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::room_connectivity::graph::exits_with_open_edges;
+
+    #[test]
+    pub fn validate_against_describe_exits_reports_mismatched_borders() {
+        let room = RoomName::new("W1N1").unwrap();
+        let mut graph = ShardConnectivityGraph::new();
+        // Open to the right and bottom only
+        graph.add_room(exits_with_open_edges("W1N1", [false, true, true, false]));
+
+        let dump = r#"{
+            "W1N1": { "1": "W1N2", "3": "W0N1", "5": "W1N0" },
+            "W5N5": { "1": "W5N6" }
+        }"#;
+        let discrepancies = validate_against_describe_exits(&graph, dump).unwrap();
+        assert_eq!(discrepancies, vec![
+            ExitDiscrepancy { room, direction: ExitDirection::Top, game: Some(RoomName::new("W1N2").unwrap()), graph: None },
+        ]);
+
+        let closed = validate_against_describe_exits(&graph, r#"{ "W1N1": null }"#).unwrap();
+        assert_eq!(closed.len(), 2);
+        assert!(validate_against_describe_exits(&graph, "[]").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Provides a generic per-tile matrix for storing derived room data.
+
+use std::mem::size_of;
+use std::ops::{Index, IndexMut};
+use screeps::{LocalCostMatrix, RoomXY, ROOM_AREA};
+use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
+
+use crate::compressed_terrain::room_bit_grid::RoomBitGrid;
+
+/// A value for every tile in a room, stored in row-major order.
+///
+/// This is the output type for per-tile analyses (path costs, distances, labels), and uses the
+/// same tile ordering as the terrain types in this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomMatrix<T> {
+    data: Box<[T; ROOM_AREA]>,
+}
+
+impl<T: Copy> RoomMatrix<T> {
+    /// Creates a new matrix with every tile set to the provided value.
+    pub fn new(value: T) -> Self {
+        Self { data: Box::new([value; ROOM_AREA]) }
+    }
+
+    /// Gets the value for the specified tile.
+    pub fn get(&self, xy: RoomXY) -> T {
+        self.data[xy_to_terrain_index(xy)]
+    }
+
+    /// Sets the value for the specified tile.
+    pub fn set(&mut self, xy: RoomXY, value: T) {
+        self.data[xy_to_terrain_index(xy)] = value;
+    }
+
+    /// Returns an iterator over every tile and its value, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (RoomXY, T)> + '_ {
+        self.data.iter().enumerate().map(|(idx, value)| (terrain_index_to_xy(idx), *value))
+    }
+
+    /// Returns a new matrix with `f` applied to the value of every tile.
+    pub fn map<U: Copy>(&self, mut f: impl FnMut(T) -> U) -> RoomMatrix<U> {
+        let mut data = Box::new([f(self.data[0]); ROOM_AREA]);
+        for (out, value) in data.iter_mut().zip(self.data.iter()).skip(1) {
+            *out = f(*value);
+        }
+        RoomMatrix { data }
+    }
+
+    /// A reference to the underlying row-major data.
+    pub fn as_slice(&self) -> &[T; ROOM_AREA] {
+        &self.data
+    }
+
+    /// The amount of memory it takes to store this data.
+    pub fn memory_size(&self) -> usize {
+        size_of::<[T; ROOM_AREA]>() + size_of::<Box<[T; ROOM_AREA]>>()
+    }
+}
+
+/// A single layer of a stacked cost matrix, as used by [RoomMatrix::from_layers].
+#[derive(Debug, Clone, Copy)]
+pub enum CostLayer<'a> {
+    /// Adds the layer's costs to the costs so far, saturating at 255.
+    Add(&'a RoomMatrix<u8>),
+    /// Keeps the larger of the layer's costs and the costs so far.
+    Max(&'a RoomMatrix<u8>),
+    /// Sets every tile in the mask to a fixed cost, replacing the costs so far.
+    Overlay(&'a RoomBitGrid, u8),
+}
+
+/// Combinators for composing cost matrices.
+impl RoomMatrix<u8> {
+    /// Returns a new matrix holding the larger of the two values for every tile.
+    pub fn max_with(&self, other: &Self) -> Self {
+        self.zip_with(other, u8::max)
+    }
+
+    /// Returns a new matrix holding the sum of the two values for every tile, saturating at 255.
+    pub fn add_saturating(&self, other: &Self) -> Self {
+        self.zip_with(other, u8::saturating_add)
+    }
+
+    /// Returns a new matrix with every tile in the mask set to `value`.
+    pub fn overlay(&self, mask: &RoomBitGrid, value: u8) -> Self {
+        let mut output = self.clone();
+        for xy in mask.iter() {
+            output.set(xy, value);
+        }
+        output
+    }
+
+    /// Builds a matrix by applying each layer in order, starting from a matrix of all zeroes.
+    pub fn from_layers(layers: &[CostLayer]) -> Self {
+        layers.iter().fold(Self::new(0), |matrix, layer| match layer {
+            CostLayer::Add(other) => matrix.add_saturating(other),
+            CostLayer::Max(other) => matrix.max_with(other),
+            CostLayer::Overlay(mask, value) => matrix.overlay(mask, *value),
+        })
+    }
+
+    /// Internal helper function to combine two matrices tile by tile.
+    fn zip_with(&self, other: &Self, f: impl Fn(u8, u8) -> u8) -> Self {
+        let mut output = self.clone();
+        for (out, value) in output.data.iter_mut().zip(other.data.iter()) {
+            *out = f(*out, *value);
+        }
+        output
+    }
+}
+
+impl From<&RoomMatrix<u8>> for LocalCostMatrix {
+    fn from(matrix: &RoomMatrix<u8>) -> Self {
+        let mut output = LocalCostMatrix::new();
+        for (xy, value) in matrix.iter() {
+            output.set(xy, value);
+        }
+        output
+    }
+}
+
+impl<T> Index<RoomXY> for RoomMatrix<T> {
+    type Output = T;
+
+    fn index(&self, xy: RoomXY) -> &T {
+        &self.data[xy_to_terrain_index(xy)]
+    }
+}
+
+impl<T> IndexMut<RoomXY> for RoomMatrix<T> {
+    fn index_mut(&mut self, xy: RoomXY) -> &mut T {
+        &mut self.data[xy_to_terrain_index(xy)]
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn room_matrix_addresses_data_in_row_major_order() {
+        let mut matrix = RoomMatrix::new(0u16);
+
+        let xy = unsafe { RoomXY::unchecked_new(1, 0) };
+        matrix.set(xy, 7);
+
+        assert_eq!(matrix.as_slice()[1], 7);
+        assert_eq!(matrix[xy], 7);
+        assert_eq!(matrix.iter().filter(|(_, v)| *v == 7).map(|(xy, _)| xy).collect::<Vec<_>>(), vec![xy]);
+    }
+
+    #[test]
+    pub fn room_matrix_cost_layers_stack_in_order() {
+        let a = RoomXY::checked_new(1, 1).unwrap();
+        let b = RoomXY::checked_new(2, 2).unwrap();
+
+        let mut terrain_costs = RoomMatrix::new(2u8);
+        terrain_costs.set(a, 250);
+        let threat = RoomMatrix::new(10u8);
+        let mut roads = RoomMatrix::new(0u8);
+        roads.set(b, 20);
+        let mut exclusion = RoomBitGrid::new();
+        exclusion.set_xy(b, true);
+
+        let stacked = RoomMatrix::from_layers(&[
+            CostLayer::Add(&terrain_costs),
+            CostLayer::Add(&threat),
+            CostLayer::Max(&roads),
+        ]);
+        assert_eq!(stacked.get(a), 255, "addition should saturate");
+        assert_eq!(stacked.get(b), 20);
+        assert_eq!(stacked.get(RoomXY::checked_new(3, 3).unwrap()), 12);
+
+        let excluded = stacked.overlay(&exclusion, 255);
+        assert_eq!(excluded.get(b), 255);
+
+        let cost_matrix = LocalCostMatrix::from(&excluded);
+        assert_eq!(cost_matrix.get(b), 255);
+        assert_eq!(cost_matrix.get(RoomXY::checked_new(3, 3).unwrap()), 12);
+    }
+
+    #[test]
+    pub fn room_matrix_map_applies_to_every_tile() {
+        let mut matrix = RoomMatrix::new(1u8);
+        matrix[unsafe { RoomXY::unchecked_new(49, 49) }] = 3;
+
+        let mapped = matrix.map(|v| v as u32 * 10);
+
+        assert_eq!(mapped.iter().filter(|(_, v)| *v == 10).count(), ROOM_AREA - 1);
+        assert_eq!(mapped.get(unsafe { RoomXY::unchecked_new(49, 49) }), 30);
+    }
+}
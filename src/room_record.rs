@@ -0,0 +1,263 @@
+//! Provides a compact record of everything typically needed about a room, loadable in one read.
+
+use screeps::{ExitDirection, RoomName};
+
+use crate::adaptive_terrain::AdaptiveRoomTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::room_connectivity::exit::{ExitSummary, RoomExitsData};
+use crate::room_connectivity::router::RoomKind;
+
+/// The version byte at the start of serialized records.
+const ROOM_RECORD_VERSION: u8 = 1;
+
+/// The most analyses a serialized record can list.
+pub const MAX_ANALYSES: usize = u8::MAX as usize;
+
+/// The longest analysis name a serialized record can hold, in bytes.
+pub const MAX_ANALYSIS_NAME_LEN: usize = u8::MAX as usize;
+
+/// A room's terrain, exits, kind, and fingerprint, bundled into a single record.
+///
+/// Analysis names can be attached to point at results stored in the `analysis_blob` table under
+/// the record's fingerprint.
+#[derive(Debug, Clone)]
+pub struct RoomRecord {
+    room: RoomName,
+    terrain: AdaptiveRoomTerrain,
+    exits: Vec<ExitSummary>,
+    kind: RoomKind,
+    fingerprint: u64,
+    analyses: Vec<String>,
+}
+
+impl RoomRecord {
+    /// Creates a record for a room from its terrain, with no analyses attached.
+    pub fn new_from_compressed_terrain(room: RoomName, terrain: &CompressedRoomTerrain) -> Self {
+        let exits = RoomExitsData::new_from_compressed_terrain(terrain, room)
+            .to_graph_edges()
+            .into_iter()
+            .map(|(_, _, summary)| summary)
+            .collect();
+
+        Self {
+            room,
+            terrain: AdaptiveRoomTerrain::new_from_compressed_terrain(terrain),
+            exits,
+            kind: RoomKind::for_room(room),
+            fingerprint: terrain.fingerprint(),
+            analyses: Vec::new(),
+        }
+    }
+
+    /// Attaches the names of analyses stored for this room's fingerprint.
+    ///
+    /// Records can only be serialized with up to [MAX_ANALYSES] analyses, each with a name of up to
+    /// [MAX_ANALYSIS_NAME_LEN] bytes.
+    pub fn with_analyses(mut self, analyses: Vec<String>) -> Self {
+        self.analyses = analyses;
+        self
+    }
+
+    /// The room this record describes.
+    pub fn room(&self) -> RoomName {
+        self.room
+    }
+
+    /// The room's terrain, in its smallest encoding.
+    pub fn terrain(&self) -> &AdaptiveRoomTerrain {
+        &self.terrain
+    }
+
+    /// A summary of the exits along each edge of the room that has any.
+    pub fn exits(&self) -> &[ExitSummary] {
+        &self.exits
+    }
+
+    /// The kind of the room.
+    pub fn kind(&self) -> RoomKind {
+        self.kind
+    }
+
+    /// The fingerprint of the room's terrain.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// The names of the analyses stored for this room's fingerprint.
+    pub fn analyses(&self) -> &[String] {
+        &self.analyses
+    }
+
+    /// Serializes this record.
+    ///
+    /// Format, with little-endian integers:
+    /// - A version byte
+    /// - The packed room name, as a u16
+    /// - The terrain fingerprint, as a u64
+    /// - A count byte, followed by 4 bytes per exit summary: direction, number of exits, total
+    ///   width, and widest exit
+    /// - A count byte, followed by each analysis name as a length byte and UTF-8 bytes
+    /// - The terrain, as written by [AdaptiveRoomTerrain::to_bytes]
+    ///
+    /// The room kind isn't stored, since it's determined by the room name. Returns
+    /// [MapProcessingError::InvalidData] if the record has more than [MAX_ANALYSES] analyses, or an
+    /// analysis name longer than [MAX_ANALYSIS_NAME_LEN] bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MapProcessingError> {
+        if self.analyses.len() > MAX_ANALYSES {
+            return Err(MapProcessingError::InvalidData { kind: "room record", reason: format!("{} analyses is more than the limit of {MAX_ANALYSES}", self.analyses.len()) });
+        }
+        if let Some(name) = self.analyses.iter().find(|name| name.len() > MAX_ANALYSIS_NAME_LEN) {
+            return Err(MapProcessingError::InvalidData { kind: "room record", reason: format!("analysis name {name:?} is longer than {MAX_ANALYSIS_NAME_LEN} bytes") });
+        }
+
+        let mut bytes = vec![ROOM_RECORD_VERSION];
+        bytes.extend(self.room.packed_repr().to_le_bytes());
+        bytes.extend(self.fingerprint.to_le_bytes());
+
+        bytes.push(self.exits.len() as u8);
+        for summary in &self.exits {
+            bytes.extend([summary.direction as u8, summary.num_exits, summary.total_width, summary.widest_exit]);
+        }
+
+        bytes.push(self.analyses.len() as u8);
+        for analysis in &self.analyses {
+            bytes.push(analysis.len() as u8);
+            bytes.extend(analysis.as_bytes());
+        }
+
+        bytes.extend(self.terrain.to_bytes());
+        Ok(bytes)
+    }
+
+    /// Deserializes a record written by [to_bytes](RoomRecord::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        let mut reader = ByteReader { bytes };
+
+        let version = reader.take::<1>()?[0];
+        if version != ROOM_RECORD_VERSION {
+            return Err(MapProcessingError::InvalidData { kind: "room record", reason: format!("unsupported version {version}") });
+        }
+        let room = RoomName::from_packed(u16::from_le_bytes(reader.take()?));
+        let fingerprint = u64::from_le_bytes(reader.take()?);
+
+        let num_exits = reader.take::<1>()?[0];
+        let exits = (0..num_exits)
+            .map(|_| {
+                let [direction, num_exits, total_width, widest_exit] = reader.take()?;
+                let direction = direction_from_u8(direction).ok_or_else(|| MapProcessingError::InvalidData {
+                    kind: "room record",
+                    reason: format!("invalid exit direction {direction}"),
+                })?;
+                Ok(ExitSummary { direction, num_exits, total_width, widest_exit })
+            })
+            .collect::<Result<Vec<_>, MapProcessingError>>()?;
+
+        let num_analyses = reader.take::<1>()?[0];
+        let analyses = (0..num_analyses)
+            .map(|_| {
+                let len = reader.take::<1>()?[0] as usize;
+                let name = reader.take_slice(len)?;
+                String::from_utf8(name.to_vec())
+                    .map_err(|_| MapProcessingError::InvalidData { kind: "room record", reason: "analysis name isn't UTF-8".to_string() })
+            })
+            .collect::<Result<Vec<_>, MapProcessingError>>()?;
+
+        let terrain = AdaptiveRoomTerrain::from_bytes(reader.bytes)?;
+
+        Ok(Self { room, terrain, exits, kind: RoomKind::for_room(room), fingerprint, analyses })
+    }
+}
+
+/// Internal helper for reading fields from the front of a byte slice.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    /// Takes the next `len` bytes.
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], MapProcessingError> {
+        if self.bytes.len() < len {
+            return Err(MapProcessingError::InvalidLength { kind: "room record", expected: len, found: self.bytes.len() });
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    /// Takes the next `N` bytes as an array.
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], MapProcessingError> {
+        // take_slice returns exactly N bytes on success, so this conversion can't fail
+        Ok(self.take_slice(N)?.try_into().expect("slice should have N bytes"))
+    }
+}
+
+/// Internal helper function to convert an [ExitDirection] value back into a direction.
+fn direction_from_u8(value: u8) -> Option<ExitDirection> {
+    match value {
+        1 => Some(ExitDirection::Top),
+        3 => Some(ExitDirection::Right),
+        5 => Some(ExitDirection::Bottom),
+        7 => Some(ExitDirection::Left),
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "db")]
+    use rusqlite::Connection;
+    use screeps::ROOM_AREA;
+    #[cfg(feature = "db")]
+    use screeps::{RoomXY, Terrain};
+    #[cfg(feature = "db")]
+    use crate::adaptive_terrain::TerrainEncoding;
+    #[cfg(feature = "db")]
+    use crate::compressed_terrain_db;
+
+    #[cfg(feature = "db")]
+    #[test]
+    pub fn room_record_round_trips_through_db() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..50].fill(1); // Wall off the top edge
+        bits[1234] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let room = RoomName::new("W5N5").unwrap();
+        let record = RoomRecord::new_from_compressed_terrain(room, &terrain)
+            .with_analyses(vec!["maze_complexity".to_string()]);
+        assert_eq!(record.exits().len(), 3);
+        assert_eq!(record.kind(), RoomKind::Center);
+
+        let conn = Connection::open_in_memory().unwrap();
+        compressed_terrain_db::create_room_record_table_if_not_exists(&conn).unwrap();
+        compressed_terrain_db::add_room_record(&conn, &record).unwrap();
+        let loaded = compressed_terrain_db::get_room_record(&conn, room).unwrap().unwrap();
+
+        assert_eq!(loaded.room(), room);
+        assert_eq!(loaded.fingerprint(), terrain.fingerprint());
+        assert_eq!(loaded.exits(), record.exits());
+        assert_eq!(loaded.kind(), RoomKind::Center);
+        assert_eq!(loaded.analyses(), record.analyses());
+        assert_ne!(loaded.terrain().encoding(), TerrainEncoding::Compressed);
+        assert_eq!(loaded.terrain().get_xy(RoomXY::checked_new(34, 24).unwrap()), Terrain::Swamp);
+        assert_eq!(loaded.terrain().to_bytes(), record.terrain().to_bytes());
+
+        assert!(compressed_terrain_db::get_room_record(&conn, RoomName::new("W1N1").unwrap()).unwrap().is_none());
+        assert!(RoomRecord::from_bytes(&record.to_bytes().unwrap()[..20]).is_err());
+    }
+
+    #[test]
+    pub fn room_record_to_bytes_enforces_analysis_limits() {
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let record = RoomRecord::new_from_compressed_terrain(RoomName::new("W1N1").unwrap(), &terrain);
+
+        let longest_name = "a".repeat(MAX_ANALYSIS_NAME_LEN);
+        let bytes = record.clone().with_analyses(vec![longest_name.clone(); MAX_ANALYSES]).to_bytes().unwrap();
+        assert_eq!(RoomRecord::from_bytes(&bytes).unwrap().analyses().len(), MAX_ANALYSES);
+
+        assert!(record.clone().with_analyses(vec!["a".to_string(); MAX_ANALYSES + 1]).to_bytes().is_err());
+        assert!(record.with_analyses(vec![longest_name + "a"]).to_bytes().is_err());
+    }
+}
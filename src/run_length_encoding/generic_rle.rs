@@ -130,6 +130,25 @@ impl<T: Clone + Eq, S: Copy + PartialEq + PartialOrd> BinarySearchRLE<T, S> {
         self.vec.len()
     }
 
+    /// Returns an iterator over the runs in the sequence, in order.
+    pub fn iter_runs(&self) -> impl Iterator<Item = &IndexedRLE<T, S>> {
+        self.vec.iter()
+    }
+
+    /// Returns an iterator over the runs in the sequence, in order, starting from the run that
+    /// contains `index`.
+    ///
+    /// If `index` is before the start of the first run, this starts from the first run.
+    pub fn iter_runs_from(&self, index: S) -> impl Iterator<Item = &IndexedRLE<T, S>> {
+        let first = self.vec.partition_point(|run| run.start <= index).saturating_sub(1);
+        self.vec[first..].iter()
+    }
+
+    /// Calls `f` with each run in the sequence, in order, without allocating.
+    pub fn for_each_run(&self, f: impl FnMut(&IndexedRLE<T, S>)) {
+        self.vec.iter().for_each(f);
+    }
+
     /// The amount of memory it takes to store this data.
     pub fn memory_size(&self) -> usize {
         self.vec.len() * size_of::<IndexedRLE<T, S>>() + size_of::<Vec<IndexedRLE<T, S>>>()
@@ -1,10 +1,13 @@
 
+use std::ops::Range;
+
 use crate::run_length_encoding::generic_rle::BinarySearchRLE;
 
-use screeps::{Terrain, LocalRoomTerrain, RoomXY, ROOM_AREA};
+use screeps::{Terrain, LocalRoomTerrain, RoomCoordinate, RoomXY, ROOM_AREA, ROOM_USIZE};
 use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
 
 use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::terrain_source::TerrainCounts;
 
 /// RLE-encoded room terrain data, using the [generic_rle](crate::run_length_encoding::generic_rle)
 /// submodule.
@@ -51,6 +54,58 @@ impl RLERoomTerrain {
         self.data.num_runs()
     }
 
+    /// Gets the terrain for every tile in the specified row.
+    ///
+    /// The row is decoded by walking the runs that cover it, after a single search for the first
+    /// one.
+    pub fn get_row(&self, y: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        let start = y.u8() as usize * ROOM_USIZE;
+        let mut row = [Terrain::Plain; ROOM_USIZE];
+        super::decode_run_range(self.data.iter_runs_from(start as u16).map(|run| (run.token, run.start as usize)), start, &mut row);
+        row
+    }
+
+    /// Gets the terrain for every tile in the specified column.
+    ///
+    /// Tiles in a column aren't contiguous in the runs, so each tile is searched for individually.
+    pub fn get_col(&self, x: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        std::array::from_fn(|y| {
+            // Safety: y is always in the range [0, 49], since it's indexing a column
+            let y_coord = unsafe { RoomCoordinate::unchecked_new(y as u8) };
+            self.get_xy(RoomXY::new(x, y_coord))
+        })
+    }
+
+    /// Counts the tiles of each kind of terrain in the room, using the length of each run.
+    pub fn terrain_counts(&self) -> TerrainCounts {
+        super::count_run_tiles(self.data.iter_runs().map(|run| (run.token, run.start as usize)))
+    }
+
+    /// Returns an iterator over every tile in the room and its terrain, in row-major order.
+    ///
+    /// Tiles are expanded from each run in turn, rather than searched for individually.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        super::iter_run_tiles(self.data.iter_runs().map(|run| (run.token, run.start as usize)))
+    }
+
+    /// Calls `f` with the terrain and the range of linear terrain indices covered by each run, in
+    /// order, without allocating.
+    pub fn for_each_run(&self, mut f: impl FnMut(Terrain, Range<usize>)) {
+        // Runs only store their start, so each run is emitted once the next one starts
+        let mut previous: Option<(Terrain, usize)> = None;
+        self.data.for_each_run(|run| {
+            let start = run.start as usize;
+            if let Some((terrain, previous_start)) = previous {
+                f(terrain, previous_start..start);
+            }
+            previous = Some((run.token, start));
+        });
+
+        if let Some((terrain, start)) = previous {
+            f(terrain, start..ROOM_AREA);
+        }
+    }
+
     /// The amount of memory it takes to store this data.
     pub fn memory_size(&self) -> usize {
         self.data.memory_size()
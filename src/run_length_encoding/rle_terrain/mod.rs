@@ -1,5 +1,10 @@
 //! Provides Terrain-specific implementations of Run Length Encoding.
 
+use screeps::{RoomXY, Terrain, ROOM_AREA};
+use screeps::local::terrain_index_to_xy;
+
+use crate::terrain_source::TerrainCounts;
+
 mod generic_rle_terrain;
 mod packed_rle_terrain;
 mod wildcard_rle_terrain;
@@ -8,3 +13,63 @@ pub use generic_rle_terrain::*;
 pub use packed_rle_terrain::*;
 pub use wildcard_rle_terrain::*;
 
+/// Internal helper function to expand `(terrain, start index)` runs, in ascending order, into every
+/// tile of the room.
+///
+/// Tiles before the first run take the first run's terrain.
+fn iter_run_tiles(runs: impl Iterator<Item = (Terrain, usize)>) -> impl Iterator<Item = (RoomXY, Terrain)> {
+    let mut runs = runs.peekable();
+    let mut current = runs.peek().map(|(terrain, _)| *terrain).unwrap_or(Terrain::Wall);
+
+    (0..ROOM_AREA).map(move |idx| {
+        while let Some(&(terrain, start)) = runs.peek() {
+            if start > idx {
+                break;
+            }
+            current = terrain;
+            runs.next();
+        }
+        (terrain_index_to_xy(idx), current)
+    })
+}
+
+/// Internal helper function to decode the tiles starting at linear index `start` into `out`, from
+/// `(terrain, start index)` runs in ascending order.
+///
+/// The runs should start with the run containing `start`; tiles before the first run take the
+/// first run's terrain.
+fn decode_run_range(runs: impl Iterator<Item = (Terrain, usize)>, start: usize, out: &mut [Terrain]) {
+    let mut runs = runs.peekable();
+    let mut current = runs.peek().map(|(terrain, _)| *terrain).unwrap_or(Terrain::Wall);
+
+    for (offset, tile) in out.iter_mut().enumerate() {
+        while let Some(&(terrain, run_start)) = runs.peek() {
+            if run_start > start + offset {
+                break;
+            }
+            current = terrain;
+            runs.next();
+        }
+        *tile = current;
+    }
+}
+
+/// Internal helper function to count the tiles of each kind of terrain from `(terrain, start index)`
+/// runs in ascending order, using the length of each run.
+///
+/// Tiles before the first run take the first run's terrain.
+fn count_run_tiles(runs: impl Iterator<Item = (Terrain, usize)>) -> TerrainCounts {
+    let mut counts = TerrainCounts::default();
+    let mut previous: Option<(Terrain, usize)> = None;
+    for (terrain, start) in runs {
+        match previous {
+            Some((previous_terrain, previous_start)) => counts.add(previous_terrain, start - previous_start),
+            None => counts.add(terrain, start),
+        }
+        previous = Some((terrain, start));
+    }
+    if let Some((terrain, start)) = previous {
+        counts.add(terrain, ROOM_AREA - start);
+    }
+    counts
+}
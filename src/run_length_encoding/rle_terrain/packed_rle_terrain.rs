@@ -1,14 +1,17 @@
 use std::mem::size_of;
+use std::ops::Range;
 
 use rle::{AppendRle, MergableSpan};
 
-use screeps::{Terrain, LocalRoomTerrain, RoomXY, ROOM_AREA};
+use screeps::{Terrain, LocalRoomTerrain, RoomCoordinate, RoomXY, ROOM_AREA, ROOM_USIZE};
 use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
 
 use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::terrain_source::TerrainCounts;
 
 /// Specialized struct that encodes a run for [Terrain](screeps::Terrain), storing data in a bit-packed format.
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct RoomTerrainPackedIndexedRLE {
     /// The compressed internal representation of the run data.
     ///
@@ -99,6 +102,7 @@ impl MergableSpan for RoomTerrainPackedIndexedRLE {
 /// Encodes the terrain for a room in a run length encoded search tree.
 ///
 /// O(lg(n)) search performance
+#[derive(Debug, Clone)]
 pub struct BinarySearchPackedRoomTerrainRLE {
     vec: Vec<RoomTerrainPackedIndexedRLE>,
 }
@@ -170,11 +174,61 @@ impl BinarySearchPackedRoomTerrainRLE {
         }
     }
 
+    /// Creates a search tree from the packed representations of its runs, as returned by
+    /// [packed_runs](BinarySearchPackedRoomTerrainRLE::packed_runs).
+    ///
+    /// Returns an error if a run has an invalid terrain or start index, or if the runs aren't in
+    /// strictly ascending order of start index.
+    pub fn try_new_from_packed_runs(packed: &[u16]) -> Result<Self, MapProcessingError> {
+        let invalid = |reason: String| MapProcessingError::InvalidData { kind: "RLE terrain", reason };
+        let mut vec = Vec::with_capacity(packed.len());
+
+        for packed_run in packed {
+            if packed_run >> 12 > 2 {
+                return Err(invalid(format!("invalid run {packed_run:#06x}")));
+            }
+            let run = RoomTerrainPackedIndexedRLE::new_from_packed_repr(*packed_run);
+            if run.start() as usize >= ROOM_AREA {
+                return Err(invalid(format!("run starts past the end of the room at {}", run.start())));
+            }
+            if vec.last().is_some_and(|last: &RoomTerrainPackedIndexedRLE| last.start() >= run.start()) {
+                return Err(invalid(format!("run at {} is out of order", run.start())));
+            }
+            vec.push(run);
+        }
+
+        Ok(Self { vec })
+    }
+
+    /// The packed representations of the runs in the search tree, in order.
+    pub fn packed_runs(&self) -> Vec<u16> {
+        self.vec.iter().map(|run| run.packed_repr()).collect()
+    }
+
     /// Returns the number of runs in the search tree.
     pub fn num_runs(&self) -> usize {
         self.vec.len()
     }
 
+    /// Returns an iterator over the runs in the search tree, in order.
+    pub fn iter_runs(&self) -> impl Iterator<Item = &RoomTerrainPackedIndexedRLE> {
+        self.vec.iter()
+    }
+
+    /// Returns an iterator over the runs in the search tree, in order, starting from the run that
+    /// contains `index`.
+    ///
+    /// If `index` is before the start of the first run, this starts from the first run.
+    pub fn iter_runs_from(&self, index: u16) -> impl Iterator<Item = &RoomTerrainPackedIndexedRLE> {
+        let first = self.vec.partition_point(|run| run.start() <= index).saturating_sub(1);
+        self.vec[first..].iter()
+    }
+
+    /// Calls `f` with each run in the search tree, in order, without allocating.
+    pub fn for_each_run(&self, f: impl FnMut(&RoomTerrainPackedIndexedRLE)) {
+        self.vec.iter().for_each(f);
+    }
+
     /// Returns the token of the last run in the search tree.
     ///
     /// Returns None if the search tree is empty.
@@ -203,6 +257,7 @@ impl BinarySearchPackedRoomTerrainRLE {
 /// User-friendly interface for getting terrain data.
 ///
 /// Uses [BinarySearchPackedRoomTerrainRLE] internally to store data efficiently.
+#[derive(Debug, Clone)]
 pub struct PackedRLERoomTerrain {
     data: BinarySearchPackedRoomTerrainRLE,
 }
@@ -234,6 +289,23 @@ impl PackedRLERoomTerrain {
         Self { data }
     }
 
+    /// Creates terrain from the packed representations of its runs, as returned by
+    /// [packed_runs](PackedRLERoomTerrain::packed_runs).
+    ///
+    /// Returns an error if the runs are invalid, or don't start at the first tile of the room.
+    pub fn try_new_from_packed_runs(packed: &[u16]) -> Result<Self, MapProcessingError> {
+        let data = BinarySearchPackedRoomTerrainRLE::try_new_from_packed_runs(packed)?;
+        if data.iter_runs().next().is_none_or(|run| run.start() != 0) {
+            return Err(MapProcessingError::InvalidData { kind: "RLE terrain", reason: "runs don't start at the first tile".to_string() });
+        }
+        Ok(Self { data })
+    }
+
+    /// The packed representations of the runs, in order.
+    pub fn packed_runs(&self) -> Vec<u16> {
+        self.data.packed_runs()
+    }
+
     /// Gets the terrain value for the specified tile.
     pub fn get_xy(&self, xy: RoomXY) -> Terrain {
         let idx = xy_to_terrain_index(xy);
@@ -246,6 +318,58 @@ impl PackedRLERoomTerrain {
         self.data.num_runs()
     }
 
+    /// Gets the terrain for every tile in the specified row.
+    ///
+    /// The row is decoded by walking the runs that cover it, after a single search for the first
+    /// one.
+    pub fn get_row(&self, y: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        let start = y.u8() as usize * ROOM_USIZE;
+        let mut row = [Terrain::Plain; ROOM_USIZE];
+        super::decode_run_range(self.data.iter_runs_from(start as u16).map(|run| (run.terrain(), run.start() as usize)), start, &mut row);
+        row
+    }
+
+    /// Gets the terrain for every tile in the specified column.
+    ///
+    /// Tiles in a column aren't contiguous in the runs, so each tile is searched for individually.
+    pub fn get_col(&self, x: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        std::array::from_fn(|y| {
+            // Safety: y is always in the range [0, 49], since it's indexing a column
+            let y_coord = unsafe { RoomCoordinate::unchecked_new(y as u8) };
+            self.get_xy(RoomXY::new(x, y_coord))
+        })
+    }
+
+    /// Counts the tiles of each kind of terrain in the room, using the length of each run.
+    pub fn terrain_counts(&self) -> TerrainCounts {
+        super::count_run_tiles(self.data.iter_runs().map(|run| (run.terrain(), run.start() as usize)))
+    }
+
+    /// Returns an iterator over every tile in the room and its terrain, in row-major order.
+    ///
+    /// Tiles are expanded from each run in turn, rather than searched for individually.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        super::iter_run_tiles(self.data.iter_runs().map(|run| (run.terrain(), run.start() as usize)))
+    }
+
+    /// Calls `f` with the terrain and the range of linear terrain indices covered by each run, in
+    /// order, without allocating.
+    pub fn for_each_run(&self, mut f: impl FnMut(Terrain, Range<usize>)) {
+        // Runs only store their start, so each run is emitted once the next one starts
+        let mut previous: Option<(Terrain, usize)> = None;
+        self.data.for_each_run(|run| {
+            let start = run.start() as usize;
+            if let Some((terrain, previous_start)) = previous {
+                f(terrain, previous_start..start);
+            }
+            previous = Some((run.terrain(), start));
+        });
+
+        if let Some((terrain, start)) = previous {
+            f(terrain, start..ROOM_AREA);
+        }
+    }
+
     /// The amount of memory it takes to store this data.
     pub fn memory_size(&self) -> usize {
         self.data.memory_size()
@@ -295,6 +419,24 @@ mod test {
         assert_eq!(Terrain::Swamp, tile_type, "Terrain mismatch at {xy}");
     }
 
+    #[test]
+    pub fn packed_rle_terrain_for_each_run_covers_the_room() {
+        let mut raw_terrain_data = [0; ROOM_AREA];
+        raw_terrain_data[10..20].fill(2);
+        raw_terrain_data[ROOM_AREA - 1] = 1;
+        let compressed_terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&raw_terrain_data);
+        let terrain = PackedRLERoomTerrain::new_from_compressed_terrain(&compressed_terrain);
+
+        let mut runs = Vec::new();
+        terrain.for_each_run(|tile, range| runs.push((tile, range)));
+        assert_eq!(runs, [
+            (Terrain::Plain, 0..10),
+            (Terrain::Swamp, 10..20),
+            (Terrain::Plain, 20..ROOM_AREA - 1),
+            (Terrain::Wall, ROOM_AREA - 1..ROOM_AREA),
+        ]);
+    }
+
     #[test]
     pub fn packed_rle_terrain_get_xy_matches_uncompressed_terrain() {
         // Initialize terrain to be all plains
@@ -1,9 +1,11 @@
 //! Specialized room terrain that compresses data using Run Length Encoding and wildcards.
 
-use screeps::{Terrain, LocalRoomTerrain, RoomXY, ROOM_AREA};
+use screeps::{Terrain, LocalRoomTerrain, RoomCoordinate, RoomXY, ROOM_AREA, ROOM_SIZE, ROOM_USIZE};
 use screeps::local::{terrain_index_to_xy, xy_to_terrain_index};
 
 use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::terrain_source::TerrainCounts;
 use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
 use super::BinarySearchPackedRoomTerrainRLE;
 
@@ -13,6 +15,7 @@ use super::BinarySearchPackedRoomTerrainRLE;
 /// Uses [BinarySearchPackedRoomTerrainRLE] internally to store data efficiently, while also using
 /// [RoomEdgeTerrain] to store edge terrain data compactly, allowing for all edge tiles to be
 /// considered wildcards in the RLE terrain data.
+#[derive(Debug, Clone)]
 pub struct WildcardRLERoomTerrain {
     data: BinarySearchPackedRoomTerrainRLE,
     edge_data: RoomEdgeTerrain,
@@ -119,12 +122,12 @@ impl WildcardRLERoomTerrain {
                         right_edge_terrain.push(Terrain::Wall);
                     },
                     (1..=48, 0) => {
-                        // Left edge
-                        left_edge_terrain.push(tile);
+                        // Top edge
+                        top_edge_terrain.push(tile);
                     },
                     (1..=48, 49) => {
-                        // Right edge
-                        right_edge_terrain.push(tile);
+                        // Bottom edge
+                        bottom_edge_terrain.push(tile);
                     },
                     (0, 1..=48) => {
                         // Left edge
@@ -149,6 +152,31 @@ impl WildcardRLERoomTerrain {
         Self { data, edge_data }
     }
 
+    /// Creates terrain from its edge terrain and the packed representations of its runs, as
+    /// returned by [edge_terrain](WildcardRLERoomTerrain::edge_terrain) and
+    /// [packed_runs](WildcardRLERoomTerrain::packed_runs).
+    ///
+    /// Returns an error if the runs are invalid, or start after the first interior tile.
+    pub fn try_new_from_packed_runs(edge_data: RoomEdgeTerrain, packed: &[u16]) -> Result<Self, MapProcessingError> {
+        const FIRST_INTERIOR_INDEX: u16 = ROOM_SIZE as u16 + 1;
+
+        let data = BinarySearchPackedRoomTerrainRLE::try_new_from_packed_runs(packed)?;
+        if data.iter_runs().next().is_none_or(|run| run.start() > FIRST_INTERIOR_INDEX) {
+            return Err(MapProcessingError::InvalidData { kind: "RLE terrain", reason: "runs don't cover the first interior tile".to_string() });
+        }
+        Ok(Self { data, edge_data })
+    }
+
+    /// The terrain along the edges of the room.
+    pub fn edge_terrain(&self) -> &RoomEdgeTerrain {
+        &self.edge_data
+    }
+
+    /// The packed representations of the runs for the interior of the room, in order.
+    pub fn packed_runs(&self) -> Vec<u16> {
+        self.data.packed_runs()
+    }
+
     /// Gets the terrain value for the specified tile.
     pub fn get_xy(&self, xy: RoomXY) -> Terrain {
         if xy.is_room_edge() {
@@ -160,6 +188,69 @@ impl WildcardRLERoomTerrain {
         }
     }
 
+    /// Gets the terrain for every tile in the specified row.
+    ///
+    /// Interior tiles are decoded by walking the runs that cover the row, after a single search
+    /// for the first one; edge tiles come from the edge terrain.
+    pub fn get_row(&self, y: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        let start = y.u8() as usize * ROOM_USIZE;
+        let mut row = [Terrain::Plain; ROOM_USIZE];
+        super::decode_run_range(self.data.iter_runs_from(start as u16).map(|run| (run.terrain(), run.start() as usize)), start, &mut row);
+
+        for (x, tile) in row.iter_mut().enumerate() {
+            // Safety: x is always in the range [0, 49], since it's enumerating a row
+            let xy = RoomXY::new(unsafe { RoomCoordinate::unchecked_new(x as u8) }, y);
+            if xy.is_room_edge() {
+                *tile = self.edge_data.get_xy(xy).unwrap_or(Terrain::Wall);
+            }
+        }
+        row
+    }
+
+    /// Gets the terrain for every tile in the specified column.
+    ///
+    /// Tiles in a column aren't contiguous in the runs, so each tile is searched for individually.
+    pub fn get_col(&self, x: RoomCoordinate) -> [Terrain; ROOM_USIZE] {
+        std::array::from_fn(|y| {
+            // Safety: y is always in the range [0, 49], since it's indexing a column
+            let y_coord = unsafe { RoomCoordinate::unchecked_new(y as u8) };
+            self.get_xy(RoomXY::new(x, y_coord))
+        })
+    }
+
+    /// Counts the tiles of each kind of terrain in the room.
+    ///
+    /// Interior tiles are counted using the length of each run, then the edge tiles are corrected
+    /// using the edge terrain.
+    pub fn terrain_counts(&self) -> TerrainCounts {
+        let mut counts = super::count_run_tiles(self.data.iter_runs().map(|run| (run.terrain(), run.start() as usize)));
+
+        let edges = (0..ROOM_SIZE).flat_map(|i| [(i, 0), (i, ROOM_SIZE - 1)])
+            .chain((1..ROOM_SIZE - 1).flat_map(|i| [(0, i), (ROOM_SIZE - 1, i)]));
+        for (x, y) in edges {
+            // Safety: x and y are both in the range [0, 49]
+            let xy = unsafe { RoomXY::unchecked_new(x, y) };
+            // Edge tiles may come before the first run, which counted them with its own terrain
+            // Safety: We'll always be populated with data, so there will always be a run
+            let run = self.data.iter_runs_from(xy_to_terrain_index(xy) as u16).next().unwrap();
+            counts.remove(run.terrain(), 1);
+            counts.add(self.edge_data.get_xy(xy).unwrap_or(Terrain::Wall), 1);
+        }
+        counts
+    }
+
+    /// Returns an iterator over every tile in the room and its terrain, in row-major order.
+    ///
+    /// Interior tiles are expanded from each run in turn, rather than searched for individually.
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        super::iter_run_tiles(self.data.iter_runs().map(|run| (run.terrain(), run.start() as usize)))
+            .map(|(xy, tile)| if xy.is_room_edge() {
+                (xy, self.edge_data.get_xy(xy).unwrap_or(Terrain::Wall))
+            } else {
+                (xy, tile)
+            })
+    }
+
     /// Returns the number of distinct runs contained.
     pub fn num_runs(&self) -> usize {
         self.data.num_runs()
@@ -177,6 +268,7 @@ mod test {
     use super::*;
     use screeps::constants::{ROOM_AREA, ROOM_SIZE};
 	use screeps::{LocalRoomTerrain, xy_to_terrain_index, RoomXY};
+    use crate::run_length_encoding::rle_terrain::{PackedRLERoomTerrain, RLERoomTerrain};
     use itertools::Itertools;
 
     #[test]
@@ -234,4 +326,74 @@ mod test {
             }
         }
     }
+
+    #[test]
+    pub fn iter_tiles_matches_get_xy_for_all_encodings() {
+        let mut raw_terrain_data = Box::new([0; ROOM_AREA]);
+        for (i, tile) in raw_terrain_data.iter_mut().enumerate() {
+            *tile = ((i / 7) % 3) as u8;
+        }
+        let terrain = LocalRoomTerrain::new_from_bits(raw_terrain_data);
+        let compressed = CompressedRoomTerrain::new_from_uncompressed_bits(terrain.get_bits());
+
+        let wildcard = WildcardRLERoomTerrain::new_from_uncompressed_terrain(&terrain);
+        let packed = crate::run_length_encoding::rle_terrain::PackedRLERoomTerrain::new_from_uncompressed_terrain(&terrain);
+        let generic = crate::run_length_encoding::rle_terrain::RLERoomTerrain::new_from_uncompressed_terrain(&terrain);
+
+        let expected: Vec<_> = (0..ROOM_AREA).map(terrain_index_to_xy).map(|xy| (xy, terrain.get_xy(xy))).collect();
+        assert_eq!(compressed.iter_tiles().collect::<Vec<_>>(), expected);
+        assert_eq!(packed.iter_tiles().collect::<Vec<_>>(), expected);
+        assert_eq!(generic.iter_tiles().collect::<Vec<_>>(), expected);
+
+        let expected_wildcard: Vec<_> = (0..ROOM_AREA).map(terrain_index_to_xy).map(|xy| (xy, wildcard.get_xy(xy))).collect();
+        assert_eq!(wildcard.iter_tiles().collect::<Vec<_>>(), expected_wildcard);
+    }
+
+    #[test]
+    pub fn rle_terrain_get_row_and_col_match_get_xy() {
+        let mut bits = Box::new([0u8; ROOM_AREA]);
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i / 3 % 5 % 3) as u8;
+        }
+        let local = LocalRoomTerrain::new_from_bits(bits);
+        let wildcard = WildcardRLERoomTerrain::new_from_uncompressed_terrain(&local);
+        let packed = PackedRLERoomTerrain::new_from_uncompressed_terrain(&local);
+        let generic = RLERoomTerrain::new_from_uncompressed_terrain(&local);
+
+        // Edge swamps are stored as plains by the wildcard encoding, so each type is compared
+        // against its own get_xy
+        let get_xys: [&dyn Fn(RoomXY) -> Terrain; 3] = [&|xy| wildcard.get_xy(xy), &|xy| packed.get_xy(xy), &|xy| generic.get_xy(xy)];
+        for i in 0..ROOM_SIZE {
+            let coord = RoomCoordinate::new(i).unwrap();
+            let rows_and_cols = [
+                (wildcard.get_row(coord), wildcard.get_col(coord)),
+                (packed.get_row(coord), packed.get_col(coord)),
+                (generic.get_row(coord), generic.get_col(coord)),
+            ];
+            for ((row, col), get_xy) in rows_and_cols.into_iter().zip(get_xys) {
+                for j in 0..ROOM_SIZE {
+                    let other = RoomCoordinate::new(j).unwrap();
+                    assert_eq!(row[j as usize], get_xy(RoomXY::new(other, coord)));
+                    assert_eq!(col[j as usize], get_xy(RoomXY::new(coord, other)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn wildcard_rle_terrain_from_compressed_terrain_keeps_edges() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..ROOM_SIZE as usize].fill(1); // Wall off the top edge
+        bits[(ROOM_AREA - 10)..].fill(1); // And the right end of the bottom edge
+        bits[ROOM_AREA - ROOM_SIZE as usize] = 1; // Corners are always walls
+        let compressed = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let local = LocalRoomTerrain::new_from_bits(Box::new(bits));
+
+        let from_compressed = WildcardRLERoomTerrain::new_from_compressed_terrain(&compressed);
+        let from_local = WildcardRLERoomTerrain::new_from_uncompressed_terrain(&local);
+        assert_eq!(from_compressed.edge_terrain().get_raw_bytes(), from_local.edge_terrain().get_raw_bytes());
+        for (xy, tile) in from_compressed.iter_tiles() {
+            assert_eq!(tile, compressed.get_xy(xy), "{xy}");
+        }
+    }
 }
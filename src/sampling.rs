@@ -0,0 +1,144 @@
+//! Picks reproducible samples of rooms, for trying out expensive experiments on a representative
+//! subset of a shard before running them on every room.
+
+use std::collections::HashSet;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::RoomName;
+
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db;
+#[cfg(feature = "db")]
+use crate::error::MapProcessingError;
+use crate::room_connectivity::router::RoomKind;
+
+/// Every room kind, in the order strata are filled.
+const ROOM_KINDS: [RoomKind; 5] = [RoomKind::Normal, RoomKind::Highway, RoomKind::Crossroads, RoomKind::SourceKeeper, RoomKind::Center];
+
+/// Samples up to `n` of the rooms with terrain in the database; see [sample_room_names].
+#[cfg(feature = "db")]
+pub fn sample_rooms(conn: &Connection, n: usize, seed: u64, stratify_by_kind: bool) -> Result<Vec<RoomName>, MapProcessingError> {
+    let rooms = compressed_terrain_db::get_rooms_with_terrain(conn)?;
+    Ok(sample_room_names(&rooms, n, seed, stratify_by_kind))
+}
+
+/// Samples up to `n` rooms, returned in the same order as `rooms`.
+///
+/// Each room is ranked by a hash of its name and the seed, and the lowest ranked rooms are picked,
+/// so the same seed always picks the same rooms regardless of the order they're passed in, and
+/// growing `n` only ever adds rooms to the sample.
+///
+/// With `stratify_by_kind`, the sample is split between [RoomKind]s in proportion to how many
+/// rooms of each kind there are, using the Sainte-Laguë method, so growing `n` still only ever adds
+/// rooms. Otherwise, rare kinds like crossroads may be missing from small samples.
+pub fn sample_room_names(rooms: &[RoomName], n: usize, seed: u64, stratify_by_kind: bool) -> Vec<RoomName> {
+    let rank = |room: &RoomName| mix(seed ^ room.packed_repr() as u64);
+
+    let picked: HashSet<RoomName> = if stratify_by_kind {
+        let strata: Vec<Vec<RoomName>> = ROOM_KINDS.iter()
+            .map(|kind| rooms.iter().copied().filter(|room| RoomKind::for_room(*room) == *kind).collect())
+            .collect();
+        let quotas = proportional_quotas(&strata.iter().map(Vec::len).collect::<Vec<_>>(), n);
+
+        strata.into_iter().zip(quotas)
+            .flat_map(|(mut stratum, quota)| {
+                stratum.sort_by_key(rank);
+                stratum.truncate(quota);
+                stratum
+            })
+            .collect()
+    } else {
+        let mut ranked = rooms.to_vec();
+        ranked.sort_by_key(rank);
+        ranked.truncate(n);
+        ranked.into_iter().collect()
+    };
+
+    rooms.iter().copied().filter(|room| picked.contains(room)).collect()
+}
+
+/// Internal helper function to split `n` places between groups in proportion to their sizes,
+/// without giving any group more places than it has members.
+///
+/// Places are handed out one at a time to the group with the highest `size / (2 * quota + 1)`
+/// (the Sainte-Laguë method), with ties going to groups in order. Since the quotas for `n + 1`
+/// places are the quotas for `n` places plus one more, no group ever loses a place as `n` grows,
+/// which the largest remainder method doesn't guarantee.
+fn proportional_quotas(sizes: &[usize], n: usize) -> Vec<usize> {
+    let mut quotas = vec![0; sizes.len()];
+
+    for _ in 0..n {
+        // Compares size / (2 * quota + 1) between groups without dividing
+        let next = (0..sizes.len())
+            .filter(|idx| quotas[*idx] < sizes[*idx])
+            .reduce(|best, idx| if sizes[idx] * (2 * quotas[best] + 1) > sizes[best] * (2 * quotas[idx] + 1) { idx } else { best });
+        match next {
+            Some(idx) => quotas[idx] += 1,
+            None => break,
+        }
+    }
+
+    quotas
+}
+
+/// Internal helper function to scramble a value with the SplitMix64 finalizer.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn sample_room_names_is_reproducible_and_stratified() {
+        // A full sector, with its bordering highways
+        let rooms: Vec<RoomName> = (0..10).flat_map(|x| (0..10).map(move |y| RoomName::new(&format!("W{x}N{y}")).unwrap())).collect();
+
+        let sample = sample_room_names(&rooms, 10, 42, false);
+        assert_eq!(sample.len(), 10);
+        let reversed: Vec<RoomName> = rooms.iter().rev().copied().collect();
+        assert!(sample_room_names(&reversed, 10, 42, false).iter().all(|room| sample.contains(room)));
+        assert_ne!(sample_room_names(&rooms, 10, 43, false), sample);
+        assert!(sample.iter().all(|room| sample_room_names(&rooms, 20, 42, false).contains(room)));
+
+        let stratified = sample_room_names(&rooms, 10, 42, true);
+        assert_eq!(stratified.len(), 10);
+        let count = |kind| stratified.iter().filter(|room| RoomKind::for_room(**room) == kind).count();
+        // 72 normal rooms, 18 highways, 1 crossroads, 8 source keeper rooms, and 1 center room
+        assert_eq!(count(RoomKind::Normal), 7);
+        assert_eq!(count(RoomKind::Highway), 2);
+        assert_eq!(count(RoomKind::SourceKeeper), 1);
+        assert_eq!(count(RoomKind::Crossroads), 0);
+
+        assert_eq!(sample_room_names(&rooms, 1000, 42, true), rooms);
+    }
+
+    #[test]
+    pub fn proportional_quotas_never_shrink_as_places_grow() {
+        // The sizes of the strata of a full sector; the largest remainder method gives the
+        // crossroads a place for 28 rooms, and takes it away for 29
+        let sizes = [72, 18, 1, 8, 1];
+        let quotas_28 = proportional_quotas(&sizes, 28);
+        let quotas_29 = proportional_quotas(&sizes, 29);
+        assert_eq!(quotas_28.iter().sum::<usize>(), 28);
+        assert_eq!(quotas_29.iter().sum::<usize>(), 29);
+        assert!(quotas_28.iter().zip(&quotas_29).all(|(before, after)| before <= after));
+
+        for n in 0..100 {
+            let quotas = proportional_quotas(&sizes, n);
+            assert!(quotas.iter().zip(proportional_quotas(&sizes, n + 1)).all(|(before, after)| *before <= after));
+            assert!(quotas.iter().zip(&sizes).all(|(quota, size)| quota <= size));
+        }
+        assert_eq!(proportional_quotas(&sizes, 1000), sizes);
+
+        let rooms: Vec<RoomName> = (0..10).flat_map(|x| (0..10).map(move |y| RoomName::new(&format!("W{x}N{y}")).unwrap())).collect();
+        let sample_28 = sample_room_names(&rooms, 28, 7, true);
+        let sample_29 = sample_room_names(&rooms, 29, 7, true);
+        assert!(sample_28.iter().all(|room| sample_29.contains(room)));
+    }
+}
@@ -0,0 +1,190 @@
+//! Encodes room terrain as strings that can be stored in memory segments, or anywhere else bots
+//! can only keep strings.
+//!
+//! Bytes are packed 15 bits to a character, using the characters from `U+4E00` up, which are each a
+//! single UTF-16 code unit and never a surrogate, so a room's compressed terrain takes 335
+//! characters. The first character records how many padding bits the last one has.
+//!
+//! The segment layout written by [encode_room_terrains] starts with a
+//! [BYTE_LAYOUT_VERSION], followed by each room's packed [RoomName] as a little-endian u16 and its
+//! compressed terrain bytes.
+
+use screeps::RoomName;
+
+use crate::byte_layout::BYTE_LAYOUT_VERSION;
+use crate::compressed_terrain::compressed_terrain::{CompressedRoomTerrain, COMPRESSED_ARRAY_SIZE};
+use crate::error::MapProcessingError;
+
+/// The most characters a memory segment can hold.
+pub const MAX_SEGMENT_LENGTH: usize = 100 * 1024;
+
+/// The character that encodes 0; every character in an encoded string is at most 2^15 - 1 above it.
+const FIRST_CHAR: u32 = 0x4E00;
+
+/// The number of bits stored in each character.
+const BITS_PER_CHAR: u32 = 15;
+
+/// The number of bytes each room takes up in a segment.
+const ROOM_ENTRY_SIZE: usize = 2 + COMPRESSED_ARRAY_SIZE;
+
+/// Encodes arbitrary bytes as a string.
+pub fn encode_bytes(bytes: &[u8]) -> String {
+    let total_bits = bytes.len() as u32 * 8;
+    let padding = (BITS_PER_CHAR - total_bits % BITS_PER_CHAR) % BITS_PER_CHAR;
+
+    let mut encoded = String::with_capacity((total_bits / BITS_PER_CHAR + 2) as usize * 3);
+    encoded.push(char_for_value(padding));
+
+    let mut buffer = 0u32;
+    let mut buffered_bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        buffered_bits += 8;
+        if buffered_bits >= BITS_PER_CHAR {
+            buffered_bits -= BITS_PER_CHAR;
+            encoded.push(char_for_value(buffer >> buffered_bits));
+            buffer &= (1 << buffered_bits) - 1;
+        }
+    }
+    if buffered_bits > 0 {
+        encoded.push(char_for_value(buffer << (BITS_PER_CHAR - buffered_bits)));
+    }
+    encoded
+}
+
+/// Decodes bytes written by [encode_bytes].
+pub fn decode_bytes(encoded: &str) -> Result<Vec<u8>, MapProcessingError> {
+    let mut values = encoded.chars().map(value_for_char);
+    let padding = values.next().ok_or(MapProcessingError::InvalidLength { kind: "segment string", expected: 1, found: 0 })??;
+    let values = values.collect::<Result<Vec<_>, _>>()?;
+
+    let total_bits = values.len() as u32 * BITS_PER_CHAR;
+    if padding >= BITS_PER_CHAR || padding > total_bits || !(total_bits - padding).is_multiple_of(8) {
+        return Err(MapProcessingError::InvalidData { kind: "segment string", reason: format!("invalid padding of {padding} bits") });
+    }
+
+    let num_bytes = ((total_bits - padding) / 8) as usize;
+    let mut bytes = Vec::with_capacity(num_bytes);
+    let mut buffer = 0u32;
+    let mut buffered_bits = 0;
+    for value in values {
+        buffer = (buffer << BITS_PER_CHAR) | value;
+        buffered_bits += BITS_PER_CHAR;
+        while buffered_bits >= 8 && bytes.len() < num_bytes {
+            buffered_bits -= 8;
+            bytes.push((buffer >> buffered_bits) as u8);
+            buffer &= (1 << buffered_bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+/// Encodes a room's terrain as a string.
+pub fn encode_terrain(terrain: &CompressedRoomTerrain) -> String {
+    encode_bytes(terrain.get_compressed_bytes())
+}
+
+/// Decodes a room's terrain written by [encode_terrain].
+pub fn decode_terrain(encoded: &str) -> Result<CompressedRoomTerrain, MapProcessingError> {
+    CompressedRoomTerrain::try_new_from_compressed_slice(&decode_bytes(encoded)?)
+}
+
+/// Encodes the terrain of several rooms as a single segment string.
+///
+/// Every room takes a little over 334 characters, so a segment of [MAX_SEGMENT_LENGTH] holds up to
+/// 306 rooms.
+pub fn encode_room_terrains<'a>(rooms: impl IntoIterator<Item = (RoomName, &'a CompressedRoomTerrain)>) -> String {
+    let mut bytes = vec![BYTE_LAYOUT_VERSION];
+    for (room, terrain) in rooms {
+        bytes.extend(room.packed_repr().to_le_bytes());
+        bytes.extend(terrain.get_compressed_bytes());
+    }
+    encode_bytes(&bytes)
+}
+
+/// Decodes the rooms written by [encode_room_terrains].
+pub fn decode_room_terrains(encoded: &str) -> Result<Vec<(RoomName, CompressedRoomTerrain)>, MapProcessingError> {
+    let bytes = decode_bytes(encoded)?;
+    let data = match bytes.split_first() {
+        Some((&BYTE_LAYOUT_VERSION, data)) => data,
+        Some((version, _)) => return Err(MapProcessingError::InvalidData { kind: "terrain segment", reason: format!("unsupported layout version {version}") }),
+        None => return Err(MapProcessingError::InvalidLength { kind: "terrain segment", expected: 1, found: 0 }),
+    };
+    if !data.len().is_multiple_of(ROOM_ENTRY_SIZE) {
+        return Err(MapProcessingError::InvalidLength { kind: "terrain segment", expected: data.len().next_multiple_of(ROOM_ENTRY_SIZE), found: data.len() });
+    }
+
+    data.chunks_exact(ROOM_ENTRY_SIZE)
+        .map(|entry| {
+            let room = RoomName::from_packed(u16::from_le_bytes([entry[0], entry[1]]));
+            Ok((room, CompressedRoomTerrain::try_new_from_compressed_slice(&entry[2..])?))
+        })
+        .collect()
+}
+
+/// Internal helper function to get the character that encodes a 15 bit value.
+fn char_for_value(value: u32) -> char {
+    // Every value up to 2^15 - 1 lands below the surrogates at U+D800
+    char::from_u32(FIRST_CHAR + value).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Internal helper function to get the 15 bit value a character encodes.
+fn value_for_char(c: char) -> Result<u32, MapProcessingError> {
+    (c as u32).checked_sub(FIRST_CHAR)
+        .filter(|value| *value < 1 << BITS_PER_CHAR)
+        .ok_or_else(|| MapProcessingError::InvalidData { kind: "segment string", reason: format!("unexpected character {c:?}") })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn segment_codec_round_trips_bytes_of_every_length() {
+        for len in 0..40usize {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37 + 11) as u8).collect();
+            let encoded = encode_bytes(&bytes);
+            assert_eq!(encoded.chars().count(), 1 + (len * 8).div_ceil(15));
+            assert!(encoded.encode_utf16().all(|unit| !(0xD800..0xE000).contains(&unit)));
+            assert_eq!(decode_bytes(&encoded).unwrap(), bytes);
+        }
+        assert_eq!(decode_bytes(&encode_bytes(&[0xFF; 15])).unwrap(), [0xFF; 15]);
+    }
+
+    #[test]
+    pub fn segment_codec_round_trips_room_terrains() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..50].fill(1);
+        bits[1234] = 2;
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0; ROOM_AREA]);
+        let rooms = [(RoomName::new("W1N1").unwrap(), &walls), (RoomName::new("E5S9").unwrap(), &plains)];
+
+        let encoded = encode_terrain(&walls);
+        assert_eq!(encoded.chars().count(), 335);
+        assert_eq!(decode_terrain(&encoded).unwrap().get_compressed_bytes(), walls.get_compressed_bytes());
+
+        let segment = encode_room_terrains(rooms);
+        assert_eq!(segment.chars().count(), 1 + ((1 + 2 * ROOM_ENTRY_SIZE) * 8).div_ceil(15));
+        let decoded = decode_room_terrains(&segment).unwrap();
+        assert_eq!(decoded.len(), 2);
+        for ((room, terrain), (decoded_room, decoded_terrain)) in rooms.iter().zip(&decoded) {
+            assert_eq!(room, decoded_room);
+            assert_eq!(terrain.get_compressed_bytes(), decoded_terrain.get_compressed_bytes());
+        }
+        assert!(decode_room_terrains(&encode_room_terrains([])).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn segment_codec_rejects_malformed_strings() {
+        assert!(decode_bytes("").is_err());
+        assert!(decode_bytes("abc").is_err());
+        // 15 padding bits would be a whole extra character
+        assert!(decode_bytes(&format!("{}{}", char_for_value(15), char_for_value(0))).is_err());
+        assert!(decode_terrain(&encode_bytes(&[0; 10])).is_err());
+        assert!(decode_room_terrains(&encode_bytes(&[2])).is_err());
+        assert!(decode_room_terrains(&encode_bytes(&[BYTE_LAYOUT_VERSION, 0, 0, 0])).is_err());
+    }
+}
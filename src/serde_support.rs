@@ -0,0 +1,240 @@
+//! Serde support for the compressed terrain types.
+//!
+//! Types are serialized using their compact byte representations rather than tile by tile, so
+//! [CompressedRoomTerrain] takes 625 bytes, [RoomEdgeTerrain] takes 24 bytes, and [TerrainPyramid]
+//! takes 32 bytes. The RLE terrain types and [AdaptiveRoomTerrain] use the same bytes as
+//! [AdaptiveRoomTerrain::to_encoded_bytes] and [AdaptiveRoomTerrain::to_bytes] respectively.
+//! Formats without native byte strings, like JSON, store the bytes as an array of numbers.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use screeps::RoomName;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::adaptive_terrain::AdaptiveRoomTerrain;
+use crate::byte_layout::{u16s_from_le_bytes, u16s_to_le_bytes};
+use crate::compressed_terrain::compressed_room_edge_terrain::RoomEdgeTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+use crate::room_connectivity::exit::{RoomExit, RoomExitsData};
+use crate::run_length_encoding::rle_terrain::{PackedRLERoomTerrain, WildcardRLERoomTerrain};
+use crate::terrain_pyramid::TerrainPyramid;
+
+impl Serialize for CompressedRoomTerrain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.get_compressed_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for CompressedRoomTerrain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(CompressedRoomTerrain::try_new_from_compressed_slice))
+    }
+}
+
+impl Serialize for RoomEdgeTerrain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.get_raw_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for RoomEdgeTerrain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(RoomEdgeTerrain::try_new_from_raw_slice))
+    }
+}
+
+impl Serialize for RoomExit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(self.packed())
+    }
+}
+
+impl<'de> Deserialize<'de> for RoomExit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let packed = u16::deserialize(deserializer)?;
+        RoomExit::try_new_from_packed(packed).map_err(de::Error::custom)
+    }
+}
+
+/// Exits data is serialized as a `(room, edge terrain)` pair; the exit counts are recomputed when
+/// deserializing.
+impl Serialize for RoomExitsData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.room(), self.edge_terrain_data()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RoomExitsData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (room, edge_terrain) = <(RoomName, RoomEdgeTerrain)>::deserialize(deserializer)?;
+        Ok(RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, room))
+    }
+}
+
+impl Serialize for PackedRLERoomTerrain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = u16s_to_le_bytes(self.packed_runs()).collect();
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackedRLERoomTerrain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(|bytes: &[u8]| {
+            PackedRLERoomTerrain::try_new_from_packed_runs(&u16s_from_le_bytes("RLE terrain", bytes)?)
+        }))
+    }
+}
+
+/// Wildcard RLE terrain is serialized as its 24 edge terrain bytes, followed by its packed runs.
+impl Serialize for WildcardRLERoomTerrain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = self.edge_terrain().get_raw_bytes().into_iter()
+            .chain(u16s_to_le_bytes(self.packed_runs()))
+            .collect();
+        serializer.serialize_bytes(&bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for WildcardRLERoomTerrain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(|bytes: &[u8]| {
+            let edge_bytes = bytes.get(..24)
+                .ok_or(MapProcessingError::InvalidLength { kind: "wildcard RLE terrain", expected: 24, found: bytes.len() })?;
+            let runs = u16s_from_le_bytes("RLE terrain", &bytes[24..])?;
+            WildcardRLERoomTerrain::try_new_from_packed_runs(RoomEdgeTerrain::try_new_from_raw_slice(edge_bytes)?, &runs)
+        }))
+    }
+}
+
+impl Serialize for TerrainPyramid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for TerrainPyramid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(TerrainPyramid::try_from_slice))
+    }
+}
+
+impl Serialize for AdaptiveRoomTerrain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for AdaptiveRoomTerrain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor::new(AdaptiveRoomTerrain::from_bytes))
+    }
+}
+
+/// Internal visitor that accepts either a byte string or a sequence of bytes, and converts them
+/// with a checked constructor.
+struct BytesVisitor<T, F> {
+    convert: F,
+    output: PhantomData<T>,
+}
+
+impl<T, F: FnOnce(&[u8]) -> Result<T, MapProcessingError>> BytesVisitor<T, F> {
+    fn new(convert: F) -> Self {
+        Self { convert, output: PhantomData }
+    }
+}
+
+impl<'de, T, F: FnOnce(&[u8]) -> Result<T, MapProcessingError>> Visitor<'de> for BytesVisitor<T, F> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "compressed terrain bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<T, E> {
+        (self.convert)(bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        (self.convert)(&bytes).map_err(de::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::{ExitDirection, Terrain, ROOM_AREA};
+    use crate::adaptive_terrain::TerrainEncoding;
+
+    #[test]
+    pub fn serde_round_trips_compressed_types_through_json() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[60] = 1;
+        bits[61] = 2;
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let json = serde_json::to_string(&terrain).unwrap();
+        let decoded: CompressedRoomTerrain = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.get_compressed_bytes(), terrain.get_compressed_bytes());
+
+        let mut edge = [Terrain::Wall; 50];
+        edge[10..20].fill(Terrain::Plain);
+        let edge_terrain = RoomEdgeTerrain::new_from_terrain_slices(&edge, &edge, &edge, &edge).unwrap();
+        let exits_data = RoomExitsData::new_from_compressed_edge_terrain_data(edge_terrain, RoomName::new("W1N1").unwrap());
+        let json = serde_json::to_string(&exits_data).unwrap();
+        let decoded: RoomExitsData = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.room(), exits_data.room());
+        assert_eq!(decoded.iter().collect::<Vec<_>>(), exits_data.iter().collect::<Vec<_>>());
+
+        let exit = RoomExit::new(10, 10, ExitDirection::Top);
+        let decoded: RoomExit = serde_json::from_str(&serde_json::to_string(&exit).unwrap()).unwrap();
+        assert_eq!(decoded, exit);
+    }
+
+    #[test]
+    pub fn serde_round_trips_rle_pyramid_and_adaptive_terrain_through_json() {
+        let mut bits = [0u8; ROOM_AREA];
+        bits[..50].fill(1);
+        bits[60..70].fill(2);
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+
+        let packed = PackedRLERoomTerrain::new_from_compressed_terrain(&terrain);
+        let decoded: PackedRLERoomTerrain = serde_json::from_str(&serde_json::to_string(&packed).unwrap()).unwrap();
+        assert_eq!(decoded.packed_runs(), packed.packed_runs());
+
+        let wildcard = WildcardRLERoomTerrain::new_from_compressed_terrain(&terrain);
+        let decoded: WildcardRLERoomTerrain = serde_json::from_str(&serde_json::to_string(&wildcard).unwrap()).unwrap();
+        assert_eq!(decoded.packed_runs(), wildcard.packed_runs());
+        assert_eq!(decoded.edge_terrain().get_raw_bytes(), wildcard.edge_terrain().get_raw_bytes());
+
+        let pyramid = TerrainPyramid::new_from_compressed_terrain(&terrain);
+        let decoded: TerrainPyramid = serde_json::from_str(&serde_json::to_string(&pyramid).unwrap()).unwrap();
+        assert_eq!(decoded.to_bytes(), pyramid.to_bytes());
+
+        for encoding in TerrainEncoding::ALL {
+            let adaptive = AdaptiveRoomTerrain::new_with_encoding(&terrain, encoding);
+            let decoded: AdaptiveRoomTerrain = serde_json::from_str(&serde_json::to_string(&adaptive).unwrap()).unwrap();
+            assert_eq!(decoded.encoding(), encoding);
+            assert_eq!(decoded.to_compressed_terrain().get_compressed_bytes(), adaptive.to_compressed_terrain().get_compressed_bytes());
+        }
+
+        assert!(serde_json::from_str::<PackedRLERoomTerrain>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<WildcardRLERoomTerrain>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<TerrainPyramid>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<AdaptiveRoomTerrain>("[9, 0]").is_err());
+    }
+
+    #[test]
+    pub fn serde_rejects_malformed_compressed_types() {
+        assert!(serde_json::from_str::<CompressedRoomTerrain>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<RoomEdgeTerrain>("[1, 2, 3]").is_err());
+        assert!(serde_json::from_str::<RoomExit>("0").is_err());
+    }
+}
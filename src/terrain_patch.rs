@@ -0,0 +1,254 @@
+//! Provides a small declarative patch format for editing stored room terrain.
+//!
+//! A patch is a JSON list of edits, applied in order. Each edit sets a shape of tiles to a single
+//! terrain value:
+//!
+//! ```json
+//! [
+//!     {"type": "point", "at": [10, 10], "terrain": "wall"},
+//!     {"type": "rect", "from": [5, 5], "to": [8, 9], "terrain": "swamp"},
+//!     {"type": "line", "from": [1, 1], "to": [20, 7], "terrain": "plain"}
+//! ]
+//! ```
+//!
+//! Rects include both corners, and lines are drawn with Bresenham's algorithm, including both
+//! endpoints. Edits are applied in order, so later edits override earlier ones.
+
+use screeps::{RoomXY, Terrain};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+
+/// The errors that prevent a patch from being loaded.
+#[derive(Debug, Error)]
+pub enum TerrainPatchError {
+    /// The file couldn't be read.
+    #[error("unable to read patch file: {0}")]
+    Io(#[source] std::io::Error),
+    /// The file isn't valid JSON.
+    #[error("patch file is not valid JSON: {0}")]
+    Json(#[source] serde_json::Error),
+    /// The JSON is valid, but isn't a list of edits.
+    #[error("patch file is not a list of edits")]
+    NotAList,
+    /// One of the edits is malformed.
+    #[error("patch edit {index} is invalid: {reason:?}")]
+    InvalidEdit { index: usize, reason: PatchEditError },
+}
+
+/// The reasons an individual edit can fail to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchEditError {
+    /// The edit isn't a JSON object.
+    NotAnObject,
+    /// The edit's `type` isn't one of `point`, `rect`, or `line`.
+    UnknownType(String),
+    /// A required field is missing.
+    MissingField(&'static str),
+    /// A coordinate field isn't an `[x, y]` pair within the room.
+    InvalidCoordinate(&'static str),
+    /// The terrain field isn't one of `plain`, `swamp`, or `wall`.
+    InvalidTerrain(String),
+}
+
+/// The set of tiles that an edit applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatchShape {
+    Point(RoomXY),
+    /// An axis-aligned rectangle, including both corners.
+    Rect(RoomXY, RoomXY),
+    /// A straight line, including both endpoints.
+    Line(RoomXY, RoomXY),
+}
+
+impl PatchShape {
+    /// The tiles covered by this shape.
+    pub fn tiles(&self) -> Vec<RoomXY> {
+        match *self {
+            PatchShape::Point(xy) => vec![xy],
+            PatchShape::Rect(a, b) => {
+                let (min_x, max_x) = min_max(a.x.u8(), b.x.u8());
+                let (min_y, max_y) = min_max(a.y.u8(), b.y.u8());
+                let mut tiles = Vec::new();
+                for y in min_y..=max_y {
+                    for x in min_x..=max_x {
+                        // Safety: x and y are between the coordinates of two valid RoomXY values
+                        tiles.push(unsafe { RoomXY::unchecked_new(x, y) });
+                    }
+                }
+                tiles
+            },
+            PatchShape::Line(a, b) => line_tiles(a, b),
+        }
+    }
+}
+
+/// A single patch edit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatchEdit {
+    pub shape: PatchShape,
+    pub terrain: Terrain,
+}
+
+/// An ordered list of terrain edits.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TerrainPatch {
+    pub edits: Vec<PatchEdit>,
+}
+
+impl TerrainPatch {
+    /// Parses a patch from a JSON string.
+    pub fn parse(json: &str) -> Result<Self, MapProcessingError> {
+        let value: Value = serde_json::from_str(json).map_err(TerrainPatchError::Json)?;
+        let entries = value.as_array().ok_or(TerrainPatchError::NotAList)?;
+
+        let edits = entries.iter().enumerate()
+            .map(|(index, entry)| parse_edit(entry).map_err(|reason| TerrainPatchError::InvalidEdit { index, reason }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { edits })
+    }
+
+    /// Loads a patch from the specified file.
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Self, MapProcessingError> {
+        let contents = std::fs::read_to_string(path).map_err(TerrainPatchError::Io)?;
+        Self::parse(&contents)
+    }
+
+    /// Applies every edit in this patch to the terrain, in order.
+    pub fn apply(&self, terrain: &mut CompressedRoomTerrain) {
+        for edit in &self.edits {
+            terrain.set_many(edit.shape.tiles().into_iter().map(|xy| (xy, edit.terrain)));
+        }
+    }
+}
+
+/// Loads the patch in the specified file and applies it to the terrain.
+pub fn apply_patch_file<P: AsRef<std::path::Path>>(terrain: &mut CompressedRoomTerrain, path: P) -> Result<(), MapProcessingError> {
+    let patch = TerrainPatch::load(path)?;
+    patch.apply(terrain);
+    Ok(())
+}
+
+/// Internal helper function that parses a single edit.
+fn parse_edit(entry: &Value) -> Result<PatchEdit, PatchEditError> {
+    let object = entry.as_object().ok_or(PatchEditError::NotAnObject)?;
+
+    let edit_type = object.get("type").and_then(|v| v.as_str()).ok_or(PatchEditError::MissingField("type"))?;
+    let shape = match edit_type {
+        "point" => PatchShape::Point(parse_coordinate(object, "at")?),
+        "rect" => PatchShape::Rect(parse_coordinate(object, "from")?, parse_coordinate(object, "to")?),
+        "line" => PatchShape::Line(parse_coordinate(object, "from")?, parse_coordinate(object, "to")?),
+        other => return Err(PatchEditError::UnknownType(other.to_string())),
+    };
+
+    let raw_terrain = object.get("terrain").and_then(|v| v.as_str()).ok_or(PatchEditError::MissingField("terrain"))?;
+    let terrain = match raw_terrain {
+        "plain" => Terrain::Plain,
+        "swamp" => Terrain::Swamp,
+        "wall" => Terrain::Wall,
+        other => return Err(PatchEditError::InvalidTerrain(other.to_string())),
+    };
+
+    Ok(PatchEdit { shape, terrain })
+}
+
+/// Internal helper function that parses an `[x, y]` coordinate field.
+fn parse_coordinate(object: &Map<String, Value>, field: &'static str) -> Result<RoomXY, PatchEditError> {
+    let value = object.get(field).ok_or(PatchEditError::MissingField(field))?;
+    let pair = value.as_array().filter(|a| a.len() == 2).ok_or(PatchEditError::InvalidCoordinate(field))?;
+
+    let coords = pair.iter()
+        .map(|v| v.as_u64().and_then(|n| u8::try_from(n).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(PatchEditError::InvalidCoordinate(field))?;
+
+    RoomXY::checked_new(coords[0], coords[1]).map_err(|_| PatchEditError::InvalidCoordinate(field))
+}
+
+/// Internal helper function that returns the smaller and larger of two values.
+fn min_max(a: u8, b: u8) -> (u8, u8) {
+    (a.min(b), a.max(b))
+}
+
+/// Internal helper function that returns the tiles along a line, using Bresenham's algorithm.
+fn line_tiles(from: RoomXY, to: RoomXY) -> Vec<RoomXY> {
+    let (mut x, mut y) = (from.x.u8() as i32, from.y.u8() as i32);
+    let (end_x, end_y) = (to.x.u8() as i32, to.y.u8() as i32);
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut tiles = Vec::new();
+    loop {
+        // Safety: Every point on the line lies between two valid RoomXY values
+        tiles.push(unsafe { RoomXY::unchecked_new(x as u8, y as u8) });
+        if x == end_x && y == end_y {
+            break;
+        }
+        let doubled_error = 2 * error;
+        if doubled_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    tiles
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    fn xy(x: u8, y: u8) -> RoomXY {
+        RoomXY::checked_new(x, y).unwrap()
+    }
+
+    #[test]
+    pub fn terrain_patch_applies_edits_in_order() {
+        let json = r#"[
+            {"type": "rect", "from": [12, 10], "to": [10, 11], "terrain": "swamp"},
+            {"type": "point", "at": [11, 11], "terrain": "wall"},
+            {"type": "line", "from": [0, 0], "to": [4, 2], "terrain": "wall"}
+        ]"#;
+        let patch = TerrainPatch::parse(json).unwrap();
+        assert_eq!(patch.edits.len(), 3);
+
+        let mut terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        patch.apply(&mut terrain);
+
+        assert_eq!(terrain.get_xy(xy(10, 10)), Terrain::Swamp);
+        assert_eq!(terrain.get_xy(xy(12, 11)), Terrain::Swamp);
+        assert_eq!(terrain.get_xy(xy(11, 11)), Terrain::Wall);
+        assert_eq!(terrain.get_xy(xy(13, 11)), Terrain::Plain);
+
+        let line: Vec<RoomXY> = PatchShape::Line(xy(0, 0), xy(4, 2)).tiles();
+        assert_eq!(line, vec![xy(0, 0), xy(1, 1), xy(2, 1), xy(3, 2), xy(4, 2)]);
+        assert!(line.iter().all(|t| terrain.get_xy(*t) == Terrain::Wall));
+    }
+
+    #[test]
+    pub fn terrain_patch_reports_invalid_edits() {
+        let cases = [
+            (r#"[{"type": "circle"}]"#, PatchEditError::UnknownType("circle".to_string())),
+            (r#"[{"type": "point", "terrain": "wall"}]"#, PatchEditError::MissingField("at")),
+            (r#"[{"type": "point", "at": [50, 0], "terrain": "wall"}]"#, PatchEditError::InvalidCoordinate("at")),
+            (r#"[{"type": "point", "at": [1, 1], "terrain": "lava"}]"#, PatchEditError::InvalidTerrain("lava".to_string())),
+        ];
+        for (json, expected) in cases {
+            match TerrainPatch::parse(json) {
+                Err(MapProcessingError::TerrainPatch(TerrainPatchError::InvalidEdit { index: 0, reason })) => assert_eq!(reason, expected),
+                other => panic!("Unexpected parse result for {json}: {other:?}"),
+            }
+        }
+    }
+}
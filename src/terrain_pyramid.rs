@@ -0,0 +1,178 @@
+//! Provides coarse, downsampled summaries of room terrain.
+//!
+//! Shard-scale renderers and heuristics rarely need full tile resolution. A [TerrainPyramid]
+//! stores each room at 10x10 and 5x5 resolution, with each cell holding the majority terrain of the
+//! block of tiles it covers, in 32 bytes.
+
+use screeps::{RoomXY, Terrain, ROOM_USIZE};
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::error::MapProcessingError;
+
+/// The number of bytes in the packed representation of a [TerrainPyramid].
+pub const TERRAIN_PYRAMID_BYTES: usize = 32;
+
+/// The resolution levels of a [TerrainPyramid].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PyramidLevel {
+    /// 10x10 cells, each covering 5x5 tiles.
+    Ten,
+    /// 5x5 cells, each covering 10x10 tiles.
+    Five,
+}
+
+impl PyramidLevel {
+    /// The number of cells along each side at this level.
+    pub fn size(&self) -> usize {
+        match self {
+            PyramidLevel::Ten => 10,
+            PyramidLevel::Five => 5,
+        }
+    }
+
+    /// The number of tiles along each side of a cell at this level.
+    pub fn block_size(&self) -> usize {
+        ROOM_USIZE / self.size()
+    }
+}
+
+/// Downsampled terrain for a single room, at 10x10 and 5x5 resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainPyramid {
+    ten: [Terrain; 100],
+    five: [Terrain; 25],
+}
+
+impl TerrainPyramid {
+    /// Computes the pyramid for the terrain.
+    pub fn new_from_compressed_terrain(terrain: &CompressedRoomTerrain) -> Self {
+        let mut ten = [Terrain::Plain; 100];
+        let mut five = [Terrain::Plain; 25];
+        downsample(terrain, PyramidLevel::Ten, &mut ten);
+        downsample(terrain, PyramidLevel::Five, &mut five);
+        Self { ten, five }
+    }
+
+    /// Gets the majority terrain of a cell at the specified level.
+    ///
+    /// Returns None if the cell coordinates are outside the level.
+    pub fn get(&self, level: PyramidLevel, x: usize, y: usize) -> Option<Terrain> {
+        let size = level.size();
+        if x >= size || y >= size {
+            return None;
+        }
+        Some(self.cells(level)[y * size + x])
+    }
+
+    /// All cells at the specified level, in row-major order.
+    pub fn cells(&self, level: PyramidLevel) -> &[Terrain] {
+        match level {
+            PyramidLevel::Ten => &self.ten,
+            PyramidLevel::Five => &self.five,
+        }
+    }
+
+    /// The packed representation of this pyramid, with 4 cells per byte, the 10x10 level first.
+    pub fn to_bytes(&self) -> [u8; TERRAIN_PYRAMID_BYTES] {
+        let mut bytes = [0u8; TERRAIN_PYRAMID_BYTES];
+        for (idx, terrain) in self.ten.iter().chain(self.five.iter()).enumerate() {
+            bytes[idx / 4] |= (*terrain as u8) << (6 - 2 * (idx % 4));
+        }
+        bytes
+    }
+
+    /// Creates a pyramid from its packed representation, as returned by
+    /// [to_bytes](TerrainPyramid::to_bytes).
+    ///
+    /// Invalid cell values are read as walls.
+    pub fn from_bytes(bytes: &[u8; TERRAIN_PYRAMID_BYTES]) -> Self {
+        let cell = |idx: usize| match (bytes[idx / 4] >> (6 - 2 * (idx % 4))) & 0b11 {
+            0b00 => Terrain::Plain,
+            0b10 => Terrain::Swamp,
+            _ => Terrain::Wall,
+        };
+        Self {
+            ten: std::array::from_fn(cell),
+            five: std::array::from_fn(|idx| cell(100 + idx)),
+        }
+    }
+
+    /// Creates a pyramid from a slice of its packed representation.
+    ///
+    /// Returns [MapProcessingError::InvalidLength] if the slice isn't [TERRAIN_PYRAMID_BYTES] long.
+    pub fn try_from_slice(bytes: &[u8]) -> Result<Self, MapProcessingError> {
+        let bytes = bytes.try_into()
+            .map_err(|_| MapProcessingError::InvalidLength { kind: "terrain pyramid", expected: TERRAIN_PYRAMID_BYTES, found: bytes.len() })?;
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+/// Internal helper function that fills `output` with the majority terrain of each block at the
+/// specified level.
+///
+/// Ties are broken in favor of the more restrictive terrain: walls, then swamps, then plains.
+fn downsample(terrain: &CompressedRoomTerrain, level: PyramidLevel, output: &mut [Terrain]) {
+    let size = level.size();
+    let block_size = level.block_size();
+
+    for (idx, cell) in output.iter_mut().enumerate() {
+        let (cell_x, cell_y) = (idx % size, idx / size);
+        let mut counts = [0usize; 3]; // Plain, Wall, Swamp
+
+        for y in cell_y * block_size..(cell_y + 1) * block_size {
+            for x in cell_x * block_size..(cell_x + 1) * block_size {
+                // Safety: x and y are both in the range [0, 49]
+                let xy = unsafe { RoomXY::unchecked_new(x as u8, y as u8) };
+                counts[terrain.get_xy(xy) as usize] += 1;
+            }
+        }
+
+        *cell = [Terrain::Wall, Terrain::Swamp, Terrain::Plain].into_iter()
+            .max_by_key(|t| (counts[*t as usize], restrictiveness(*t)))
+            .unwrap_or(Terrain::Plain);
+    }
+}
+
+/// Internal helper function ranking terrain by how restrictive it is to movement.
+fn restrictiveness(terrain: Terrain) -> u8 {
+    match terrain {
+        Terrain::Plain => 0,
+        Terrain::Swamp => 1,
+        Terrain::Wall => 2,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    #[test]
+    pub fn terrain_pyramid_uses_block_majority_and_round_trips() {
+        // Walls in the top-left 5x5 block, and swamps in the top 3 rows of the next block over
+        let mut bits = [0u8; ROOM_AREA];
+        for y in 0..5 {
+            for x in 0..5 {
+                bits[y * 50 + x] = 1;
+            }
+        }
+        for y in 0..3 {
+            for x in 5..10 {
+                bits[y * 50 + x] = 2;
+            }
+        }
+        let terrain = CompressedRoomTerrain::new_from_uncompressed_bits(&bits);
+        let pyramid = TerrainPyramid::new_from_compressed_terrain(&terrain);
+
+        assert_eq!(pyramid.get(PyramidLevel::Ten, 0, 0), Some(Terrain::Wall));
+        assert_eq!(pyramid.get(PyramidLevel::Ten, 1, 0), Some(Terrain::Swamp));
+        assert_eq!(pyramid.get(PyramidLevel::Ten, 0, 1), Some(Terrain::Plain));
+        assert_eq!(pyramid.get(PyramidLevel::Ten, 10, 0), None);
+
+        // 25 walls and 15 swamps out of 100 tiles
+        assert_eq!(pyramid.get(PyramidLevel::Five, 0, 0), Some(Terrain::Plain));
+
+        assert_eq!(TerrainPyramid::from_bytes(&pyramid.to_bytes()), pyramid);
+    }
+}
@@ -0,0 +1,218 @@
+//! Provides a common interface for storing room terrain, regardless of the backend.
+//!
+//! The SQLite database in [compressed_terrain_db] implements it through [ShardDb] for a single
+//! shard, and through [Connection] for [DEFAULT_SHARD]. [InMemoryTerrainRepository] keeps terrain
+//! in memory, which is useful for tests and short-lived tools.
+
+use std::collections::HashMap;
+#[cfg(feature = "db")]
+use rusqlite::Connection;
+use screeps::RoomName;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+#[cfg(feature = "db")]
+use crate::compressed_terrain_db::{self, ShardDb, DEFAULT_SHARD};
+use crate::error::MapProcessingError;
+
+/// Anything that room terrain can be stored in and loaded from.
+pub trait TerrainRepository {
+    /// Gets the stored terrain for a room, or None if there isn't any.
+    fn get_terrain(&self, room: RoomName) -> Result<Option<CompressedRoomTerrain>, MapProcessingError>;
+
+    /// Stores the terrain for a room, replacing any terrain already stored for it.
+    fn put_terrain(&mut self, room: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError>;
+
+    /// Deletes the stored terrain for a room, returning true if there was any.
+    fn delete_terrain(&mut self, room: RoomName) -> Result<bool, MapProcessingError>;
+
+    /// Gets every room with stored terrain, ordered by room x coordinate, then room y coordinate.
+    fn list_rooms(&self) -> Result<Vec<RoomName>, MapProcessingError>;
+
+    /// Gets the stored terrain for each of the rooms that has any, in the order given.
+    ///
+    /// The default implementation calls [get_terrain](TerrainRepository::get_terrain) for each
+    /// room; backends that can load rooms in bulk override it.
+    fn get_terrains(&self, rooms: &[RoomName]) -> Result<Vec<(RoomName, CompressedRoomTerrain)>, MapProcessingError> {
+        let mut terrains = Vec::with_capacity(rooms.len());
+        for room in rooms {
+            if let Some(terrain) = self.get_terrain(*room)? {
+                terrains.push((*room, terrain));
+            }
+        }
+        Ok(terrains)
+    }
+
+    /// Stores the terrain for each of the rooms, replacing any terrain already stored for them.
+    ///
+    /// The default implementation calls [put_terrain](TerrainRepository::put_terrain) for each
+    /// room; backends that can store rooms in bulk override it.
+    fn put_terrains(&mut self, rooms: &[(RoomName, CompressedRoomTerrain)]) -> Result<(), MapProcessingError> {
+        for (room, terrain) in rooms {
+            self.put_terrain(*room, terrain)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "db")]
+impl TerrainRepository for ShardDb<'_> {
+    fn get_terrain(&self, room: RoomName) -> Result<Option<CompressedRoomTerrain>, MapProcessingError> {
+        match self.get_terrain_for_room(room) {
+            Ok(terrain) => Ok(Some(terrain)),
+            Err(MapProcessingError::Database(rusqlite::Error::QueryReturnedNoRows)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn put_terrain(&mut self, room: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+        self.upsert_terrain_for_room(room, terrain)
+    }
+
+    fn delete_terrain(&mut self, room: RoomName) -> Result<bool, MapProcessingError> {
+        self.delete_terrain_for_room(room)
+    }
+
+    fn list_rooms(&self) -> Result<Vec<RoomName>, MapProcessingError> {
+        self.get_rooms_with_terrain()
+    }
+
+    /// Loads every room with [get_terrains_for_rooms](ShardDb::get_terrains_for_rooms), instead of
+    /// querying each room separately.
+    fn get_terrains(&self, rooms: &[RoomName]) -> Result<Vec<(RoomName, CompressedRoomTerrain)>, MapProcessingError> {
+        let terrains = self.get_terrains_for_rooms(rooms)?;
+        Ok(rooms.iter()
+            .filter_map(|room| terrains.get(room).map(|terrain| (*room, CompressedRoomTerrain::new_from_compressed_bytes(Box::new(*terrain.get_compressed_bytes())))))
+            .collect())
+    }
+
+    /// Stores every room in a single transaction, so either all of them are stored or none are.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(shard = self.shard(), rooms = rooms.len())))]
+    fn put_terrains(&mut self, rooms: &[(RoomName, CompressedRoomTerrain)]) -> Result<(), MapProcessingError> {
+        let transaction = self.connection().unchecked_transaction()?;
+        for (room, terrain) in rooms {
+            self.upsert_terrain_for_room(*room, terrain)?;
+        }
+        transaction.commit()?;
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(bytes_written = rooms.len() * crate::compressed_terrain::compressed_terrain::COMPRESSED_ARRAY_SIZE, "stored terrain for rooms");
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "db")]
+impl TerrainRepository for Connection {
+    fn get_terrain(&self, room: RoomName) -> Result<Option<CompressedRoomTerrain>, MapProcessingError> {
+        ShardDb::new(self, DEFAULT_SHARD).get_terrain(room)
+    }
+
+    fn put_terrain(&mut self, room: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+        compressed_terrain_db::upsert_terrain_for_room(self, room, terrain)
+    }
+
+    fn delete_terrain(&mut self, room: RoomName) -> Result<bool, MapProcessingError> {
+        compressed_terrain_db::delete_terrain_for_room(self, room)
+    }
+
+    fn list_rooms(&self) -> Result<Vec<RoomName>, MapProcessingError> {
+        compressed_terrain_db::get_rooms_with_terrain(self)
+    }
+
+    fn get_terrains(&self, rooms: &[RoomName]) -> Result<Vec<(RoomName, CompressedRoomTerrain)>, MapProcessingError> {
+        ShardDb::new(self, DEFAULT_SHARD).get_terrains(rooms)
+    }
+
+    fn put_terrains(&mut self, rooms: &[(RoomName, CompressedRoomTerrain)]) -> Result<(), MapProcessingError> {
+        ShardDb::new(self, DEFAULT_SHARD).put_terrains(rooms)
+    }
+}
+
+/// Room terrain kept in memory.
+#[derive(Default)]
+pub struct InMemoryTerrainRepository {
+    rooms: HashMap<RoomName, CompressedRoomTerrain>,
+}
+
+impl InMemoryTerrainRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of rooms with stored terrain.
+    pub fn len(&self) -> usize {
+        self.rooms.len()
+    }
+
+    /// Returns true if no rooms have stored terrain.
+    pub fn is_empty(&self) -> bool {
+        self.rooms.is_empty()
+    }
+}
+
+impl TerrainRepository for InMemoryTerrainRepository {
+    fn get_terrain(&self, room: RoomName) -> Result<Option<CompressedRoomTerrain>, MapProcessingError> {
+        Ok(self.rooms.get(&room).map(|terrain| CompressedRoomTerrain::new_from_compressed_bytes(Box::new(*terrain.get_compressed_bytes()))))
+    }
+
+    fn put_terrain(&mut self, room: RoomName, terrain: &CompressedRoomTerrain) -> Result<(), MapProcessingError> {
+        let terrain = CompressedRoomTerrain::new_from_compressed_bytes(Box::new(*terrain.get_compressed_bytes()));
+        self.rooms.insert(room, terrain);
+        Ok(())
+    }
+
+    fn delete_terrain(&mut self, room: RoomName) -> Result<bool, MapProcessingError> {
+        Ok(self.rooms.remove(&room).is_some())
+    }
+
+    fn list_rooms(&self) -> Result<Vec<RoomName>, MapProcessingError> {
+        let mut rooms: Vec<RoomName> = self.rooms.keys().copied().collect();
+        rooms.sort_by_key(|room| (room.x_coord(), room.y_coord()));
+        Ok(rooms)
+    }
+}
+
+
+#[cfg(all(test, feature = "db"))]
+mod test {
+    use super::*;
+    use screeps::ROOM_AREA;
+
+    /// Runs the same operations against any repository.
+    fn check_repository(repo: &mut impl TerrainRepository) {
+        let room = |name: &str| RoomName::new(name).unwrap();
+        let plains = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let walls = CompressedRoomTerrain::new_from_uncompressed_bits(&[1u8; ROOM_AREA]);
+
+        assert!(repo.get_terrain(room("W1N1")).unwrap().is_none());
+        repo.put_terrain(room("W1N1"), &plains).unwrap();
+        repo.put_terrain(room("W1N1"), &walls).unwrap();
+        assert_eq!(repo.get_terrain(room("W1N1")).unwrap().unwrap().fingerprint(), walls.fingerprint());
+
+        let bulk = vec![
+            (room("E1N1"), CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA])),
+            (room("W1N2"), CompressedRoomTerrain::new_from_uncompressed_bits(&[2u8; ROOM_AREA])),
+        ];
+        repo.put_terrains(&bulk).unwrap();
+        assert_eq!(repo.list_rooms().unwrap(), vec![room("W1N2"), room("W1N1"), room("E1N1")]);
+
+        let loaded = repo.get_terrains(&[room("E1N1"), room("E9N9"), room("W1N1")]).unwrap();
+        assert_eq!(loaded.iter().map(|(room, _)| *room).collect::<Vec<_>>(), vec![room("E1N1"), room("W1N1")]);
+
+        assert!(repo.delete_terrain(room("W1N1")).unwrap());
+        assert!(!repo.delete_terrain(room("W1N1")).unwrap());
+        assert_eq!(repo.list_rooms().unwrap().len(), 2);
+    }
+
+    #[test]
+    pub fn terrain_repositories_behave_the_same() {
+        check_repository(&mut InMemoryTerrainRepository::new());
+
+        let conn = compressed_terrain_db::open_in_memory_db().unwrap();
+        check_repository(&mut ShardDb::new(&conn, "shard3"));
+        let mut conn = conn;
+        check_repository(&mut conn);
+        assert_eq!(compressed_terrain_db::get_shards(&conn).unwrap(), vec!["", "shard3"]);
+    }
+}
@@ -0,0 +1,300 @@
+//! Provides a common interface for reading room terrain, regardless of how it's stored.
+
+use screeps::{LocalRoomTerrain, RoomXY, Terrain, ROOM_AREA};
+use screeps::local::terrain_index_to_xy;
+
+use crate::adaptive_terrain::AdaptiveRoomTerrain;
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+use crate::compressed_terrain::sub_rect::SubRect;
+use crate::run_length_encoding::rle_terrain::{PackedRLERoomTerrain, RLERoomTerrain, WildcardRLERoomTerrain};
+
+/// Anything that terrain can be read from, one tile at a time.
+///
+/// This is implemented by every terrain encoding in the crate, so analyses can be written once and
+/// run against whichever encoding a room is stored in.
+pub trait TerrainSource {
+    /// Gets the terrain at the specified position in the room.
+    fn get_xy(&self, xy: RoomXY) -> Terrain;
+
+    /// The amount of memory it takes to store this terrain, in bytes.
+    fn memory_size(&self) -> usize;
+
+    /// Returns an iterator over every tile in the room and its terrain, in row-major order.
+    ///
+    /// The default implementation calls [get_xy](TerrainSource::get_xy) for each tile; encodings
+    /// that can decode tiles in bulk override it.
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        (0..ROOM_AREA).map(terrain_index_to_xy).map(|xy| (xy, self.get_xy(xy)))
+    }
+
+    /// Gets the terrain for every tile in the rectangle between two corners, inclusive, in
+    /// row-major order.
+    ///
+    /// The result is empty if `bottom_right` is above or to the left of `top_left`.
+    fn get_rect(&self, top_left: RoomXY, bottom_right: RoomXY) -> Vec<Terrain> {
+        SubRect::between(self, top_left, bottom_right).map(|rect| rect.to_vec()).unwrap_or_default()
+    }
+
+    /// Counts the tiles of each kind of terrain in the room.
+    ///
+    /// The default implementation checks every tile; encodings that can count without decoding
+    /// override it.
+    fn terrain_counts(&self) -> TerrainCounts {
+        let mut counts = TerrainCounts::default();
+        self.iter_tiles().for_each(|(_, terrain)| counts.add(terrain, 1));
+        counts
+    }
+}
+
+/// The number of tiles of each kind of terrain in a room.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TerrainCounts {
+    pub plain: usize,
+    pub swamp: usize,
+    pub wall: usize,
+}
+
+impl TerrainCounts {
+    /// The total number of tiles counted.
+    pub fn total(&self) -> usize {
+        self.plain + self.swamp + self.wall
+    }
+
+    /// The number of tiles of the specified terrain.
+    pub fn get(&self, terrain: Terrain) -> usize {
+        match terrain {
+            Terrain::Plain => self.plain,
+            Terrain::Swamp => self.swamp,
+            Terrain::Wall => self.wall,
+        }
+    }
+
+    /// The fraction of tiles that are swamps, or 0 if there are no tiles.
+    pub fn swamp_ratio(&self) -> f32 {
+        self.ratio(self.swamp)
+    }
+
+    /// The fraction of tiles that are walls, or 0 if there are no tiles.
+    pub fn wall_ratio(&self) -> f32 {
+        self.ratio(self.wall)
+    }
+
+    /// Internal helper function to adjust the count for a kind of terrain.
+    pub(crate) fn add(&mut self, terrain: Terrain, n: usize) {
+        match terrain {
+            Terrain::Plain => self.plain += n,
+            Terrain::Swamp => self.swamp += n,
+            Terrain::Wall => self.wall += n,
+        }
+    }
+
+    /// Internal helper function to remove tiles from the count for a kind of terrain.
+    pub(crate) fn remove(&mut self, terrain: Terrain, n: usize) {
+        match terrain {
+            Terrain::Plain => self.plain -= n,
+            Terrain::Swamp => self.swamp -= n,
+            Terrain::Wall => self.wall -= n,
+        }
+    }
+
+    fn ratio(&self, count: usize) -> f32 {
+        match self.total() {
+            0 => 0.0,
+            total => count as f32 / total as f32,
+        }
+    }
+}
+
+impl TerrainSource for CompressedRoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        CompressedRoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        CompressedRoomTerrain::memory_size(self)
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        CompressedRoomTerrain::iter_tiles(self)
+    }
+
+    fn terrain_counts(&self) -> TerrainCounts {
+        CompressedRoomTerrain::terrain_counts(self)
+    }
+
+    fn get_rect(&self, top_left: RoomXY, bottom_right: RoomXY) -> Vec<Terrain> {
+        CompressedRoomTerrain::get_rect(self, top_left, bottom_right)
+    }
+}
+
+impl TerrainSource for LocalRoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        LocalRoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        size_of::<[u8; ROOM_AREA]>() + size_of::<LocalRoomTerrain>()
+    }
+}
+
+impl TerrainSource for PackedRLERoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        PackedRLERoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        PackedRLERoomTerrain::memory_size(self)
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        PackedRLERoomTerrain::iter_tiles(self)
+    }
+
+    fn terrain_counts(&self) -> TerrainCounts {
+        PackedRLERoomTerrain::terrain_counts(self)
+    }
+}
+
+impl TerrainSource for WildcardRLERoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        WildcardRLERoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        WildcardRLERoomTerrain::memory_size(self)
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        WildcardRLERoomTerrain::iter_tiles(self)
+    }
+
+    fn terrain_counts(&self) -> TerrainCounts {
+        WildcardRLERoomTerrain::terrain_counts(self)
+    }
+}
+
+impl TerrainSource for RLERoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        RLERoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        RLERoomTerrain::memory_size(self)
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        RLERoomTerrain::iter_tiles(self)
+    }
+
+    fn terrain_counts(&self) -> TerrainCounts {
+        RLERoomTerrain::terrain_counts(self)
+    }
+}
+
+impl TerrainSource for AdaptiveRoomTerrain {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        AdaptiveRoomTerrain::get_xy(self, xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        AdaptiveRoomTerrain::memory_size(self)
+    }
+}
+
+impl<T: TerrainSource + ?Sized> TerrainSource for &T {
+    fn get_xy(&self, xy: RoomXY) -> Terrain {
+        (**self).get_xy(xy)
+    }
+
+    fn memory_size(&self) -> usize {
+        (**self).memory_size()
+    }
+
+    fn iter_tiles(&self) -> impl Iterator<Item = (RoomXY, Terrain)> + '_ {
+        (**self).iter_tiles()
+    }
+
+    fn get_rect(&self, top_left: RoomXY, bottom_right: RoomXY) -> Vec<Terrain> {
+        (**self).get_rect(top_left, bottom_right)
+    }
+
+    fn terrain_counts(&self) -> TerrainCounts {
+        (**self).terrain_counts()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Counts walls through the trait, so it runs against every encoding.
+    fn count_walls(terrain: &impl TerrainSource) -> usize {
+        terrain.iter_tiles().filter(|(_, tile)| *tile == Terrain::Wall).count()
+    }
+
+    #[test]
+    pub fn terrain_source_is_consistent_across_encodings() {
+        let mut bits = Box::new([0u8; ROOM_AREA]);
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = if i % 11 == 0 { 1 } else { 0 };
+        }
+        let local = LocalRoomTerrain::new_from_bits(bits);
+        let compressed = CompressedRoomTerrain::new_from_uncompressed_bits(local.get_bits());
+        let packed = PackedRLERoomTerrain::new_from_compressed_terrain(&compressed);
+        let generic = RLERoomTerrain::new_from_compressed_terrain(&compressed);
+        let adaptive = AdaptiveRoomTerrain::new_from_compressed_terrain(&compressed);
+
+        let expected = count_walls(&local);
+        assert_eq!(expected, ROOM_AREA.div_ceil(11));
+        assert_eq!(count_walls(&compressed), expected);
+        assert_eq!(count_walls(&packed), expected);
+        assert_eq!(count_walls(&generic), expected);
+        assert_eq!(count_walls(&&adaptive), expected);
+
+        assert_eq!(TerrainSource::memory_size(&compressed), compressed.memory_size());
+        assert!(TerrainSource::memory_size(&local) > TerrainSource::memory_size(&compressed));
+    }
+
+    #[test]
+    pub fn get_rect_is_consistent_across_encodings() {
+        let mut bits = Box::new([0u8; ROOM_AREA]);
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = if i % 11 == 0 { 1 } else { 0 };
+        }
+        let local = LocalRoomTerrain::new_from_bits(bits);
+        let compressed = CompressedRoomTerrain::new_from_uncompressed_bits(local.get_bits());
+        let packed = PackedRLERoomTerrain::new_from_compressed_terrain(&compressed);
+
+        let top_left = RoomXY::checked_new(3, 4).unwrap();
+        let bottom_right = RoomXY::checked_new(7, 6).unwrap();
+        let expected = TerrainSource::get_rect(&local, top_left, bottom_right);
+        assert_eq!(expected.len(), 15);
+        assert_eq!(expected[5], local.get_xy(RoomXY::checked_new(3, 5).unwrap()));
+        assert_eq!(TerrainSource::get_rect(&compressed, top_left, bottom_right), expected);
+        assert_eq!(packed.get_rect(top_left, bottom_right), expected);
+        assert!(compressed.get_rect(bottom_right, top_left).is_empty());
+    }
+
+    #[test]
+    pub fn terrain_counts_are_consistent_across_encodings() {
+        let mut bits = Box::new([0u8; ROOM_AREA]);
+        for (i, tile) in bits.iter_mut().enumerate() {
+            *tile = (i / 3 % 7 % 3) as u8;
+        }
+        let local = LocalRoomTerrain::new_from_bits(bits);
+        let compressed = CompressedRoomTerrain::new_from_uncompressed_bits(local.get_bits());
+        let wildcard = WildcardRLERoomTerrain::new_from_uncompressed_terrain(&local);
+
+        let expected = TerrainSource::terrain_counts(&local);
+        assert_eq!(expected.total(), ROOM_AREA);
+        assert_eq!(compressed.terrain_counts(), expected);
+        assert_eq!(PackedRLERoomTerrain::new_from_compressed_terrain(&compressed).terrain_counts(), expected);
+        assert_eq!(RLERoomTerrain::new_from_compressed_terrain(&compressed).terrain_counts(), expected);
+        // Wildcard edges don't store swamps, so compare against its own tiles
+        let mut wildcard_expected = TerrainCounts::default();
+        wildcard.iter_tiles().for_each(|(_, tile)| wildcard_expected.add(tile, 1));
+        assert_eq!(wildcard.terrain_counts(), wildcard_expected);
+        assert_eq!(expected.swamp_ratio(), expected.swamp as f32 / ROOM_AREA as f32);
+    }
+}
@@ -0,0 +1,67 @@
+//! Utilities for producing controlled corruption of terrain data, for testing how decoders handle
+//! malformed input.
+//!
+//! All corruption is driven by a caller-provided [Rng], so seeded generators produce the same
+//! corruption on every run.
+
+use rand::Rng;
+use rand::seq::index;
+use screeps::ROOM_AREA;
+use screeps::local::terrain_index_to_xy;
+
+use crate::compressed_terrain::compressed_terrain::CompressedRoomTerrain;
+
+impl CompressedRoomTerrain {
+    /// Changes the raw terrain bits of `n_tiles` distinct, randomly chosen tiles.
+    ///
+    /// Every chosen tile is set to a different raw value than it had before, which can include the
+    /// `0b11` swamp-wall value. `n_tiles` is capped at the number of tiles in a room.
+    pub fn randomly_corrupt(&mut self, rng: &mut impl Rng, n_tiles: usize) {
+        for idx in index::sample(rng, ROOM_AREA, n_tiles.min(ROOM_AREA)) {
+            let xy = terrain_index_to_xy(idx);
+            let bits = self.get_uncompressed_terrain_byte(xy);
+            self.set_uncompressed_terrain_byte(xy, (bits + rng.random_range(1..4)) & 0b11);
+        }
+    }
+}
+
+/// Returns a copy of `bytes` with `n` distinct, randomly chosen bits flipped.
+///
+/// `n` is capped at the number of bits in `bytes`.
+pub fn with_bit_flips(bytes: &[u8], n: usize, rng: &mut impl Rng) -> Vec<u8> {
+    let mut res = bytes.to_vec();
+    for bit in index::sample(rng, bytes.len() * 8, n.min(bytes.len() * 8)) {
+        res[bit / 8] ^= 1 << (bit % 8);
+    }
+    res
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    pub fn randomly_corrupt_changes_exactly_the_requested_tiles() {
+        let original = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        let mut corrupted = CompressedRoomTerrain::new_from_uncompressed_bits(&[0u8; ROOM_AREA]);
+        corrupted.randomly_corrupt(&mut StdRng::seed_from_u64(1), 25);
+
+        let changed = (0..ROOM_AREA)
+            .map(terrain_index_to_xy)
+            .filter(|xy| original.get_uncompressed_terrain_byte(*xy) != corrupted.get_uncompressed_terrain_byte(*xy))
+            .count();
+        assert_eq!(changed, 25);
+    }
+
+    #[test]
+    pub fn with_bit_flips_is_deterministic_for_a_seed() {
+        let bytes = [0u8; 16];
+        let flipped = with_bit_flips(&bytes, 10, &mut StdRng::seed_from_u64(7));
+        assert_eq!(flipped.iter().map(|byte| byte.count_ones()).sum::<u32>(), 10);
+        assert_eq!(flipped, with_bit_flips(&bytes, 10, &mut StdRng::seed_from_u64(7)));
+        assert_eq!(with_bit_flips(&bytes, 1000, &mut StdRng::seed_from_u64(7)), [u8::MAX; 16]);
+    }
+}